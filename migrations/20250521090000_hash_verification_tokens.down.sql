@@ -0,0 +1,6 @@
+-- Add down migration script here
+-- Hashes can't be reversed, so outstanding tokens are invalidated instead
+UPDATE verification_tokens
+SET used_at = CURRENT_TIMESTAMP,
+    updated_at = CURRENT_TIMESTAMP
+WHERE used_at IS NULL;
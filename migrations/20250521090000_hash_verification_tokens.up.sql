@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Tokens are now stored as hex-encoded SHA-256 hashes; hash the existing plaintext ones
+UPDATE verification_tokens
+SET token = encode(sha256(convert_to(token, 'UTF8')), 'hex'),
+    updated_at = CURRENT_TIMESTAMP;
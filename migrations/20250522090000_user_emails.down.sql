@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_user_emails_user_email;
+DROP INDEX IF EXISTS idx_user_emails_verified_email;
+DROP INDEX IF EXISTS idx_user_emails_verification_token_id;
+DROP INDEX IF EXISTS idx_user_emails_user_id;
+DROP TABLE IF EXISTS user_emails;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS user_emails (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    is_verified BOOLEAN NOT NULL DEFAULT FALSE,
+    verification_token_id UUID REFERENCES verification_tokens (id) ON DELETE SET NULL,
+    verified_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_emails_user_id ON user_emails (user_id);
+
+CREATE INDEX IF NOT EXISTS idx_user_emails_verification_token_id ON user_emails (verification_token_id);
+
+-- An address can be pending on several accounts, but only verified on one
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_emails_verified_email ON user_emails (email)
+WHERE is_verified AND deleted_at IS NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_emails_user_email ON user_emails (user_id, email)
+WHERE deleted_at IS NULL;
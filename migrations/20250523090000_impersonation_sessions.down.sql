@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_impersonation_sessions_user_id;
+DROP INDEX IF EXISTS idx_impersonation_sessions_admin_id;
+DROP TABLE IF EXISTS impersonation_sessions;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Existing accounts start their password age from the migration, not from sign-up
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS password_changed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
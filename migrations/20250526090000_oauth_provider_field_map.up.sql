@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Where to find id/email/name/avatar in the provider's user info response; NULL uses the built-in mapping
+ALTER TABLE oauth_providers
+ADD COLUMN IF NOT EXISTS field_map JSONB;
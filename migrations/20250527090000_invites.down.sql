@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_invites_email;
+DROP TABLE IF EXISTS invites;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Tokens issued at or before this time are rejected, so an admin can sign a user out everywhere
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS tokens_revoked_at TIMESTAMPTZ;
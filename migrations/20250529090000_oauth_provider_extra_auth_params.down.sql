@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE oauth_providers DROP COLUMN IF EXISTS extra_auth_params;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Extra query parameters for the authorization URL (e.g. prompt, access_type); NULL uses the provider's defaults
+ALTER TABLE oauth_providers
+ADD COLUMN IF NOT EXISTS extra_auth_params JSONB;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS user_badges_user_id_badge_id_key;
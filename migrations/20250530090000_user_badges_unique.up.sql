@@ -0,0 +1,17 @@
+-- Add up migration script here
+-- Concurrent awards could give a user the same badge twice; keep the earliest of each
+UPDATE user_badges ub
+SET deleted_at = now(), updated_at = now()
+WHERE ub.deleted_at IS NULL
+  AND EXISTS (
+    SELECT 1 FROM user_badges earlier
+    WHERE earlier.user_id = ub.user_id
+      AND earlier.badge_id = ub.badge_id
+      AND earlier.deleted_at IS NULL
+      AND (earlier.created_at, earlier.id) < (ub.created_at, ub.id)
+  );
+
+-- A badge is held at most once; removed (soft-deleted) awards don't count
+CREATE UNIQUE INDEX IF NOT EXISTS user_badges_user_id_badge_id_key
+ON user_badges (user_id, badge_id)
+WHERE deleted_at IS NULL;
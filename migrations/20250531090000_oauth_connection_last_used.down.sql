@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE user_oauth_connections DROP COLUMN IF EXISTS last_used_at;
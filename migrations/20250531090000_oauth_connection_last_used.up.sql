@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- When the connection was last used to log in; every login so far also bumped updated_at
+ALTER TABLE user_oauth_connections
+ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMPTZ;
+
+UPDATE user_oauth_connections SET last_used_at = updated_at WHERE last_used_at IS NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS reserved_usernames;
+
+ALTER TABLE users DROP COLUMN IF EXISTS username_changed_at;
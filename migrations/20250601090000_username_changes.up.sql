@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- When the user last changed their username; NULL if they never have
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS username_changed_at TIMESTAMPTZ;
+
+-- Usernames given up by a user, kept from everyone else until reserved_until
+CREATE TABLE IF NOT EXISTS reserved_usernames (
+    username VARCHAR(50) PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reserved_until TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- The language the user prefers to be addressed in; NULL leaves it to their client
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS locale VARCHAR(35);
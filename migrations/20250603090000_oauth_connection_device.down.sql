@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE user_oauth_connections
+DROP COLUMN IF EXISTS device_info,
+DROP COLUMN IF EXISTS user_agent;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- The client of the connection's last login, like sessions keep, so users can tell
+-- where each linked provider was used
+ALTER TABLE user_oauth_connections
+ADD COLUMN IF NOT EXISTS user_agent TEXT,
+ADD COLUMN IF NOT EXISTS device_info JSONB;
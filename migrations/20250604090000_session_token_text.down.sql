@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE sessions
+ALTER COLUMN token TYPE VARCHAR(255),
+ALTER COLUMN refresh_token TYPE VARCHAR(255);
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- JWTs outgrow 255 characters once they carry a client_id or fingerprint, and
+-- replaced refresh tokens are now recorded here
+ALTER TABLE sessions
+ALTER COLUMN token TYPE TEXT,
+ALTER COLUMN refresh_token TYPE TEXT;
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+
+use crate::errors::AppError;
+use crate::models::audit::AuditExportQuery;
+use crate::models::common::response::ApiResponse;
+use crate::services::audit::AuditService;
+
+// Handler to download the audit entries matching the filters as CSV or JSON lines.
+// The body is streamed, so exports of any size can be taken in one request.
+pub async fn export_audit_logs(
+    Query(query): Query<AuditExportQuery>,
+    State(audit_service): State<Arc<AuditService>>,
+) -> Result<Response, AppError> {
+    let format = query.format;
+    let rows = audit_service.export(query)?;
+    let filename = format!(
+        "audit-{}.{}",
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        format.extension()
+    );
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(rows),
+    )
+        .into_response())
+}
+
+// Handler to get how many logins, refreshes and logouts succeeded and failed since
+// this instance started
+pub async fn auth_event_counts(State(audit_service): State<Arc<AuditService>>) -> Response {
+    ApiResponse::success(StatusCode::OK, audit_service.auth_event_counts())
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use axum::{middleware, routing::get, Router};
+
+use crate::db::repositories::Repositories;
+use crate::middleware::auth::{require_admin, require_auth, require_verified_email};
+use crate::services::audit::AuditService;
+use crate::services::auth::TokenService;
+
+use super::handlers;
+
+// Configure the audit log routes, all admin only
+pub fn configure(
+    state: Arc<Repositories>,
+    token_service: Arc<TokenService>,
+    audit_service: Arc<AuditService>,
+) -> Router {
+    Router::new()
+        .route("/export", get(handlers::export_audit_logs))
+        .route("/auth-events", get(handlers::auth_event_counts))
+        .route_layer(middleware::from_fn(require_admin))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_verified_email,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (state, token_service),
+            require_auth,
+        ))
+        .with_state(audit_service)
+}
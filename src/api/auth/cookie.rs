@@ -0,0 +1,79 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use chrono::{DateTime, Utc};
+
+use crate::config::AppConfig;
+use crate::errors::AppError;
+
+// Name of the cookie that carries the refresh token with REFRESH_TOKEN_COOKIE on
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+
+// The refresh token cookie: out of reach of scripts, only sent over HTTPS, never on
+// cross-site requests and only to the auth endpoints
+pub fn refresh_token_cookie(
+    config: &AppConfig,
+    refresh_token: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<HeaderValue, AppError> {
+    let max_age = (expires_at - Utc::now()).num_seconds().max(0);
+    cookie_header(config, refresh_token, max_age)
+}
+
+// Tells the browser to drop the refresh token cookie
+pub fn expired_refresh_token_cookie(config: &AppConfig) -> Result<HeaderValue, AppError> {
+    cookie_header(config, "", 0)
+}
+
+fn cookie_header(config: &AppConfig, value: &str, max_age: i64) -> Result<HeaderValue, AppError> {
+    HeaderValue::from_str(&format!(
+        "{}={}; Path={}/auth; Max-Age={}; HttpOnly; Secure; SameSite=Strict",
+        REFRESH_TOKEN_COOKIE, value, config.api_base_path, max_age
+    ))
+    .map_err(|_| AppError::Internal("Failed to build refresh token cookie".into()))
+}
+
+// The refresh token the browser sent, if any
+pub fn refresh_token_from_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == REFRESH_TOKEN_COOKIE)
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use chrono::Duration;
+
+    #[test]
+    fn refresh_token_round_trips_through_the_cookie() {
+        let mut config = test_config();
+        config.api_base_path = "/api/v1".to_string();
+
+        let cookie =
+            refresh_token_cookie(&config, "abc.def", Utc::now() + Duration::hours(1)).unwrap();
+        let cookie = cookie.to_str().unwrap();
+        assert!(cookie.starts_with("refresh_token=abc.def; Path=/api/v1/auth; Max-Age=3"));
+        assert!(cookie.ends_with("; HttpOnly; Secure; SameSite=Strict"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; refresh_token=abc.def"),
+        );
+        assert_eq!(refresh_token_from_cookie(&headers), Some("abc.def"));
+
+        headers.insert(header::COOKIE, HeaderValue::from_static("refresh_token="));
+        assert_eq!(refresh_token_from_cookie(&headers), None);
+        assert!(expired_refresh_token_cookie(&config)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .contains("Max-Age=0;"));
+    }
+}
@@ -2,37 +2,109 @@ use std::sync::Arc;
 
 use axum::extract::Extension;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Redirect, Response},
-    Json,
+    extract::{rejection::FormRejection, OriginalUri, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
 };
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{Duration, Utc};
+use oauth2::url::{form_urlencoded, Url};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 use validator::Validate;
 
+use super::cookie::{
+    expired_refresh_token_cookie, refresh_token_cookie, refresh_token_from_cookie,
+};
 use super::routes::AuthApiState;
+use crate::api::extract::Json;
+use crate::config::{AppConfig, OAuthTokenDelivery};
 use crate::errors::AppError;
 use crate::middleware::auth::Claims;
-use crate::models::auth::oauth::{OAuthCallbackQuery, OAuthStartQuery};
+use crate::middleware::client_context::ClientContext;
+use crate::middleware::request_id::RequestId;
+use crate::models::audit::AuthEventKind;
+use crate::models::auth::grant::{
+    TokenGrantDto, TokenGrantError, TokenGrantResponse, GRANT_TYPE_PASSWORD,
+    GRANT_TYPE_REFRESH_TOKEN,
+};
+use crate::models::auth::invite::CreateInviteDto;
+use crate::models::auth::oauth::{
+    CreateOAuthProviderDto, OAuthCallbackQuery, OAuthConnectionListQuery, OAuthProviderListQuery,
+    OAuthStartQuery, UpdateOAuthProviderDto,
+};
+use crate::models::auth::token::{EmailTokenDto, RefreshTokenState};
+use crate::models::common::pagination::PaginationQuery;
 use crate::models::common::response::ApiResponse;
 use crate::models::user::{
-    CreateUserDto, LoginDto, PasswordResetDto, ResendVerificationEmailDto, UserResponse,
+    AuthResponse, CreateUserDto, LoginDto, PasswordResetDto, RegisterResponse,
+    ResendVerificationEmailDto,
 };
 use crate::services::validation::validation_err_to_app_error;
 
 // Login handler
 pub async fn login(
+    request_id: RequestId,
+    client: ClientContext,
     State(state): State<Arc<AuthApiState>>,
     Json(credentials): Json<LoginDto>,
 ) -> Result<Response, AppError> {
+    let mut response = password_login(&state, &request_id, &client, &credentials).await?;
+
+    if !state.config.refresh_token_cookie {
+        return Ok(ApiResponse::success(StatusCode::OK, response));
+    }
+
+    // Scripts never see the refresh token; the browser sends it back to /refresh
+    let refresh_token = std::mem::take(&mut response.refresh_token);
+    let expires_at = Utc::now() + Duration::seconds(state.config.refresh_token_expiration);
+    let cookie = refresh_token_cookie(&state.config, &refresh_token, expires_at)?;
+
+    let mut response = ApiResponse::success(StatusCode::OK, response);
+    response.headers_mut().append(header::SET_COOKIE, cookie);
+    Ok(response)
+}
+
+// Check the credentials and issue tokens, recording the attempt
+async fn password_login(
+    state: &AuthApiState,
+    request_id: &RequestId,
+    client: &ClientContext,
+    credentials: &LoginDto,
+) -> Result<AuthResponse, AppError> {
     // Validate credentials
     credentials
         .validate()
         .map_err(validation_err_to_app_error)?;
 
     // Call auth service to login
-    let response = state.auth_service.login(&credentials).await?;
+    let result = state.auth_service.login(credentials, client).await;
+
+    // Record the login attempt. What was typed in is only kept when it names an
+    // account, so guesses at unknown accounts leave no personal data behind.
+    let user_id = match &result {
+        Ok(response) => Some(response.user.id),
+        Err(_) => {
+            state
+                .auth_service
+                .login_account_id(&credentials.email)
+                .await
+        }
+    };
+    state
+        .audit_service
+        .record_auth_event(
+            AuthEventKind::Login,
+            user_id,
+            &result,
+            request_id,
+            client,
+            user_id.map(|_| serde_json::json!({ "identifier": credentials.email })),
+        )
+        .await;
 
-    Ok(ApiResponse::success(StatusCode::OK, response))
+    result
 }
 
 // Register handler
@@ -43,72 +115,250 @@ pub async fn register(
     // Validate registration data
     dto.validate().map_err(validation_err_to_app_error)?;
 
-    // Register the user
-    let user = state
-        .user_management_service
-        .register_user(dto.clone())
-        .await?;
+    // Register the user, redeeming the invite if there is one
+    let user = state.invite_service.register(dto).await?;
 
-    // Send verification email (non-blocking)
-    state
-        .email_service
-        .send_verification_email(user.id, &user.email, &user.username)
-        .await?;
+    // Invited accounts are already verified. Without email there's no way to verify,
+    // so accounts are verified straight away.
+    let (user, verification_email_sent) = if user.is_email_verified {
+        (state.user_management_service.user_response(user), false)
+    } else if !state.email_service.is_enabled() {
+        let user = state.user_management_service.verify_email(user.id).await?;
+        (user, false)
+    } else {
+        // The account exists either way; the user can ask for the email again
+        let sent = state
+            .email_service
+            .send_verification_email(user.id, &user.email, &user.username)
+            .await;
+        if let Err(e) = &sent {
+            tracing::error!("Failed to send verification email to {}: {}", user.id, e);
+        }
+        (
+            state.user_management_service.user_response(user),
+            sent.is_ok(),
+        )
+    };
 
-    // Return registered user data
-    Ok(ApiResponse::created(UserResponse::from(user)))
+    Ok(ApiResponse::created(RegisterResponse {
+        user,
+        verification_email_sent,
+    }))
 }
 
-// Refresh token handler
+// Refresh token handler. API clients send the refresh token in the body; with the
+// refresh token cookie on, browsers send the cookie and get a rotated one back.
 pub async fn refresh_token(
+    request_id: RequestId,
+    client: ClientContext,
+    headers: HeaderMap,
     State(state): State<Arc<AuthApiState>>,
-    Json(data): Json<serde_json::Value>,
+    body: Option<Json<serde_json::Value>>,
 ) -> Result<Response, AppError> {
-    // Extract refresh token from request
-    let refresh_token = data
-        .get("refresh_token")
+    let (refresh_token, from_cookie) = request_refresh_token(&state.config, &body, &headers)?;
+
+    let new_token = refresh_access_token(&state, &request_id, &client, refresh_token).await?;
+
+    let mut response =
+        ApiResponse::success(StatusCode::OK, serde_json::json!({ "token": new_token }));
+    if from_cookie {
+        let (rotated, expires_at) = state
+            .auth_service
+            .rotate_refresh_token(refresh_token, &new_token)
+            .await?;
+        let cookie = refresh_token_cookie(&state.config, &rotated, expires_at)?;
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+
+    Ok(response)
+}
+
+// The refresh token from the body or, with the refresh token cookie on, the cookie.
+// The body wins, so API clients are unaffected by the cookie. Also says whether the
+// token came from the cookie.
+fn request_refresh_token<'a>(
+    config: &AppConfig,
+    body: &'a Option<Json<serde_json::Value>>,
+    headers: &'a HeaderMap,
+) -> Result<(&'a str, bool), AppError> {
+    if let Some(refresh_token) = body
+        .as_ref()
+        .and_then(|Json(data)| data.get("refresh_token"))
         .and_then(|v| v.as_str())
-        .ok_or_else(|| AppError::Validation("Refresh token is required".to_string()))?;
+    {
+        return Ok((refresh_token, false));
+    }
 
-    // Call token service to refresh
-    let new_token = state.token_service.refresh_token(refresh_token)?;
+    config
+        .refresh_token_cookie
+        .then(|| refresh_token_from_cookie(headers))
+        .flatten()
+        .map(|refresh_token| (refresh_token, true))
+        .ok_or_else(|| AppError::Validation("Refresh token is required".to_string()))
+}
 
-    Ok(ApiResponse::success(
-        StatusCode::OK,
-        serde_json::json!({ "token": new_token }),
-    ))
+// Issue a new access token from a refresh token, recording the attempt
+async fn refresh_access_token(
+    state: &AuthApiState,
+    request_id: &RequestId,
+    client: &ClientContext,
+    refresh_token: &str,
+) -> Result<String, AppError> {
+    // Call auth service to refresh
+    let result = state
+        .auth_service
+        .refresh_token(refresh_token, client)
+        .await;
+
+    // Record the refresh attempt
+    state
+        .audit_service
+        .record_auth_event(
+            AuthEventKind::Refresh,
+            result.as_ref().ok().map(|(user_id, _)| *user_id),
+            &result,
+            request_id,
+            client,
+            None,
+        )
+        .await;
+
+    result.map(|(_, new_token)| new_token)
+}
+
+// OAuth2 token endpoint for generic clients and CLI tools: the password and
+// refresh_token grants, with RFC 6749 requests, responses and errors. The app's own
+// frontend uses /login and /refresh.
+pub async fn token(
+    request_id: RequestId,
+    client: ClientContext,
+    State(state): State<Arc<AuthApiState>>,
+    form: Result<Form<TokenGrantDto>, FormRejection>,
+) -> Result<TokenGrantResponse, TokenGrantError> {
+    let Form(grant) =
+        form.map_err(|rejection| TokenGrantError::invalid_request(rejection.body_text()))?;
+    match grant.grant_type.as_str() {
+        GRANT_TYPE_PASSWORD => {
+            let (Some(email), Some(password)) = (grant.username, grant.password) else {
+                return Err(TokenGrantError::invalid_request(
+                    "username and password are required".to_string(),
+                ));
+            };
+            let credentials = LoginDto {
+                email,
+                password,
+                // Some clients send every parameter, empty or not
+                client_id: grant.client_id.filter(|client_id| !client_id.is_empty()),
+            };
+            let auth = password_login(&state, &request_id, &client, &credentials).await?;
+            let expires_in = access_token_expires_in(&state, &auth.token)?;
+
+            Ok(TokenGrantResponse::bearer(
+                auth.token,
+                Some(auth.refresh_token),
+                expires_in,
+            ))
+        }
+        GRANT_TYPE_REFRESH_TOKEN => {
+            let refresh_token = grant.refresh_token.ok_or_else(|| {
+                TokenGrantError::invalid_request("refresh_token is required".to_string())
+            })?;
+            let access_token =
+                refresh_access_token(&state, &request_id, &client, &refresh_token).await?;
+            let expires_in = access_token_expires_in(&state, &access_token)?;
+
+            Ok(TokenGrantResponse::bearer(access_token, None, expires_in))
+        }
+        other => Err(TokenGrantError::unsupported_grant_type(other)),
+    }
+}
+
+// Access token lifetimes depend on the client they were issued to, so expires_in is
+// read back from the token
+fn access_token_expires_in(state: &AuthApiState, access_token: &str) -> Result<i64, AppError> {
+    let claims = state.token_service.verify_token(access_token)?;
+    Ok(claims.exp - claims.iat)
 }
 
 // Logout handler
 pub async fn logout(
+    request_id: RequestId,
+    client: ClientContext,
+    headers: HeaderMap,
     State(state): State<Arc<AuthApiState>>,
-    Json(data): Json<serde_json::Value>,
+    body: Option<Json<serde_json::Value>>,
 ) -> Result<Response, AppError> {
     // Extract refresh token from request
-    let refresh_token = data
-        .get("refresh_token")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| AppError::Validation("Refresh token is required".to_string()))?;
+    let (refresh_token, _) = request_refresh_token(&state.config, &body, &headers)?;
 
-    // Get user ID from token
-    let user_id = state.token_service.get_user_id_from_token(refresh_token)?;
+    let result = state.auth_service.logout(refresh_token).await;
 
-    // Call auth service to logout
-    state.auth_service.logout(user_id).await?;
+    // Record the logout attempt
+    state
+        .audit_service
+        .record_auth_event(
+            AuthEventKind::Logout,
+            result.as_ref().ok().and_then(|(user_id, _)| *user_id),
+            &result,
+            &request_id,
+            &client,
+            result
+                .as_ref()
+                .ok()
+                .map(|(_, token_state)| serde_json::json!({ "token_state": token_state })),
+        )
+        .await;
 
-    Ok(ApiResponse::success(
+    let (_, token_state) = result?;
+
+    let mut response = ApiResponse::success(
         StatusCode::OK,
-        "Logged out successfully",
-    ))
+        serde_json::json!({
+            "was_active": token_state == RefreshTokenState::Active,
+            "token_state": token_state,
+        }),
+    );
+    if state.config.refresh_token_cookie {
+        let cookie = expired_refresh_token_cookie(&state.config)?;
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+
+    Ok(response)
 }
 
-// Verify email handler
-pub async fn verify_email(
+// Check an emailed verification link without using it (the token in the path)
+pub async fn check_email_verification(
     Path(token): Path<String>,
     State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let pending = state
+        .auth_service
+        .check_email_verification_token(&token)
+        .await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, pending))
+}
+
+// Same as check_email_verification, with the token as a query parameter
+pub async fn check_email_verification_query(
+    Query(query): Query<EmailTokenDto>,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let pending = state
+        .auth_service
+        .check_email_verification_token(&query.token)
+        .await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, pending))
+}
+
+// Verify email handler. Takes a POST so that fetching the emailed link can't verify.
+pub async fn verify_email(
+    State(state): State<Arc<AuthApiState>>,
+    Json(dto): Json<EmailTokenDto>,
 ) -> Result<Response, AppError> {
     // Verify the token
-    let user = state.auth_service.verify_email_token(&token).await?;
+    let user = state.auth_service.verify_email_token(&dto.token).await?;
 
     Ok(ApiResponse::success(StatusCode::OK, user))
 }
@@ -124,20 +374,14 @@ pub async fn request_password_reset(
         .and_then(|v| v.as_str())
         .ok_or_else(|| AppError::Validation("Email is required".to_string()))?;
 
-    // Call service to request password reset
-    let token = state.auth_service.request_password_reset(email).await?;
-
-    // Get user by email
-    let user = state
-        .user_management_service
-        .get_user_by_email(email)
-        .await?;
-
-    // Send password reset email (non-blocking)
-    state
-        .email_service
-        .send_password_reset_email(email, &user.username, &token)
-        .await?;
+    // Only email when there's a fresh link to send; the response is the same either way
+    let reset = state.auth_service.request_password_reset(email).await?;
+    if let Some((user, address, token)) = reset {
+        state
+            .email_service
+            .send_password_reset_email(&address, &user.username, &token)
+            .await?;
+    }
 
     Ok(ApiResponse::success(
         StatusCode::OK,
@@ -176,6 +420,19 @@ pub async fn resend_verification_email(
     ))
 }
 
+// Check an emailed password reset link without using it
+pub async fn check_password_reset(
+    Query(query): Query<EmailTokenDto>,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let pending = state
+        .auth_service
+        .check_password_reset_token(&query.token)
+        .await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, pending))
+}
+
 // Reset password handler
 pub async fn reset_password(
     State(state): State<Arc<AuthApiState>>,
@@ -211,6 +468,179 @@ pub async fn get_current_user(
     Ok(ApiResponse::success(StatusCode::OK, user))
 }
 
+// Handler to sign out every other device, keeping the session making this request
+pub async fn revoke_other_sessions(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let user_id = claims
+        .sub
+        .parse()
+        .map_err(|_| AppError::Authentication("Invalid user ID in token".into()))?;
+    let current_session = claims
+        .session_id
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok());
+
+    let revoked = state
+        .auth_service
+        .revoke_other_sessions(user_id, current_session)
+        .await?;
+
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        serde_json::json!({ "revoked": revoked }),
+    ))
+}
+
+// Handler for the number of active sessions, for clients that only need the count
+pub async fn count_current_user_sessions(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let user_id = claims
+        .sub
+        .parse()
+        .map_err(|_| AppError::Authentication("Invalid user ID in token".into()))?;
+    let count = state.auth_service.count_active_sessions(user_id).await?;
+
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        serde_json::json!({ "count": count }),
+    ))
+}
+
+// Handler for the current user's effective capabilities
+pub async fn get_current_user_permissions(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let user_id = claims
+        .sub
+        .parse()
+        .map_err(|_| AppError::Authentication("Invalid user ID in token".into()))?;
+    let permissions = state
+        .auth_service
+        .get_permissions(user_id, claims.impersonator.is_some())
+        .await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, permissions))
+}
+
+// Handler to list the OAuth providers available for login
+pub async fn list_oauth_providers(
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let providers = state.auth_service.list_active_oauth_providers().await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, providers))
+}
+
+// Handler to list OAuth providers for administration, optionally including disabled ones
+pub async fn list_oauth_providers_admin(
+    Query(query): Query<OAuthProviderListQuery>,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let providers = state
+        .auth_service
+        .list_oauth_providers(query.include_inactive)
+        .await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, providers))
+}
+
+// Handler to list the OAuth logins linked to the current user's account
+pub async fn list_current_user_oauth_connections(
+    Extension(claims): Extension<Claims>,
+    pagination: PaginationQuery,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let user_id = claims
+        .sub
+        .parse()
+        .map_err(|_| AppError::Authentication("Invalid user ID in token".into()))?;
+    let connections = state
+        .auth_service
+        .list_oauth_connections(Some(user_id), None, pagination.page, pagination.limit)
+        .await?
+        .with_max_limit(pagination.max_limit)
+        .with_links(&uri);
+
+    Ok(ApiResponse::success(StatusCode::OK, connections))
+}
+
+// Handler to list OAuth logins across users, optionally by provider or user (admin only)
+pub async fn list_oauth_connections_admin(
+    Query(query): Query<OAuthConnectionListQuery>,
+    pagination: PaginationQuery,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    let provider = query.provider.as_deref().map(str::trim);
+    let connections = state
+        .auth_service
+        .list_oauth_connections(query.user_id, provider, pagination.page, pagination.limit)
+        .await?
+        .with_max_limit(pagination.max_limit)
+        .with_links(&uri);
+
+    Ok(ApiResponse::success(StatusCode::OK, connections))
+}
+
+// Handler to register an OAuth provider (admin only)
+pub async fn create_oauth_provider(
+    State(state): State<Arc<AuthApiState>>,
+    Json(dto): Json<CreateOAuthProviderDto>,
+) -> Result<Response, AppError> {
+    let provider = state.auth_service.create_oauth_provider(dto).await?;
+
+    Ok(ApiResponse::created(provider))
+}
+
+// Handler to update an OAuth provider (admin only)
+pub async fn update_oauth_provider(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AuthApiState>>,
+    Json(dto): Json<UpdateOAuthProviderDto>,
+) -> Result<Response, AppError> {
+    let provider = state.auth_service.update_oauth_provider(id, dto).await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, provider))
+}
+
+// Handler to invite someone to register (admin only)
+pub async fn create_invite(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<AuthApiState>>,
+    Json(dto): Json<CreateInviteDto>,
+) -> Result<Response, AppError> {
+    let admin_id = claims
+        .sub
+        .parse()
+        .map_err(|_| AppError::Authentication("Invalid user ID in token".into()))?;
+    let invite = state.invite_service.create_invite(admin_id, dto).await?;
+
+    Ok(ApiResponse::created(invite))
+}
+
+// Handler to list invites that can still be used (admin only)
+pub async fn list_invites(State(state): State<Arc<AuthApiState>>) -> Result<Response, AppError> {
+    let invites = state.invite_service.list_pending().await?;
+
+    Ok(ApiResponse::success(StatusCode::OK, invites))
+}
+
+// Handler to withdraw an unused invite (admin only)
+pub async fn revoke_invite(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AuthApiState>>,
+) -> Result<Response, AppError> {
+    state.invite_service.revoke_invite(id).await?;
+
+    Ok(ApiResponse::no_content())
+}
+
 // Handler to start the OAuth login process
 pub async fn oauth_start(
     Path(provider): Path<String>,
@@ -222,6 +652,13 @@ pub async fn oauth_start(
 
     // If redirect_uri is provided, modify the state parameter to include it
     if let Some(redirect_uri) = &query.redirect_uri {
+        // Refuse to start a flow that would end on an untrusted site
+        if !is_trusted_redirect(&state.config, redirect_uri) {
+            return Err(AppError::Validation(
+                "redirect_uri is not an allowed redirect URI".into(),
+            ));
+        }
+
         // Check if the URL already has a state parameter
         if auth_url.contains("state=") {
             // Extract the existing state value
@@ -259,6 +696,8 @@ pub async fn oauth_start(
 
 // Handler for OAuth callback
 pub async fn oauth_callback(
+    request_id: RequestId,
+    client: ClientContext,
     Path(provider): Path<String>,
     Query(query): Query<OAuthCallbackQuery>,
     State(state): State<Arc<AuthApiState>>,
@@ -287,41 +726,193 @@ pub async fn oauth_callback(
     };
 
     // Exchange code for token
-    let auth_response = state
+    let result = state
         .auth_service
-        .handle_oauth_callback(&provider, &query.code)
-        .await?;
+        .handle_oauth_callback(&provider, &query.code, &client)
+        .await;
 
-    let frontend_url = state.config.email.frontend_url.clone();
-
-    // Determine the redirect URL
-    // Always redirect to frontend callback first, passing redirect_uri as a query parameter
-    let redirect_url = if let Some(redirect_uri) = query.redirect_uri {
-        format!(
-            "{}/auth/callback?redirect_uri={}&token={}&refresh_token={}",
-            frontend_url.trim_end_matches('/'),
-            urlencoding::encode(&redirect_uri),
-            auth_response.token,
-            auth_response.refresh_token
-        )
-    } else if let Some(custom_redirect) = custom_redirect {
-        format!(
-            "{}/auth/callback?redirect_uri={}&token={}&refresh_token={}",
-            frontend_url.trim_end_matches('/'),
-            urlencoding::encode(&custom_redirect),
-            auth_response.token,
-            auth_response.refresh_token
-        )
-    } else {
-        // Default to frontend URL with /auth/callback without redirect_uri
-        format!(
-            "{}/auth/callback?token={}&refresh_token={}",
-            frontend_url.trim_end_matches('/'),
-            auth_response.token,
-            auth_response.refresh_token
+    // Record the OAuth login attempt
+    state
+        .audit_service
+        .record_auth_event(
+            AuthEventKind::OAuthLogin,
+            result.as_ref().ok().map(|response| response.user.id),
+            &result,
+            &request_id,
+            &client,
+            Some(serde_json::json!({ "provider": provider })),
         )
+        .await;
+
+    let auth_response = result?;
+
+    // Only pass a redirect_uri on to the frontend if it points at a trusted frontend;
+    // otherwise the frontend could be tricked into forwarding the tokens elsewhere
+    let redirect_uri = query.redirect_uri.or(custom_redirect).filter(|uri| {
+        let trusted = is_trusted_redirect(&state.config, uri);
+        if !trusted {
+            tracing::warn!(
+                request_id = %request_id,
+                redirect_uri = %uri,
+                "Ignoring untrusted OAuth redirect_uri"
+            );
+        }
+        trusted
+    });
+
+    // Always send the browser to the frontend callback first, with every parameter encoded
+    let mut callback_url = Url::parse(&format!(
+        "{}/auth/callback",
+        state.config.email.frontend_url.trim_end_matches('/')
+    ))
+    .map_err(|e| AppError::Configuration(format!("Invalid FRONTEND_URL: {}", e)))?;
+
+    if let Some(redirect_uri) = &redirect_uri {
+        callback_url
+            .query_pairs_mut()
+            .append_pair("redirect_uri", redirect_uri);
+    }
+
+    // The browser never sees the JSON AuthResponse, so pass its login details along too
+    let is_new_user = auth_response.is_new_user.unwrap_or(false).to_string();
+    let tokens = [
+        ("token", auth_response.token.as_str()),
+        ("refresh_token", auth_response.refresh_token.as_str()),
+        ("login_method", auth_response.login_method),
+        (
+            "provider",
+            auth_response.provider.as_deref().unwrap_or(&provider),
+        ),
+        ("is_new_user", is_new_user.as_str()),
+    ];
+
+    let response = match state.config.oauth.token_delivery {
+        OAuthTokenDelivery::Fragment => {
+            let fragment = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(tokens)
+                .finish();
+            callback_url.set_fragment(Some(&fragment));
+            Redirect::to(callback_url.as_str()).into_response()
+        }
+        OAuthTokenDelivery::FormPost => token_form_post(&callback_url, &tokens),
+        OAuthTokenDelivery::Query => {
+            callback_url.query_pairs_mut().extend_pairs(tokens);
+            Redirect::to(callback_url.as_str()).into_response()
+        }
+    };
+
+    // Keep the tokens out of caches and away from the next page's Referer
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::REFERRER_POLICY, "no-referrer"),
+        ],
+        response,
+    )
+        .into_response())
+}
+
+// Submits the token form as soon as the page loads
+const FORM_POST_SCRIPT: &str = "document.forms[0].submit()";
+
+// A page that immediately POSTs the tokens to the frontend callback. The API's default
+// Content-Security-Policy allows no scripts or form submissions, so the page carries its
+// own: just its script, by hash, and posting to the callback's origin.
+fn token_form_post(action: &Url, fields: &[(&str, &str)]) -> Response {
+    let csp = format!(
+        "default-src 'none'; script-src 'sha256-{}'; form-action {}; frame-ancestors 'none'",
+        STANDARD.encode(Sha256::digest(FORM_POST_SCRIPT)),
+        action.origin().ascii_serialization()
+    );
+
+    (
+        [(header::CONTENT_SECURITY_POLICY, csp)],
+        Html(token_form_page(action, fields)),
+    )
+        .into_response()
+}
+
+fn token_form_page(action: &Url, fields: &[(&str, &str)]) -> String {
+    let inputs: String = fields
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                r#"<input type="hidden" name="{}" value="{}">"#,
+                html_escape(name),
+                html_escape(value)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>Signing in...</title></head><body><form method="post" action="{}">{}<noscript><button type="submit">Continue</button></noscript></form><script>{}</script></body></html>"#,
+        html_escape(action.as_str()),
+        inputs,
+        FORM_POST_SCRIPT
+    )
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+// A redirect is trusted if, once resolved against the frontend URL, it is on
+// OAUTH_ALLOWED_REDIRECT_URIS, or (when no allowlist is configured) on the frontend's
+// origin or one of the explicitly allowed CORS origins
+fn is_trusted_redirect(config: &AppConfig, redirect_uri: &str) -> bool {
+    // Resolving also turns "//host" and "/\host" into the external URLs they really are
+    let resolved = match Url::parse(&config.email.frontend_url)
+        .and_then(|frontend| frontend.join(redirect_uri))
+    {
+        Ok(url) => url,
+        Err(_) => return false,
     };
 
-    // Redirect to frontend with tokens
-    Ok(Redirect::to(&redirect_url).into_response())
+    if !config.oauth.allowed_redirect_uris.is_empty() {
+        return config.oauth.is_allowed_redirect(&resolved);
+    }
+
+    std::iter::once(config.email.frontend_url.as_str())
+        .chain(config.cors_allowed_origins.iter().map(String::as_str))
+        .filter_map(|trusted| Url::parse(trusted).ok())
+        .any(|trusted| trusted.origin() == resolved.origin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn form_post_page_is_allowed_to_submit_itself() {
+        let action = Url::parse("https://app.example.com/auth/callback").unwrap();
+        let response = token_form_post(&action, &[("token", "abc")]);
+
+        let csp = response.headers()[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        // The page's only script is the one the policy allows by hash
+        let script = body
+            .split_once("<script>")
+            .and_then(|(_, rest)| rest.split_once("</script>"))
+            .map(|(script, _)| script)
+            .unwrap();
+        assert!(csp.contains(&format!(
+            "script-src 'sha256-{}'",
+            STANDARD.encode(Sha256::digest(script))
+        )));
+        assert!(!body.contains("onload"));
+        assert!(csp.contains("form-action https://app.example.com;"));
+        assert!(body.contains(r#"action="https://app.example.com/auth/callback""#));
+    }
 }
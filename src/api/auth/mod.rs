@@ -1,3 +1,4 @@
+mod cookie;
 mod handlers;
 mod routes;
 
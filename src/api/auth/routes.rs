@@ -1,26 +1,37 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     middleware,
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Router,
 };
 
 use crate::config::AppConfig;
 use crate::db::repositories::Repositories;
-use crate::middleware::auth::{require_auth, require_verified_email};
+use crate::middleware::auth::{
+    deny_impersonation, require_admin, require_auth, require_auth_allow_expired_password,
+    require_verified_email,
+};
+use crate::middleware::rate_limit::{
+    limit_failed_attempts, rate_limit_store, throttle_registrations, AttemptLimiter,
+    RegistrationThrottle,
+};
+use crate::services::audit::AuditService;
 use crate::services::auth::{AuthService, TokenService};
 use crate::services::email::EmailService;
-use crate::services::user::UserManagementService;
+use crate::services::user::{InviteService, UserManagementService};
 
 use super::handlers;
 
 // Auth API State struct
 pub struct AuthApiState {
-    pub token_service: Arc<TokenService>,
     pub user_management_service: Arc<UserManagementService>,
     pub auth_service: Arc<AuthService>,
     pub email_service: Arc<EmailService>,
+    pub audit_service: Arc<AuditService>,
+    pub invite_service: Arc<InviteService>,
+    pub token_service: Arc<TokenService>,
     pub config: AppConfig,
 }
 
@@ -31,30 +42,82 @@ pub fn configure(
     user_management_service: Arc<UserManagementService>,
     auth_service: Arc<AuthService>,
     email_service: Arc<EmailService>,
+    audit_service: Arc<AuditService>,
     config: AppConfig,
 ) -> Router {
+    let token_attempt_limiter = Arc::new(AttemptLimiter::new(
+        rate_limit_store(&config.rate_limit_store),
+        "auth_token_attempts",
+        config.token_attempt_limit,
+        Duration::from_secs(config.token_attempt_window),
+    ));
+
+    let registration_throttle = Arc::new(RegistrationThrottle::new(
+        rate_limit_store(&config.rate_limit_store),
+        config.registration_limit_global,
+        config.registration_limit_per_subnet,
+    ));
+
+    let invite_service = Arc::new(
+        InviteService::new(
+            repos.clone(),
+            user_management_service.clone(),
+            email_service.clone(),
+            config.registration_mode,
+        )
+        .with_allowed_email_domains(config.allowed_email_domains.clone()),
+    );
+
     let state = Arc::new(AuthApiState {
-        token_service: token_service.clone(),
         user_management_service,
         auth_service,
         email_service,
+        audit_service,
+        invite_service,
+        token_service: token_service.clone(),
         config,
     });
 
     // Public routes - no auth required
     let public_routes = Router::new()
         .route("/login", post(handlers::login))
-        .route("/register", post(handlers::register))
         .route("/refresh", post(handlers::refresh_token))
-        .route("/verify-email/:token", get(handlers::verify_email))
+        .route("/token", post(handlers::token))
         .route(
             "/request-password-reset",
             post(handlers::request_password_reset),
         )
-        .route("/reset-password", post(handlers::reset_password))
+        .route("/oauth/providers", get(handlers::list_oauth_providers))
         .route("/oauth/:provider", get(handlers::oauth_start))
         .route("/oauth/:provider/callback", get(handlers::oauth_callback));
 
+    // Self-registration, throttled to survive signup floods
+    let registration_routes = Router::new()
+        .route("/register", post(handlers::register))
+        .route_layer(middleware::from_fn_with_state(
+            registration_throttle,
+            throttle_registrations,
+        ));
+
+    // Public routes that take a secret token - failed guesses lock the client out
+    let token_routes = Router::new()
+        .route(
+            "/verify-email",
+            get(handlers::check_email_verification_query).post(handlers::verify_email),
+        )
+        .route(
+            "/verify-email/:token",
+            get(handlers::check_email_verification),
+        )
+        .route(
+            "/reset-password",
+            get(handlers::check_password_reset).post(handlers::reset_password),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            token_attempt_limiter,
+            limit_failed_attempts,
+        ));
+
     // Auth routes that don't require email verification
     let unverified_auth_routes = Router::new()
         .route(
@@ -66,10 +129,62 @@ pub fn configure(
             require_auth,
         ));
 
+    // Available before email verification and after the password expires, so clients
+    // can find out what the user is blocked on
+    let permission_routes = Router::new()
+        .route(
+            "/me/permissions",
+            get(handlers::get_current_user_permissions),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            (repos.clone(), token_service.clone()),
+            require_auth_allow_expired_password,
+        ));
+
     // Auth routes that require email verification
     let verified_auth_routes = Router::new()
         .route("/logout", post(handlers::logout))
         .route("/me", get(handlers::get_current_user))
+        .route(
+            "/me/sessions/count",
+            get(handlers::count_current_user_sessions),
+        )
+        .route(
+            "/me/oauth/connections",
+            get(handlers::list_current_user_oauth_connections),
+        )
+        .route(
+            "/sessions/revoke-others",
+            post(handlers::revoke_other_sessions).layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            repos.clone(),
+            require_verified_email,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (repos.clone(), token_service.clone()),
+            require_auth,
+        ));
+
+    // OAuth provider, connection and invite management - admin only
+    let admin_routes = Router::new()
+        .route("/oauth/providers", post(handlers::create_oauth_provider))
+        .route(
+            "/oauth/providers/admin",
+            get(handlers::list_oauth_providers_admin),
+        )
+        .route(
+            "/oauth/providers/:id",
+            patch(handlers::update_oauth_provider),
+        )
+        .route(
+            "/oauth/connections",
+            get(handlers::list_oauth_connections_admin),
+        )
+        .route("/invites", post(handlers::create_invite))
+        .route("/invites", get(handlers::list_invites))
+        .route("/invites/:id", delete(handlers::revoke_invite))
+        .route_layer(middleware::from_fn(require_admin))
         .route_layer(middleware::from_fn_with_state(
             repos.clone(),
             require_verified_email,
@@ -81,7 +196,11 @@ pub fn configure(
 
     // Merge all routes
     public_routes
+        .merge(registration_routes)
+        .merge(token_routes)
         .merge(unverified_auth_routes)
+        .merge(permission_routes)
         .merge(verified_auth_routes)
+        .merge(admin_routes)
         .with_state(state)
 }
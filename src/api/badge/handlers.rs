@@ -1,46 +1,68 @@
 use std::sync::Arc;
 
+use crate::api::extract::Json;
 use crate::db::repositories::Repositories;
 use crate::errors::AppError;
+use crate::middleware::auth::Claims;
+use crate::middleware::request_id::RequestId;
+use crate::models::audit::AUDIT_EVENT_ADMIN_REVOKE_BADGE;
 use crate::models::badge::{CreateBadgeDto, UpdateBadgeDto};
 use crate::models::common::response::ApiResponse;
-use crate::models::common::PaginationQuery;
-use crate::models::user::AwardBadgeDto;
+use crate::models::common::{BulkOperationQuery, CreatedRangeQuery, FieldsQuery, PaginationQuery};
+use crate::models::user::{
+    AwardBadgeDto, AwardBadgeQuery, BulkAwardBadgeDto, BulkRevokeBadgeDto, RevokeAllBadgeResponse,
+};
+use crate::services::audit::AuditService;
 use crate::services::badge::BadgeService;
 use crate::services::validation::validation_err_to_app_error;
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Extension, OriginalUri, Path, Query, State},
     http::StatusCode,
     response::Response,
-    Json,
 };
+use jsonwebtoken::jwk::JwkSet;
 use uuid::Uuid;
 use validator::Validate;
 
 // Handler to get all badges with pagination
 pub async fn get_badges(
-    Query(query): Query<PaginationQuery>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    pagination: PaginationQuery,
+    created: CreatedRangeQuery,
+    fields: FieldsQuery,
+    OriginalUri(uri): OriginalUri,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
 ) -> Result<Response, AppError> {
-    let page = query.page.max(1);
-    let limit = query.limit.max(1).min(100);
-
-    let badges = badge_service.get_badges(page, limit).await?;
-    Ok(ApiResponse::success(StatusCode::OK, badges))
+    let badges = badge_service
+        .get_badges(pagination.page, pagination.limit, &created)
+        .await?
+        .with_max_limit(pagination.max_limit)
+        .with_links(&uri);
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        fields.select_page(badges)?,
+    ))
 }
 
 // Handler to get a single badge by ID
 pub async fn get_badge(
     Path(id): Path<Uuid>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    fields: FieldsQuery,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
 ) -> Result<Response, AppError> {
     let badge = badge_service.get_badge(id).await?;
-    Ok(ApiResponse::success(StatusCode::OK, badge))
+    Ok(ApiResponse::success(StatusCode::OK, fields.select(&badge)?))
+}
+
+// Handler to publish the public keys badge credentials are signed with, as a JWKS
+pub async fn get_credential_keys(
+    State(badge_service): State<Arc<BadgeService>>,
+) -> axum::Json<JwkSet> {
+    axum::Json(badge_service.credential_keys())
 }
 
 // Handler to create a new badge (admin only)
 pub async fn create_badge(
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
     Json(dto): Json<CreateBadgeDto>,
 ) -> Result<Response, AppError> {
     // Validate DTO
@@ -53,7 +75,7 @@ pub async fn create_badge(
 // Handler to update a badge (admin only)
 pub async fn update_badge(
     Path(id): Path<Uuid>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
     Json(dto): Json<UpdateBadgeDto>,
 ) -> Result<Response, AppError> {
     // Validate DTO
@@ -66,7 +88,7 @@ pub async fn update_badge(
 // Handler to delete a badge (admin only)
 pub async fn delete_badge(
     Path(id): Path<Uuid>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
 ) -> Result<Response, AppError> {
     badge_service.delete_badge(id).await?;
     Ok(ApiResponse::no_content())
@@ -74,20 +96,118 @@ pub async fn delete_badge(
 
 // Handler to award a badge to a user (admin only)
 pub async fn award_badge(
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    Query(query): Query<AwardBadgeQuery>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
     Json(dto): Json<AwardBadgeDto>,
 ) -> Result<Response, AppError> {
     // Validate DTO
     dto.validate().map_err(validation_err_to_app_error)?;
 
-    badge_service.award_badge(dto).await?;
+    if !query.idempotent {
+        badge_service.award_badge(dto).await?;
+    } else if !badge_service.ensure_badge(dto).await? {
+        return Ok(ApiResponse::success(
+            StatusCode::OK,
+            "User already has this badge",
+        ));
+    }
+
     Ok(ApiResponse::created("Badge awarded successfully"))
 }
 
+// Handler to award a badge to many users at once, optionally as a dry run (admin only)
+pub async fn bulk_award_badge(
+    Query(query): Query<BulkOperationQuery>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
+    Json(dto): Json<BulkAwardBadgeDto>,
+) -> Result<Response, AppError> {
+    let result = badge_service.bulk_award_badge(dto, query.dry_run).await?;
+    Ok(ApiResponse::success(StatusCode::OK, result))
+}
+
+// Handler to remove a badge from many users at once, optionally as a dry run (admin only)
+pub async fn bulk_revoke_badge(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<BulkOperationQuery>,
+    State((_, badge_service, audit_service)): State<(
+        Arc<Repositories>,
+        Arc<BadgeService>,
+        Arc<AuditService>,
+    )>,
+    Json(dto): Json<BulkRevokeBadgeDto>,
+) -> Result<Response, AppError> {
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    let result = badge_service
+        .bulk_revoke_badge(id, dto, query.dry_run)
+        .await?;
+
+    if !result.dry_run {
+        for &user_id in &result.succeeded {
+            audit_service
+                .record_admin_action(
+                    AUDIT_EVENT_ADMIN_REVOKE_BADGE,
+                    admin_id,
+                    user_id,
+                    &request_id,
+                    Some(serde_json::json!({ "badge_id": id, "bulk": true })),
+                )
+                .await;
+        }
+    }
+
+    Ok(ApiResponse::success(StatusCode::OK, result))
+}
+
+// Handler to remove a badge from everyone who holds it, optionally as a dry run (admin only)
+pub async fn revoke_badge_from_all(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<BulkOperationQuery>,
+    State((_, badge_service, audit_service)): State<(
+        Arc<Repositories>,
+        Arc<BadgeService>,
+        Arc<AuditService>,
+    )>,
+) -> Result<Response, AppError> {
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    let revoked = badge_service
+        .revoke_badge_from_all(id, query.dry_run)
+        .await?;
+
+    if !query.dry_run {
+        for &user_id in &revoked {
+            audit_service
+                .record_admin_action(
+                    AUDIT_EVENT_ADMIN_REVOKE_BADGE,
+                    admin_id,
+                    user_id,
+                    &request_id,
+                    Some(serde_json::json!({ "badge_id": id, "all": true })),
+                )
+                .await;
+        }
+    }
+
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        RevokeAllBadgeResponse {
+            dry_run: query.dry_run,
+            revoked: revoked.len(),
+        },
+    ))
+}
+
 // Handler to remove a badge from a user (admin only)
 pub async fn remove_badge(
     Path((user_id, badge_id)): Path<(Uuid, Uuid)>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
 ) -> Result<Response, AppError> {
     badge_service.remove_badge(user_id, badge_id).await?;
     Ok(ApiResponse::no_content())
@@ -96,7 +216,7 @@ pub async fn remove_badge(
 // Handler to get all badges for a user
 pub async fn get_user_badges(
     Path(user_id): Path<Uuid>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
 ) -> Result<Response, AppError> {
     let user_badges = badge_service.get_user_badges(user_id).await?;
     Ok(ApiResponse::success(StatusCode::OK, user_badges))
@@ -105,7 +225,7 @@ pub async fn get_user_badges(
 // Handler to get all users who have a specific badge
 pub async fn get_badge_users(
     Path(badge_id): Path<Uuid>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
 ) -> Result<Response, AppError> {
     let badge_users = badge_service.get_badge_users(badge_id).await?;
     Ok(ApiResponse::success(StatusCode::OK, badge_users))
@@ -114,7 +234,7 @@ pub async fn get_badge_users(
 // Handler to check if a user has a specific badge
 pub async fn check_user_badge(
     Path((user_id, badge_id)): Path<(Uuid, Uuid)>,
-    State((_, badge_service)): State<(Arc<Repositories>, Arc<BadgeService>)>,
+    State((_, badge_service, _)): State<(Arc<Repositories>, Arc<BadgeService>, Arc<AuditService>)>,
 ) -> Result<Response, AppError> {
     let has_badge = badge_service.check_user_badge(user_id, badge_id).await?;
     Ok(ApiResponse::success(StatusCode::OK, has_badge))
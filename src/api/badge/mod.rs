@@ -1,4 +1,4 @@
 mod handlers;
 mod routes;
 
-pub use self::routes::configure;
+pub use self::routes::{configure, configure_keys};
@@ -8,6 +8,8 @@ use axum::{
 
 use crate::db::repositories::Repositories;
 use crate::middleware::auth::{require_admin, require_auth, require_verified_email};
+use crate::middleware::cache::cache_publicly;
+use crate::services::audit::AuditService;
 use crate::services::auth::TokenService;
 use crate::services::badge::BadgeService;
 
@@ -18,18 +20,26 @@ pub fn configure(
     repo: Arc<Repositories>,
     token_service: Arc<TokenService>,
     badge_service: Arc<BadgeService>,
+    audit_service: Arc<AuditService>,
 ) -> Router {
     // Public routes - no auth required
     let public_routes = Router::new()
         .route("/", get(handlers::get_badges))
-        .route("/:id", get(handlers::get_badge));
+        .route("/:id", get(handlers::get_badge))
+        .route_layer(middleware::from_fn(cache_publicly));
 
     // Admin-only routes
     let admin_routes = Router::new()
         .route("/", post(handlers::create_badge))
-        .route("/:id", put(handlers::update_badge))
+        .route(
+            "/:id",
+            put(handlers::update_badge).patch(handlers::update_badge),
+        )
         .route("/:id", delete(handlers::delete_badge))
         .route("/award", post(handlers::award_badge))
+        .route("/award/bulk", post(handlers::bulk_award_badge))
+        .route("/:id/revoke-bulk", post(handlers::bulk_revoke_badge))
+        .route("/:id/revoke-all", post(handlers::revoke_badge_from_all))
         .route(
             "/users/:user_id/badges/:badge_id",
             delete(handlers::remove_badge),
@@ -60,5 +70,13 @@ pub fn configure(
     // Merge all routes
     public_routes
         .merge(auth_routes)
-        .with_state((repo, badge_service))
+        .with_state((repo, badge_service, audit_service))
+}
+
+// Configure the keys badge credentials verify against, at /.well-known
+pub fn configure_keys(badge_service: Arc<BadgeService>) -> Router {
+    Router::new()
+        .route("/jwks.json", get(handlers::get_credential_keys))
+        .route_layer(middleware::from_fn(cache_publicly))
+        .with_state(badge_service)
 }
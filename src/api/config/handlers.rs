@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::Response};
+
+use crate::config::FeatureFlags;
+use crate::errors::{AppError, ErrorCatalog};
+use crate::models::common::features::FeaturesResponse;
+use crate::models::common::response::ApiResponse;
+use crate::services::auth::AuthService;
+
+// Handler to describe which optional features are enabled
+pub async fn get_features(
+    State((flags, auth_service)): State<(FeatureFlags, Arc<AuthService>)>,
+) -> Result<Response, AppError> {
+    let oauth_providers = auth_service
+        .list_active_oauth_providers()
+        .await?
+        .into_iter()
+        .map(|provider| provider.provider_name)
+        .collect();
+
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        FeaturesResponse {
+            flags,
+            oauth_providers,
+        },
+    ))
+}
+
+// Handler to list every error code the API can return
+pub async fn get_error_catalog() -> Response {
+    ApiResponse::success(StatusCode::OK, ErrorCatalog::build())
+}
@@ -0,0 +1,18 @@
+use std::sync::Arc;
+
+use axum::{middleware, routing::get, Router};
+
+use crate::config::{AppConfig, FeatureFlags};
+use crate::middleware::cache::cache_publicly;
+use crate::services::auth::AuthService;
+
+use super::handlers;
+
+// Configure the public, read-only view of the server's configuration
+pub fn configure(config: &AppConfig, auth_service: Arc<AuthService>) -> Router {
+    Router::new()
+        .route("/features", get(handlers::get_features))
+        .route("/errors", get(handlers::get_error_catalog))
+        .route_layer(middleware::from_fn(cache_publicly))
+        .with_state((FeatureFlags::from_config(config), auth_service))
+}
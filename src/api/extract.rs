@@ -0,0 +1,237 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{rejection::JsonRejection, FromRequest, FromRequestParts, Query, Request},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+};
+use serde::de::DeserializeOwned;
+
+use crate::errors::AppError;
+use crate::models::common::fields::FieldsQuery;
+use crate::models::common::filter::CreatedRangeQuery;
+use crate::models::common::pagination::{PaginationQuery, DEFAULT_MAX_PAGE_SIZE};
+
+// Drop-in replacement for axum's Json extractor that reports bad bodies through
+// the standard AppError envelope instead of axum's plain-text rejections
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json_content_type(req.headers()) {
+            return Err(AppError::Validation(json_rejection_message(
+                JsonRejection::MissingJsonContentType(Default::default()),
+            )));
+        }
+
+        let max_depth = req
+            .extensions()
+            .get::<JsonLimits>()
+            .map_or(DEFAULT_JSON_MAX_DEPTH, |limits| limits.max_depth);
+
+        let bytes = Bytes::from_request(req, state).await.map_err(|rejection| {
+            if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                AppError::Validation("Request body is too large".to_string())
+            } else {
+                AppError::Validation(rejection.body_text())
+            }
+        })?;
+
+        // Checked before deserializing, so a crafted body can't make serde recurse deeply
+        if exceeds_depth(&bytes, max_depth) {
+            return Err(AppError::Validation(format!(
+                "JSON body is nested too deeply (maximum depth is {})",
+                max_depth
+            )));
+        }
+
+        match axum::Json::<T>::from_bytes(&bytes) {
+            Ok(axum::Json(value)) => Ok(Json(value)),
+            Err(rejection) => Err(AppError::Validation(json_rejection_message(rejection))),
+        }
+    }
+}
+
+pub const DEFAULT_JSON_MAX_DEPTH: usize = 32;
+
+// Limits for JSON request bodies, added to requests as an extension
+#[derive(Debug, Clone, Copy)]
+pub struct JsonLimits {
+    pub max_depth: usize,
+}
+
+// Limits for paginated endpoints, added to requests as an extension
+#[derive(Debug, Clone, Copy)]
+pub struct PaginationLimits {
+    pub max_page_size: i64,
+}
+
+// Pagination parameters from the query string, clamped to the configured maximum page size
+#[async_trait]
+impl<S> FromRequestParts<S> for PaginationQuery
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let max_page_size = parts
+            .extensions
+            .get::<PaginationLimits>()
+            .map_or(DEFAULT_MAX_PAGE_SIZE, |limits| limits.max_page_size);
+
+        let Query(query) = Query::<PaginationQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| AppError::Validation(rejection.body_text()))?;
+
+        Ok(query.clamped(max_page_size))
+    }
+}
+
+// Creation date filters from the query string, rejecting a malformed or inverted window
+#[async_trait]
+impl<S> FromRequestParts<S> for CreatedRangeQuery
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<CreatedRangeQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| AppError::Validation(rejection.body_text()))?;
+
+        query.checked()
+    }
+}
+
+// The fields parameter from the query string. Which names are allowed depends on the
+// response, so they're checked when it's built, see FieldsQuery::select.
+#[async_trait]
+impl<S> FromRequestParts<S> for FieldsQuery
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<FieldsQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| AppError::Validation(rejection.body_text()))?;
+
+        Ok(query)
+    }
+}
+
+// Same rule as axum's Json: application/json or any application/*+json type
+fn is_json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    match mime.split_once('/') {
+        Some(("application", subtype)) => subtype == "json" || subtype.ends_with("+json"),
+        _ => false,
+    }
+}
+
+// Whether objects and arrays in the body nest more than `max_depth` levels.
+// Brackets inside strings don't count; malformed input is left for serde to report.
+fn exceeds_depth(bytes: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &byte in bytes {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    false
+}
+
+fn json_rejection_message(rejection: JsonRejection) -> String {
+    match rejection {
+        JsonRejection::MissingJsonContentType(_) => {
+            "Expected application/json body; set the Content-Type header to application/json"
+                .to_string()
+        }
+        JsonRejection::JsonSyntaxError(e) => format!("Malformed JSON body: {}", source_text(&e)),
+        // e.g. "user_ids[0]: invalid type: ..." or "missing field `email`"
+        JsonRejection::JsonDataError(e) => format!("Invalid request body: {}", source_text(&e)),
+        other => other.body_text(),
+    }
+}
+
+// The serde error without axum's generic "Failed to ..." prefix
+fn source_text(error: &dyn std::error::Error) -> String {
+    match error.source() {
+        Some(source) => source.to_string(),
+        None => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_counts_nesting_outside_strings() {
+        assert!(!exceeds_depth(br#"{"a": [1, {"b": 2}]}"#, 3));
+        assert!(exceeds_depth(br#"{"a": [1, {"b": 2}]}"#, 2));
+        assert!(!exceeds_depth(br#"{"a": "[[[[{{{{\"]]]"}"#, 1));
+        assert!(exceeds_depth(
+            "[".repeat(10_000).as_bytes(),
+            DEFAULT_JSON_MAX_DEPTH
+        ));
+    }
+
+    #[test]
+    fn pagination_is_clamped_to_max_page_size() {
+        let query = PaginationQuery {
+            page: 0,
+            limit: 1_000_000,
+            ..Default::default()
+        }
+        .clamped(50);
+        assert_eq!((query.page, query.limit, query.max_limit), (1, 50, 50));
+
+        let query = PaginationQuery::default().clamped(DEFAULT_MAX_PAGE_SIZE);
+        assert_eq!((query.page, query.limit), (1, 10));
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{
+    extract::{OriginalUri, State},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse},
+};
+
+use super::routes::GraphQLApiState;
+use crate::db::repositories::Repositories;
+use crate::errors::AppError;
+use crate::middleware::auth::{
+    authenticate_token, check_password_expiry, extract_token_from_headers,
+};
+use crate::middleware::client_context::ClientContext;
+use crate::services::auth::token::Claims;
+use crate::services::auth::TokenService;
+
+// What the playground page needs: its bundle from jsDelivr, Google Fonts, inline
+// scripts and styles, and requests back to the API
+const PLAYGROUND_CSP: &str = "default-src 'self'; \
+    script-src 'self' 'unsafe-inline' https://cdn.jsdelivr.net; \
+    style-src 'self' 'unsafe-inline' https://cdn.jsdelivr.net https://fonts.googleapis.com; \
+    font-src 'self' data: https://fonts.gstatic.com; \
+    img-src 'self' data: https://cdn.jsdelivr.net; \
+    frame-ancestors 'none'";
+
+// Execute a GraphQL request, attaching the caller's claims when a token is present
+pub async fn graphql(
+    State(state): State<Arc<GraphQLApiState>>,
+    headers: HeaderMap,
+    client: ClientContext,
+    request: GraphQLRequest,
+) -> Result<GraphQLResponse, AppError> {
+    let mut request = request.into_inner();
+
+    if let Some(token) = extract_token_from_headers(&headers) {
+        let claims =
+            authenticate_caller(&state.repos, &state.token_service, &token, &client).await?;
+        request = request.data(claims);
+    }
+
+    Ok(state.schema.execute(request).await.into())
+}
+
+// Apply the same checks as the REST require_auth + require_verified_email middleware
+async fn authenticate_caller(
+    repos: &Repositories,
+    token_service: &TokenService,
+    token: &str,
+    client: &ClientContext,
+) -> Result<Claims, AppError> {
+    let (claims, user) = authenticate_token(repos, token_service, token).await?;
+
+    // Impersonated requests are only audited by the REST middleware, so they stay there
+    if claims.impersonator.is_some() {
+        return Err(AppError::Authorization(
+            "This action is not allowed while impersonating a user".into(),
+        ));
+    }
+
+    if !user.is_email_verified {
+        return Err(AppError::Authorization(
+            "Email verification required".into(),
+        ));
+    }
+    check_password_expiry(token_service, &claims, &user)?;
+    token_service.check_fingerprint(&claims, client)?;
+
+    Ok(claims)
+}
+
+// Serve the GraphQL playground UI
+pub async fn graphql_playground(OriginalUri(uri): OriginalUri) -> impl IntoResponse {
+    // The endpoint sits next to the playground, wherever the API is mounted
+    let endpoint = uri.path().trim_end_matches("/playground");
+
+    // The page loads its scripts, styles and fonts from CDNs, which the API's default
+    // Content-Security-Policy would block
+    (
+        [(header::CONTENT_SECURITY_POLICY, PLAYGROUND_CSP)],
+        Html(playground_source(GraphQLPlaygroundConfig::new(endpoint))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::Role;
+    use crate::test_support::{test_client, TestApp};
+    use chrono::{Duration, Utc};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn impersonation_tokens_are_refused(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("olga").await;
+        app.repos
+            .user()
+            .update_email_verification(user.id, true)
+            .await
+            .unwrap();
+        let client = test_client("203.0.113.7", "test-agent");
+
+        let (token, _) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+        assert!(
+            authenticate_caller(&app.repos, &app.token_service, &token, &client)
+                .await
+                .is_ok()
+        );
+
+        let admin_id = app.create_user("admin").await.id;
+        app.user_management
+            .set_global_role(admin_id, Role::Admin)
+            .await
+            .unwrap();
+        let expires_at = Utc::now() + Duration::minutes(5);
+        let session = app
+            .repos
+            .impersonation()
+            .create(admin_id, user.id, "support ticket", expires_at)
+            .await
+            .unwrap();
+        let token = app
+            .token_service
+            .generate_impersonation_token(&user, admin_id, session.id, expires_at)
+            .unwrap();
+        assert!(matches!(
+            authenticate_caller(&app.repos, &app.token_service, &token, &client).await,
+            Err(AppError::Authorization(_))
+        ));
+    }
+}
@@ -0,0 +1,5 @@
+mod handlers;
+mod routes;
+mod schema;
+
+pub use self::routes::configure;
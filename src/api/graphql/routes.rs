@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::config::AppConfig;
+use crate::db::repositories::Repositories;
+use crate::services::auth::TokenService;
+use crate::services::badge::BadgeService;
+use crate::services::user::UserManagementService;
+
+use super::handlers;
+use super::schema::{build_schema, AppSchema};
+
+// GraphQL API State struct
+pub struct GraphQLApiState {
+    pub schema: AppSchema,
+    pub repos: Arc<Repositories>,
+    pub token_service: Arc<TokenService>,
+}
+
+// Configure GraphQL routes
+pub fn configure(
+    repos: Arc<Repositories>,
+    config: AppConfig,
+    token_service: Arc<TokenService>,
+    user_management_service: Arc<UserManagementService>,
+    badge_service: Arc<BadgeService>,
+) -> Router {
+    let state = Arc::new(GraphQLApiState {
+        schema: build_schema(user_management_service, badge_service, &config),
+        repos,
+        token_service,
+    });
+
+    // Authentication is optional here; resolvers enforce it per field
+    let mut router = Router::new().route("/", post(handlers::graphql));
+
+    // The playground is only exposed when explicitly enabled
+    if config.graphql_playground_enabled {
+        router = router.route("/playground", get(handlers::graphql_playground));
+    }
+
+    router.with_state(state)
+}
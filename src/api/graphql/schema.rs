@@ -0,0 +1,361 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    Context, EmptySubscription, ErrorExtensions, InputObject, MaybeUndefined, Object, Schema,
+    SimpleObject,
+};
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::db::error::DatabaseError;
+use crate::errors::AppError;
+use crate::models::badge::BadgeResponse;
+use crate::models::common::filter::CreatedRangeQuery;
+use crate::models::common::pagination::PaginationQuery;
+use crate::models::user::{
+    AwardBadgeDto, BadgeWithUsersResponse, Permission, PublicUserResponse, Role, UpdateUserDto,
+    UserResponse, UserWithBadgesResponse,
+};
+use crate::services::auth::token::Claims;
+use crate::services::badge::BadgeService;
+use crate::services::user::UserManagementService;
+
+pub type AppSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+// Build the GraphQL schema on top of the existing services
+pub fn build_schema(
+    user_management: Arc<UserManagementService>,
+    badge_service: Arc<BadgeService>,
+    config: &AppConfig,
+) -> AppSchema {
+    Schema::build(
+        QueryRoot {
+            user_management: user_management.clone(),
+            badge_service: badge_service.clone(),
+            max_page_size: config.max_page_size,
+        },
+        MutationRoot {
+            user_management,
+            badge_service,
+        },
+        EmptySubscription,
+    )
+    // Nested selections fan out into queries per item, so bound how much one request can ask for
+    .limit_depth(config.graphql_max_depth)
+    .limit_complexity(config.graphql_max_complexity)
+    .finish()
+}
+
+// Map application errors to GraphQL errors with the same code REST responses carry
+impl ErrorExtensions for AppError {
+    fn extend(&self) -> async_graphql::Error {
+        let code = self.code();
+        let message = match self {
+            AppError::Database(DatabaseError::Duplicate(msg) | DatabaseError::Validation(msg)) => {
+                msg.clone()
+            }
+            // Don't leak internals to clients
+            AppError::Database(_)
+            | AppError::Internal(_)
+            | AppError::Unexpected(_)
+            | AppError::Configuration(_)
+            | AppError::UsernameChangeCooldown(_) => code.default_message().to_string(),
+            AppError::Authentication(msg)
+            | AppError::Authorization(msg)
+            | AppError::AccountDisabled(msg)
+            | AppError::EmailNotVerified(msg)
+            | AppError::PasswordExpired(msg)
+            | AppError::RegistrationDisabled(msg)
+            | AppError::OAuthEmailUnverified(msg)
+            | AppError::OAuthReauthRequired(msg)
+            | AppError::EmailDisabled(msg)
+            | AppError::Validation(msg)
+            | AppError::PasswordReused(msg)
+            | AppError::NotFound(msg)
+            | AppError::InvalidToken(msg)
+            | AppError::Timeout(msg)
+            | AppError::TooManyRequests(msg) => msg.clone(),
+        };
+
+        async_graphql::Error::new(message).extend_with(|_, e| e.set("code", code.as_str()))
+    }
+}
+
+// Get the authenticated caller's claims, if the request carried a valid token
+fn require_claims<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a Claims> {
+    ctx.data_opt::<Claims>()
+        .ok_or_else(|| AppError::Authentication("Authentication required".into()).extend())
+}
+
+// Same as require_claims, but the caller must also be an admin
+fn require_admin<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a Claims> {
+    let claims = require_claims(ctx)?;
+    if !claims.role.has_at_least(Role::Admin) {
+        return Err(AppError::Authorization("Admin access required".into()).extend());
+    }
+    Ok(claims)
+}
+
+fn caller_id(claims: &Claims) -> async_graphql::Result<Uuid> {
+    Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Invalid user ID in token".into()).extend())
+}
+
+// Clamp pagination arguments to the same bounds as the REST endpoints
+fn page_bounds(page: Option<i64>, limit: Option<i64>, max_page_size: i64) -> PaginationQuery {
+    let defaults = PaginationQuery::default();
+    PaginationQuery {
+        page: page.unwrap_or(defaults.page),
+        limit: limit.unwrap_or(defaults.limit),
+        ..defaults
+    }
+    .clamped(max_page_size)
+}
+
+#[derive(SimpleObject)]
+pub struct UserPage {
+    pub data: Vec<UserResponse>,
+    pub total: i64,
+    pub page: i64,
+    pub limit: i64,
+    pub total_pages: i64,
+    pub max_limit: i64,
+}
+
+#[derive(SimpleObject)]
+pub struct BadgePage {
+    pub data: Vec<BadgeResponse>,
+    pub total: i64,
+    pub page: i64,
+    pub limit: i64,
+    pub total_pages: i64,
+    pub max_limit: i64,
+}
+
+// Fields left out are kept; null clears full_name and avatar_url
+#[derive(InputObject)]
+pub struct UpdateProfileInput {
+    pub username: Option<String>,
+    pub full_name: MaybeUndefined<String>,
+    pub avatar_url: MaybeUndefined<String>,
+}
+
+pub struct QueryRoot {
+    user_management: Arc<UserManagementService>,
+    badge_service: Arc<BadgeService>,
+    max_page_size: i64,
+}
+
+#[Object]
+impl QueryRoot {
+    // The currently authenticated user
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<UserResponse> {
+        let user_id = caller_id(require_claims(ctx)?)?;
+        self.user_management
+            .get_user_by_id(user_id)
+            .await
+            .map_err(|e| e.extend())
+    }
+
+    // List users (admin only)
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<i64>,
+        limit: Option<i64>,
+    ) -> async_graphql::Result<UserPage> {
+        require_admin(ctx)?;
+        let PaginationQuery {
+            page,
+            limit,
+            max_limit,
+        } = page_bounds(page, limit, self.max_page_size);
+
+        let (data, total) = self
+            .user_management
+            .get_all_users(page, limit, false, &CreatedRangeQuery::default())
+            .await
+            .map_err(|e| e.extend())?;
+        let total = total as i64;
+
+        Ok(UserPage {
+            data,
+            total,
+            page,
+            limit,
+            total_pages: (total as f64 / limit as f64).ceil() as i64,
+            max_limit,
+        })
+    }
+
+    // Get a user by ID: the user themselves or an admin
+    async fn user(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<UserResponse> {
+        let claims = require_claims(ctx)?;
+        if caller_id(claims)? != id && !claims.role.can(Permission::UsersManage) {
+            return Err(
+                AppError::Authorization("You can only view your own account".into()).extend(),
+            );
+        }
+
+        self.user_management
+            .get_user_by_id(id)
+            .await
+            .map_err(|e| e.extend())
+    }
+
+    // Anyone's public profile
+    async fn public_user(&self, id: Uuid) -> async_graphql::Result<PublicUserResponse> {
+        self.badge_service
+            .get_public_profile(id)
+            .await
+            .map_err(|e| e.extend())
+    }
+
+    // List badges
+    async fn badges(
+        &self,
+        page: Option<i64>,
+        limit: Option<i64>,
+    ) -> async_graphql::Result<BadgePage> {
+        let pagination = page_bounds(page, limit, self.max_page_size);
+
+        let badges = self
+            .badge_service
+            .get_badges(
+                pagination.page,
+                pagination.limit,
+                &CreatedRangeQuery::default(),
+            )
+            .await
+            .map_err(|e| e.extend())?;
+
+        Ok(BadgePage {
+            data: badges.data,
+            total: badges.total,
+            page: badges.page,
+            limit: badges.limit,
+            total_pages: badges.total_pages,
+            max_limit: pagination.max_limit,
+        })
+    }
+
+    // Get a badge by ID
+    async fn badge(&self, id: Uuid) -> async_graphql::Result<BadgeResponse> {
+        self.badge_service
+            .get_badge(id)
+            .await
+            .map_err(|e| e.extend())
+    }
+
+    // Badges held by a user
+    async fn user_badges(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+    ) -> async_graphql::Result<UserWithBadgesResponse> {
+        require_claims(ctx)?;
+        self.badge_service
+            .get_user_badges(user_id)
+            .await
+            .map_err(|e| e.extend())
+    }
+
+    // Users holding a badge
+    async fn badge_users(
+        &self,
+        ctx: &Context<'_>,
+        badge_id: Uuid,
+    ) -> async_graphql::Result<BadgeWithUsersResponse> {
+        require_claims(ctx)?;
+        self.badge_service
+            .get_badge_users(badge_id)
+            .await
+            .map_err(|e| e.extend())
+    }
+}
+
+pub struct MutationRoot {
+    user_management: Arc<UserManagementService>,
+    badge_service: Arc<BadgeService>,
+}
+
+#[Object]
+impl MutationRoot {
+    // Update the current user's profile
+    async fn update_profile(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateProfileInput,
+    ) -> async_graphql::Result<UserResponse> {
+        let user_id = caller_id(require_claims(ctx)?)?;
+
+        let dto = UpdateUserDto {
+            username: input.username,
+            full_name: input.full_name.into(),
+            avatar_url: input.avatar_url.into(),
+        };
+
+        self.user_management
+            .update_profile(user_id, dto)
+            .await
+            .map_err(|e| e.extend())
+    }
+
+    // Award a badge to a user (admin only). With idempotent, a badge the user already
+    // has isn't an error.
+    async fn award_badge(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+        badge_id: Uuid,
+        #[graphql(default)] idempotent: bool,
+    ) -> async_graphql::Result<UserWithBadgesResponse> {
+        require_admin(ctx)?;
+
+        let dto = AwardBadgeDto { user_id, badge_id };
+        if idempotent {
+            self.badge_service.ensure_badge(dto).await.map(|_| ())
+        } else {
+            self.badge_service.award_badge(dto).await
+        }
+        .map_err(|e| e.extend())?;
+
+        self.badge_service
+            .get_user_badges(user_id)
+            .await
+            .map_err(|e| e.extend())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_config, TestApp};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn deep_and_wide_queries_are_refused(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let mut config = test_config();
+        config.graphql_max_depth = 3;
+        config.graphql_max_complexity = 5;
+        let schema = build_schema(
+            app.user_management.clone(),
+            app.badge_service.clone(),
+            &config,
+        );
+
+        let response = schema.execute("{ __typename }").await;
+        assert!(response.errors.is_empty());
+
+        let response = schema
+            .execute("{ __schema { types { fields { type { name } } } } }")
+            .await;
+        assert!(response.errors[0].message.contains("too deep"));
+
+        let response = schema
+            .execute("{ me { id email username fullName avatarUrl globalRole } }")
+            .await;
+        assert!(response.errors[0].message.contains("too complex"));
+    }
+}
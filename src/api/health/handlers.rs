@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Response,
+};
+
+use crate::models::common::health::{HealthStatus, LivenessResponse, ReadinessQuery};
+use crate::models::common::response::ApiResponse;
+use crate::services::health::HealthService;
+
+// Handler to tell that the process is up, without touching any dependency
+pub async fn liveness() -> Response {
+    ApiResponse::success(
+        StatusCode::OK,
+        LivenessResponse {
+            status: HealthStatus::Healthy,
+        },
+    )
+}
+
+// Handler to tell whether the service can take traffic. Degraded dependencies are
+// reported but still ready; only a database outage is a 503.
+pub async fn readiness(
+    Query(query): Query<ReadinessQuery>,
+    State(health_service): State<Arc<HealthService>>,
+) -> Response {
+    let readiness = health_service.readiness(query.deep).await;
+    let status = if readiness.status == HealthStatus::Unavailable {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+
+    ApiResponse::success(status, readiness)
+}
@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use axum::{routing::get, Router};
+
+use crate::services::health::HealthService;
+
+use super::handlers;
+
+// Configure the liveness and readiness checks for load balancers and monitoring
+pub fn configure(health_service: Arc<HealthService>) -> Router {
+    Router::new()
+        .route("/", get(handlers::liveness))
+        .route("/ready", get(handlers::readiness))
+        .with_state(health_service)
+}
@@ -1,40 +1,79 @@
+mod audit;
 mod auth;
 mod badge;
+mod config;
+mod extract;
+mod graphql;
 mod health;
 mod users;
+mod ws;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     http::{Method, StatusCode},
     response::IntoResponse,
-    Router,
+    Extension, Router,
 };
-use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::cors::{AllowHeaders, AllowOrigin, Any, CorsLayer};
 
+use self::extract::{JsonLimits, PaginationLimits};
 use crate::config::AppConfig;
 use crate::db::repositories::Repositories;
+use crate::errors::{AppError, ErrorCode};
+use crate::middleware::cache::cache_headers;
+use crate::middleware::client_context::ClientContextConfig;
+use crate::middleware::compression::compress_responses;
+use crate::middleware::envelope::negotiate_envelope;
+use crate::middleware::request_id::{
+    propagate_request_id_layer, request_span, set_request_id_layer,
+};
+use crate::middleware::security_headers::{security_headers, SecurityHeaders};
+use crate::middleware::session_activity::{track_session_activity, SessionActivityTracker};
 use crate::models::common::response::ApiResponse;
+use crate::services::audit::AuditService;
 use crate::services::auth::{AuthService, TokenService};
 use crate::services::badge::BadgeService;
 use crate::services::email::EmailService;
-use crate::services::user::UserManagementService;
+use crate::services::events::EventBus;
+use crate::services::health::HealthService;
+use crate::services::user::{UserEmailService, UserManagementService};
 
 // Handler for unmatched routes (404 Not Found)
 async fn handle_404() -> impl IntoResponse {
-    ApiResponse::error(StatusCode::NOT_FOUND, "Resource not found".to_string())
+    AppError::NotFound("Resource not found".to_string())
+}
+
+// The repositories and services the routes are built from
+pub struct ApiState {
+    pub repos: Arc<Repositories>,
+    pub token_service: Arc<TokenService>,
+    pub user_management_service: Arc<UserManagementService>,
+    pub auth_service: Arc<AuthService>,
+    pub badge_service: Arc<BadgeService>,
+    pub email_service: Arc<EmailService>,
+    pub audit_service: Arc<AuditService>,
+    pub event_bus: Arc<EventBus>,
+    pub user_email_service: Arc<UserEmailService>,
+    pub health_service: Arc<HealthService>,
 }
 
 // Function to configure all API routes
-pub fn configure_api(
-    state: Arc<Repositories>,
-    config: AppConfig,
-    token_service: Arc<TokenService>,
-    user_management_service: Arc<UserManagementService>,
-    auth_service: Arc<AuthService>,
-    badge_service: Arc<BadgeService>,
-    email_service: Arc<EmailService>,
-) -> Router {
+pub fn configure_api(api: ApiState, config: AppConfig) -> Router {
+    let ApiState {
+        repos,
+        token_service,
+        user_management_service,
+        auth_service,
+        badge_service,
+        email_service,
+        audit_service,
+        event_bus,
+        health_service,
+        ..
+    } = &api;
+
     // Configure CORS
     let cors = if config.cors_allowed_origins.contains(&"*".to_string()) {
         // If wildcard is allowed, use Any
@@ -44,6 +83,7 @@ pub fn configure_api(
                 Method::GET,
                 Method::POST,
                 Method::PUT,
+                Method::PATCH,
                 Method::DELETE,
                 Method::OPTIONS,
             ])
@@ -56,63 +96,171 @@ pub fn configure_api(
             .filter_map(|origin| origin.parse().ok())
             .collect::<Vec<_>>();
 
-        CorsLayer::new()
+        let cors = CorsLayer::new()
             .allow_origin(AllowOrigin::list(origins))
             .allow_methods([
                 Method::GET,
                 Method::POST,
                 Method::PUT,
+                Method::PATCH,
                 Method::DELETE,
                 Method::OPTIONS,
-            ])
-            .allow_headers(Any)
+            ]);
+
+        // Browsers only send the refresh token cookie to another origin when credentials
+        // are allowed, and that rules out wildcard headers
+        if config.refresh_token_cookie {
+            cors.allow_credentials(true)
+                .allow_headers(AllowHeaders::mirror_request())
+        } else {
+            cors.allow_headers(Any)
+        }
     };
 
     // Create main router and attach all sub-routers
-    Router::new()
-        .nest(
-            "/users",
-            users::configure(
-                state.clone(),
-                config.clone(),
-                user_management_service.clone(),
-                token_service.clone(),
-                auth_service.clone(),
-            ),
-        )
+    let router = Router::new()
+        .nest("/users", users::configure(&api, config.clone()))
         // Add auth routes
         .nest(
             "/auth",
             auth::configure(
-                state.clone(),
+                repos.clone(),
                 token_service.clone(),
                 user_management_service.clone(),
                 auth_service.clone(),
                 email_service.clone(),
+                audit_service.clone(),
                 config.clone(),
             ),
         )
+        // Add admin audit log exports
+        .nest(
+            "/audit",
+            audit::configure(repos.clone(), token_service.clone(), audit_service.clone()),
+        )
         // Add badge routes
         .nest(
             "/badges",
-            badge::configure(state.clone(), token_service.clone(), badge_service.clone()),
+            badge::configure(
+                repos.clone(),
+                token_service.clone(),
+                badge_service.clone(),
+                audit_service.clone(),
+            ),
         )
-        // Add additional routes as they are implemented
-        // .nest("/health", health::configure_health(...))
+        // Add the keys that verify badge credentials
+        .nest("/.well-known", badge::configure_keys(badge_service.clone()))
+        // Add GraphQL endpoint
+        .nest(
+            "/graphql",
+            graphql::configure(
+                repos.clone(),
+                config.clone(),
+                token_service.clone(),
+                user_management_service.clone(),
+                badge_service.clone(),
+            ),
+        )
+        // Add the features clients can adapt their UI to
+        .nest("/config", config::configure(&config, auth_service.clone()))
+        // Add WebSocket for live account events
+        .nest(
+            "/ws",
+            ws::configure(repos.clone(), token_service.clone(), event_bus.clone()),
+        )
+        // Add liveness and readiness checks
+        .nest("/health", health::configure(health_service.clone()))
         // Add fallback route for handling 404 errors
-        .fallback(handle_404)
+        .fallback(handle_404);
+
+    // Optionally keep sessions' last activity up to date
+    let router = if config.track_session_activity {
+        router.layer(axum::middleware::from_fn_with_state(
+            Arc::new(SessionActivityTracker::new(
+                repos.clone(),
+                token_service.clone(),
+                Duration::from_secs(config.session_activity_interval),
+            )),
+            track_session_activity,
+        ))
+    } else {
+        router
+    };
+
+    // Serve everything under the configured base path, if any
+    let router = if config.api_base_path.is_empty() {
+        router
+    } else {
+        Router::new()
+            .nest(&config.api_base_path, router)
+            .fallback(handle_404)
+    };
+
+    // Which proxies' headers to believe for the client's address and protocol
+    let client_context = ClientContextConfig {
+        trust_proxy: config.trust_proxy,
+        trusted_proxies: config.trusted_proxies.clone(),
+    };
+
+    let router = router
         // Apply CORS middleware
         .layer(cors)
         // Add middleware for handling method not allowed
         .layer(axum::middleware::map_response(
             |res: axum::response::Response| async move {
                 if res.status() == StatusCode::METHOD_NOT_ALLOWED {
-                    return ApiResponse::error(
-                        StatusCode::METHOD_NOT_ALLOWED,
-                        "Method not allowed for this endpoint".to_string(),
+                    let code = ErrorCode::MethodNotAllowed;
+                    return ApiResponse::error_with_code(
+                        code.status(),
+                        code.as_str(),
+                        code.default_message().to_string(),
                     );
                 }
                 res
             },
         ))
+        // Limits the JSON extractor enforces on request bodies
+        .layer(Extension(JsonLimits {
+            max_depth: config.json_max_depth,
+        }))
+        .layer(Extension(PaginationLimits {
+            max_page_size: config.max_page_size,
+        }))
+        // How ClientContext reads the client's address
+        .layer(Extension(client_context.clone()))
+        // Strip the response envelope for clients that opted out of it
+        .layer(axum::middleware::from_fn_with_state(
+            config.response_envelope,
+            negotiate_envelope,
+        ))
+        // ETags and Cache-Control, computed on the final body
+        .layer(axum::middleware::from_fn_with_state(
+            config.cache_max_age,
+            cache_headers,
+        ));
+
+    // Compress outside the caching layer so ETags and 304s are computed on the plain body
+    let router = if config.compression_enabled {
+        router.layer(axum::middleware::from_fn_with_state(
+            config.compression_min_size,
+            compress_responses,
+        ))
+    } else {
+        router
+    };
+
+    router
+        // nosniff, CSP, framing, Referrer-Policy and HSTS on every response
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(SecurityHeaders::new(
+                &config.security_headers,
+                client_context,
+            )),
+            security_headers,
+        ))
+        // Log everything the request does with its ID
+        .layer(axum::middleware::from_fn(request_span))
+        // Echo the request ID on responses and assign one if the client didn't
+        .layer(propagate_request_id_layer())
+        .layer(set_request_id_layer())
 }
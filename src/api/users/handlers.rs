@@ -1,38 +1,63 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Extension, Json, Path, Query, State},
+    extract::{Extension, OriginalUri, Path, Query, State},
     http::StatusCode,
     response::Response,
 };
 use uuid::Uuid;
+use validator::Validate;
 
-use crate::config::AppConfig;
-use crate::db::repositories::Repositories;
+use super::routes::UsersApiState;
+use crate::api::extract::Json;
 use crate::errors::AppError;
-use crate::middleware::auth::Claims;
+use crate::middleware::auth::{Claims, Impersonator};
+use crate::middleware::client_context::ClientContext;
+use crate::middleware::request_id::RequestId;
+use crate::models::audit::{
+    AUDIT_EVENT_ADMIN_DEACTIVATE, AUDIT_EVENT_ADMIN_FORCE_LOGOUT, AUDIT_EVENT_ADMIN_MERGE,
+    AUDIT_EVENT_ADMIN_REACTIVATE, AUDIT_EVENT_ADMIN_RESEND_VERIFICATION,
+    AUDIT_EVENT_ADMIN_SEND_PASSWORD_RESET, AUDIT_EVENT_ADMIN_VERIFY_EMAIL,
+    AUDIT_EVENT_IMPERSONATION_END, AUDIT_EVENT_IMPERSONATION_START,
+};
+use crate::models::auth::impersonation::ImpersonateUserDto;
+use crate::models::auth::token::EmailTokenDto;
+use crate::models::common::bulk::BulkOperationQuery;
+use crate::models::common::fields::FieldsQuery;
+use crate::models::common::filter::CreatedRangeQuery;
 use crate::models::common::pagination::PaginationQuery;
 use crate::models::common::response::{ApiResponse, PaginatedResponse};
 use crate::models::user::{
-    CreateUserDto, UpdatePasswordDto, UpdateUserDto, UserResponse, GLOBAL_ROLE_ADMIN,
+    AccountStatusDto, AddUserEmailDto, BulkAccountStatusDto, ChangeUsernameDto, CreateUserDto,
+    IncludeDeletedQuery, LocaleResponse, Permission, UpdateLocaleDto, UpdatePasswordDto,
+    UpdateUserDto,
 };
-use crate::services::auth::AuthService;
-use crate::services::user::UserManagementService;
+use crate::services::audit::AuditService;
+use crate::services::auth::ImpersonationService;
+use crate::services::badge::BadgeService;
+use crate::services::locale::LocaleService;
+use crate::services::user::UserEmailService;
+use crate::services::validation::validation_err_to_app_error;
 
 // Get all users with pagination
 pub async fn list_users(
     Extension(_claims): Extension<Claims>,
-    Query(pagination): Query<PaginationQuery>,
-    State((_repos, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    pagination: PaginationQuery,
+    Query(filter): Query<IncludeDeletedQuery>,
+    created: CreatedRangeQuery,
+    fields: FieldsQuery,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<Arc<UsersApiState>>,
 ) -> Result<Response, AppError> {
     // Admin check is now handled by middleware
-    let (users, total) = user_management
-        .get_all_users(pagination.page, pagination.limit)
+    let (users, total) = state
+        .user_management_service
+        .get_all_users(
+            pagination.page,
+            pagination.limit,
+            filter.include_deleted,
+            &created,
+        )
         .await?;
 
     let total_pages = (total as f64 / pagination.limit as f64).ceil() as i64;
@@ -43,54 +68,102 @@ pub async fn list_users(
         page: pagination.page,
         limit: pagination.limit,
         total_pages,
-    };
+        max_limit: Some(pagination.max_limit),
+        links: None,
+    }
+    .with_links(&uri);
 
-    Ok(ApiResponse::success(StatusCode::OK, response))
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        fields.select_page(response)?,
+    ))
 }
 
 // Get current user
 pub async fn get_current_user(
     Extension(_claims): Extension<Claims>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    fields: FieldsQuery,
+    State(state): State<Arc<UsersApiState>>,
 ) -> Result<Response, AppError> {
     let user_id = Uuid::parse_str(&_claims.sub).unwrap();
-    let user = user_management.get_user_by_id(user_id).await?;
-    Ok(ApiResponse::success(StatusCode::OK, user))
+    let user = state
+        .user_management_service
+        .get_user_by_id(user_id)
+        .await?;
+    Ok(ApiResponse::success(StatusCode::OK, fields.select(&user)?))
 }
 
-// Get user by ID
+// Get a user's public profile by ID
 pub async fn get_user(
     Path(id): Path<Uuid>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    fields: FieldsQuery,
+    State(badge_service): State<Arc<BadgeService>>,
 ) -> Result<Response, AppError> {
-    let user = user_management.get_user_by_id(id).await?;
-    Ok(ApiResponse::success(StatusCode::OK, user))
+    let profile = badge_service.get_public_profile(id).await?;
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        fields.select(&profile)?,
+    ))
+}
+
+// Download a badge a user holds as a signed Open Badges credential
+pub async fn get_user_badge_credential(
+    Extension(claims): Extension<Claims>,
+    Path((id, badge_id)): Path<(Uuid, Uuid)>,
+    State(badge_service): State<Arc<BadgeService>>,
+) -> Result<Response, AppError> {
+    // The credential identifies its recipient, so it's theirs to download
+    if claims.sub != id.to_string() && !claims.role.can(Permission::UsersManage) {
+        return Err(AppError::Authorization(
+            "Access denied. You can only download your own badge credentials.".into(),
+        ));
+    }
+
+    let credential = badge_service.issue_credential(id, badge_id).await?;
+    Ok(ApiResponse::success(StatusCode::OK, credential))
+}
+
+// Get a user by ID for administration, optionally including deleted accounts
+pub async fn get_user_admin(
+    Path(id): Path<Uuid>,
+    Query(filter): Query<IncludeDeletedQuery>,
+    fields: FieldsQuery,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    let user = if filter.include_deleted {
+        state
+            .user_management_service
+            .get_user_by_id_including_deleted(id)
+            .await?
+    } else {
+        state.user_management_service.get_user_by_id(id).await?
+    };
+
+    Ok(ApiResponse::success(StatusCode::OK, fields.select(&user)?))
+}
+
+// List a user's emailed verification and reset tokens, without their values, so
+// support can see why a link doesn't work (admin only)
+pub async fn list_user_tokens(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    let tokens = state.auth_service.list_verification_tokens(id).await?;
+    Ok(ApiResponse::success(StatusCode::OK, tokens))
 }
 
 // Create a new user (admin only)
 pub async fn create_user(
     Extension(_claims): Extension<Claims>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    State(state): State<Arc<UsersApiState>>,
     Json(create_dto): Json<CreateUserDto>,
 ) -> Result<Response, AppError> {
     // Admin check is now handled by middleware
-    let user = user_management.register_user(create_dto).await?;
-    let user_response = UserResponse::from(user);
+    let user = state
+        .user_management_service
+        .register_user(create_dto)
+        .await?;
+    let user_response = state.user_management_service.user_response(user);
 
     Ok(ApiResponse::created(user_response))
 }
@@ -98,54 +171,74 @@ pub async fn create_user(
 // Update current user
 pub async fn update_current_user(
     Extension(_claims): Extension<Claims>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    State(state): State<Arc<UsersApiState>>,
     Json(update_dto): Json<UpdateUserDto>,
 ) -> Result<Response, AppError> {
     let user_id = Uuid::parse_str(&_claims.sub).unwrap();
 
-    // If not admin, they can't modify the is_active field
-    if _claims.role != GLOBAL_ROLE_ADMIN && update_dto.is_active.is_some() {
-        return Err(crate::errors::AppError::Authorization(
-            "Access denied. Only administrators can change a user's active status.".into(),
-        ));
-    }
+    let user = state
+        .user_management_service
+        .update_profile(user_id, update_dto)
+        .await?;
+    Ok(ApiResponse::success(StatusCode::OK, user))
+}
+
+// Change the current user's username, at most once per USERNAME_CHANGE_COOLDOWN_DAYS
+pub async fn change_current_user_username(
+    Extension(claims): Extension<Claims>,
+    State(state): State<Arc<UsersApiState>>,
+    Json(dto): Json<ChangeUsernameDto>,
+) -> Result<Response, AppError> {
+    let user_id = Uuid::parse_str(&claims.sub).unwrap();
 
-    let user = user_management.update_user(user_id, update_dto).await?;
+    let user = state
+        .user_management_service
+        .change_username(user_id, dto)
+        .await?;
     Ok(ApiResponse::success(StatusCode::OK, user))
 }
 
+// Re-sync the current user's profile from one of their linked OAuth logins
+pub async fn refresh_current_user_connection(
+    Extension(claims): Extension<Claims>,
+    Path(provider): Path<String>,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    let user_id = claims_user_id(&claims)?;
+
+    let sync = state
+        .auth_service
+        .refresh_oauth_connection_profile(user_id, &provider)
+        .await?;
+    Ok(ApiResponse::success(StatusCode::OK, sync))
+}
+
 // Update user
 pub async fn update_user(
     Extension(_claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    State(state): State<Arc<UsersApiState>>,
     Json(update_dto): Json<UpdateUserDto>,
 ) -> Result<Response, AppError> {
     // Users can only update their own data, unless they are admin
-    if _claims.sub != id.to_string() && _claims.role != GLOBAL_ROLE_ADMIN {
+    if _claims.sub != id.to_string() && !_claims.role.can(Permission::UsersManage) {
         return Err(crate::errors::AppError::Authorization(
             "Access denied. You can only modify your own data.".into(),
         ));
     }
 
-    // If not admin, they can't modify the is_active field
-    if _claims.role != GLOBAL_ROLE_ADMIN && update_dto.is_active.is_some() {
-        return Err(crate::errors::AppError::Authorization(
-            "Access denied. Only administrators can change a user's active status.".into(),
-        ));
-    }
-
-    let user = user_management.update_user(id, update_dto).await?;
+    // Admins can rename anyone, without the cooldown users have on their own username
+    let user = if _claims.role.can(Permission::UsersManage) {
+        state
+            .user_management_service
+            .update_user(id, update_dto)
+            .await?
+    } else {
+        state
+            .user_management_service
+            .update_profile(id, update_dto)
+            .await?
+    };
     Ok(ApiResponse::success(StatusCode::OK, user))
 }
 
@@ -153,33 +246,24 @@ pub async fn update_user(
 pub async fn delete_user(
     Extension(_claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    State(state): State<Arc<UsersApiState>>,
 ) -> Result<Response, AppError> {
     // Admin check is handled by middleware
-    user_management.delete_user(id).await?;
+    state.user_management_service.delete_user(id).await?;
     Ok(ApiResponse::no_content())
 }
 
 // Update current user's password
 pub async fn update_current_user_password(
     Extension(_claims): Extension<Claims>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    State(state): State<Arc<UsersApiState>>,
     Json(password_request): Json<UpdatePasswordDto>,
 ) -> Result<Response, AppError> {
     let user_id = Uuid::parse_str(&_claims.sub).unwrap();
 
     // Use the user management service to update the password
-    user_management
+    state
+        .user_management_service
         .update_password(
             user_id,
             &password_request.current_password,
@@ -197,29 +281,26 @@ pub async fn update_current_user_password(
 pub async fn update_user_password(
     Extension(_claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    State((_, _, user_management, _auth_service)): State<(
-        Arc<Repositories>,
-        AppConfig,
-        Arc<UserManagementService>,
-        Arc<AuthService>,
-    )>,
+    State(state): State<Arc<UsersApiState>>,
     Json(password_request): Json<UpdatePasswordDto>,
 ) -> Result<Response, AppError> {
     // Only admin can change other users' passwords
-    if _claims.sub != id.to_string() && _claims.role != GLOBAL_ROLE_ADMIN {
+    if _claims.sub != id.to_string() && !_claims.role.can(Permission::UsersManage) {
         return Err(crate::errors::AppError::Authorization(
             "Access denied. You can only change your own password.".into(),
         ));
     }
 
     // If it's admin changing another user's password, we don't need to verify the current password
-    if _claims.sub != id.to_string() && _claims.role == GLOBAL_ROLE_ADMIN {
-        user_management
+    if _claims.sub != id.to_string() && _claims.role.can(Permission::UsersManage) {
+        state
+            .user_management_service
             .update_user_password(id, &password_request.new_password)
             .await?;
     } else {
         // For users changing their own passwords, we need to verify with the update_password method
-        user_management
+        state
+            .user_management_service
             .update_password(
                 id,
                 &password_request.current_password,
@@ -233,3 +314,453 @@ pub async fn update_user_password(
         "Password updated successfully",
     ))
 }
+
+// Mark a user's email as verified without the email flow (admin only)
+pub async fn verify_user_email(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    let user = state.user_management_service.verify_email(id).await?;
+
+    // Record which admin bypassed the verification flow
+    state
+        .audit_service
+        .record_admin_action(
+            AUDIT_EVENT_ADMIN_VERIFY_EMAIL,
+            admin_id,
+            id,
+            &request_id,
+            None,
+        )
+        .await;
+
+    Ok(ApiResponse::success(StatusCode::OK, user))
+}
+
+// Suspend a user account and log them out everywhere (admin only)
+pub async fn deactivate_user(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<UsersApiState>>,
+    Json(dto): Json<AccountStatusDto>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    dto.validate().map_err(validation_err_to_app_error)?;
+
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    if admin_id == id {
+        return Err(AppError::Validation(
+            "You cannot deactivate your own account".into(),
+        ));
+    }
+
+    let user = state
+        .user_management_service
+        .set_user_active(id, false)
+        .await?;
+
+    // Log the user out of every session immediately
+    state.auth_service.revoke_all_sessions(id).await?;
+
+    state
+        .audit_service
+        .record_admin_action(
+            AUDIT_EVENT_ADMIN_DEACTIVATE,
+            admin_id,
+            id,
+            &request_id,
+            Some(serde_json::json!({ "reason": dto.reason })),
+        )
+        .await;
+
+    Ok(ApiResponse::success(StatusCode::OK, user))
+}
+
+// Deactivate many user accounts at once, optionally as a dry run (admin only)
+pub async fn bulk_deactivate_users(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<BulkOperationQuery>,
+    State(state): State<Arc<UsersApiState>>,
+    Json(dto): Json<BulkAccountStatusDto>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    dto.validate().map_err(validation_err_to_app_error)?;
+
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    let result = state
+        .user_management_service
+        .bulk_deactivate_users(&dto.user_ids, admin_id, query.dry_run)
+        .await?;
+
+    if !result.dry_run {
+        for &id in &result.succeeded {
+            // Log the user out of every session immediately
+            state.auth_service.revoke_all_sessions(id).await?;
+
+            state
+                .audit_service
+                .record_admin_action(
+                    AUDIT_EVENT_ADMIN_DEACTIVATE,
+                    admin_id,
+                    id,
+                    &request_id,
+                    Some(serde_json::json!({ "reason": dto.reason, "bulk": true })),
+                )
+                .await;
+        }
+    }
+
+    Ok(ApiResponse::success(StatusCode::OK, result))
+}
+
+// Reactivate a suspended user account (admin only)
+pub async fn reactivate_user(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<UsersApiState>>,
+    Json(dto): Json<AccountStatusDto>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    dto.validate().map_err(validation_err_to_app_error)?;
+
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    let user = state
+        .user_management_service
+        .set_user_active(id, true)
+        .await?;
+
+    state
+        .audit_service
+        .record_admin_action(
+            AUDIT_EVENT_ADMIN_REACTIVATE,
+            admin_id,
+            id,
+            &request_id,
+            Some(serde_json::json!({ "reason": dto.reason })),
+        )
+        .await;
+
+    Ok(ApiResponse::success(StatusCode::OK, user))
+}
+
+// Sign a user out of every session and invalidate their tokens (admin only)
+pub async fn force_logout_user(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    let terminated = state.auth_service.force_logout(id).await?;
+
+    state
+        .audit_service
+        .record_admin_action(
+            AUDIT_EVENT_ADMIN_FORCE_LOGOUT,
+            admin_id,
+            id,
+            &request_id,
+            Some(serde_json::json!({ "terminated": terminated })),
+        )
+        .await;
+
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        serde_json::json!({ "terminated": terminated }),
+    ))
+}
+
+// Send a user a fresh email verification link on their behalf (admin only)
+pub async fn resend_user_verification_email(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    state.auth_service.resend_verification_for_user(id).await?;
+
+    state
+        .audit_service
+        .record_admin_action(
+            AUDIT_EVENT_ADMIN_RESEND_VERIFICATION,
+            admin_id,
+            id,
+            &request_id,
+            None,
+        )
+        .await;
+
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        "Verification email sent",
+    ))
+}
+
+// Send a user a password reset link on their behalf (admin only)
+pub async fn send_user_password_reset(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    state.auth_service.send_password_reset_for_user(id).await?;
+
+    state
+        .audit_service
+        .record_admin_action(
+            AUDIT_EVENT_ADMIN_SEND_PASSWORD_RESET,
+            admin_id,
+            id,
+            &request_id,
+            None,
+        )
+        .await;
+
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        "Password reset email sent",
+    ))
+}
+
+// Merge a duplicate account into another one (admin only)
+pub async fn merge_user(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path((source_id, target_id)): Path<(Uuid, Uuid)>,
+    State(state): State<Arc<UsersApiState>>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+    let merge = state
+        .user_management_service
+        .merge_users(source_id, target_id)
+        .await?;
+
+    state
+        .audit_service
+        .record_admin_action(
+            AUDIT_EVENT_ADMIN_MERGE,
+            admin_id,
+            target_id,
+            &request_id,
+            Some(serde_json::json!({
+                "source_id": source_id,
+                "badges_moved": merge.merged.badges_moved,
+                "badges_dropped": merge.merged.badges_dropped,
+                "connections_moved": merge.merged.connections_moved,
+                "connections_dropped": merge.merged.connections_dropped,
+            })),
+        )
+        .await;
+
+    Ok(ApiResponse::success(StatusCode::OK, merge))
+}
+
+// List the current user's email addresses
+pub async fn list_current_user_emails(
+    Extension(claims): Extension<Claims>,
+    State(user_email_service): State<Arc<UserEmailService>>,
+) -> Result<Response, AppError> {
+    let user_id = claims_user_id(&claims)?;
+    let emails = user_email_service.list_emails(user_id).await?;
+    Ok(ApiResponse::success(StatusCode::OK, emails))
+}
+
+// Add a secondary email to the current user and send it a verification link
+pub async fn add_current_user_email(
+    Extension(claims): Extension<Claims>,
+    State(user_email_service): State<Arc<UserEmailService>>,
+    Json(dto): Json<AddUserEmailDto>,
+) -> Result<Response, AppError> {
+    let user_id = claims_user_id(&claims)?;
+    let email = user_email_service.add_email(user_id, dto).await?;
+    Ok(ApiResponse::created(email))
+}
+
+// Resend the verification link for one of the current user's secondary emails
+pub async fn resend_current_user_email_verification(
+    Extension(claims): Extension<Claims>,
+    Path(email_id): Path<Uuid>,
+    State(user_email_service): State<Arc<UserEmailService>>,
+) -> Result<Response, AppError> {
+    let user_id = claims_user_id(&claims)?;
+    user_email_service
+        .resend_verification(user_id, email_id)
+        .await?;
+    Ok(ApiResponse::success(
+        StatusCode::OK,
+        "Verification email sent successfully",
+    ))
+}
+
+// Remove one of the current user's secondary emails
+pub async fn remove_current_user_email(
+    Extension(claims): Extension<Claims>,
+    Path(email_id): Path<Uuid>,
+    State(user_email_service): State<Arc<UserEmailService>>,
+) -> Result<Response, AppError> {
+    let user_id = claims_user_id(&claims)?;
+    user_email_service.remove_email(user_id, email_id).await?;
+    Ok(ApiResponse::no_content())
+}
+
+// Verify a secondary email from the token in its verification link. Takes a POST so
+// that fetching the emailed link can't verify.
+pub async fn verify_secondary_email(
+    State(user_email_service): State<Arc<UserEmailService>>,
+    Json(dto): Json<EmailTokenDto>,
+) -> Result<Response, AppError> {
+    let email = user_email_service.verify_email(&dto.token).await?;
+    Ok(ApiResponse::success(StatusCode::OK, email))
+}
+
+// Check a secondary email verification link without using it (the token in the path)
+pub async fn check_secondary_email_verification(
+    Path(token): Path<String>,
+    State(user_email_service): State<Arc<UserEmailService>>,
+) -> Result<Response, AppError> {
+    let pending = user_email_service.check_verification_token(&token).await?;
+    Ok(ApiResponse::success(StatusCode::OK, pending))
+}
+
+// Same as check_secondary_email_verification, with the token as a query parameter
+pub async fn check_secondary_email_verification_query(
+    Query(query): Query<EmailTokenDto>,
+    State(user_email_service): State<Arc<UserEmailService>>,
+) -> Result<Response, AppError> {
+    let pending = user_email_service
+        .check_verification_token(&query.token)
+        .await?;
+    Ok(ApiResponse::success(StatusCode::OK, pending))
+}
+
+// Start impersonating a user (admin only)
+pub async fn impersonate_user(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    State((impersonation_service, audit_service)): State<(
+        Arc<ImpersonationService>,
+        Arc<AuditService>,
+    )>,
+    Json(dto): Json<ImpersonateUserDto>,
+) -> Result<Response, AppError> {
+    // Admin check is handled by middleware
+    let admin_id = claims_user_id(&claims)?;
+
+    let impersonation = impersonation_service.start(admin_id, id, &dto).await?;
+
+    audit_service
+        .record_admin_action(
+            AUDIT_EVENT_IMPERSONATION_START,
+            admin_id,
+            id,
+            &request_id,
+            Some(serde_json::json!({
+                "reason": dto.reason,
+                "impersonation_id": impersonation.impersonation_id,
+                "expires_at": impersonation.expires_at,
+            })),
+        )
+        .await;
+
+    Ok(ApiResponse::created(impersonation))
+}
+
+// End the impersonation session behind the current (impersonation) token
+pub async fn end_impersonation(
+    request_id: RequestId,
+    Extension(claims): Extension<Claims>,
+    impersonator: Option<Extension<Impersonator>>,
+    State((impersonation_service, audit_service)): State<(
+        Arc<ImpersonationService>,
+        Arc<AuditService>,
+    )>,
+) -> Result<Response, AppError> {
+    if impersonator.is_none() {
+        return Err(AppError::Validation("Not an impersonation token".into()));
+    }
+
+    let session = impersonation_service.end(&claims).await?;
+
+    audit_service
+        .record_admin_action(
+            AUDIT_EVENT_IMPERSONATION_END,
+            session.admin_id,
+            session.user_id,
+            &request_id,
+            Some(serde_json::json!({ "impersonation_id": session.id })),
+        )
+        .await;
+
+    Ok(ApiResponse::no_content())
+}
+
+// Get the language the current user is addressed in, and the one they chose
+pub async fn get_current_user_locale(
+    Extension(claims): Extension<Claims>,
+    client: ClientContext,
+    State(locale_service): State<Arc<LocaleService>>,
+) -> Result<Response, AppError> {
+    let user_id = claims_user_id(&claims)?;
+    let locale = LocaleResponse {
+        locale: locale_service
+            .resolve(Some(user_id), client.accept_language.as_deref())
+            .await?,
+        preferred: locale_service.get_preference(user_id).await?,
+    };
+    Ok(ApiResponse::success(StatusCode::OK, locale))
+}
+
+// Choose the current user's language, or clear the choice with a null locale
+pub async fn update_current_user_locale(
+    Extension(claims): Extension<Claims>,
+    client: ClientContext,
+    State(locale_service): State<Arc<LocaleService>>,
+    Json(dto): Json<UpdateLocaleDto>,
+) -> Result<Response, AppError> {
+    let user_id = claims_user_id(&claims)?;
+    let preferred = locale_service
+        .set_preference(user_id, dto.locale.as_deref())
+        .await?;
+    let locale = LocaleResponse {
+        locale: locale_service
+            .resolve(Some(user_id), client.accept_language.as_deref())
+            .await?,
+        preferred,
+    };
+    Ok(ApiResponse::success(StatusCode::OK, locale))
+}
+
+fn claims_user_id(claims: &Claims) -> Result<Uuid, AppError> {
+    Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::Authentication("Invalid user ID in token".into()))
+}
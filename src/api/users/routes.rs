@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     middleware,
@@ -6,44 +7,227 @@ use axum::{
     Router,
 };
 
+use crate::api::ApiState;
 use crate::config::AppConfig;
-use crate::db::repositories::Repositories;
-use crate::middleware::auth::{require_admin, require_auth, require_verified_email};
-use crate::services::auth::{AuthService, TokenService};
+use crate::middleware::auth::{
+    deny_impersonation, require_admin, require_auth, require_auth_allow_expired_password,
+    require_verified_email,
+};
+use crate::middleware::cache::cache_publicly;
+use crate::middleware::rate_limit::{limit_failed_attempts, rate_limit_store, AttemptLimiter};
+use crate::services::audit::AuditService;
+use crate::services::auth::{AuthService, ImpersonationService};
+use crate::services::locale::LocaleService;
 use crate::services::user::UserManagementService;
 
 use super::handlers;
 
-pub fn configure(
-    state: Arc<Repositories>,
-    config: AppConfig,
-    user_management_service: Arc<UserManagementService>,
-    token_service: Arc<TokenService>,
-    auth_service: Arc<AuthService>,
-) -> Router {
+// Users API State struct
+pub struct UsersApiState {
+    pub user_management_service: Arc<UserManagementService>,
+    pub auth_service: Arc<AuthService>,
+    pub audit_service: Arc<AuditService>,
+}
+
+pub fn configure(api: &ApiState, config: AppConfig) -> Router {
+    let state = api.repos.clone();
+    let token_service = api.token_service.clone();
+    let user_management_service = api.user_management_service.clone();
+    let auth_service = api.auth_service.clone();
+    let audit_service = api.audit_service.clone();
+    let user_email_service = api.user_email_service.clone();
+    let badge_service = api.badge_service.clone();
+
     // Create nested router for /users routes with admin-only routes
     let admin_routes = Router::new()
         .route("/", get(handlers::list_users))
         .route("/", post(handlers::create_user))
-        .route("/:id", delete(handlers::delete_user))
+        .route("/:id/admin", get(handlers::get_user_admin))
+        .route("/:id/tokens", get(handlers::list_user_tokens))
+        .route(
+            "/:id",
+            delete(handlers::delete_user).layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route("/:id/verify-email", post(handlers::verify_user_email))
+        .route("/:id/deactivate", post(handlers::deactivate_user))
+        .route("/:id/reactivate", post(handlers::reactivate_user))
+        .route(
+            "/:id/resend-verification",
+            post(handlers::resend_user_verification_email),
+        )
+        .route(
+            "/:id/send-password-reset",
+            post(handlers::send_user_password_reset),
+        )
+        .route(
+            "/:id/logout",
+            post(handlers::force_logout_user).layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route(
+            "/:id/merge-into/:target_id",
+            post(handlers::merge_user).layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route("/bulk/deactivate", post(handlers::bulk_deactivate_users))
         .route_layer(middleware::from_fn(require_admin));
 
     // Create nested router for user routes (accessible to all authenticated users)
     let user_routes = Router::new()
         .route("/me", get(handlers::get_current_user))
-        .route("/me", put(handlers::update_current_user))
-        .route("/me/password", put(handlers::update_current_user_password))
-        .route("/:id", put(handlers::update_user))
-        .route("/:id/password", put(handlers::update_user_password));
+        .route(
+            "/me",
+            put(handlers::update_current_user).patch(handlers::update_current_user),
+        )
+        .route("/me/username", put(handlers::change_current_user_username))
+        .route(
+            "/me/connections/:provider/refresh",
+            get(handlers::refresh_current_user_connection),
+        )
+        .route(
+            "/:id",
+            put(handlers::update_user).patch(handlers::update_user),
+        )
+        .route(
+            "/:id/password",
+            put(handlers::update_user_password).layer(middleware::from_fn(deny_impersonation)),
+        );
 
-    // Public routes that don't require authentication
+    // Public routes that don't require authentication. The same for every caller; the
+    // user themselves and admins get the full account from /me and /:id/admin.
     let public_routes = Router::new()
         .route("/:id", get(handlers::get_user))
-        .with_state((
+        .route_layer(middleware::from_fn(cache_publicly))
+        .with_state(badge_service.clone());
+
+    // Secondary email routes for the current user
+    let email_routes = Router::new()
+        .route("/me/emails", get(handlers::list_current_user_emails))
+        .route(
+            "/me/emails",
+            post(handlers::add_current_user_email).layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route(
+            "/me/emails/:email_id",
+            delete(handlers::remove_current_user_email)
+                .layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route(
+            "/me/emails/:email_id/resend-verification",
+            post(handlers::resend_current_user_email_verification),
+        )
+        .route_layer(middleware::from_fn_with_state(
             state.clone(),
-            config.clone(),
-            user_management_service.clone(),
-            auth_service.clone(),
+            require_verified_email,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (state.clone(), token_service.clone()),
+            require_auth,
+        ));
+
+    // Secondary email verification takes a secret token - failed guesses lock the client out
+    let email_token_routes = Router::new()
+        .route(
+            "/emails/verify",
+            get(handlers::check_secondary_email_verification_query)
+                .post(handlers::verify_secondary_email),
+        )
+        .route(
+            "/emails/verify/:token",
+            get(handlers::check_secondary_email_verification),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(AttemptLimiter::new(
+                rate_limit_store(&config.rate_limit_store),
+                "email_token_attempts",
+                config.token_attempt_limit,
+                Duration::from_secs(config.token_attempt_window),
+            )),
+            limit_failed_attempts,
+        ));
+
+    let email_routes = email_routes
+        .merge(email_token_routes)
+        .with_state(user_email_service);
+
+    // A user's badges as signed credentials, for themselves and admins
+    let credential_routes = Router::new()
+        .route(
+            "/:id/badges/:badge_id/credential",
+            get(handlers::get_user_badge_credential),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_verified_email,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (state.clone(), token_service.clone()),
+            require_auth,
+        ))
+        .with_state(badge_service);
+
+    // The current user's language
+    let locale_routes = Router::new()
+        .route(
+            "/me/locale",
+            get(handlers::get_current_user_locale).put(handlers::update_current_user_locale),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_verified_email,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (state.clone(), token_service.clone()),
+            require_auth,
+        ))
+        .with_state(Arc::new(LocaleService::new(
+            state.clone(),
+            config.locale.clone(),
+        )));
+
+    let impersonation_service = Arc::new(
+        ImpersonationService::new(
+            state.clone(),
+            token_service.clone(),
+            config.impersonation_token_expiration,
+        )
+        .with_avatars(config.avatars.clone()),
+    );
+
+    // Starting an impersonation is admin only, and can't be chained
+    let impersonate_routes = Router::new()
+        .route(
+            "/:id/impersonate",
+            post(handlers::impersonate_user).layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route_layer(middleware::from_fn(require_admin))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_verified_email,
+        ));
+
+    // Ending one is done with the impersonation token itself
+    let impersonation_routes = Router::new()
+        .route("/impersonation/end", post(handlers::end_impersonation))
+        .merge(impersonate_routes)
+        .route_layer(middleware::from_fn_with_state(
+            (state.clone(), token_service.clone()),
+            require_auth,
+        ))
+        .with_state((impersonation_service, audit_service.clone()));
+
+    // Changing your own password stays reachable once it has expired
+    let password_routes = Router::new()
+        .route(
+            "/me/password",
+            put(handlers::update_current_user_password)
+                .layer(middleware::from_fn(deny_impersonation)),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_verified_email,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (state.clone(), token_service.clone()),
+            require_auth_allow_expired_password,
         ));
 
     // Merge authenticated routes and apply authentication middleware
@@ -57,8 +241,18 @@ pub fn configure(
             (state.clone(), token_service),
             require_auth,
         ))
-        .with_state((state, config, user_management_service, auth_service));
+        .merge(password_routes)
+        .with_state(Arc::new(UsersApiState {
+            user_management_service,
+            auth_service,
+            audit_service,
+        }));
 
     // Merge public and authenticated routes without applying auth middleware to public routes
-    public_routes.merge(authenticated_routes)
+    public_routes
+        .merge(authenticated_routes)
+        .merge(email_routes)
+        .merge(credential_routes)
+        .merge(locale_routes)
+        .merge(impersonation_routes)
 }
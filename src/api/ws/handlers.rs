@@ -0,0 +1,122 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::HeaderMap,
+    response::Response,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{interval_at, sleep, Instant};
+use uuid::Uuid;
+
+use super::routes::WsApiState;
+use crate::errors::AppError;
+use crate::middleware::auth::{authenticate_token, extract_token_from_headers};
+use crate::middleware::client_context::ClientContext;
+
+// How often an open socket checks that its token is still accepted, so deactivated or
+// signed-out users stop receiving events
+const RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+pub struct WsAuthQuery {
+    pub token: Option<String>,
+}
+
+// Upgrade to a WebSocket that streams the authenticated user's account events
+pub async fn account_events(
+    State(state): State<Arc<WsApiState>>,
+    Query(query): Query<WsAuthQuery>,
+    headers: HeaderMap,
+    client: ClientContext,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    // Browsers can't set headers on WebSocket requests, so also accept ?token=
+    let token = extract_token_from_headers(&headers)
+        .or(query.token)
+        .ok_or_else(|| AppError::Authentication("No authentication token provided".into()))?;
+
+    let (claims, user) = authenticate_token(&state.repos, &state.token_service, &token).await?;
+    state.token_service.check_fingerprint(&claims, &client)?;
+
+    if !user.is_email_verified {
+        return Err(AppError::Authorization(
+            "Email verification required".into(),
+        ));
+    }
+
+    let expires_in = Duration::from_secs((claims.exp - Utc::now().timestamp()).max(0) as u64);
+    Ok(ws.on_upgrade(move |socket| stream_events(socket, state, token, expires_in, user.id)))
+}
+
+// Forward this user's events until either side goes away or the token stops being valid
+async fn stream_events(
+    mut socket: WebSocket,
+    state: Arc<WsApiState>,
+    token: String,
+    expires_in: Duration,
+    user_id: Uuid,
+) {
+    let mut events = state.events.subscribe();
+    let expiry = sleep(expires_in);
+    tokio::pin!(expiry);
+    let mut recheck = interval_at(Instant::now() + RECHECK_INTERVAL, RECHECK_INTERVAL);
+    tracing::debug!("WebSocket connected for user {}", user_id);
+
+    let reason = loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if event.user_id == user_id => {
+                    let payload = match serde_json::to_string(&event) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            tracing::error!("Failed to serialize account event: {}", e);
+                            continue;
+                        }
+                    };
+                    if socket.send(Message::Text(payload)).await.is_err() {
+                        break None;
+                    }
+                }
+                Ok(_) => {}
+                // Slow consumer; skip what was dropped and keep streaming
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("WebSocket for user {} skipped {} events", user_id, skipped);
+                }
+                Err(RecvError::Closed) => break None,
+            },
+            message = socket.recv() => match message {
+                // Client messages are ignored; pings are answered by axum
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break None,
+                Some(Ok(_)) => {}
+            },
+            _ = &mut expiry => break Some("Token expired"),
+            // Deactivation, revocation and ended sessions all show up here
+            _ = recheck.tick() => {
+                if authenticate_token(&state.repos, &state.token_service, &token)
+                    .await
+                    .is_err()
+                {
+                    break Some("Token no longer valid");
+                }
+            }
+        }
+    };
+
+    if let Some(reason) = reason {
+        let frame = CloseFrame {
+            code: close_code::POLICY,
+            reason: reason.into(),
+        };
+        let _ = socket.send(Message::Close(Some(frame))).await;
+    }
+
+    // Dropping the receiver unsubscribes from the bus
+    tracing::debug!("WebSocket disconnected for user {}", user_id);
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use axum::{routing::get, Router};
+
+use crate::db::repositories::Repositories;
+use crate::services::auth::TokenService;
+use crate::services::events::EventBus;
+
+use super::handlers;
+
+// WebSocket API State struct
+pub struct WsApiState {
+    pub repos: Arc<Repositories>,
+    pub token_service: Arc<TokenService>,
+    pub events: Arc<EventBus>,
+}
+
+// Configure WebSocket routes
+pub fn configure(
+    repos: Arc<Repositories>,
+    token_service: Arc<TokenService>,
+    events: Arc<EventBus>,
+) -> Router {
+    let state = Arc::new(WsApiState {
+        repos,
+        token_service,
+        events,
+    });
+
+    // Authentication happens in the handler, before the upgrade
+    Router::new()
+        .route("/", get(handlers::account_events))
+        .with_state(state)
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+
+use crate::config::DatabaseConfig;
+use crate::db;
+use crate::db::repositories::{Repositories, UserRepository};
+use crate::models::auth::token::{TOKEN_TYPE_EMAIL_VERIFICATION, TOKEN_TYPE_PASSWORD_RESET};
+use crate::models::user::{CreateUserDto, Role, User};
+use crate::services::events::EventBus;
+use crate::services::user::UserManagementService;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "safatanc-connect-core",
+    version,
+    about = "Safatanc Connect core service"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Create a verified admin account
+    CreateAdmin {
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "admin")]
+        username: String,
+        /// Prefer the environment variable so the password stays out of shell history
+        #[arg(long, env = "CLI_ADMIN_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+    /// Give an existing account the admin role
+    Promote { email: String },
+    /// Mark an account's email address as verified
+    VerifyEmail { email: String },
+    /// Set a new password and sign the account out everywhere
+    ResetPassword {
+        email: String,
+        #[arg(long, env = "CLI_NEW_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+    /// Apply pending database migrations
+    RunMigrations,
+}
+
+// Run an admin subcommand against the configured database, without the HTTP server.
+// Only DATABASE_URL is needed, so these work even when the rest of the config is incomplete.
+pub async fn run(command: Command) -> Result<()> {
+    let pool = db::pool::init_db_pool(&DatabaseConfig::from_env()).await?;
+
+    if let Command::RunMigrations = command {
+        db::pool::run_migrations(&pool).await?;
+        println!("Migrations applied");
+        return Ok(());
+    }
+
+    let repos = Repositories::new(pool.as_ref().clone());
+    let user_management = UserManagementService::new(
+        UserRepository::new(pool.as_ref().clone()),
+        Arc::new(EventBus::new()),
+    );
+
+    match command {
+        Command::CreateAdmin {
+            email,
+            username,
+            password,
+        } => {
+            let admin = user_management
+                .create_admin(CreateUserDto {
+                    email,
+                    username,
+                    password,
+                    full_name: None,
+                    avatar_url: None,
+                    invite_token: None,
+                })
+                .await?;
+            println!(
+                "Created admin {} <{}> ({})",
+                admin.username, admin.email, admin.id
+            );
+        }
+        Command::Promote { email } => {
+            let user = find_user(&repos, &email).await?;
+            user_management
+                .set_global_role(user.id, Role::Admin)
+                .await?;
+            println!("Promoted {} <{}> to admin", user.username, user.email);
+        }
+        Command::VerifyEmail { email } => {
+            let user = find_user(&repos, &email).await?;
+            user_management.verify_email(user.id).await?;
+            println!("Verified email for {} <{}>", user.username, user.email);
+        }
+        Command::ResetPassword { email, password } => {
+            let user = find_user(&repos, &email).await?;
+            user_management
+                .update_user_password(user.id, &password)
+                .await?;
+
+            // Same clean-up as a self-service reset: no old session or emailed link stays valid
+            repos.session().deactivate_all_for_user(user.id).await?;
+            for token_type in [TOKEN_TYPE_EMAIL_VERIFICATION, TOKEN_TYPE_PASSWORD_RESET] {
+                repos
+                    .token()
+                    .invalidate_by_user_and_type(user.id, token_type)
+                    .await?;
+            }
+            println!("Reset password for {} <{}>", user.username, user.email);
+        }
+        Command::Serve | Command::RunMigrations => unreachable!(),
+    }
+
+    Ok(())
+}
+
+async fn find_user(repos: &Repositories, email: &str) -> Result<User> {
+    repos
+        .user()
+        .find_by_email(email)
+        .await
+        .map_err(|_| anyhow!("No account found for {}", email))
+}
@@ -1,17 +1,151 @@
-use crate::config::{DatabaseConfig, EmailConfig, OAuthConfig};
+use crate::config::proxy::trusted_proxies_from_env;
+use crate::config::{
+    AvatarConfig, BootstrapAdminConfig, CredentialConfig, DatabaseConfig, DormancyConfig,
+    EmailConfig, IpNetwork, LocaleConfig, OAuthConfig, RateLimitStoreConfig, SecurityHeadersConfig,
+};
+use crate::errors::AppError;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::env;
 
+// Who may create an account through POST /auth/register (and first-time OAuth sign-in)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    // Anyone can register
+    Open,
+    // Only holders of an invite issued by an admin
+    InviteOnly,
+    // Nobody; only admins can create accounts
+    Closed,
+}
+
+impl RegistrationMode {
+    // Why self-registration is refused in this mode, if it is
+    pub fn check(self, has_invite: bool) -> Result<(), AppError> {
+        match self {
+            Self::Open => Ok(()),
+            Self::InviteOnly if has_invite => Ok(()),
+            Self::InviteOnly => Err(AppError::RegistrationDisabled(
+                "Registration is by invitation only".into(),
+            )),
+            Self::Closed => Err(AppError::RegistrationDisabled(
+                "Registration is currently closed".into(),
+            )),
+        }
+    }
+
+    fn from_env() -> Self {
+        match env::var("REGISTRATION_MODE")
+            .unwrap_or_else(|_| "open".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "open" => Self::Open,
+            "invite_only" => Self::InviteOnly,
+            "closed" => Self::Closed,
+            other => panic!(
+                "REGISTRATION_MODE must be open, invite_only or closed, got {}",
+                other
+            ),
+        }
+    }
+}
+
+// What to do when a token is used from a different client than it was issued to. Tokens
+// issued while this is on carry a fingerprint of the client's network and user agent.
+// A change only counts when both differ, but that still happens legitimately: a phone
+// moving from Wi-Fi to mobile data around a browser update, or a VPN being switched on
+// in a browser that updated itself. Start with `warn` and watch the logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenBinding {
+    // Tokens aren't bound to clients
+    Off,
+    // Mismatches are logged, but the token is accepted
+    Warn,
+    // Mismatches are rejected; the client has to sign in again
+    Enforce,
+}
+
+impl TokenBinding {
+    fn from_env() -> Self {
+        match env::var("TOKEN_BINDING")
+            .unwrap_or_else(|_| "off".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "off" => Self::Off,
+            "warn" => Self::Warn,
+            "enforce" => Self::Enforce,
+            other => panic!("TOKEN_BINDING must be off, warn or enforce, got {}", other),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub database: DatabaseConfig,
     pub email: EmailConfig,
     pub oauth: OAuthConfig,
+    pub dormancy: DormancyConfig,
+    pub bootstrap_admin: Option<BootstrapAdminConfig>,
     pub server_host: String,
     pub server_port: u16,
     pub jwt_secret: String,
-    pub jwt_expiration: i64,           // in seconds
-    pub refresh_token_expiration: i64, // in seconds
+    pub jwt_expiration: i64, // in seconds
+    // Access token lifetimes in seconds for client apps that ask for their own, by the
+    // client_id they log in with; clients not listed can't log in with a client_id
+    pub jwt_client_expirations: HashMap<String, i64>,
+    pub refresh_token_expiration: i64,       // in seconds
+    pub impersonation_token_expiration: i64, // in seconds
+    // Trust access tokens without looking the account up on each request. Saves a query
+    // per request, but suspending, deleting or signing out a user only takes effect
+    // once their access tokens expire (JWT_EXPIRATION), instead of immediately.
+    pub auth_stateless: bool,
+    pub token_binding: TokenBinding,
+    // Hand refresh tokens to browsers in an HttpOnly cookie instead of response bodies.
+    // /auth/login sets it, /auth/refresh reads and rotates it and /auth/logout clears it;
+    // API clients can keep sending the token in the body.
+    pub refresh_token_cookie: bool,
     pub cors_allowed_origins: Vec<String>,
+    pub graphql_playground_enabled: bool,
+    pub response_envelope: bool, // default for clients that don't negotiate via Accept
+    pub api_base_path: String,   // e.g. "/api/v1"; empty serves the API at the root
+    pub token_attempt_limit: u32, // failed verify-email/reset-password attempts per IP
+    pub token_attempt_window: u64, // in seconds
+    pub rate_limit_store: RateLimitStoreConfig,
+    pub registration_limit_global: u32, // self-registrations per minute overall; 0 disables
+    pub registration_limit_per_subnet: u32, // per minute from one /24 or /64; 0 disables
+    pub password_history_size: usize,   // previous passwords that can't be reused; 0 disables
+    pub password_max_age_days: i64,     // force a password change after this long; 0 disables
+    pub json_max_depth: usize,          // deepest object/array nesting accepted in JSON bodies
+    pub graphql_max_depth: usize,       // deepest selection nesting accepted in GraphQL queries
+    pub graphql_max_complexity: usize,  // most fields a single GraphQL query may select
+    pub track_session_activity: bool,   // update sessions' last_activity_at from requests
+    pub session_activity_interval: u64, // in seconds; minimum time between updates per session
+    pub session_idle_timeout_secs: u64, // sessions idle this long are expired; 0 disables
+    pub max_page_size: i64,             // largest `limit` paginated endpoints accept
+    pub registration_mode: RegistrationMode,
+    pub allowed_email_domains: Vec<String>, // self-registration only from these; empty allows all
+    pub trust_proxy: bool,                  // take client IPs from X-Forwarded-For/X-Real-IP
+    pub trusted_proxies: Vec<IpNetwork>, // with trust_proxy, peers whose headers count; empty trusts any
+    pub cache_max_age: u64,              // in seconds; max-age for publicly cacheable GET responses
+    pub security_headers: SecurityHeadersConfig,
+    pub compression_enabled: bool, // gzip responses for clients that accept it
+    pub compression_min_size: usize, // in bytes; smaller responses are sent as is
+    pub avatars: AvatarConfig,
+    pub credentials: CredentialConfig,
+    pub username_change_cooldown_days: i64, // between a user's own username changes; 0 disables
+    pub username_reservation_days: i64, // a changed-away username stays with its user; 0 frees it
+    pub password_reset_cooldown_secs: u64, // between reset emails to one user; 0 disables
+    // A successful password login voids the user's outstanding reset links: they
+    // remembered the password, and a leaked link stops working sooner
+    pub invalidate_reset_on_login: bool,
+    // Refuse password logins until the account's email is verified. Accounts created
+    // through OAuth are verified from the start.
+    pub require_verified_email_for_login: bool,
+    pub login_verification_resend_secs: u64, // a refused login resends the link this often; 0 never
+    pub locale: LocaleConfig,
 }
 
 impl AppConfig {
@@ -27,6 +161,8 @@ impl AppConfig {
             database: DatabaseConfig::from_env(),
             email: EmailConfig::from_env(),
             oauth: OAuthConfig::from_env(),
+            dormancy: DormancyConfig::from_env(),
+            bootstrap_admin: BootstrapAdminConfig::from_env(),
             server_host: env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
             server_port: env::var("SERVER_PORT")
                 .unwrap_or_else(|_| "8080".to_string())
@@ -37,11 +173,164 @@ impl AppConfig {
                 .unwrap_or_else(|_| "3600".to_string()) // 1 hour
                 .parse()
                 .expect("JWT_EXPIRATION must be a number"),
+            jwt_client_expirations: env::var("JWT_CLIENT_EXPIRATIONS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|entry| {
+                    entry
+                        .split_once('=')
+                        .and_then(|(client_id, ttl)| {
+                            Some((client_id.trim().to_string(), ttl.trim().parse().ok()?))
+                        })
+                        .expect("JWT_CLIENT_EXPIRATIONS must be client_id=seconds pairs")
+                })
+                .collect(),
             refresh_token_expiration: env::var("REFRESH_TOKEN_EXPIRATION")
                 .unwrap_or_else(|_| "604800".to_string()) // 7 days
                 .parse()
                 .expect("REFRESH_TOKEN_EXPIRATION must be a number"),
+            impersonation_token_expiration: env::var("IMPERSONATION_TOKEN_EXPIRATION")
+                .unwrap_or_else(|_| "900".to_string()) // 15 minutes
+                .parse()
+                .expect("IMPERSONATION_TOKEN_EXPIRATION must be a number"),
+            auth_stateless: env::var("AUTH_STATELESS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("AUTH_STATELESS must be true or false"),
+            token_binding: TokenBinding::from_env(),
+            refresh_token_cookie: env::var("REFRESH_TOKEN_COOKIE")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("REFRESH_TOKEN_COOKIE must be true or false"),
             cors_allowed_origins: cors_origins,
+            graphql_playground_enabled: env::var("GRAPHQL_PLAYGROUND_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("GRAPHQL_PLAYGROUND_ENABLED must be true or false"),
+            response_envelope: env::var("RESPONSE_ENVELOPE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .expect("RESPONSE_ENVELOPE must be true or false"),
+            api_base_path: api_base_path_from_env(),
+            token_attempt_limit: env::var("TOKEN_ATTEMPT_LIMIT")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("TOKEN_ATTEMPT_LIMIT must be a number"),
+            token_attempt_window: env::var("TOKEN_ATTEMPT_WINDOW")
+                .unwrap_or_else(|_| "900".to_string()) // 15 minutes
+                .parse()
+                .expect("TOKEN_ATTEMPT_WINDOW must be a number"),
+            rate_limit_store: RateLimitStoreConfig::from_env(),
+            registration_limit_global: env::var("REGISTRATION_LIMIT_GLOBAL")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("REGISTRATION_LIMIT_GLOBAL must be a number"),
+            registration_limit_per_subnet: env::var("REGISTRATION_LIMIT_PER_SUBNET")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("REGISTRATION_LIMIT_PER_SUBNET must be a number"),
+            password_history_size: env::var("PASSWORD_HISTORY_SIZE")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("PASSWORD_HISTORY_SIZE must be a number"),
+            password_max_age_days: env::var("PASSWORD_MAX_AGE_DAYS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("PASSWORD_MAX_AGE_DAYS must be a number"),
+            json_max_depth: env::var("JSON_MAX_DEPTH")
+                .unwrap_or_else(|_| "32".to_string())
+                .parse()
+                .expect("JSON_MAX_DEPTH must be a number"),
+            graphql_max_depth: env::var("GRAPHQL_MAX_DEPTH")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("GRAPHQL_MAX_DEPTH must be a number"),
+            graphql_max_complexity: env::var("GRAPHQL_MAX_COMPLEXITY")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .expect("GRAPHQL_MAX_COMPLEXITY must be a number"),
+            track_session_activity: env::var("TRACK_SESSION_ACTIVITY")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("TRACK_SESSION_ACTIVITY must be true or false"),
+            session_activity_interval: env::var("SESSION_ACTIVITY_INTERVAL")
+                .unwrap_or_else(|_| "60".to_string()) // 1 minute
+                .parse()
+                .expect("SESSION_ACTIVITY_INTERVAL must be a number"),
+            session_idle_timeout_secs: env::var("SESSION_IDLE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("SESSION_IDLE_TIMEOUT_SECS must be a number"),
+            max_page_size: env::var("MAX_PAGE_SIZE")
+                .unwrap_or_else(|_| "100".to_string())
+                .parse()
+                .expect("MAX_PAGE_SIZE must be a number"),
+            registration_mode: RegistrationMode::from_env(),
+            allowed_email_domains: env::var("ALLOWED_EMAIL_DOMAINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().trim_start_matches('@').to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            trust_proxy: env::var("TRUST_PROXY")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("TRUST_PROXY must be true or false"),
+            trusted_proxies: trusted_proxies_from_env(),
+            cache_max_age: env::var("CACHE_MAX_AGE")
+                .unwrap_or_else(|_| "60".to_string()) // 1 minute
+                .parse()
+                .expect("CACHE_MAX_AGE must be a number"),
+            security_headers: SecurityHeadersConfig::from_env(),
+            compression_enabled: env::var("COMPRESSION_ENABLED")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .expect("COMPRESSION_ENABLED must be true or false"),
+            compression_min_size: env::var("COMPRESSION_MIN_SIZE")
+                .unwrap_or_else(|_| "1024".to_string())
+                .parse()
+                .expect("COMPRESSION_MIN_SIZE must be a number"),
+            avatars: AvatarConfig::from_env(),
+            credentials: CredentialConfig::from_env(),
+            username_change_cooldown_days: env::var("USERNAME_CHANGE_COOLDOWN_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .expect("USERNAME_CHANGE_COOLDOWN_DAYS must be a number"),
+            username_reservation_days: env::var("USERNAME_RESERVATION_DAYS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("USERNAME_RESERVATION_DAYS must be a number"),
+            password_reset_cooldown_secs: env::var("PASSWORD_RESET_COOLDOWN_SECS")
+                .unwrap_or_else(|_| "300".to_string()) // 5 minutes
+                .parse()
+                .expect("PASSWORD_RESET_COOLDOWN_SECS must be a number"),
+            invalidate_reset_on_login: env::var("INVALIDATE_RESET_ON_LOGIN")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("INVALIDATE_RESET_ON_LOGIN must be true or false"),
+            require_verified_email_for_login: env::var("REQUIRE_VERIFIED_EMAIL_FOR_LOGIN")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("REQUIRE_VERIFIED_EMAIL_FOR_LOGIN must be true or false"),
+            login_verification_resend_secs: env::var("LOGIN_VERIFICATION_RESEND_SECS")
+                .unwrap_or_else(|_| "600".to_string()) // 10 minutes
+                .parse()
+                .expect("LOGIN_VERIFICATION_RESEND_SECS must be a number"),
+            locale: LocaleConfig::from_env(),
         }
     }
 }
+
+// Read API_BASE_PATH, normalized to either "" or "/segment[/segment...]" without a trailing slash
+pub fn api_base_path_from_env() -> String {
+    let path = env::var("API_BASE_PATH").unwrap_or_default();
+    let path = path.trim().trim_matches('/');
+
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", path)
+    }
+}
@@ -0,0 +1,79 @@
+use std::env;
+
+use sha2::{Digest, Sha256};
+
+// What `avatar_url` shows for users who haven't set one. Both are off by default,
+// leaving it null.
+#[derive(Debug, Clone, Default)]
+pub struct AvatarConfig {
+    // Link to the Gravatar for the user's email address
+    pub gravatar_fallback: bool,
+    // Shown when there is no Gravatar either, or instead of one when it's off
+    pub default_url: Option<String>,
+}
+
+impl AvatarConfig {
+    pub fn from_env() -> Self {
+        Self {
+            gravatar_fallback: env::var("AVATAR_GRAVATAR_FALLBACK")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .expect("AVATAR_GRAVATAR_FALLBACK must be true or false"),
+            default_url: env::var("AVATAR_DEFAULT_URL")
+                .ok()
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty()),
+        }
+    }
+
+    // The avatar for a user without one of their own, if any is configured
+    pub fn fallback_for(&self, email: &str) -> Option<String> {
+        if !self.gravatar_fallback {
+            return self.default_url.clone();
+        }
+
+        // Gravatar looks addresses up by the SHA-256 of the trimmed, lowercased address,
+        // and serves `d` (an image URL or one of its generated styles) when it has none
+        let hash = Sha256::digest(email.trim().to_lowercase().as_bytes());
+        let default = self
+            .default_url
+            .as_deref()
+            .map_or("identicon".into(), urlencoding::encode);
+        Some(format!(
+            "https://www.gravatar.com/avatar/{:x}?d={}",
+            hash, default
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_follows_the_configuration() {
+        let mut config = AvatarConfig::default();
+        assert_eq!(config.fallback_for("user@example.com"), None);
+
+        config.default_url = Some("https://cdn.example.com/avatar.png".to_string());
+        assert_eq!(
+            config.fallback_for("user@example.com").as_deref(),
+            Some("https://cdn.example.com/avatar.png")
+        );
+
+        config.gravatar_fallback = true;
+        assert_eq!(
+            config.fallback_for(" User@Example.com ").as_deref(),
+            Some(
+                "https://www.gravatar.com/avatar/\
+                 b4c9a289323b21a01c3e940f150eb9b8c542587f1abfd8f0e1cc1ffc5e475514\
+                 ?d=https%3A%2F%2Fcdn.example.com%2Favatar.png"
+            )
+        );
+
+        config.default_url = None;
+        assert!(config
+            .fallback_for("user@example.com")
+            .is_some_and(|url| url.ends_with("?d=identicon")));
+    }
+}
@@ -0,0 +1,32 @@
+use std::env;
+
+// First admin account, created at startup only while no admin exists
+#[derive(Debug, Clone)]
+pub struct BootstrapAdminConfig {
+    pub email: String,
+    pub password: String,
+    pub username: String,
+}
+
+impl BootstrapAdminConfig {
+    // None unless both BOOTSTRAP_ADMIN_EMAIL and BOOTSTRAP_ADMIN_PASSWORD are set
+    pub fn from_env() -> Option<Self> {
+        let email = env::var("BOOTSTRAP_ADMIN_EMAIL")
+            .ok()
+            .filter(|v| !v.is_empty());
+        let password = env::var("BOOTSTRAP_ADMIN_PASSWORD")
+            .ok()
+            .filter(|v| !v.is_empty());
+
+        match (email, password) {
+            (Some(email), Some(password)) => Some(Self {
+                email,
+                password,
+                username: env::var("BOOTSTRAP_ADMIN_USERNAME")
+                    .unwrap_or_else(|_| "admin".to_string()),
+            }),
+            (None, None) => None,
+            _ => panic!("BOOTSTRAP_ADMIN_EMAIL and BOOTSTRAP_ADMIN_PASSWORD must be set together"),
+        }
+    }
+}
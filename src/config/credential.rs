@@ -0,0 +1,25 @@
+use std::env;
+
+// Signing of badges as verifiable credentials. Off unless a key is configured.
+#[derive(Debug, Clone, Default)]
+pub struct CredentialConfig {
+    // PEM file with a PKCS#8 Ed25519 private key, e.g. from
+    // `openssl genpkey -algorithm ed25519`
+    pub signing_key_file: Option<String>,
+    // Who credentials say issued them, as a URL verifiers can recognize
+    pub issuer: String,
+}
+
+impl CredentialConfig {
+    pub fn from_env() -> Self {
+        Self {
+            signing_key_file: env::var("CREDENTIAL_SIGNING_KEY_FILE")
+                .ok()
+                .map(|path| path.trim().to_string())
+                .filter(|path| !path.is_empty()),
+            issuer: env::var("CREDENTIAL_ISSUER")
+                .or_else(|_| env::var("FRONTEND_URL"))
+                .unwrap_or_default(),
+        }
+    }
+}
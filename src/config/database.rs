@@ -4,6 +4,7 @@ use std::env;
 pub struct DatabaseConfig {
     pub connection_string: String,
     pub max_connections: u32,
+    pub slow_query_ms: u64, // log statements slower than this at WARN; 0 disables
 }
 
 impl DatabaseConfig {
@@ -14,6 +15,10 @@ impl DatabaseConfig {
                 .unwrap_or_else(|_| "5".to_string())
                 .parse()
                 .expect("DB_MAX_CONNECTIONS must be a number"),
+            slow_query_ms: env::var("SLOW_QUERY_MS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("SLOW_QUERY_MS must be a number"),
         }
     }
 }
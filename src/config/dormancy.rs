@@ -0,0 +1,37 @@
+use std::env;
+
+// Lifecycle handling for accounts that stop logging in. Every stage is off (0) by default.
+#[derive(Debug, Clone)]
+pub struct DormancyConfig {
+    pub notice_after_days: i64, // inactivity before the "we miss you" notice is emailed
+    pub deactivate_after_days: i64, // grace period after the notice before deactivation
+    pub check_interval: u64,    // in seconds
+}
+
+impl DormancyConfig {
+    pub fn from_env() -> Self {
+        Self {
+            notice_after_days: env::var("DORMANCY_NOTICE_AFTER_DAYS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("DORMANCY_NOTICE_AFTER_DAYS must be a number"),
+            deactivate_after_days: env::var("DORMANCY_DEACTIVATE_AFTER_DAYS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("DORMANCY_DEACTIVATE_AFTER_DAYS must be a number"),
+            check_interval: env::var("DORMANCY_CHECK_INTERVAL")
+                .unwrap_or_else(|_| "86400".to_string()) // once a day
+                .parse()
+                .expect("DORMANCY_CHECK_INTERVAL must be a number"),
+        }
+    }
+
+    pub fn notice_enabled(&self) -> bool {
+        self.notice_after_days > 0
+    }
+
+    // Deactivation only ever follows a notice, so it needs both stages
+    pub fn deactivation_enabled(&self) -> bool {
+        self.notice_enabled() && self.deactivate_after_days > 0
+    }
+}
@@ -2,6 +2,7 @@ use std::env;
 
 #[derive(Debug, Clone)]
 pub struct EmailConfig {
+    pub enabled: bool, // false sends nothing, and new accounts are verified without an email
     pub smtp_host: String,
     pub smtp_port: u16,
     pub smtp_username: String,
@@ -13,14 +14,26 @@ pub struct EmailConfig {
 
 impl EmailConfig {
     pub fn from_env() -> Self {
+        let enabled: bool = env::var("EMAIL_ENABLED")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .expect("EMAIL_ENABLED must be true or false");
+        // SMTP credentials are only needed when email is sent
+        let smtp_var = |name: &str| match env::var(name) {
+            Ok(value) => value,
+            Err(_) if !enabled => String::new(),
+            Err(_) => panic!("{} must be set", name),
+        };
+
         Self {
+            enabled,
             smtp_host: env::var("SMTP_HOST").unwrap_or_else(|_| "smtp.gmail.com".to_string()),
             smtp_port: env::var("SMTP_PORT")
                 .unwrap_or_else(|_| "587".to_string())
                 .parse()
                 .expect("SMTP_PORT must be a number"),
-            smtp_username: env::var("SMTP_USERNAME").expect("SMTP_USERNAME must be set"),
-            smtp_password: env::var("SMTP_PASSWORD").expect("SMTP_PASSWORD must be set"),
+            smtp_username: smtp_var("SMTP_USERNAME"),
+            smtp_password: smtp_var("SMTP_PASSWORD"),
             sender_email: env::var("SENDER_EMAIL")
                 .unwrap_or_else(|_| "noreply@safatanc-connect.com".to_string()),
             sender_name: env::var("SENDER_NAME").unwrap_or_else(|_| "Safatanc Connect".to_string()),
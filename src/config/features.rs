@@ -0,0 +1,29 @@
+use serde::Serialize;
+
+use crate::config::{AppConfig, RegistrationMode};
+
+// The optional behaviors this deployment has switched on, for clients to adapt their UI
+// to. Derived from the settings that control each one, so it can't disagree with them;
+// nothing secret belongs here since it is served publicly.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureFlags {
+    pub registration_mode: RegistrationMode,
+    pub allowed_email_domains: Vec<String>, // empty allows all
+    pub email_enabled: bool,                // verification and password reset emails are sent
+    pub password_expiry_enabled: bool,
+    pub password_history_enabled: bool,
+    pub graphql_playground_enabled: bool,
+}
+
+impl FeatureFlags {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            registration_mode: config.registration_mode,
+            allowed_email_domains: config.allowed_email_domains.clone(),
+            email_enabled: config.email.enabled,
+            password_expiry_enabled: config.password_max_age_days > 0,
+            password_history_enabled: config.password_history_size > 0,
+            graphql_playground_enabled: config.graphql_playground_enabled,
+        }
+    }
+}
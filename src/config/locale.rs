@@ -0,0 +1,103 @@
+use std::env;
+
+// Languages users can be addressed in: SUPPORTED_LOCALES (comma-separated) and
+// DEFAULT_LOCALE ("en"). The default is used when neither the user nor their client
+// asks for a supported one, and is always supported itself.
+#[derive(Debug, Clone)]
+pub struct LocaleConfig {
+    pub default: String,
+    pub supported: Vec<String>,
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        Self {
+            default: "en".to_string(),
+            supported: vec!["en".to_string()],
+        }
+    }
+}
+
+impl LocaleConfig {
+    pub fn from_env() -> Self {
+        let default = env::var("DEFAULT_LOCALE")
+            .map(|locale| locale.trim().to_string())
+            .ok()
+            .filter(|locale| !locale.is_empty())
+            .unwrap_or_else(|| "en".to_string());
+
+        let mut supported: Vec<String> = env::var("SUPPORTED_LOCALES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if !supported.iter().any(|s| s.eq_ignore_ascii_case(&default)) {
+            supported.insert(0, default.clone());
+        }
+
+        Self { default, supported }
+    }
+
+    // The supported locale for a language tag: the same tag, or else one for the same
+    // language ("pt-BR" gets "pt", "en" gets "en-US"). Tags are case-insensitive.
+    pub fn find(&self, tag: &str) -> Option<&str> {
+        let tag = tag.trim();
+        let language = primary_subtag(tag);
+
+        self.supported
+            .iter()
+            .find(|s| s.eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                self.supported
+                    .iter()
+                    .find(|s| primary_subtag(s).eq_ignore_ascii_case(language))
+            })
+            .map(String::as_str)
+    }
+
+    // The best supported locale for an Accept-Language header, trying the client's
+    // languages from most to least preferred. None if it asks for none we have.
+    pub fn negotiate(&self, accept_language: &str) -> Option<&str> {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+                (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, so equally preferred languages keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges.into_iter().find_map(|(tag, _)| self.find(tag))
+    }
+}
+
+fn primary_subtag(tag: &str) -> &str {
+    tag.split(['-', '_']).next().unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiation_follows_client_preference() {
+        let config = LocaleConfig {
+            default: "en".to_string(),
+            supported: vec!["en".to_string(), "id".to_string(), "pt-BR".to_string()],
+        };
+
+        assert_eq!(config.negotiate("id-ID,id;q=0.9,en;q=0.8"), Some("id"));
+        assert_eq!(config.negotiate("fr;q=0.9, en;q=0.5, ID;q=0.7"), Some("id"));
+        assert_eq!(config.negotiate("pt"), Some("pt-BR"));
+        assert_eq!(config.negotiate("en;q=0, id;q=0.1"), Some("id"));
+        assert_eq!(config.negotiate("fr, de;q=0.5, *;q=0.1"), None);
+        assert_eq!(config.negotiate("id;q=high"), None);
+        assert_eq!(config.negotiate(""), None);
+    }
+}
@@ -0,0 +1,36 @@
+use std::env;
+
+// How log lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    // Human-readable, for development
+    Pretty,
+    // One JSON object per line, for log aggregators
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    // A level ("debug") or per-target directives ("info,sqlx=warn")
+    pub level: String,
+}
+
+impl LogConfig {
+    pub fn from_env() -> Self {
+        let format = match env::var("LOG_FORMAT")
+            .unwrap_or_else(|_| "pretty".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "pretty" => LogFormat::Pretty,
+            "json" => LogFormat::Json,
+            other => panic!("LOG_FORMAT must be pretty or json, got {}", other),
+        };
+
+        Self {
+            format,
+            level: env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string()),
+        }
+    }
+}
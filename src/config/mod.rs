@@ -1,12 +1,32 @@
 mod app;
+mod avatar;
+mod bootstrap;
+mod credential;
 mod database;
+mod dormancy;
 mod email;
+mod features;
+mod locale;
+mod logging;
 mod oauth;
+mod proxy;
+mod rate_limit;
+mod security_headers;
 
-pub use app::AppConfig;
+pub use app::{AppConfig, RegistrationMode, TokenBinding};
+pub use avatar::AvatarConfig;
+pub use bootstrap::BootstrapAdminConfig;
+pub use credential::CredentialConfig;
 pub use database::DatabaseConfig;
+pub use dormancy::DormancyConfig;
 pub use email::EmailConfig;
-pub use oauth::OAuthConfig;
+pub use features::FeatureFlags;
+pub use locale::LocaleConfig;
+pub use logging::{LogConfig, LogFormat};
+pub use oauth::{OAuthConfig, OAuthTokenDelivery};
+pub use proxy::IpNetwork;
+pub use rate_limit::RateLimitStoreConfig;
+pub use security_headers::SecurityHeadersConfig;
 
 use dotenv::dotenv;
 
@@ -1,5 +1,38 @@
 use std::env;
 
+use oauth2::url::Url;
+
+use super::app::api_base_path_from_env;
+
+// How the OAuth callback hands tokens to the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OAuthTokenDelivery {
+    // In the URL fragment, which browsers never send to servers
+    Fragment,
+    // In an auto-submitting form POSTed to the frontend callback
+    FormPost,
+    // In the query string (legacy; leaks into history, logs and Referer)
+    Query,
+}
+
+impl OAuthTokenDelivery {
+    fn from_env() -> Self {
+        match env::var("OAUTH_TOKEN_DELIVERY")
+            .unwrap_or_else(|_| "fragment".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "fragment" => Self::Fragment,
+            "form_post" => Self::FormPost,
+            "query" => Self::Query,
+            other => panic!(
+                "OAUTH_TOKEN_DELIVERY must be fragment, form_post or query, got {}",
+                other
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OAuthConfig {
     // Google OAuth
@@ -17,10 +50,30 @@ pub struct OAuthConfig {
     pub github_token_url: String,
     pub github_redirect_url: String,
     pub github_user_info_url: String,
+
+    // Where the frontend may be sent after login. Entries ending in `*` are prefix
+    // matches, everything else must match exactly. Empty means "same origin as the
+    // frontend or an allowed CORS origin".
+    pub allowed_redirect_uris: Vec<String>,
+
+    pub token_delivery: OAuthTokenDelivery,
+
+    // Outbound calls to providers (token exchange, user info)
+    pub http_timeout: u64,      // in seconds
+    pub http_max_attempts: u32, // including the first attempt
+    pub http_retry_base_delay_ms: u64,
+    pub http_retry_max_delay_ms: u64, // also the longest Retry-After we'll wait for
 }
 
 impl OAuthConfig {
     pub fn from_env() -> Self {
+        // Default callback URLs follow the API base path; without one they keep their
+        // original /api prefix, which deployments behind a gateway already registered
+        let base_path = match env::var("API_BASE_PATH") {
+            Ok(_) => api_base_path_from_env(),
+            Err(_) => "/api".to_string(),
+        };
+
         Self {
             // Google OAuth config
             google_client_id: env::var("OAUTH_GOOGLE_CLIENT_ID")
@@ -32,7 +85,10 @@ impl OAuthConfig {
             google_token_url: env::var("OAUTH_GOOGLE_TOKEN_URL")
                 .unwrap_or_else(|_| "https://oauth2.googleapis.com/token".to_string()),
             google_redirect_url: env::var("OAUTH_GOOGLE_REDIRECT_URL").unwrap_or_else(|_| {
-                "http://localhost:8080/api/auth/oauth/google/callback".to_string()
+                format!(
+                    "http://localhost:8080{}/auth/oauth/google/callback",
+                    base_path
+                )
             }),
             google_user_info_url: env::var("OAUTH_GOOGLE_USER_INFO_URL")
                 .unwrap_or_else(|_| "https://www.googleapis.com/oauth2/v2/userinfo".to_string()),
@@ -47,10 +103,110 @@ impl OAuthConfig {
             github_token_url: env::var("OAUTH_GITHUB_TOKEN_URL")
                 .unwrap_or_else(|_| "https://github.com/login/oauth/access_token".to_string()),
             github_redirect_url: env::var("OAUTH_GITHUB_REDIRECT_URL").unwrap_or_else(|_| {
-                "http://localhost:8080/api/auth/oauth/github/callback".to_string()
+                format!(
+                    "http://localhost:8080{}/auth/oauth/github/callback",
+                    base_path
+                )
             }),
             github_user_info_url: env::var("OAUTH_GITHUB_USER_INFO_URL")
                 .unwrap_or_else(|_| "https://api.github.com/user".to_string()),
+
+            allowed_redirect_uris: env::var("OAUTH_ALLOWED_REDIRECT_URIS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+
+            token_delivery: OAuthTokenDelivery::from_env(),
+
+            http_timeout: env::var("OAUTH_HTTP_TIMEOUT")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("OAUTH_HTTP_TIMEOUT must be a number"),
+            http_max_attempts: env::var("OAUTH_HTTP_MAX_ATTEMPTS")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .expect("OAUTH_HTTP_MAX_ATTEMPTS must be a number"),
+            http_retry_base_delay_ms: env::var("OAUTH_HTTP_RETRY_BASE_DELAY_MS")
+                .unwrap_or_else(|_| "250".to_string())
+                .parse()
+                .expect("OAUTH_HTTP_RETRY_BASE_DELAY_MS must be a number"),
+            http_retry_max_delay_ms: env::var("OAUTH_HTTP_RETRY_MAX_DELAY_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .expect("OAUTH_HTTP_RETRY_MAX_DELAY_MS must be a number"),
         }
     }
+
+    // Check an absolute redirect URL against OAUTH_ALLOWED_REDIRECT_URIS
+    pub fn is_allowed_redirect(&self, url: &Url) -> bool {
+        self.allowed_redirect_uris.iter().any(|allowed| {
+            match allowed.strip_suffix('*') {
+                Some(prefix) => Url::parse(prefix).is_ok_and(|prefix| matches_prefix(&prefix, url)),
+                // Compare parsed URLs so "https://app.com" equals "https://app.com/"
+                None => Url::parse(allowed).is_ok_and(|allowed| &allowed == url),
+            }
+        })
+    }
+}
+
+// A wildcard entry only widens the path: the scheme, host and port must be the entry's
+// exactly, so "https://app.com*" doesn't let "https://app.com.evil.net" or
+// "https://app.com@evil.net" through
+fn matches_prefix(prefix: &Url, url: &Url) -> bool {
+    url.scheme() == prefix.scheme()
+        && url.host() == prefix.host()
+        && url.port_or_known_default() == prefix.port_or_known_default()
+        && url.username().is_empty()
+        && url.password().is_none()
+        && path_within(prefix.path(), url.path())
+}
+
+// Paths match on segment boundaries, so "/auth" covers "/auth" and "/auth/cb" but not
+// "/authevil"
+fn path_within(prefix: &str, path: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+
+    #[test]
+    fn wildcards_only_widen_the_path() {
+        let mut config = test_config().oauth;
+        config.allowed_redirect_uris = vec![
+            "https://app.example.com*".to_string(),
+            "https://admin.example.com/auth/*".to_string(),
+            "https://docs.example.com/auth*".to_string(),
+        ];
+        let allowed = |url: &str| config.is_allowed_redirect(&Url::parse(url).unwrap());
+
+        assert!(allowed("https://app.example.com"));
+        assert!(allowed("https://app.example.com/cb?next=/home"));
+        assert!(allowed("https://app.example.com:443/cb"));
+        assert!(allowed("https://admin.example.com/auth/done"));
+        assert!(allowed("https://docs.example.com/auth"));
+        assert!(allowed("https://docs.example.com/auth/done"));
+
+        // Lookalike hosts
+        assert!(!allowed("https://app.example.com.evil.net/cb"));
+        assert!(!allowed("https://app.example.comevil.net/cb"));
+        // Userinfo that reads like the allowed host
+        assert!(!allowed("https://app.example.com@evil.net/cb"));
+        assert!(!allowed("https://app.example.com:pw@evil.net/cb"));
+        assert!(!allowed("https://user@app.example.com/cb"));
+        // Anything but the entry's scheme, port and path
+        assert!(!allowed("http://app.example.com/cb"));
+        assert!(!allowed("https://app.example.com:8443/cb"));
+        assert!(!allowed("https://admin.example.com/other"));
+        // A prefix ending mid-segment doesn't extend into longer names
+        assert!(!allowed("https://docs.example.com/authevil"));
+        assert!(!allowed("https://docs.example.com/auth-evil/cb"));
+    }
 }
@@ -0,0 +1,111 @@
+use std::env;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+// An address range in CIDR notation, e.g. 10.0.0.0/8 or fd00::/8. A bare address is
+// a range of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4-mapped IPv6 peers (::ffff:10.0.0.1) are matched as IPv4
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.split_once('/').unwrap_or((s, ""));
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} is not an IP address or CIDR range", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix.trim() {
+            "" => max,
+            prefix => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("{} has an invalid prefix length", s))?,
+        };
+
+        Ok(Self {
+            addr: addr.to_canonical(),
+            prefix,
+        })
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+// Comma-separated TRUSTED_PROXIES, e.g. "10.0.0.0/8, 172.16.0.0/12"
+pub fn trusted_proxies_from_env() -> Vec<IpNetwork> {
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .unwrap_or_else(|e| panic!("TRUSTED_PROXIES: {}", e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn networks_match_their_addresses() {
+        let private: IpNetwork = "10.0.0.0/8".parse().unwrap();
+        assert!(private.contains(IpAddr::from([10, 200, 3, 4])));
+        assert!(private.contains("::ffff:10.1.1.1".parse().unwrap()));
+        assert!(!private.contains(IpAddr::from([11, 0, 0, 1])));
+        assert!(!private.contains("fd00::1".parse().unwrap()));
+
+        let host: IpNetwork = "192.0.2.1".parse().unwrap();
+        assert_eq!(host.to_string(), "192.0.2.1/32");
+        assert!(host.contains(IpAddr::from([192, 0, 2, 1])));
+        assert!(!host.contains(IpAddr::from([192, 0, 2, 2])));
+
+        let ula: IpNetwork = "fd00::/8".parse().unwrap();
+        assert!(ula.contains("fd12:3456::1".parse().unwrap()));
+        assert!("0.0.0.0/0"
+            .parse::<IpNetwork>()
+            .unwrap()
+            .contains(IpAddr::from([8, 8, 8, 8])));
+
+        for invalid in [
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0/8",
+            "proxy",
+            "10.0.0.0/x",
+        ] {
+            assert!(invalid.parse::<IpNetwork>().is_err(), "{}", invalid);
+        }
+    }
+}
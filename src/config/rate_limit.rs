@@ -0,0 +1,27 @@
+use std::env;
+
+// Where rate limit and lockout counters are kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimitStoreConfig {
+    // In this process only; each instance behind a load balancer counts separately
+    Memory,
+    // Shared by every instance, e.g. redis://:password@localhost:6379/0
+    Redis { url: String },
+}
+
+impl RateLimitStoreConfig {
+    pub fn from_env() -> Self {
+        match env::var("RATE_LIMIT_STORE")
+            .unwrap_or_else(|_| "memory".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "memory" => Self::Memory,
+            "redis" => Self::Redis {
+                url: env::var("REDIS_URL")
+                    .expect("REDIS_URL must be set when RATE_LIMIT_STORE=redis"),
+            },
+            other => panic!("RATE_LIMIT_STORE must be memory or redis, got {}", other),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::env;
+
+// Security headers added to every response. Each header can be turned off by
+// setting its variable to an empty value (or HSTS_MAX_AGE to 0).
+#[derive(Debug, Clone)]
+pub struct SecurityHeadersConfig {
+    pub content_security_policy: Option<String>,
+    pub frame_options: Option<String>,
+    pub referrer_policy: Option<String>,
+    pub hsts_max_age: u64, // in seconds; only sent on requests that came over HTTPS
+}
+
+impl Default for SecurityHeadersConfig {
+    // The API only serves JSON, so nothing needs to load or frame it
+    fn default() -> Self {
+        Self {
+            content_security_policy: Some("default-src 'none'; frame-ancestors 'none'".to_string()),
+            frame_options: Some("DENY".to_string()),
+            // Links carry tokens in their query strings; don't pass them on in Referer
+            referrer_policy: Some("no-referrer".to_string()),
+            hsts_max_age: 31_536_000, // 1 year
+        }
+    }
+}
+
+impl SecurityHeadersConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let header_var = |name: &str, default: Option<String>| match env::var(name) {
+            Ok(value) => Some(value.trim().to_string()).filter(|v| !v.is_empty()),
+            Err(_) => default,
+        };
+
+        Self {
+            content_security_policy: header_var(
+                "CONTENT_SECURITY_POLICY",
+                defaults.content_security_policy,
+            ),
+            frame_options: header_var("X_FRAME_OPTIONS", defaults.frame_options),
+            referrer_policy: header_var("REFERRER_POLICY", defaults.referrer_policy),
+            hsts_max_age: env::var("HSTS_MAX_AGE")
+                .map(|value| value.parse().expect("HSTS_MAX_AGE must be a number"))
+                .unwrap_or(defaults.hsts_max_age),
+        }
+    }
+}
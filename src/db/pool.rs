@@ -1,6 +1,10 @@
 use anyhow::Result;
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use log::LevelFilter;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::ConnectOptions;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::config::DatabaseConfig;
 
@@ -10,16 +14,39 @@ pub type DbPool = Arc<PgPool>;
 pub async fn init_db_pool(config: &DatabaseConfig) -> Result<DbPool> {
     let pool = PgPoolOptions::new()
         .max_connections(config.max_connections)
-        .connect(&config.connection_string)
+        .connect_with(connect_options(config)?)
         .await?;
 
     // Run migrations if in development mode
     #[cfg(debug_assertions)]
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    run_migrations(&pool).await?;
 
     Ok(Arc::new(pool))
 }
 
+// Statement logging goes through tracing under the `sqlx::query` target. Slow statements are
+// logged at WARN with their SQL summary, elapsed time and row counts when SLOW_QUERY_MS is set.
+fn connect_options(config: &DatabaseConfig) -> Result<PgConnectOptions> {
+    let options = PgConnectOptions::from_str(&config.connection_string)?;
+
+    let options = if config.slow_query_ms > 0 {
+        options.log_slow_statements(
+            LevelFilter::Warn,
+            Duration::from_millis(config.slow_query_ms),
+        )
+    } else {
+        options.log_slow_statements(LevelFilter::Off, Duration::default())
+    };
+
+    Ok(options)
+}
+
+/// Apply any pending migrations
+pub async fn run_migrations(pool: &PgPool) -> Result<()> {
+    sqlx::migrate!("./migrations").run(pool).await?;
+    Ok(())
+}
+
 /// Check database connection
 pub async fn check_connection(pool: &PgPool) -> Result<()> {
     // Simple query to check if the database is responsive
@@ -0,0 +1,73 @@
+use futures_util::stream::{BoxStream, StreamExt};
+use sqlx::PgPool;
+
+use crate::db::error::{DatabaseError, DatabaseResult};
+use crate::models::audit::{AuditExportQuery, AuditLog, CreateAuditLogDto};
+
+#[derive(Clone)]
+pub struct AuditRepository {
+    pool: PgPool,
+}
+
+impl AuditRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Record a new audit log entry
+    pub async fn create(&self, dto: &CreateAuditLogDto) -> DatabaseResult<AuditLog> {
+        sqlx::query_as!(
+            AuditLog,
+            r#"
+            INSERT INTO audit_logs (
+                event_type, user_id, actor_id, success, request_id,
+                ip_address, user_agent, details
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING
+                id, event_type, user_id, actor_id, success, request_id,
+                ip_address, user_agent, details, created_at
+            "#,
+            dto.event_type,
+            dto.user_id,
+            dto.actor_id,
+            dto.success,
+            dto.request_id,
+            dto.ip_address,
+            dto.user_agent,
+            dto.details
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Every entry matching the filters, oldest first, read from the database as the
+    // stream is polled rather than all at once
+    pub fn stream<'a>(
+        &'a self,
+        query: &'a AuditExportQuery,
+    ) -> BoxStream<'a, DatabaseResult<AuditLog>> {
+        sqlx::query_as!(
+            AuditLog,
+            r#"
+            SELECT
+                id, event_type, user_id, actor_id, success, request_id,
+                ip_address, user_agent, details, created_at
+            FROM audit_logs
+            WHERE ($1::timestamptz IS NULL OR created_at >= $1)
+                AND ($2::timestamptz IS NULL OR created_at <= $2)
+                AND ($3::uuid IS NULL OR user_id = $3)
+                AND ($4::text IS NULL OR event_type = $4)
+            ORDER BY created_at, id
+            "#,
+            query.from,
+            query.to,
+            query.user_id,
+            query.action
+        )
+        .fetch(&self.pool)
+        .map(|row| row.map_err(DatabaseError::ConnectionError))
+        .boxed()
+    }
+}
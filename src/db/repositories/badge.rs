@@ -3,6 +3,7 @@ use uuid::Uuid;
 
 use crate::db::error::{DatabaseError, DatabaseResult};
 use crate::models::badge::{Badge, CreateBadgeDto, UpdateBadgeDto};
+use crate::models::common::{CreatedRangeQuery, NullablePatch};
 
 #[derive(Clone)]
 pub struct BadgeRepository {
@@ -94,7 +95,12 @@ impl BadgeRepository {
     }
 
     // Get all badges with pagination
-    pub async fn find_all(&self, limit: i64, offset: i64) -> DatabaseResult<Vec<Badge>> {
+    pub async fn find_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        created: &CreatedRangeQuery,
+    ) -> DatabaseResult<Vec<Badge>> {
         let badges = sqlx::query_as!(
             Badge,
             r#"
@@ -103,11 +109,15 @@ impl BadgeRepository {
                 created_at, updated_at, deleted_at
             FROM badges
             WHERE deleted_at IS NULL
-            ORDER BY created_at DESC
+              AND ($3::timestamptz IS NULL OR created_at >= $3)
+              AND ($4::timestamptz IS NULL OR created_at <= $4)
+            ORDER BY created_at DESC, id
             LIMIT $1 OFFSET $2
             "#,
             limit,
-            offset
+            offset,
+            created.created_after,
+            created.created_before
         )
         .fetch_all(&self.pool)
         .await
@@ -117,13 +127,17 @@ impl BadgeRepository {
     }
 
     // Count all badges
-    pub async fn count(&self) -> DatabaseResult<i64> {
+    pub async fn count(&self, created: &CreatedRangeQuery) -> DatabaseResult<i64> {
         let count = sqlx::query!(
             r#"
             SELECT COUNT(*) as count
             FROM badges
             WHERE deleted_at IS NULL
-            "#
+              AND ($1::timestamptz IS NULL OR created_at >= $1)
+              AND ($2::timestamptz IS NULL OR created_at <= $2)
+            "#,
+            created.created_after,
+            created.created_before
         )
         .fetch_one(&self.pool)
         .await
@@ -132,7 +146,7 @@ impl BadgeRepository {
         Ok(count.count.unwrap_or(0))
     }
 
-    // Update badge
+    // Apply a merge patch to a badge
     pub async fn update(&self, id: Uuid, dto: &UpdateBadgeDto) -> DatabaseResult<Badge> {
         sqlx::query_as!(
             Badge,
@@ -140,17 +154,19 @@ impl BadgeRepository {
             UPDATE badges
             SET
                 name = COALESCE($1, name),
-                description = COALESCE($2, description),
-                image_url = COALESCE($3, image_url),
+                description = CASE WHEN $2 THEN $3 ELSE description END,
+                image_url = CASE WHEN $4 THEN $5 ELSE image_url END,
                 updated_at = now()
-            WHERE id = $4 AND deleted_at IS NULL
+            WHERE id = $6 AND deleted_at IS NULL
             RETURNING 
                 id, name, description, image_url,
                 created_at, updated_at, deleted_at
             "#,
             dto.name,
-            dto.description,
-            dto.image_url,
+            dto.description.is_patched(),
+            dto.description.patched(),
+            dto.image_url.is_patched(),
+            dto.image_url.patched(),
             id
         )
         .fetch_optional(&self.pool)
@@ -174,8 +190,15 @@ impl BadgeRepository {
         .ok_or(DatabaseError::NotFound)
     }
 
-    // Soft delete a badge
+    // Soft delete a badge, taking it away from everyone who holds it. Their awards are
+    // removed like any other, so they don't come back unless awarded again.
     pub async fn delete(&self, id: Uuid) -> DatabaseResult<PgQueryResult> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(DatabaseError::ConnectionError)?;
+
         let result = sqlx::query!(
             r#"
             UPDATE badges
@@ -186,7 +209,7 @@ impl BadgeRepository {
             "#,
             id
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await
         .map_err(DatabaseError::ConnectionError)?;
 
@@ -194,6 +217,23 @@ impl BadgeRepository {
             return Err(DatabaseError::NotFound);
         }
 
+        // Same as UserBadgeRepository::remove_badge_from_all, within this transaction
+        sqlx::query!(
+            r#"
+            UPDATE user_badges
+            SET
+                deleted_at = now(),
+                updated_at = now()
+            WHERE badge_id = $1 AND deleted_at IS NULL
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        tx.commit().await.map_err(DatabaseError::ConnectionError)?;
+
         Ok(result)
     }
 }
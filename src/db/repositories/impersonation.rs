@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::error::{DatabaseError, DatabaseResult};
+use crate::models::auth::impersonation::ImpersonationSession;
+
+#[derive(Clone)]
+pub struct ImpersonationRepository {
+    pool: PgPool,
+}
+
+impl ImpersonationRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Start an impersonation session
+    pub async fn create(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DatabaseResult<ImpersonationSession> {
+        sqlx::query_as!(
+            ImpersonationSession,
+            r#"
+            INSERT INTO impersonation_sessions (admin_id, user_id, reason, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, admin_id, user_id, reason, expires_at, ended_at, created_at
+            "#,
+            admin_id,
+            user_id,
+            reason,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Find an impersonation session that has neither ended nor expired
+    pub async fn find_active(&self, id: Uuid) -> DatabaseResult<ImpersonationSession> {
+        let session = sqlx::query_as!(
+            ImpersonationSession,
+            r#"
+            SELECT id, admin_id, user_id, reason, expires_at, ended_at, created_at
+            FROM impersonation_sessions
+            WHERE id = $1 AND ended_at IS NULL AND expires_at > NOW()
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        session.ok_or(DatabaseError::NotFound)
+    }
+
+    // End an impersonation session
+    pub async fn end(&self, id: Uuid) -> DatabaseResult<ImpersonationSession> {
+        let session = sqlx::query_as!(
+            ImpersonationSession,
+            r#"
+            UPDATE impersonation_sessions
+            SET ended_at = NOW()
+            WHERE id = $1 AND ended_at IS NULL
+            RETURNING id, admin_id, user_id, reason, expires_at, ended_at, created_at
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        session.ok_or(DatabaseError::NotFound)
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgQueryResult, PgPool};
+use uuid::Uuid;
+
+use super::token::hash_token;
+use crate::db::error::{DatabaseError, DatabaseResult};
+use crate::models::auth::invite::Invite;
+
+#[derive(Clone)]
+pub struct InviteRepository {
+    pool: PgPool,
+}
+
+impl InviteRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Create an invite. Like verification tokens, only the hash of `token` is stored.
+    pub async fn create(
+        &self,
+        email: &str,
+        token: &str,
+        global_role: &str,
+        invited_by: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> DatabaseResult<Invite> {
+        sqlx::query_as!(
+            Invite,
+            r#"
+            INSERT INTO invites (email, token, global_role, invited_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, email, global_role, invited_by, expires_at, used_at, created_at
+            "#,
+            email,
+            hash_token(token),
+            global_role,
+            invited_by,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Invites that can still be redeemed, newest first
+    pub async fn find_pending(&self) -> DatabaseResult<Vec<Invite>> {
+        sqlx::query_as!(
+            Invite,
+            r#"
+            SELECT id, email, global_role, invited_by, expires_at, used_at, created_at
+            FROM invites
+            WHERE used_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC, id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Claim an unused, unexpired invite so no one else can register with it
+    pub async fn claim(&self, token: &str) -> DatabaseResult<Invite> {
+        let invite = sqlx::query_as!(
+            Invite,
+            r#"
+            UPDATE invites
+            SET used_at = NOW()
+            WHERE token = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING id, email, global_role, invited_by, expires_at, used_at, created_at
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        invite.ok_or(DatabaseError::NotFound)
+    }
+
+    // Give back an invite claimed by a registration that then failed
+    pub async fn release(&self, id: Uuid) -> DatabaseResult<PgQueryResult> {
+        sqlx::query!(
+            r#"
+            UPDATE invites
+            SET used_at = NULL
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Delete an invite that hasn't been used yet
+    pub async fn delete_pending(&self, id: Uuid) -> DatabaseResult<()> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM invites
+            WHERE id = $1 AND used_at IS NULL
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -1,18 +1,26 @@
+pub mod audit;
 pub mod badge;
+pub mod impersonation;
+pub mod invite;
 pub mod oauth;
 pub mod session;
 pub mod token;
 pub mod user;
 pub mod user_badge;
+pub mod user_email;
 
 use sqlx::PgPool;
 
+pub use audit::*;
 pub use badge::*;
+pub use impersonation::*;
+pub use invite::*;
 pub use oauth::*;
 pub use session::*;
 pub use token::*;
 pub use user::*;
 pub use user_badge::*;
+pub use user_email::*;
 
 #[derive(Clone)]
 pub struct Repositories {
@@ -22,6 +30,10 @@ pub struct Repositories {
     token: TokenRepository,
     badge: BadgeRepository,
     user_badge: UserBadgeRepository,
+    user_email: UserEmailRepository,
+    audit: AuditRepository,
+    impersonation: ImpersonationRepository,
+    invite: InviteRepository,
 }
 
 impl Repositories {
@@ -32,7 +44,11 @@ impl Repositories {
             oauth: OAuthRepository::new(pool.clone()),
             token: TokenRepository::new(pool.clone()),
             badge: BadgeRepository::new(pool.clone()),
-            user_badge: UserBadgeRepository::new(pool),
+            user_badge: UserBadgeRepository::new(pool.clone()),
+            user_email: UserEmailRepository::new(pool.clone()),
+            audit: AuditRepository::new(pool.clone()),
+            impersonation: ImpersonationRepository::new(pool.clone()),
+            invite: InviteRepository::new(pool),
         }
     }
 
@@ -59,4 +75,20 @@ impl Repositories {
     pub fn user_badge(&self) -> &UserBadgeRepository {
         &self.user_badge
     }
+
+    pub fn user_email(&self) -> &UserEmailRepository {
+        &self.user_email
+    }
+
+    pub fn audit(&self) -> &AuditRepository {
+        &self.audit
+    }
+
+    pub fn impersonation(&self) -> &ImpersonationRepository {
+        &self.impersonation
+    }
+
+    pub fn invite(&self) -> &InviteRepository {
+        &self.invite
+    }
 }
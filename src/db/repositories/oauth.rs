@@ -1,11 +1,13 @@
 use chrono::{DateTime, Utc};
-use sqlx::{postgres::PgQueryResult, PgPool};
+use sqlx::{postgres::PgQueryResult, types::Json, PgPool};
 use uuid::Uuid;
 
 use crate::db::error::{DatabaseError, DatabaseResult};
 use crate::models::auth::oauth::{
-    CreateOAuthProviderDto, OAuthProvider, UpdateOAuthProviderDto, UserOAuthConnection,
+    CreateOAuthConnectionDto, CreateOAuthProviderDto, OAuthAuthParams, OAuthConnectionResponse,
+    OAuthFieldMap, OAuthProvider, UpdateOAuthProviderDto, UserOAuthConnection,
 };
+use crate::models::common::NullablePatch;
 
 #[derive(Clone)]
 pub struct OAuthRepository {
@@ -29,13 +31,16 @@ impl OAuthRepository {
             r#"
             INSERT INTO oauth_providers (
                 provider_name, display_name, client_id, client_secret, auth_url, 
-                token_url, user_info_url, redirect_url, scope, icon_url
+                token_url, user_info_url, redirect_url, scope, icon_url, field_map,
+                extra_auth_params
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
             RETURNING 
                 id, provider_name, display_name, client_id, client_secret, 
                 auth_url, token_url, user_info_url, redirect_url, scope, 
-                is_active, icon_url, created_at, updated_at, deleted_at
+                is_active, icon_url, field_map as "field_map: Json<OAuthFieldMap>",
+                extra_auth_params as "extra_auth_params: Json<OAuthAuthParams>",
+                created_at, updated_at, deleted_at
             "#,
             dto.provider_name,
             dto.display_name,
@@ -46,7 +51,9 @@ impl OAuthRepository {
             dto.user_info_url,
             dto.redirect_url,
             dto.scope,
-            dto.icon_url
+            dto.icon_url,
+            dto.field_map.as_ref().map(Json) as _,
+            dto.extra_auth_params.as_ref().map(Json) as _
         )
         .fetch_one(&self.pool)
         .await
@@ -76,7 +83,9 @@ impl OAuthRepository {
             SELECT 
                 id, provider_name, display_name, client_id, client_secret, 
                 auth_url, token_url, user_info_url, redirect_url, scope, 
-                is_active, icon_url, created_at, updated_at, deleted_at
+                is_active, icon_url, field_map as "field_map: Json<OAuthFieldMap>",
+                extra_auth_params as "extra_auth_params: Json<OAuthAuthParams>",
+                created_at, updated_at, deleted_at
             FROM oauth_providers
             WHERE id = $1 AND deleted_at IS NULL
             "#,
@@ -100,7 +109,9 @@ impl OAuthRepository {
             SELECT 
                 id, provider_name, display_name, client_id, client_secret, 
                 auth_url, token_url, user_info_url, redirect_url, scope, 
-                is_active, icon_url, created_at, updated_at, deleted_at
+                is_active, icon_url, field_map as "field_map: Json<OAuthFieldMap>",
+                extra_auth_params as "extra_auth_params: Json<OAuthAuthParams>",
+                created_at, updated_at, deleted_at
             FROM oauth_providers
             WHERE provider_name = $1 AND deleted_at IS NULL
             "#,
@@ -113,19 +124,25 @@ impl OAuthRepository {
         provider.ok_or(DatabaseError::NotFound)
     }
 
-    // Get all active OAuth providers
-    pub async fn find_all_providers(&self) -> DatabaseResult<Vec<OAuthProvider>> {
+    // Get all OAuth providers, optionally only the active ones
+    pub async fn find_all_providers(
+        &self,
+        active_only: bool,
+    ) -> DatabaseResult<Vec<OAuthProvider>> {
         let providers = sqlx::query_as!(
             OAuthProvider,
             r#"
             SELECT 
                 id, provider_name, display_name, client_id, client_secret, 
                 auth_url, token_url, user_info_url, redirect_url, scope, 
-                is_active, icon_url, created_at, updated_at, deleted_at
+                is_active, icon_url, field_map as "field_map: Json<OAuthFieldMap>",
+                extra_auth_params as "extra_auth_params: Json<OAuthAuthParams>",
+                created_at, updated_at, deleted_at
             FROM oauth_providers
-            WHERE deleted_at IS NULL
-            ORDER BY display_name
-            "#
+            WHERE deleted_at IS NULL AND (is_active OR NOT $1)
+            ORDER BY display_name, id
+            "#,
+            active_only
         )
         .fetch_all(&self.pool)
         .await
@@ -134,7 +151,7 @@ impl OAuthRepository {
         Ok(providers)
     }
 
-    // Update OAuth provider
+    // Apply a merge patch to an OAuth provider
     pub async fn update_provider(
         &self,
         id: Uuid,
@@ -154,13 +171,17 @@ impl OAuthRepository {
                 redirect_url = COALESCE($7, redirect_url),
                 scope = COALESCE($8, scope),
                 is_active = COALESCE($9, is_active),
-                icon_url = $10,
+                icon_url = CASE WHEN $10 THEN $11 ELSE icon_url END,
+                field_map = CASE WHEN $12 THEN $13 ELSE field_map END,
+                extra_auth_params = CASE WHEN $14 THEN $15 ELSE extra_auth_params END,
                 updated_at = NOW()
-            WHERE id = $11 AND deleted_at IS NULL
+            WHERE id = $16 AND deleted_at IS NULL
             RETURNING 
                 id, provider_name, display_name, client_id, client_secret, 
                 auth_url, token_url, user_info_url, redirect_url, scope, 
-                is_active, icon_url, created_at, updated_at, deleted_at
+                is_active, icon_url, field_map as "field_map: Json<OAuthFieldMap>",
+                extra_auth_params as "extra_auth_params: Json<OAuthAuthParams>",
+                created_at, updated_at, deleted_at
             "#,
             dto.display_name,
             dto.client_id,
@@ -171,7 +192,12 @@ impl OAuthRepository {
             dto.redirect_url,
             dto.scope,
             dto.is_active,
-            dto.icon_url,
+            dto.icon_url.is_patched(),
+            dto.icon_url.patched(),
+            dto.field_map.is_patched(),
+            dto.field_map.patched().map(Json) as _,
+            dto.extra_auth_params.is_patched(),
+            dto.extra_auth_params.patched().map(Json) as _,
             id
         )
         .fetch_optional(&self.pool)
@@ -194,7 +220,9 @@ impl OAuthRepository {
             RETURNING 
                 id, provider_name, display_name, client_id, client_secret, 
                 auth_url, token_url, user_info_url, redirect_url, scope, 
-                is_active, icon_url, created_at, updated_at, deleted_at
+                is_active, icon_url, field_map as "field_map: Json<OAuthFieldMap>",
+                extra_auth_params as "extra_auth_params: Json<OAuthAuthParams>",
+                created_at, updated_at, deleted_at
             "#,
             id
         )
@@ -207,28 +235,21 @@ impl OAuthRepository {
 
     // *** User OAuth Connection Methods ***
 
-    // Create or update a user OAuth connection
+    // Create or update a user OAuth connection. Done on every OAuth login, so it also
+    // records when and from which client the connection was last used.
     pub async fn upsert_connection(
         &self,
-        user_id: Uuid,
-        provider_id: Uuid,
-        provider_user_id: &str,
-        email: Option<&str>,
-        name: Option<&str>,
-        avatar_url: Option<&str>,
-        access_token: Option<&str>,
-        refresh_token: Option<&str>,
-        expires_at: Option<DateTime<Utc>>,
-        raw_user_info: Option<serde_json::Value>,
+        connection: &CreateOAuthConnectionDto,
     ) -> DatabaseResult<UserOAuthConnection> {
         sqlx::query_as!(
             UserOAuthConnection,
             r#"
             INSERT INTO user_oauth_connections (
                 user_id, provider_id, provider_user_id, email, name, 
-                avatar_url, access_token, refresh_token, expires_at, raw_user_info
+                avatar_url, access_token, refresh_token, expires_at, raw_user_info,
+                user_agent, device_info, last_used_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, NOW())
             ON CONFLICT (user_id, provider_id) DO UPDATE
             SET
                 provider_user_id = $3,
@@ -239,26 +260,98 @@ impl OAuthRepository {
                 refresh_token = COALESCE($8, user_oauth_connections.refresh_token),
                 expires_at = COALESCE($9, user_oauth_connections.expires_at),
                 raw_user_info = COALESCE($10, user_oauth_connections.raw_user_info),
+                user_agent = $11,
+                device_info = $12,
+                last_used_at = NOW(),
                 updated_at = NOW()
             RETURNING 
                 id, user_id, provider_id, provider_user_id, email, name, 
                 avatar_url, access_token, refresh_token, expires_at, raw_user_info,
-                created_at, updated_at, deleted_at
+                user_agent, device_info, last_used_at, created_at, updated_at, deleted_at
+            "#,
+            connection.user_id,
+            connection.provider_id,
+            connection.provider_user_id,
+            connection.email,
+            connection.name,
+            connection.avatar_url,
+            connection.access_token,
+            connection.refresh_token,
+            connection.expires_at,
+            connection.raw_user_info,
+            connection.user_agent,
+            connection.device_info
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Store the provider's latest profile on a connection. Not a login, so unlike
+    // upsert_connection it leaves last_used_at alone.
+    pub async fn update_connection_profile(
+        &self,
+        id: Uuid,
+        email: Option<&str>,
+        name: Option<&str>,
+        avatar_url: Option<&str>,
+    ) -> DatabaseResult<UserOAuthConnection> {
+        let connection = sqlx::query_as!(
+            UserOAuthConnection,
+            r#"
+            UPDATE user_oauth_connections
+            SET
+                email = COALESCE($1, email),
+                name = COALESCE($2, name),
+                avatar_url = COALESCE($3, avatar_url),
+                updated_at = NOW()
+            WHERE id = $4 AND deleted_at IS NULL
+            RETURNING 
+                id, user_id, provider_id, provider_user_id, email, name, 
+                avatar_url, access_token, refresh_token, expires_at, raw_user_info,
+                user_agent, device_info, last_used_at, created_at, updated_at, deleted_at
             "#,
-            user_id,
-            provider_id,
-            provider_user_id,
             email,
             name,
             avatar_url,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        connection.ok_or(DatabaseError::NotFound)
+    }
+
+    // Store tokens the provider issued from the connection's refresh token. Providers
+    // that don't rotate refresh tokens leave the current one in place.
+    pub async fn update_connection_tokens(
+        &self,
+        id: Uuid,
+        access_token: &str,
+        refresh_token: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> DatabaseResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE user_oauth_connections
+            SET
+                access_token = $1,
+                refresh_token = COALESCE($2, refresh_token),
+                expires_at = $3,
+                updated_at = NOW()
+            WHERE id = $4 AND deleted_at IS NULL
+            "#,
             access_token,
             refresh_token,
             expires_at,
-            raw_user_info
+            id
         )
-        .fetch_one(&self.pool)
+        .execute(&self.pool)
         .await
-        .map_err(DatabaseError::ConnectionError)
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(())
     }
 
     // Find user OAuth connection by ID
@@ -269,7 +362,7 @@ impl OAuthRepository {
             SELECT 
                 id, user_id, provider_id, provider_user_id, email, name, 
                 avatar_url, access_token, refresh_token, expires_at, raw_user_info,
-                created_at, updated_at, deleted_at
+                user_agent, device_info, last_used_at, created_at, updated_at, deleted_at
             FROM user_oauth_connections
             WHERE id = $1 AND deleted_at IS NULL
             "#,
@@ -294,7 +387,7 @@ impl OAuthRepository {
             SELECT 
                 id, user_id, provider_id, provider_user_id, email, name, 
                 avatar_url, access_token, refresh_token, expires_at, raw_user_info,
-                created_at, updated_at, deleted_at
+                user_agent, device_info, last_used_at, created_at, updated_at, deleted_at
             FROM user_oauth_connections
             WHERE provider_id = $1 AND provider_user_id = $2 AND deleted_at IS NULL
             "#,
@@ -319,10 +412,10 @@ impl OAuthRepository {
             SELECT 
                 id, user_id, provider_id, provider_user_id, email, name, 
                 avatar_url, access_token, refresh_token, expires_at, raw_user_info,
-                created_at, updated_at, deleted_at
+                user_agent, device_info, last_used_at, created_at, updated_at, deleted_at
             FROM user_oauth_connections
             WHERE user_id = $1 AND deleted_at IS NULL
-            ORDER BY created_at DESC
+            ORDER BY created_at DESC, id
             "#,
             user_id
         )
@@ -333,6 +426,66 @@ impl OAuthRepository {
         Ok(connections)
     }
 
+    // A page of connections with their provider's names, newest first, optionally
+    // narrowed to one user and/or one provider
+    pub async fn find_connections(
+        &self,
+        user_id: Option<Uuid>,
+        provider_name: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> DatabaseResult<Vec<OAuthConnectionResponse>> {
+        sqlx::query_as!(
+            OAuthConnectionResponse,
+            r#"
+            SELECT
+                c.id, c.user_id, c.provider_id, p.provider_name,
+                p.display_name AS provider_display_name, c.provider_user_id,
+                c.email, c.name, c.avatar_url, c.device_info, c.last_used_at, c.created_at,
+                c.updated_at
+            FROM user_oauth_connections c
+            JOIN oauth_providers p ON p.id = c.provider_id
+            WHERE c.deleted_at IS NULL
+                AND ($1::uuid IS NULL OR c.user_id = $1)
+                AND ($2::text IS NULL OR p.provider_name = $2)
+            ORDER BY c.created_at DESC, c.id
+            LIMIT $3 OFFSET $4
+            "#,
+            user_id,
+            provider_name,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Count the connections find_connections pages through
+    pub async fn count_connections(
+        &self,
+        user_id: Option<Uuid>,
+        provider_name: Option<&str>,
+    ) -> DatabaseResult<i64> {
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM user_oauth_connections c
+            JOIN oauth_providers p ON p.id = c.provider_id
+            WHERE c.deleted_at IS NULL
+                AND ($1::uuid IS NULL OR c.user_id = $1)
+                AND ($2::text IS NULL OR p.provider_name = $2)
+            "#,
+            user_id,
+            provider_name
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(count.count.unwrap_or(0))
+    }
+
     // Find user OAuth connection by user ID and provider ID
     pub async fn find_connection_by_user_and_provider(
         &self,
@@ -345,7 +498,7 @@ impl OAuthRepository {
             SELECT 
                 id, user_id, provider_id, provider_user_id, email, name, 
                 avatar_url, access_token, refresh_token, expires_at, raw_user_info,
-                created_at, updated_at, deleted_at
+                user_agent, device_info, last_used_at, created_at, updated_at, deleted_at
             FROM user_oauth_connections
             WHERE user_id = $1 AND provider_id = $2 AND deleted_at IS NULL
             "#,
@@ -372,7 +525,7 @@ impl OAuthRepository {
             RETURNING 
                 id, user_id, provider_id, provider_user_id, email, name, 
                 avatar_url, access_token, refresh_token, expires_at, raw_user_info,
-                created_at, updated_at, deleted_at
+                user_agent, device_info, last_used_at, created_at, updated_at, deleted_at
             "#,
             id
         )
@@ -403,3 +556,236 @@ impl OAuthRepository {
         .map_err(DatabaseError::ConnectionError)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+
+    async fn create_test_provider(repo: &OAuthRepository) -> OAuthProvider {
+        repo.create_provider(&CreateOAuthProviderDto {
+            provider_name: "example".to_string(),
+            display_name: "Example".to_string(),
+            client_id: "client-id".to_string(),
+            client_secret: "client-secret".to_string(),
+            auth_url: "https://example.com/oauth/authorize".to_string(),
+            token_url: "https://example.com/oauth/token".to_string(),
+            user_info_url: "https://example.com/oauth/userinfo".to_string(),
+            redirect_url: "http://localhost:8080/auth/oauth/example/callback".to_string(),
+            scope: "openid email".to_string(),
+            icon_url: Some("https://example.com/icon.png".to_string()),
+            field_map: Some(OAuthFieldMap {
+                id: "sub".to_string(),
+                email: "email".to_string(),
+                name: None,
+                avatar: None,
+                username: None,
+                email_verified: Some("email_verified".to_string()),
+            }),
+            extra_auth_params: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn update_provider_keeps_icon_when_omitted(pool: PgPool) {
+        let repo = OAuthRepository::new(pool);
+        let provider = create_test_provider(&repo).await;
+
+        let updated = repo
+            .update_provider(
+                provider.id,
+                &UpdateOAuthProviderDto {
+                    scope: Some("openid email profile".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(updated.scope, "openid email profile");
+        assert_eq!(updated.display_name, "Example");
+        assert_eq!(
+            updated.icon_url.as_deref(),
+            Some("https://example.com/icon.png")
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn update_provider_replaces_or_clears_icon(pool: PgPool) {
+        let repo = OAuthRepository::new(pool);
+        let provider = create_test_provider(&repo).await;
+
+        let updated = repo
+            .update_provider(
+                provider.id,
+                &UpdateOAuthProviderDto {
+                    icon_url: Some(Some("https://example.com/new.png".to_string())),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            updated.icon_url.as_deref(),
+            Some("https://example.com/new.png")
+        );
+
+        let cleared = repo
+            .update_provider(
+                provider.id,
+                &UpdateOAuthProviderDto {
+                    icon_url: Some(None),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(cleared.icon_url, None);
+        assert_eq!(cleared.scope, "openid email");
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn upsert_connection_updates_existing_connection(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("dave").await;
+        let repo = OAuthRepository::new(pool);
+        let provider = create_test_provider(&repo).await;
+
+        let created = repo
+            .upsert_connection(&CreateOAuthConnectionDto {
+                user_id: user.id,
+                provider_id: provider.id,
+                provider_user_id: "provider-user-1".to_string(),
+                email: Some("dave@example.com".to_string()),
+                name: Some("Dave".to_string()),
+                access_token: Some("access-1".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // Logging in again refreshes the stored tokens on the same connection
+        let updated = repo
+            .upsert_connection(&CreateOAuthConnectionDto {
+                user_id: user.id,
+                provider_id: provider.id,
+                provider_user_id: "provider-user-1".to_string(),
+                email: Some("dave@example.com".to_string()),
+                name: Some("Dave D.".to_string()),
+                access_token: Some("access-2".to_string()),
+                refresh_token: Some("refresh-2".to_string()),
+                user_agent: Some("Mozilla/5.0 (Macintosh) Chrome/125.0".to_string()),
+                device_info: Some(serde_json::json!({ "browser": "Chrome", "os": "macOS" })),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(updated.id, created.id);
+        assert_eq!(created.device_info, None);
+        assert_eq!(
+            updated
+                .device_info
+                .as_ref()
+                .and_then(|device| device["os"].as_str()),
+            Some("macOS")
+        );
+        assert_eq!(updated.name.as_deref(), Some("Dave D."));
+        assert_eq!(updated.access_token.as_deref(), Some("access-2"));
+        assert!(created.last_used_at.is_some());
+        assert!(updated.last_used_at > created.last_used_at);
+
+        let found = repo
+            .find_connection_by_provider_user_id(provider.id, "provider-user-1")
+            .await
+            .unwrap();
+        assert_eq!(found.user_id, user.id);
+        assert_eq!(
+            repo.find_connections_by_user_id(user.id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn connections_are_paged_and_filtered_by_provider(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let repo = OAuthRepository::new(pool);
+        let example = create_test_provider(&repo).await;
+        let github = repo
+            .create_provider(&CreateOAuthProviderDto {
+                provider_name: "github".to_string(),
+                display_name: "GitHub".to_string(),
+                client_id: "client-id".to_string(),
+                client_secret: "client-secret".to_string(),
+                auth_url: "https://github.com/login/oauth/authorize".to_string(),
+                token_url: "https://github.com/login/oauth/access_token".to_string(),
+                user_info_url: "https://api.github.com/user".to_string(),
+                redirect_url: "http://localhost:8080/auth/oauth/github/callback".to_string(),
+                scope: "user:email".to_string(),
+                icon_url: None,
+                field_map: None,
+                extra_auth_params: None,
+            })
+            .await
+            .unwrap();
+
+        let erin = app.create_user("erin").await;
+        let frank = app.create_user("frank").await;
+        for (user, provider, provider_user_id) in [
+            (&erin, &example, "erin-example"),
+            (&erin, &github, "erin-github"),
+            (&frank, &github, "frank-github"),
+        ] {
+            repo.upsert_connection(&CreateOAuthConnectionDto {
+                user_id: user.id,
+                provider_id: provider.id,
+                provider_user_id: provider_user_id.to_string(),
+                access_token: Some("secret-access-token".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        }
+
+        let github_logins = repo
+            .find_connections(None, Some("github"), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(github_logins.len(), 2);
+        assert!(
+            github_logins
+                .iter()
+                .all(|c| c.provider_name == "github"
+                    && c.provider_display_name == github.display_name)
+        );
+        assert_eq!(
+            repo.count_connections(None, Some("github")).await.unwrap(),
+            2
+        );
+
+        // One user's connections, a page at a time
+        let first_page = repo
+            .find_connections(Some(erin.id), None, 1, 0)
+            .await
+            .unwrap();
+        let second_page = repo
+            .find_connections(Some(erin.id), None, 1, 1)
+            .await
+            .unwrap();
+        assert_eq!(first_page.len(), 1);
+        assert_eq!(second_page.len(), 1);
+        assert_ne!(first_page[0].id, second_page[0].id);
+        assert_eq!(
+            repo.count_connections(Some(erin.id), None).await.unwrap(),
+            2
+        );
+
+        // Tokens never make it into the listing
+        let json = serde_json::to_string(&first_page).unwrap();
+        assert!(!json.contains("secret-access-token"));
+    }
+}
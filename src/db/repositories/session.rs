@@ -3,7 +3,7 @@ use sqlx::{postgres::PgQueryResult, PgPool};
 use uuid::Uuid;
 
 use crate::db::error::{DatabaseError, DatabaseResult};
-use crate::models::auth::session::Session;
+use crate::models::auth::session::{CreateSessionDto, Session};
 
 #[derive(Clone)]
 pub struct SessionRepository {
@@ -16,38 +16,29 @@ impl SessionRepository {
     }
 
     // Create a new session
-    pub async fn create(
-        &self,
-        user_id: Uuid,
-        token: &str,
-        refresh_token: Option<&str>,
-        expires_at: DateTime<Utc>,
-        refresh_token_expires_at: Option<DateTime<Utc>>,
-        ip_address: Option<&str>,
-        user_agent: Option<&str>,
-        device_info: Option<serde_json::Value>,
-    ) -> DatabaseResult<Session> {
+    pub async fn create(&self, session: &CreateSessionDto) -> DatabaseResult<Session> {
         sqlx::query_as!(
             Session,
             r#"
             INSERT INTO sessions (
-                user_id, token, refresh_token, expires_at, refresh_token_expires_at,
+                id, user_id, token, refresh_token, expires_at, refresh_token_expires_at,
                 ip_address, user_agent, device_info
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING 
                 id, user_id, token, refresh_token, expires_at, refresh_token_expires_at,
                 ip_address, user_agent, device_info, is_active, last_activity_at,
                 created_at, updated_at
             "#,
-            user_id,
-            token,
-            refresh_token,
-            expires_at,
-            refresh_token_expires_at,
-            ip_address,
-            user_agent,
-            device_info
+            session.id,
+            session.user_id,
+            session.token,
+            session.refresh_token,
+            session.expires_at,
+            session.refresh_token_expires_at,
+            session.ip_address,
+            session.user_agent,
+            session.device_info
         )
         .fetch_one(&self.pool)
         .await
@@ -55,7 +46,7 @@ impl SessionRepository {
             if let sqlx::Error::Database(ref db_err) = e {
                 if let Some(constraint) = db_err.constraint() {
                     match constraint {
-                        "sessions_token_key" => {
+                        "sessions_token_key" | "sessions_refresh_token_key" => {
                             DatabaseError::Duplicate("Token already exists".to_string())
                         }
                         _ => DatabaseError::ConnectionError(e),
@@ -69,6 +60,42 @@ impl SessionRepository {
         })
     }
 
+    // Record a refresh token that was swapped for a new one as an ended session, so it's
+    // refused from then on. It has no access token of its own, so it stands in for one.
+    pub async fn end_refresh_token(
+        &self,
+        user_id: Uuid,
+        refresh_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DatabaseResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (
+                user_id, token, refresh_token, expires_at, refresh_token_expires_at, is_active
+            )
+            VALUES ($1, $2, $2, $3, $3, false)
+            "#,
+            user_id,
+            refresh_token,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db_err)
+                if matches!(
+                    db_err.constraint(),
+                    Some("sessions_token_key" | "sessions_refresh_token_key")
+                ) =>
+            {
+                DatabaseError::Duplicate("Refresh token was already replaced".to_string())
+            }
+            _ => DatabaseError::ConnectionError(e),
+        })?;
+
+        Ok(())
+    }
+
     // Find session by ID
     pub async fn find_by_id(&self, id: Uuid) -> DatabaseResult<Session> {
         let session = sqlx::query_as!(
@@ -90,8 +117,11 @@ impl SessionRepository {
         session.ok_or(DatabaseError::NotFound)
     }
 
-    // Find session by token
-    pub async fn find_by_token(&self, token: &str) -> DatabaseResult<Session> {
+    // Find session by refresh token, whether or not it is still active
+    pub async fn find_by_refresh_token_including_inactive(
+        &self,
+        refresh_token: &str,
+    ) -> DatabaseResult<Session> {
         let session = sqlx::query_as!(
             Session,
             r#"
@@ -100,9 +130,9 @@ impl SessionRepository {
                 ip_address, user_agent, device_info, is_active, last_activity_at,
                 created_at, updated_at
             FROM sessions
-            WHERE token = $1 AND is_active = true
+            WHERE refresh_token = $1
             "#,
-            token
+            refresh_token
         )
         .fetch_optional(&self.pool)
         .await
@@ -132,50 +162,25 @@ impl SessionRepository {
         session.ok_or(DatabaseError::NotFound)
     }
 
-    // Get all active sessions for a user
-    pub async fn find_by_user_id(&self, user_id: Uuid) -> DatabaseResult<Vec<Session>> {
-        let sessions = sqlx::query_as!(
-            Session,
-            r#"
-            SELECT 
-                id, user_id, token, refresh_token, expires_at, refresh_token_expires_at,
-                ip_address, user_agent, device_info, is_active, last_activity_at,
-                created_at, updated_at
-            FROM sessions
-            WHERE user_id = $1 AND is_active = true
-            ORDER BY created_at DESC
-            "#,
-            user_id
-        )
-        .fetch_all(&self.pool)
-        .await
-        .map_err(DatabaseError::ConnectionError)?;
-
-        Ok(sessions)
-    }
-
-    // Update session last activity
-    pub async fn update_activity(&self, id: Uuid) -> DatabaseResult<Session> {
-        let session = sqlx::query_as!(
-            Session,
+    // Mark an active session as active now, unless it was already marked within the
+    // last `min_interval_secs` seconds. Returns whether it was updated
+    pub async fn touch(&self, id: Uuid, min_interval_secs: i64) -> DatabaseResult<bool> {
+        let result = sqlx::query!(
             r#"
             UPDATE sessions
-            SET
-                last_activity_at = NOW(),
-                updated_at = NOW()
-            WHERE id = $1 AND is_active = true
-            RETURNING 
-                id, user_id, token, refresh_token, expires_at, refresh_token_expires_at,
-                ip_address, user_agent, device_info, is_active, last_activity_at,
-                created_at, updated_at
+            SET last_activity_at = NOW()
+            WHERE id = $1
+                AND is_active = true
+                AND last_activity_at < NOW() - make_interval(secs => $2)
             "#,
-            id
+            id,
+            min_interval_secs as f64
         )
-        .fetch_optional(&self.pool)
+        .execute(&self.pool)
         .await
         .map_err(DatabaseError::ConnectionError)?;
 
-        session.ok_or(DatabaseError::NotFound)
+        Ok(result.rows_affected() > 0)
     }
 
     // Refresh session token
@@ -241,46 +246,94 @@ impl SessionRepository {
         session.ok_or(DatabaseError::NotFound)
     }
 
-    // Deactivate all sessions for a user (logout from all devices)
-    pub async fn deactivate_all_for_user(&self, user_id: Uuid) -> DatabaseResult<PgQueryResult> {
-        sqlx::query!(
+    // Deactivate all sessions for a user (logout from all devices). Returns how many of
+    // them were still open, as count_active_for_user counts them
+    pub async fn deactivate_all_for_user(&self, user_id: Uuid) -> DatabaseResult<i64> {
+        let ended = sqlx::query!(
             r#"
-            UPDATE sessions
-            SET
-                is_active = false,
-                updated_at = NOW()
-            WHERE user_id = $1 AND is_active = true
+            WITH ended AS (
+                UPDATE sessions
+                SET
+                    is_active = false,
+                    updated_at = NOW()
+                WHERE user_id = $1 AND is_active = true
+                RETURNING expires_at, refresh_token_expires_at
+            )
+            SELECT COUNT(*) FILTER (
+                WHERE COALESCE(refresh_token_expires_at, expires_at) > NOW()
+            ) AS count
+            FROM ended
             "#,
             user_id
         )
-        .execute(&self.pool)
+        .fetch_one(&self.pool)
         .await
-        .map_err(DatabaseError::ConnectionError)
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(ended.count.unwrap_or(0))
+    }
+
+    // Deactivate all of a user's sessions except `current_id`. Returns how many of them
+    // were still open, as count_active_for_user counts them
+    pub async fn deactivate_all_for_user_except(
+        &self,
+        user_id: Uuid,
+        current_id: Uuid,
+    ) -> DatabaseResult<i64> {
+        let ended = sqlx::query!(
+            r#"
+            WITH ended AS (
+                UPDATE sessions
+                SET
+                    is_active = false,
+                    updated_at = NOW()
+                WHERE user_id = $1 AND id <> $2 AND is_active = true
+                RETURNING expires_at, refresh_token_expires_at
+            )
+            SELECT COUNT(*) FILTER (
+                WHERE COALESCE(refresh_token_expires_at, expires_at) > NOW()
+            ) AS count
+            FROM ended
+            "#,
+            user_id,
+            current_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(ended.count.unwrap_or(0))
     }
 
-    // Deactivate expired sessions
-    pub async fn deactivate_expired(&self) -> DatabaseResult<PgQueryResult> {
+    // Deactivate sessions with no activity since `idle_since`
+    pub async fn deactivate_idle(
+        &self,
+        idle_since: DateTime<Utc>,
+    ) -> DatabaseResult<PgQueryResult> {
         sqlx::query!(
             r#"
             UPDATE sessions
             SET
                 is_active = false,
                 updated_at = NOW()
-            WHERE (expires_at < NOW() OR refresh_token_expires_at < NOW()) AND is_active = true
-            "#
+            WHERE last_activity_at < $1 AND is_active = true
+            "#,
+            idle_since
         )
         .execute(&self.pool)
         .await
         .map_err(DatabaseError::ConnectionError)
     }
 
-    // Count active sessions for a user
+    // Count a user's open sessions: active, and not yet past the point they could be resumed
     pub async fn count_active_for_user(&self, user_id: Uuid) -> DatabaseResult<i64> {
         let count = sqlx::query!(
             r#"
             SELECT COUNT(*) as count
             FROM sessions
-            WHERE user_id = $1 AND is_active = true
+            WHERE user_id = $1
+                AND is_active = true
+                AND COALESCE(refresh_token_expires_at, expires_at) > NOW()
             "#,
             user_id
         )
@@ -291,3 +344,67 @@ impl SessionRepository {
         Ok(count.count.unwrap_or(0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+
+    fn new_session(user_id: Uuid, token: &str) -> CreateSessionDto {
+        CreateSessionDto {
+            id: Uuid::new_v4(),
+            user_id,
+            token: token.to_string(),
+            refresh_token: None,
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+            refresh_token_expires_at: None,
+            ip_address: None,
+            user_agent: None,
+            device_info: None,
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn touch_is_throttled(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("ivan").await;
+        let repo = SessionRepository::new(pool.clone());
+        let session = repo
+            .create(&new_session(user.id, "access-token"))
+            .await
+            .unwrap();
+
+        // Just created, so it's too soon to record activity again
+        assert!(!repo.touch(session.id, 60).await.unwrap());
+
+        sqlx::query(
+            "UPDATE sessions SET last_activity_at = now() - interval '2 minutes' WHERE id = $1",
+        )
+        .bind(session.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(repo.touch(session.id, 60).await.unwrap());
+        assert!(!repo.touch(session.id, 60).await.unwrap());
+        assert!(!repo.touch(Uuid::new_v4(), 60).await.unwrap());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn deactivating_other_sessions_keeps_the_current_one(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("kate").await;
+        let repo = SessionRepository::new(pool);
+        let mut sessions = Vec::new();
+        for token in ["laptop", "phone", "tablet"] {
+            sessions.push(repo.create(&new_session(user.id, token)).await.unwrap());
+        }
+
+        let result = repo
+            .deactivate_all_for_user_except(user.id, sessions[1].id)
+            .await
+            .unwrap();
+        assert_eq!(result, 2);
+        assert!(repo.find_by_id(sessions[1].id).await.unwrap().is_active);
+        assert!(!repo.find_by_id(sessions[0].id).await.unwrap().is_active);
+    }
+}
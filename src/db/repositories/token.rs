@@ -1,10 +1,17 @@
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
 use sqlx::{postgres::PgQueryResult, PgPool};
 use uuid::Uuid;
 
 use crate::db::error::{DatabaseError, DatabaseResult};
 use crate::models::auth::token::{CreateVerificationTokenDto, VerificationToken};
 
+// Tokens are only ever stored as a hex SHA-256 hash; the plaintext is emailed and discarded.
+// The token already has ~190 bits of entropy, so a plain unsalted hash is enough.
+pub(crate) fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
 #[derive(Clone)]
 pub struct TokenRepository {
     pool: PgPool,
@@ -15,7 +22,8 @@ impl TokenRepository {
         Self { pool }
     }
 
-    // Create a verification token (for email verification, password reset, etc.)
+    // Create a verification token (for email verification, password reset, etc.).
+    // The returned row holds the hash; callers must keep the plaintext themselves.
     pub async fn create(
         &self,
         dto: &CreateVerificationTokenDto,
@@ -36,7 +44,7 @@ impl TokenRepository {
                 created_at, updated_at
             "#,
             dto.user_id,
-            token,
+            hash_token(token),
             dto.token_type,
             expires_at
         )
@@ -75,7 +83,7 @@ impl TokenRepository {
             FROM verification_tokens
             WHERE token = $1 AND type = $2
             "#,
-            token,
+            hash_token(token),
             token_type
         )
         .fetch_optional(&self.pool)
@@ -100,7 +108,7 @@ impl TokenRepository {
             FROM verification_tokens
             WHERE user_id = $1 AND type = $2 
             AND used_at IS NULL AND expires_at > NOW()
-            ORDER BY created_at DESC
+            ORDER BY created_at DESC, id
             "#,
             user_id,
             token_type
@@ -112,6 +120,48 @@ impl TokenRepository {
         Ok(tokens)
     }
 
+    // Every token issued to a user that hasn't been cleaned up yet, newest first
+    pub async fn find_by_user(&self, user_id: Uuid) -> DatabaseResult<Vec<VerificationToken>> {
+        let tokens = sqlx::query_as!(
+            VerificationToken,
+            r#"
+            SELECT 
+                id, user_id, token, type as "token_type", expires_at, used_at,
+                created_at, updated_at
+            FROM verification_tokens
+            WHERE user_id = $1
+            ORDER BY created_at DESC, id
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(tokens)
+    }
+
+    // When the user's most recent unused token of a type was issued, if there is one
+    pub async fn latest_unused_created_at(
+        &self,
+        user_id: Uuid,
+        token_type: &str,
+    ) -> DatabaseResult<Option<DateTime<Utc>>> {
+        let created_at = sqlx::query_scalar!(
+            r#"
+            SELECT MAX(created_at) FROM verification_tokens
+            WHERE user_id = $1 AND type = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            token_type
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(created_at)
+    }
+
     // Mark a token as used
     pub async fn mark_as_used(&self, token_id: Uuid) -> DatabaseResult<VerificationToken> {
         let now = Utc::now();
@@ -192,7 +242,7 @@ impl TokenRepository {
             WHERE token = $1 AND type = $2 
             AND used_at IS NULL AND expires_at > NOW()
             "#,
-            token,
+            hash_token(token),
             token_type
         )
         .fetch_optional(&self.pool)
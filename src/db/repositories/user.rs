@@ -1,8 +1,11 @@
+use chrono::{DateTime, Utc};
 use sqlx::{postgres::PgQueryResult, PgPool};
 use uuid::Uuid;
 
 use crate::db::error::{DatabaseError, DatabaseResult};
-use crate::models::user::{CreateUserDto, UpdateUserDto, User, GLOBAL_ROLE_USER};
+use crate::models::audit::AUDIT_EVENT_DORMANCY_NOTICE;
+use crate::models::common::{CreatedRangeQuery, NullablePatch};
+use crate::models::user::{CreateUserDto, Role, UpdateUserDto, User, UserMergeResult};
 
 #[derive(Clone)]
 pub struct UserRepository {
@@ -26,7 +29,8 @@ impl UserRepository {
             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             RETURNING 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             "#,
             dto.email,
@@ -34,9 +38,9 @@ impl UserRepository {
             password_hash,
             dto.full_name,
             dto.avatar_url,
-            GLOBAL_ROLE_USER, // Default role
-            false,            // Email not verified by default
-            true,             // User active by default
+            Role::User.as_str(), // Default role
+            false,               // Email not verified by default
+            true,                // User active by default
         )
         .fetch_one(&self.pool)
         .await
@@ -70,7 +74,8 @@ impl UserRepository {
             r#"
             SELECT 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             FROM users
             WHERE id = $1 AND deleted_at IS NULL
@@ -84,17 +89,49 @@ impl UserRepository {
         user.ok_or(DatabaseError::NotFound)
     }
 
-    // Find user by email
+    // Find user by ID, soft-deleted or not. Only for admin tooling
+    pub async fn find_by_id_including_deleted(&self, id: Uuid) -> DatabaseResult<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT 
+                id, email, username, password_hash, full_name, avatar_url,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
+                created_at, updated_at, deleted_at
+            FROM users
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        user.ok_or(DatabaseError::NotFound)
+    }
+
+    // Find user by email, matching the primary address or any verified secondary address
     pub async fn find_by_email(&self, email: &str) -> DatabaseResult<User> {
         let user = sqlx::query_as!(
             User,
             r#"
             SELECT 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             FROM users
-            WHERE email = $1 AND deleted_at IS NULL
+            WHERE deleted_at IS NULL
+                AND (
+                    email = $1
+                    OR id IN (
+                        SELECT user_id FROM user_emails
+                        WHERE email = $1 AND is_verified AND deleted_at IS NULL
+                    )
+                )
+            ORDER BY email = $1 DESC
+            LIMIT 1
             "#,
             email
         )
@@ -112,7 +149,8 @@ impl UserRepository {
             r#"
             SELECT 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             FROM users
             WHERE username = $1 AND deleted_at IS NULL
@@ -126,22 +164,34 @@ impl UserRepository {
         user.ok_or(DatabaseError::NotFound)
     }
 
-    // Get all users with pagination
-    pub async fn find_all(&self, limit: i64, offset: i64) -> DatabaseResult<Vec<User>> {
+    // Get all users with pagination, optionally including soft-deleted ones
+    pub async fn find_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        include_deleted: bool,
+        created: &CreatedRangeQuery,
+    ) -> DatabaseResult<Vec<User>> {
         let users = sqlx::query_as!(
             User,
             r#"
             SELECT 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             FROM users
-            WHERE deleted_at IS NULL
-            ORDER BY created_at DESC
+            WHERE (deleted_at IS NULL OR $3)
+              AND ($4::timestamptz IS NULL OR created_at >= $4)
+              AND ($5::timestamptz IS NULL OR created_at <= $5)
+            ORDER BY created_at DESC, id
             LIMIT $1 OFFSET $2
             "#,
             limit,
-            offset
+            offset,
+            include_deleted,
+            created.created_after,
+            created.created_before
         )
         .fetch_all(&self.pool)
         .await
@@ -150,14 +200,23 @@ impl UserRepository {
         Ok(users)
     }
 
-    // Count all users
-    pub async fn count(&self) -> DatabaseResult<i64> {
+    // Count all users, optionally including soft-deleted ones
+    pub async fn count(
+        &self,
+        include_deleted: bool,
+        created: &CreatedRangeQuery,
+    ) -> DatabaseResult<i64> {
         let count = sqlx::query!(
             r#"
             SELECT COUNT(*) as count
             FROM users
-            WHERE deleted_at IS NULL
-            "#
+            WHERE (deleted_at IS NULL OR $1)
+              AND ($2::timestamptz IS NULL OR created_at >= $2)
+              AND ($3::timestamptz IS NULL OR created_at <= $3)
+            "#,
+            include_deleted,
+            created.created_after,
+            created.created_before
         )
         .fetch_one(&self.pool)
         .await
@@ -166,7 +225,85 @@ impl UserRepository {
         Ok(count.count.unwrap_or(0))
     }
 
-    // Update user
+    // Active users with no login (or, if they never logged in, no sign-up) since `inactive_since`
+    // who have not been sent a dormancy notice since they were last seen
+    pub async fn find_dormant_without_notice(
+        &self,
+        inactive_since: DateTime<Utc>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<User>> {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT 
+                u.id, u.email, u.username, u.password_hash, u.full_name, u.avatar_url,
+                u.global_role, u.is_email_verified, u.is_active, u.last_login_at,
+                u.password_changed_at, u.tokens_revoked_at,
+                u.created_at, u.updated_at, u.deleted_at
+            FROM users u
+            WHERE u.deleted_at IS NULL
+                AND u.is_active = TRUE
+                AND COALESCE(u.last_login_at, u.created_at) < $1
+                AND NOT EXISTS (
+                    SELECT 1 FROM audit_logs a
+                    WHERE a.user_id = u.id
+                        AND a.event_type = $2
+                        AND a.created_at > COALESCE(u.last_login_at, u.created_at)
+                )
+            ORDER BY COALESCE(u.last_login_at, u.created_at), u.id
+            LIMIT $3
+            "#,
+            inactive_since,
+            AUDIT_EVENT_DORMANCY_NOTICE,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(users)
+    }
+
+    // Active users who were sent a dormancy notice before `notified_before`
+    // and have not logged in since
+    pub async fn find_dormant_notified_before(
+        &self,
+        notified_before: DateTime<Utc>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<User>> {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT 
+                u.id, u.email, u.username, u.password_hash, u.full_name, u.avatar_url,
+                u.global_role, u.is_email_verified, u.is_active, u.last_login_at,
+                u.password_changed_at, u.tokens_revoked_at,
+                u.created_at, u.updated_at, u.deleted_at
+            FROM users u
+            WHERE u.deleted_at IS NULL
+                AND u.is_active = TRUE
+                AND EXISTS (
+                    SELECT 1 FROM audit_logs a
+                    WHERE a.user_id = u.id
+                        AND a.event_type = $2
+                        AND a.created_at > COALESCE(u.last_login_at, u.created_at)
+                        AND a.created_at < $1
+                )
+            ORDER BY COALESCE(u.last_login_at, u.created_at), u.id
+            LIMIT $3
+            "#,
+            notified_before,
+            AUDIT_EVENT_DORMANCY_NOTICE,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(users)
+    }
+
+    // Apply a merge patch to a user
     pub async fn update(&self, id: Uuid, dto: &UpdateUserDto) -> DatabaseResult<User> {
         sqlx::query_as!(
             User,
@@ -174,20 +311,21 @@ impl UserRepository {
             UPDATE users
             SET
                 username = COALESCE($1, username),
-                full_name = COALESCE($2, full_name),
-                avatar_url = COALESCE($3, avatar_url),
-                is_active = COALESCE($4, is_active),
+                full_name = CASE WHEN $2 THEN $3 ELSE full_name END,
+                avatar_url = CASE WHEN $4 THEN $5 ELSE avatar_url END,
                 updated_at = now()
-            WHERE id = $5 AND deleted_at IS NULL
+            WHERE id = $6 AND deleted_at IS NULL
             RETURNING 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             "#,
             dto.username,
-            dto.full_name,
-            dto.avatar_url,
-            dto.is_active,
+            dto.full_name.is_patched(),
+            dto.full_name.patched(),
+            dto.avatar_url.is_patched(),
+            dto.avatar_url.patched(),
             id
         )
         .fetch_optional(&self.pool)
@@ -211,6 +349,186 @@ impl UserRepository {
         .ok_or(DatabaseError::NotFound)
     }
 
+    // When the user last changed their username; None if they never have
+    pub async fn find_username_changed_at(
+        &self,
+        id: Uuid,
+    ) -> DatabaseResult<Option<DateTime<Utc>>> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT username_changed_at
+            FROM users
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?
+        .ok_or(DatabaseError::NotFound)
+    }
+
+    // The language the user prefers to be addressed in; None if they haven't chosen one
+    pub async fn find_locale(&self, id: Uuid) -> DatabaseResult<Option<String>> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT locale
+            FROM users
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?
+        .ok_or(DatabaseError::NotFound)
+    }
+
+    // Set (or with None, clear) the user's preferred language
+    pub async fn update_locale(&self, id: Uuid, locale: Option<&str>) -> DatabaseResult<()> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET
+                locale = $1,
+                updated_at = now()
+            WHERE id = $2 AND deleted_at IS NULL
+            "#,
+            locale,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    // Whether a username is free for `user_id` (or anyone, when None): no other account
+    // has it, deleted ones included, and nobody else who gave it up is still holding it
+    pub async fn is_username_available(
+        &self,
+        username: &str,
+        user_id: Option<Uuid>,
+    ) -> DatabaseResult<bool> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT NOT EXISTS (
+                SELECT 1 FROM users
+                WHERE username = $1 AND id IS DISTINCT FROM $2
+            ) AND NOT EXISTS (
+                SELECT 1 FROM reserved_usernames
+                WHERE username = $1 AND user_id IS DISTINCT FROM $2 AND reserved_until > NOW()
+            ) AS "available!"
+            "#,
+            username,
+            user_id as Option<Uuid>
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Change the username, unless it was already changed after `changed_before`, and
+    // keep the old one for the user until `reserve_until`. None when it was changed
+    // too recently.
+    pub async fn change_username(
+        &self,
+        id: Uuid,
+        username: &str,
+        changed_before: DateTime<Utc>,
+        reserve_until: Option<DateTime<Utc>>,
+    ) -> DatabaseResult<Option<User>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(DatabaseError::ConnectionError)?;
+
+        // Lock the account so concurrent changes are counted against the cooldown
+        let old_username = sqlx::query_scalar!(
+            r#"
+            SELECT username FROM users
+            WHERE id = $1 AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?
+        .ok_or(DatabaseError::NotFound)?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET
+                username = $1,
+                username_changed_at = now(),
+                updated_at = now()
+            WHERE id = $2 AND (username_changed_at IS NULL OR username_changed_at <= $3)
+            RETURNING
+                id, email, username, password_hash, full_name, avatar_url,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
+                created_at, updated_at, deleted_at
+            "#,
+            username,
+            id,
+            changed_before
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_err) if db_err.constraint() == Some("users_username_key") => {
+                DatabaseError::Duplicate("Username already exists".to_string())
+            }
+            _ => DatabaseError::ConnectionError(e),
+        })?;
+        let Some(user) = user else {
+            return Ok(None);
+        };
+
+        // Taking back a name the user gave up ends its reservation; expired ones go too
+        sqlx::query!(
+            r#"
+            DELETE FROM reserved_usernames
+            WHERE (username = $1 AND user_id = $2) OR reserved_until <= NOW()
+            "#,
+            username,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        if let Some(reserve_until) = reserve_until {
+            sqlx::query!(
+                r#"
+                INSERT INTO reserved_usernames (username, user_id, reserved_until)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (username) DO UPDATE
+                SET user_id = EXCLUDED.user_id, reserved_until = EXCLUDED.reserved_until
+                "#,
+                old_username,
+                id,
+                reserve_until
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(DatabaseError::ConnectionError)?;
+        }
+
+        tx.commit().await.map_err(DatabaseError::ConnectionError)?;
+
+        Ok(Some(user))
+    }
+
     // Update password
     pub async fn update_password(&self, id: Uuid, password_hash: &str) -> DatabaseResult<User> {
         let user = sqlx::query_as!(
@@ -219,11 +537,13 @@ impl UserRepository {
             UPDATE users
             SET
                 password_hash = $1,
+                password_changed_at = now(),
                 updated_at = now()
             WHERE id = $2 AND deleted_at IS NULL
             RETURNING 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             "#,
             password_hash,
@@ -236,6 +556,100 @@ impl UserRepository {
         user.ok_or(DatabaseError::NotFound)
     }
 
+    // Reject every token issued to the user up to now
+    pub async fn revoke_tokens(&self, id: Uuid) -> DatabaseResult<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET
+                tokens_revoked_at = now(),
+                updated_at = now()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING 
+                id, email, username, password_hash, full_name, avatar_url,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
+                created_at, updated_at, deleted_at
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        user.ok_or(DatabaseError::NotFound)
+    }
+
+    // Hashes of the user's previous passwords, most recent first
+    pub async fn find_password_history(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+    ) -> DatabaseResult<Vec<String>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT password_hash
+            FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC, id
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(rows.into_iter().map(|row| row.password_hash).collect())
+    }
+
+    // Remember a replaced password hash, keeping only the `keep` most recent entries
+    pub async fn add_password_history(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+        keep: i64,
+    ) -> DatabaseResult<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(DatabaseError::ConnectionError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO password_history (user_id, password_hash)
+            VALUES ($1, $2)
+            "#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM password_history
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM password_history
+                WHERE user_id = $1
+                ORDER BY created_at DESC, id
+                LIMIT $2
+            )
+            "#,
+            user_id,
+            keep
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        tx.commit().await.map_err(DatabaseError::ConnectionError)
+    }
+
     // Update email verification status
     pub async fn update_email_verification(
         &self,
@@ -252,7 +666,8 @@ impl UserRepository {
             WHERE id = $2 AND deleted_at IS NULL
             RETURNING 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             "#,
             is_verified,
@@ -265,6 +680,75 @@ impl UserRepository {
         user.ok_or(DatabaseError::NotFound)
     }
 
+    // Change a user's global role
+    pub async fn update_role(&self, id: Uuid, role: &str) -> DatabaseResult<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET
+                global_role = $1,
+                updated_at = now()
+            WHERE id = $2 AND deleted_at IS NULL
+            RETURNING 
+                id, email, username, password_hash, full_name, avatar_url,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
+                created_at, updated_at, deleted_at
+            "#,
+            role,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        user.ok_or(DatabaseError::NotFound)
+    }
+
+    // Whether any (non-deleted) user holds the given global role
+    pub async fn exists_with_role(&self, role: &str) -> DatabaseResult<bool> {
+        let row = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM users WHERE global_role = $1 AND deleted_at IS NULL
+            ) as exists
+            "#,
+            role
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(row.exists.unwrap_or(false))
+    }
+
+    // Update account active status
+    pub async fn update_active_status(&self, id: Uuid, is_active: bool) -> DatabaseResult<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET
+                is_active = $1,
+                updated_at = now()
+            WHERE id = $2 AND deleted_at IS NULL
+            RETURNING 
+                id, email, username, password_hash, full_name, avatar_url,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
+                created_at, updated_at, deleted_at
+            "#,
+            is_active,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        user.ok_or(DatabaseError::NotFound)
+    }
+
     // Update last login timestamp
     pub async fn update_last_login(&self, id: Uuid) -> DatabaseResult<User> {
         let user = sqlx::query_as!(
@@ -277,7 +761,8 @@ impl UserRepository {
             WHERE id = $1 AND deleted_at IS NULL
             RETURNING 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             "#,
             id
@@ -289,19 +774,174 @@ impl UserRepository {
         user.ok_or(DatabaseError::NotFound)
     }
 
-    // Delete user (soft delete)
+    // Move the source account's badges and OAuth connections to the target, then
+    // soft-delete the source and sign it out, all or nothing
+    pub async fn merge_into(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid,
+    ) -> DatabaseResult<UserMergeResult> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(DatabaseError::ConnectionError)?;
+
+        // Lock both accounts so neither is changed or deleted halfway through
+        let locked = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM users
+            WHERE id IN ($1, $2) AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+            source_id,
+            target_id
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        if locked.len() != 2 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        let mut result = UserMergeResult::default();
+
+        result.badges_dropped = sqlx::query!(
+            r#"
+            UPDATE user_badges
+            SET deleted_at = now(), updated_at = now()
+            WHERE user_id = $1 AND deleted_at IS NULL AND badge_id IN (
+                SELECT badge_id FROM user_badges
+                WHERE user_id = $2 AND deleted_at IS NULL
+            )
+            "#,
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?
+        .rows_affected();
+
+        result.badges_moved = sqlx::query!(
+            r#"
+            UPDATE user_badges
+            SET user_id = $2, updated_at = now()
+            WHERE user_id = $1 AND deleted_at IS NULL
+            "#,
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?
+        .rows_affected();
+
+        result.connections_dropped = sqlx::query!(
+            r#"
+            UPDATE user_oauth_connections
+            SET deleted_at = NOW(), updated_at = NOW()
+            WHERE user_id = $1 AND deleted_at IS NULL AND provider_id IN (
+                SELECT provider_id FROM user_oauth_connections
+                WHERE user_id = $2 AND deleted_at IS NULL
+            )
+            "#,
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?
+        .rows_affected();
+
+        // (user_id, provider_id) is unique even for deleted connections, so clear the
+        // target's deleted ones out of the way of those being moved
+        sqlx::query!(
+            r#"
+            DELETE FROM user_oauth_connections
+            WHERE user_id = $2 AND deleted_at IS NOT NULL AND provider_id IN (
+                SELECT provider_id FROM user_oauth_connections
+                WHERE user_id = $1 AND deleted_at IS NULL
+            )
+            "#,
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        result.connections_moved = sqlx::query!(
+            r#"
+            UPDATE user_oauth_connections
+            SET user_id = $2, updated_at = NOW()
+            WHERE user_id = $1 AND deleted_at IS NULL
+            "#,
+            source_id,
+            target_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?
+        .rows_affected();
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET
+                is_active = false,
+                updated_at = NOW()
+            WHERE user_id = $1 AND is_active = true
+            "#,
+            source_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET
+                deleted_at = now(),
+                tokens_revoked_at = now(),
+                updated_at = now()
+            WHERE id = $1
+            "#,
+            source_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        tx.commit().await.map_err(DatabaseError::ConnectionError)?;
+
+        Ok(result)
+    }
+
+    // Delete user (soft delete). Everything that lets the account be used goes with it:
+    // sessions, outstanding verification tokens, OAuth connections, impersonations and
+    // any JWTs already issued.
     pub async fn delete(&self, id: Uuid) -> DatabaseResult<PgQueryResult> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(DatabaseError::ConnectionError)?;
+
         let result = sqlx::query!(
             r#"
             UPDATE users
             SET
                 deleted_at = now(),
+                tokens_revoked_at = now(),
                 updated_at = now()
             WHERE id = $1 AND deleted_at IS NULL
             "#,
             id
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await
         .map_err(DatabaseError::ConnectionError)?;
 
@@ -309,6 +949,65 @@ impl UserRepository {
             return Err(DatabaseError::NotFound);
         }
 
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET
+                is_active = false,
+                updated_at = NOW()
+            WHERE user_id = $1 AND is_active = true
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        // Unused email verification and password reset links stop working
+        sqlx::query!(
+            r#"
+            UPDATE verification_tokens
+            SET
+                used_at = NOW(),
+                updated_at = NOW()
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        // Same as OAuthRepository::delete_all_user_connections, within this transaction
+        sqlx::query!(
+            r#"
+            UPDATE user_oauth_connections
+            SET
+                deleted_at = NOW(),
+                updated_at = NOW()
+            WHERE user_id = $1 AND deleted_at IS NULL
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        // Whether the user was being impersonated or was the admin doing it
+        sqlx::query!(
+            r#"
+            UPDATE impersonation_sessions
+            SET ended_at = NOW()
+            WHERE (user_id = $1 OR admin_id = $1) AND ended_at IS NULL
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        tx.commit().await.map_err(DatabaseError::ConnectionError)?;
+
         Ok(result)
     }
 }
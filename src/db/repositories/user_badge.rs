@@ -17,43 +17,77 @@ impl UserBadgeRepository {
         Self { pool }
     }
 
-    // Award a badge to a user
-    pub async fn award_badge(&self, dto: &AwardBadgeDto) -> DatabaseResult<UserBadge> {
-        let user_badge = sqlx::query_as!(
+    // Award a badge unless the user already holds it. Returns None if they do.
+    // Safe to race: the unique index decides, so concurrent calls award it once.
+    pub async fn award_badge(&self, dto: &AwardBadgeDto) -> DatabaseResult<Option<UserBadge>> {
+        // A badge that was removed is given back rather than awarded a second time
+        if let Some(user_badge) = self.restore_badge(dto).await? {
+            return Ok(Some(user_badge));
+        }
+
+        sqlx::query_as!(
             UserBadge,
             r#"
-            INSERT INTO user_badges (
-                user_id, badge_id
-            )
+            INSERT INTO user_badges (user_id, badge_id)
             VALUES ($1, $2)
-            RETURNING 
-                id, user_id, badge_id, created_at, updated_at, deleted_at
+            ON CONFLICT (user_id, badge_id) WHERE deleted_at IS NULL DO NOTHING
+            RETURNING id, user_id, badge_id, created_at, updated_at, deleted_at
             "#,
             dto.user_id,
             dto.badge_id,
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&self.pool)
         .await
-        .map_err(|e| {
-            if let sqlx::Error::Database(ref db_err) = e {
-                if let Some(constraint) = db_err.constraint() {
-                    match constraint {
-                        "user_badges_user_id_badge_id_key" => {
-                            DatabaseError::Duplicate("User already has this badge".to_string())
-                        }
-                        "user_badges_user_id_fkey" => DatabaseError::NotFound,
-                        "user_badges_badge_id_fkey" => DatabaseError::NotFound,
-                        _ => DatabaseError::ConnectionError(e),
-                    }
-                } else {
-                    DatabaseError::ConnectionError(e)
-                }
-            } else {
-                DatabaseError::ConnectionError(e)
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db_err)
+                if matches!(
+                    db_err.constraint(),
+                    Some("user_badges_user_id_fkey" | "user_badges_badge_id_fkey")
+                ) =>
+            {
+                DatabaseError::NotFound
             }
-        })?;
+            _ => DatabaseError::ConnectionError(e),
+        })
+    }
 
-        Ok(user_badge)
+    // Clear deleted_at on the user's most recently removed award of the badge, unless
+    // they hold it again already. Returns None if there was nothing to restore.
+    async fn restore_badge(&self, dto: &AwardBadgeDto) -> DatabaseResult<Option<UserBadge>> {
+        sqlx::query_as!(
+            UserBadge,
+            r#"
+            UPDATE user_badges
+            SET
+                deleted_at = NULL,
+                updated_at = now()
+            WHERE id = (
+                SELECT id FROM user_badges
+                WHERE user_id = $1 AND badge_id = $2 AND deleted_at IS NOT NULL
+                ORDER BY deleted_at DESC
+                LIMIT 1
+            )
+            AND deleted_at IS NOT NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM user_badges
+                WHERE user_id = $1 AND badge_id = $2 AND deleted_at IS NULL
+            )
+            RETURNING id, user_id, badge_id, created_at, updated_at, deleted_at
+            "#,
+            dto.user_id,
+            dto.badge_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .or_else(|e| match e {
+            // Someone else awarded it in the meantime
+            sqlx::Error::Database(ref db_err)
+                if db_err.constraint() == Some("user_badges_user_id_badge_id_key") =>
+            {
+                Ok(None)
+            }
+            _ => Err(DatabaseError::ConnectionError(e)),
+        })
     }
 
     // Find user_badge by ID
@@ -88,7 +122,7 @@ impl UserBadgeRepository {
             WHERE ub.user_id = $1 
               AND ub.deleted_at IS NULL
               AND b.deleted_at IS NULL
-            ORDER BY ub.created_at DESC
+            ORDER BY ub.created_at DESC, ub.id
             "#,
             user_id
         )
@@ -107,13 +141,14 @@ impl UserBadgeRepository {
             SELECT 
                 u.id, u.email, u.username, u.password_hash, u.full_name, u.avatar_url,
                 u.global_role, u.is_email_verified, u.is_active, u.last_login_at,
+                u.password_changed_at, u.tokens_revoked_at,
                 u.created_at, u.updated_at, u.deleted_at
             FROM users u
             JOIN user_badges ub ON u.id = ub.user_id
             WHERE ub.badge_id = $1 
               AND ub.deleted_at IS NULL
               AND u.deleted_at IS NULL
-            ORDER BY ub.created_at DESC
+            ORDER BY ub.created_at DESC, ub.id
             "#,
             badge_id
         )
@@ -143,6 +178,26 @@ impl UserBadgeRepository {
         Ok(result.exists.unwrap_or(false))
     }
 
+    // Find a user's award of a specific badge
+    pub async fn find_award(&self, user_id: Uuid, badge_id: Uuid) -> DatabaseResult<UserBadge> {
+        let user_badge = sqlx::query_as!(
+            UserBadge,
+            r#"
+            SELECT
+                id, user_id, badge_id, created_at, updated_at, deleted_at
+            FROM user_badges
+            WHERE user_id = $1 AND badge_id = $2 AND deleted_at IS NULL
+            "#,
+            user_id,
+            badge_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        user_badge.ok_or(DatabaseError::NotFound)
+    }
+
     // Get user with all their badges
     pub async fn get_user_with_badges(
         &self,
@@ -154,7 +209,8 @@ impl UserBadgeRepository {
             r#"
             SELECT 
                 id, email, username, password_hash, full_name, avatar_url,
-                global_role, is_email_verified, is_active, last_login_at,
+                global_role, is_email_verified, is_active, last_login_at, password_changed_at,
+                tokens_revoked_at,
                 created_at, updated_at, deleted_at
             FROM users
             WHERE id = $1 AND deleted_at IS NULL
@@ -238,4 +294,67 @@ impl UserBadgeRepository {
 
         Ok(result)
     }
+
+    // Remove a badge from each of the given users who holds it. Returns the users it was
+    // removed from.
+    pub async fn remove_badge_from_users(
+        &self,
+        badge_id: Uuid,
+        user_ids: &[Uuid],
+        dry_run: bool,
+    ) -> DatabaseResult<Vec<Uuid>> {
+        self.remove_badge_from(badge_id, Some(user_ids), dry_run)
+            .await
+    }
+
+    // Remove a badge from everyone who holds it. Returns the users it was removed from.
+    pub async fn remove_badge_from_all(
+        &self,
+        badge_id: Uuid,
+        dry_run: bool,
+    ) -> DatabaseResult<Vec<Uuid>> {
+        self.remove_badge_from(badge_id, None, dry_run).await
+    }
+
+    // A dry run makes the same changes and rolls them back, so it reports exactly the
+    // holders a real run would have affected at that moment
+    async fn remove_badge_from(
+        &self,
+        badge_id: Uuid,
+        user_ids: Option<&[Uuid]>,
+        dry_run: bool,
+    ) -> DatabaseResult<Vec<Uuid>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(DatabaseError::ConnectionError)?;
+
+        let removed = sqlx::query_scalar!(
+            r#"
+            UPDATE user_badges
+            SET
+                deleted_at = now(),
+                updated_at = now()
+            WHERE badge_id = $1
+              AND deleted_at IS NULL
+              AND ($2::uuid[] IS NULL OR user_id = ANY($2))
+            RETURNING user_id
+            "#,
+            badge_id,
+            user_ids as Option<&[Uuid]>
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        if dry_run {
+            tx.rollback().await
+        } else {
+            tx.commit().await
+        }
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(removed)
+    }
 }
@@ -0,0 +1,198 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::error::{DatabaseError, DatabaseResult};
+use crate::models::user::UserEmail;
+
+#[derive(Clone)]
+pub struct UserEmailRepository {
+    pool: PgPool,
+}
+
+// Map unique-index violations on user_emails to duplicate errors
+fn map_unique_violation(e: sqlx::Error) -> DatabaseError {
+    if let sqlx::Error::Database(ref db_err) = e {
+        match db_err.constraint() {
+            Some("idx_user_emails_user_email") => {
+                return DatabaseError::Duplicate(
+                    "Email is already added to this account".to_string(),
+                )
+            }
+            Some("idx_user_emails_verified_email") => {
+                return DatabaseError::Duplicate("Email already exists".to_string())
+            }
+            _ => {}
+        }
+    }
+    DatabaseError::ConnectionError(e)
+}
+
+impl UserEmailRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Add an unverified secondary email to a user
+    pub async fn create(&self, user_id: Uuid, email: &str) -> DatabaseResult<UserEmail> {
+        sqlx::query_as!(
+            UserEmail,
+            r#"
+            INSERT INTO user_emails (user_id, email)
+            VALUES ($1, $2)
+            RETURNING
+                id, user_id, email, is_verified, verification_token_id,
+                verified_at, created_at, updated_at, deleted_at
+            "#,
+            user_id,
+            email
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(map_unique_violation)
+    }
+
+    // Find one of a user's secondary emails by ID
+    pub async fn find_by_id_for_user(&self, id: Uuid, user_id: Uuid) -> DatabaseResult<UserEmail> {
+        let email = sqlx::query_as!(
+            UserEmail,
+            r#"
+            SELECT
+                id, user_id, email, is_verified, verification_token_id,
+                verified_at, created_at, updated_at, deleted_at
+            FROM user_emails
+            WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        email.ok_or(DatabaseError::NotFound)
+    }
+
+    // Find the secondary email a verification token was issued for
+    pub async fn find_by_verification_token(&self, token_id: Uuid) -> DatabaseResult<UserEmail> {
+        let email = sqlx::query_as!(
+            UserEmail,
+            r#"
+            SELECT
+                id, user_id, email, is_verified, verification_token_id,
+                verified_at, created_at, updated_at, deleted_at
+            FROM user_emails
+            WHERE verification_token_id = $1 AND deleted_at IS NULL
+            "#,
+            token_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        email.ok_or(DatabaseError::NotFound)
+    }
+
+    // Get all secondary emails of a user
+    pub async fn find_by_user_id(&self, user_id: Uuid) -> DatabaseResult<Vec<UserEmail>> {
+        sqlx::query_as!(
+            UserEmail,
+            r#"
+            SELECT
+                id, user_id, email, is_verified, verification_token_id,
+                verified_at, created_at, updated_at, deleted_at
+            FROM user_emails
+            WHERE user_id = $1 AND deleted_at IS NULL
+            ORDER BY created_at, id
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)
+    }
+
+    // Count a user's secondary emails
+    pub async fn count_for_user(&self, user_id: Uuid) -> DatabaseResult<i64> {
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM user_emails
+            WHERE user_id = $1 AND deleted_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(count.count.unwrap_or(0))
+    }
+
+    // Link the latest verification token to a secondary email
+    pub async fn set_verification_token(&self, id: Uuid, token_id: Uuid) -> DatabaseResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET
+                verification_token_id = $1,
+                updated_at = NOW()
+            WHERE id = $2 AND deleted_at IS NULL
+            "#,
+            token_id,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        Ok(())
+    }
+
+    // Mark a secondary email as verified
+    pub async fn mark_verified(&self, id: Uuid) -> DatabaseResult<UserEmail> {
+        let email = sqlx::query_as!(
+            UserEmail,
+            r#"
+            UPDATE user_emails
+            SET
+                is_verified = TRUE,
+                verified_at = NOW(),
+                verification_token_id = NULL,
+                updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING
+                id, user_id, email, is_verified, verification_token_id,
+                verified_at, created_at, updated_at, deleted_at
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(map_unique_violation)?;
+
+        email.ok_or(DatabaseError::NotFound)
+    }
+
+    // Remove a secondary email (soft delete)
+    pub async fn delete(&self, id: Uuid) -> DatabaseResult<()> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_emails
+            SET
+                deleted_at = NOW(),
+                updated_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(DatabaseError::ConnectionError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        Ok(())
+    }
+}
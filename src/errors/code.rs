@@ -0,0 +1,198 @@
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::services::validation::VALIDATION_MESSAGES;
+
+// The machine-readable `code` of an error response, shared by REST and GraphQL.
+// Clients branch on these, so renaming one is a breaking change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    BadRequest,
+    PasswordReused,
+    Unauthenticated,
+    Forbidden,
+    AccountDisabled,
+    EmailNotVerified,
+    PasswordExpired,
+    RegistrationDisabled,
+    OAuthEmailUnverified,
+    OAuthReauthRequired,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    TooManyRequests,
+    UsernameChangeCooldown,
+    Internal,
+    UpstreamTimeout,
+    EmailDisabled,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 18] = [
+        ErrorCode::BadRequest,
+        ErrorCode::PasswordReused,
+        ErrorCode::Unauthenticated,
+        ErrorCode::Forbidden,
+        ErrorCode::AccountDisabled,
+        ErrorCode::EmailNotVerified,
+        ErrorCode::PasswordExpired,
+        ErrorCode::RegistrationDisabled,
+        ErrorCode::OAuthEmailUnverified,
+        ErrorCode::OAuthReauthRequired,
+        ErrorCode::NotFound,
+        ErrorCode::MethodNotAllowed,
+        ErrorCode::Conflict,
+        ErrorCode::TooManyRequests,
+        ErrorCode::UsernameChangeCooldown,
+        ErrorCode::Internal,
+        ErrorCode::UpstreamTimeout,
+        ErrorCode::EmailDisabled,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::PasswordReused => "PASSWORD_REUSED",
+            ErrorCode::Unauthenticated => "UNAUTHENTICATED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::AccountDisabled => "ACCOUNT_DISABLED",
+            ErrorCode::EmailNotVerified => "EMAIL_NOT_VERIFIED",
+            ErrorCode::PasswordExpired => "PASSWORD_EXPIRED",
+            ErrorCode::RegistrationDisabled => "REGISTRATION_DISABLED",
+            ErrorCode::OAuthEmailUnverified => "OAUTH_EMAIL_UNVERIFIED",
+            ErrorCode::OAuthReauthRequired => "OAUTH_REAUTH_REQUIRED",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::MethodNotAllowed => "METHOD_NOT_ALLOWED",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::TooManyRequests => "TOO_MANY_REQUESTS",
+            ErrorCode::UsernameChangeCooldown => "USERNAME_CHANGE_COOLDOWN",
+            ErrorCode::Internal => "INTERNAL",
+            ErrorCode::UpstreamTimeout => "UPSTREAM_TIMEOUT",
+            ErrorCode::EmailDisabled => "EMAIL_DISABLED",
+        }
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest | ErrorCode::PasswordReused => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthenticated => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden
+            | ErrorCode::AccountDisabled
+            | ErrorCode::EmailNotVerified
+            | ErrorCode::PasswordExpired
+            | ErrorCode::RegistrationDisabled
+            | ErrorCode::OAuthEmailUnverified
+            | ErrorCode::OAuthReauthRequired => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::TooManyRequests | ErrorCode::UsernameChangeCooldown => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::UpstreamTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::EmailDisabled => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    // What the error means, for clients without a message of their own. Responses
+    // usually carry a more specific one.
+    pub fn default_message(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "The request is invalid",
+            ErrorCode::PasswordReused => "The new password was used recently",
+            ErrorCode::Unauthenticated => "Authentication is required or the token is invalid",
+            ErrorCode::Forbidden => "You don't have permission to do this",
+            ErrorCode::AccountDisabled => "The account has been deactivated",
+            ErrorCode::EmailNotVerified => "The account's email address must be verified first",
+            ErrorCode::PasswordExpired => "The password has expired and must be changed",
+            ErrorCode::RegistrationDisabled => "Registration is not open",
+            ErrorCode::OAuthEmailUnverified => "The provider hasn't verified this email address",
+            ErrorCode::OAuthReauthRequired => {
+                "The provider no longer accepts this login; sign in with it again"
+            }
+            ErrorCode::NotFound => "Resource not found",
+            ErrorCode::MethodNotAllowed => "Method not allowed for this endpoint",
+            ErrorCode::Conflict => "The resource already exists",
+            ErrorCode::TooManyRequests => "Too many requests; retry after the Retry-After delay",
+            ErrorCode::UsernameChangeCooldown => "The username was changed too recently",
+            ErrorCode::Internal => "An internal error occurred",
+            ErrorCode::UpstreamTimeout => {
+                "An external service took too long to respond; retry later"
+            }
+            ErrorCode::EmailDisabled => "Email is not enabled on this server",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorCatalogEntry {
+    pub code: &'static str,
+    pub status: u16,
+    pub message: &'static str,
+}
+
+// A rule a field failed, reported as "field: message" in a BAD_REQUEST message
+#[derive(Debug, Serialize)]
+pub struct ValidationCatalogEntry {
+    pub code: &'static str,
+    pub message: &'static str,
+}
+
+// Every error code the API can return, for clients to handle and translate exhaustively
+#[derive(Debug, Serialize)]
+pub struct ErrorCatalog {
+    pub errors: Vec<ErrorCatalogEntry>,
+    pub validation: Vec<ValidationCatalogEntry>,
+}
+
+impl ErrorCatalog {
+    pub fn build() -> Self {
+        Self {
+            errors: ErrorCode::ALL
+                .into_iter()
+                .map(|code| ErrorCatalogEntry {
+                    code: code.as_str(),
+                    status: code.status().as_u16(),
+                    message: code.default_message(),
+                })
+                .collect(),
+            validation: VALIDATION_MESSAGES
+                .iter()
+                .map(|&(code, message)| ValidationCatalogEntry { code, message })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::error::DatabaseError;
+    use crate::errors::AppError;
+    use std::collections::HashSet;
+
+    #[test]
+    fn catalog_lists_every_code_once() {
+        let catalog = ErrorCatalog::build();
+        let codes: HashSet<_> = catalog.errors.iter().map(|entry| entry.code).collect();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+
+        for error in [
+            AppError::InvalidToken(String::new()),
+            AppError::PasswordReused(String::new()),
+            AppError::Database(DatabaseError::Duplicate(String::new())),
+            AppError::Configuration(String::new()),
+            AppError::Timeout(String::new()),
+            AppError::OAuthReauthRequired(String::new()),
+            AppError::EmailNotVerified(String::new()),
+            AppError::UsernameChangeCooldown(chrono::Utc::now()),
+        ] {
+            assert!(codes.contains(error.code().as_str()));
+        }
+        assert!(catalog
+            .validation
+            .iter()
+            .any(|entry| entry.code == "password_too_short"));
+    }
+}
@@ -1,7 +1,12 @@
+mod code;
+
+pub use code::{ErrorCatalog, ErrorCode};
+
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue},
     response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Utc};
 use thiserror::Error;
 
 use crate::db::error::DatabaseError;
@@ -15,6 +20,9 @@ pub enum AppError {
     #[error("Authorization error: {0}")]
     Authorization(String),
 
+    #[error("Account disabled: {0}")]
+    AccountDisabled(String),
+
     #[error("Validation error: {0}")]
     Validation(String),
 
@@ -33,36 +41,142 @@ pub enum AppError {
     #[error("Unexpected error: {0}")]
     Unexpected(String),
 
+    #[error("Upstream timeout: {0}")]
+    Timeout(String),
+
     #[error("Configuration error: {0}")]
     Configuration(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
+    #[error("Password reused: {0}")]
+    PasswordReused(String),
+
+    #[error("Email not verified: {0}")]
+    EmailNotVerified(String),
+
+    #[error("Password expired: {0}")]
+    PasswordExpired(String),
+
+    #[error("Registration disabled: {0}")]
+    RegistrationDisabled(String),
+
+    #[error("OAuth email unverified: {0}")]
+    OAuthEmailUnverified(String),
+
+    #[error("OAuth reauthentication required: {0}")]
+    OAuthReauthRequired(String),
+
+    #[error("Email disabled: {0}")]
+    EmailDisabled(String),
+
+    #[error("Username change cooldown until {0}")]
+    UsernameChangeCooldown(DateTime<Utc>),
+}
+
+impl AppError {
+    // The code clients see for this error, which also decides the HTTP status
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::Authentication(_) | AppError::InvalidToken(_) => ErrorCode::Unauthenticated,
+            AppError::Authorization(_) => ErrorCode::Forbidden,
+            // The token is valid but the account isn't; a distinct code tells clients not to refresh
+            AppError::AccountDisabled(_) => ErrorCode::AccountDisabled,
+            // REQUIRE_VERIFIED_EMAIL_FOR_LOGIN is on and the password was right
+            AppError::EmailNotVerified(_) => ErrorCode::EmailNotVerified,
+            // Only the change-password endpoint accepts the token until the password is changed
+            AppError::PasswordExpired(_) => ErrorCode::PasswordExpired,
+            // REGISTRATION_MODE doesn't allow this signup; admins can still create the account
+            AppError::RegistrationDisabled(_) => ErrorCode::RegistrationDisabled,
+            // The provider hasn't verified the address, so it can't be trusted to identify anyone
+            AppError::OAuthEmailUnverified(_) => ErrorCode::OAuthEmailUnverified,
+            // The provider rejected the tokens we hold; only signing in with it again helps
+            AppError::OAuthReauthRequired(_) => ErrorCode::OAuthReauthRequired,
+            // EMAIL_ENABLED=false, so nothing that depends on an email can be done
+            AppError::EmailDisabled(_) => ErrorCode::EmailDisabled,
+            // Usernames can only be changed once per USERNAME_CHANGE_COOLDOWN_DAYS
+            AppError::UsernameChangeCooldown(_) => ErrorCode::UsernameChangeCooldown,
+            AppError::Validation(_) => ErrorCode::BadRequest,
+            // A validation failure clients may want to explain specifically
+            AppError::PasswordReused(_) => ErrorCode::PasswordReused,
+            AppError::NotFound(_) => ErrorCode::NotFound,
+            AppError::Database(e) => match e {
+                DatabaseError::NotFound => ErrorCode::NotFound,
+                DatabaseError::Duplicate(_) => ErrorCode::Conflict,
+                DatabaseError::Validation(_) => ErrorCode::BadRequest,
+                _ => ErrorCode::Internal,
+            },
+            AppError::Internal(_) | AppError::Unexpected(_) | AppError::Configuration(_) => {
+                ErrorCode::Internal
+            }
+            AppError::TooManyRequests(_) => ErrorCode::TooManyRequests,
+            // An OAuth provider or other service we called didn't answer in time; unlike
+            // INTERNAL it's worth retrying
+            AppError::Timeout(_) => ErrorCode::UpstreamTimeout,
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            AppError::Authentication(msg) => (StatusCode::UNAUTHORIZED, msg),
-            AppError::Authorization(msg) => (StatusCode::FORBIDDEN, msg),
-            AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+        let code = self.code();
+        let message = match self {
+            AppError::UsernameChangeCooldown(available_at) => {
+                let remaining = (available_at - Utc::now()).num_seconds().max(1) as u64;
+                let mut response = ApiResponse::error_with_code(
+                    code.status(),
+                    code.as_str(),
+                    format!(
+                        "Your username was changed recently. It can be changed again in {} \
+                         (at {}).",
+                        describe_duration(remaining),
+                        available_at.to_rfc3339()
+                    ),
+                );
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(remaining));
+                return response;
+            }
             AppError::Database(e) => match e {
-                DatabaseError::NotFound => {
-                    (StatusCode::NOT_FOUND, "Resource not found".to_string())
-                }
-                DatabaseError::Duplicate(msg) => (StatusCode::CONFLICT, msg),
-                DatabaseError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
-                _ => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "An internal database error occurred".to_string(),
-                ),
+                DatabaseError::NotFound => "Resource not found".to_string(),
+                DatabaseError::Duplicate(msg) | DatabaseError::Validation(msg) => msg,
+                _ => "An internal database error occurred".to_string(),
             },
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::InvalidToken(msg) => (StatusCode::UNAUTHORIZED, msg),
-            AppError::Unexpected(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::Configuration(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+            AppError::Authentication(msg)
+            | AppError::Authorization(msg)
+            | AppError::AccountDisabled(msg)
+            | AppError::EmailNotVerified(msg)
+            | AppError::PasswordExpired(msg)
+            | AppError::RegistrationDisabled(msg)
+            | AppError::OAuthEmailUnverified(msg)
+            | AppError::OAuthReauthRequired(msg)
+            | AppError::EmailDisabled(msg)
+            | AppError::Validation(msg)
+            | AppError::PasswordReused(msg)
+            | AppError::NotFound(msg)
+            | AppError::Internal(msg)
+            | AppError::InvalidToken(msg)
+            | AppError::Unexpected(msg)
+            | AppError::Timeout(msg)
+            | AppError::Configuration(msg)
+            | AppError::TooManyRequests(msg) => msg,
         };
 
-        ApiResponse::error(status, message)
+        ApiResponse::error_with_code(code.status(), code.as_str(), message)
     }
 }
 
+// A wait in seconds, rounded up to the largest unit that fits: "3 days", "1 hour"
+fn describe_duration(seconds: u64) -> String {
+    let (count, unit) = [(86_400, "day"), (3_600, "hour"), (60, "minute")]
+        .into_iter()
+        .find(|&(unit_seconds, _)| seconds >= unit_seconds)
+        .map_or((seconds, "second"), |(unit_seconds, unit)| {
+            (seconds.div_ceil(unit_seconds), unit)
+        });
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
 pub type AppResult<T> = Result<T, AppError>;
@@ -1,34 +1,49 @@
 mod api;
+mod cli;
 mod config;
 mod db;
 mod errors;
 mod middleware;
 mod models;
 mod services;
+#[cfg(test)]
+mod test_support;
 mod utils;
 
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+
+use clap::Parser;
+use tracing::{error, info, warn};
 
 use db::repositories::OAuthRepository;
 use db::repositories::Repositories;
+use db::repositories::SessionRepository;
 use db::repositories::TokenRepository;
 use db::repositories::UserRepository;
+use services::audit::AuditService;
 use services::auth::{AuthService, OAuthService, TokenService};
-use services::badge::BadgeService;
+use services::badge::{BadgeService, CredentialSigner};
 use services::email::EmailService;
+use services::events::EventBus;
+use services::health::HealthService;
 use services::scheduler::SchedulerService;
-use services::user::UserManagementService;
+use services::user::{UserEmailService, UserManagementService};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logger
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to set up global logger");
+    // Initialize logger; .env may set LOG_FORMAT and LOG_LEVEL
+    dotenv::dotenv().ok();
+    utils::logging::init(&config::LogConfig::from_env());
+
+    // Without a subcommand the binary runs the server, as it always has
+    match cli::Cli::parse().command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => serve().await,
+        command => cli::run(command).await,
+    }
+}
 
+async fn serve() -> anyhow::Result<()> {
     // Load configuration
     let config = config::load_config();
     info!("Configuration loaded");
@@ -50,18 +65,35 @@ async fn main() -> anyhow::Result<()> {
     let user_repo = UserRepository::new(db_pool.as_ref().clone());
     let token_repo = TokenRepository::new(db_pool.as_ref().clone());
     let oauth_repo = OAuthRepository::new(db_pool.as_ref().clone());
-
-    let user_management_service = Arc::new(UserManagementService::new(user_repo.clone()));
+    let session_repo = SessionRepository::new(db_pool.as_ref().clone());
+
+    // Initialize the account event bus shared by services and the WebSocket endpoint
+    let event_bus = Arc::new(EventBus::new());
+
+    let user_management_service = Arc::new(
+        UserManagementService::new(user_repo.clone(), event_bus.clone())
+            .with_password_history(config.password_history_size)
+            .with_avatars(config.avatars.clone())
+            .with_username_policy(
+                config.username_change_cooldown_days,
+                config.username_reservation_days,
+            ),
+    );
 
     // Initialize Email service
     let email_service = Arc::new(EmailService::new(config.email.clone(), token_repo.clone()));
+    if !config.email.enabled {
+        warn!(
+            "EMAIL_ENABLED is false: no emails are sent, and new accounts are verified \
+             without one"
+        );
+    }
     info!("Email service initialized");
 
     // Initialize OAuth service
     let oauth_service = Arc::new(OAuthService::new(
         user_repo.clone(),
         oauth_repo,
-        token_service.clone(),
         user_management_service.clone(),
         config.clone(),
     ));
@@ -71,29 +103,90 @@ async fn main() -> anyhow::Result<()> {
         AuthService::new(
             user_repo,
             token_repo,
+            session_repo,
             token_service.clone(),
             user_management_service.clone(),
         )
-        .with_oauth_service(oauth_service),
+        .with_oauth_service(oauth_service)
+        .with_email_service(email_service.clone())
+        .with_password_reset_cooldown(config.password_reset_cooldown_secs)
+        .with_reset_invalidation_on_login(config.invalidate_reset_on_login)
+        .with_verified_email_login(
+            config.require_verified_email_for_login,
+            config.login_verification_resend_secs,
+        ),
     );
 
-    let badge_service = Arc::new(BadgeService::new(repos.clone()));
+    // Create the first admin account on a fresh deployment
+    if let Some(bootstrap) = &config.bootstrap_admin {
+        match user_management_service.bootstrap_admin(bootstrap).await {
+            Ok(Some(admin)) => warn!(
+                "Created bootstrap admin {} ({}); log in and change its password, \
+                 then unset BOOTSTRAP_ADMIN_PASSWORD",
+                admin.username, admin.email
+            ),
+            Ok(None) => info!("An admin account already exists, skipping admin bootstrap"),
+            Err(e) => error!("Failed to create bootstrap admin: {}", e),
+        }
+    }
+
+    let badge_service = Arc::new(
+        BadgeService::new(repos.clone(), event_bus.clone())
+            .with_avatars(config.avatars.clone())
+            .with_credentials(
+                CredentialSigner::from_config(&config.credentials)
+                    .unwrap_or_else(|e| panic!("{}", e)),
+            ),
+    );
+    let audit_service = Arc::new(AuditService::new(repos.clone(), event_bus.clone()));
+    let user_email_service = Arc::new(UserEmailService::new(repos.clone(), email_service.clone()));
+    let health_service = Arc::new(HealthService::new(
+        db_pool.as_ref().clone(),
+        email_service.clone(),
+        auth_service.clone(),
+    ));
     info!("Services initialized");
 
     // Initialize and start scheduler service
-    let scheduler = SchedulerService::new(repos.clone());
+    let scheduler = SchedulerService::new(repos.clone())
+        .with_dormancy(config.dormancy.clone(), email_service.clone())
+        .with_session_idle_timeout(config.session_idle_timeout_secs);
+    if config.session_idle_timeout_secs > 0
+        && (!config.track_session_activity
+            || config.session_activity_interval >= config.session_idle_timeout_secs)
+    {
+        warn!(
+            "SESSION_IDLE_TIMEOUT_SECS is set but session activity isn't tracked more often \
+             than that; enable TRACK_SESSION_ACTIVITY with a shorter SESSION_ACTIVITY_INTERVAL \
+             or active sessions will time out"
+        );
+    }
+    if config.auth_stateless {
+        warn!(
+            "AUTH_STATELESS is enabled: access tokens are accepted without checking the \
+             account, so suspensions, deletions and sign-outs take up to JWT_EXPIRATION ({}s) \
+             to apply",
+            config.jwt_expiration
+        );
+    }
     scheduler.start_background_tasks();
     info!("Background tasks started");
 
     // Initialize API router
     let app = api::configure_api(
-        repos.clone(),
+        api::ApiState {
+            repos: repos.clone(),
+            token_service: token_service.clone(),
+            user_management_service: user_management_service.clone(),
+            auth_service: auth_service.clone(),
+            badge_service: badge_service.clone(),
+            email_service: email_service.clone(),
+            audit_service: audit_service.clone(),
+            event_bus: event_bus.clone(),
+            user_email_service: user_email_service.clone(),
+            health_service,
+        },
         config.clone(),
-        token_service.clone(),
-        user_management_service.clone(),
-        auth_service.clone(),
-        badge_service.clone(),
-        email_service.clone(),
     );
     info!("API routes configured");
 
@@ -102,7 +195,12 @@ async fn main() -> anyhow::Result<()> {
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     info!("Starting server on http://{}", addr);
 
-    axum::serve(listener, app).await?;
+    // Expose the peer address to handlers and middleware (e.g. rate limiting)
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
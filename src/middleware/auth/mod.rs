@@ -1,16 +1,20 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Request, State},
-    http::header,
+    extract::{OriginalUri, Request, State},
+    http::{header, HeaderMap, Method},
     middleware::Next,
     response::Response,
 };
 use uuid::Uuid;
 
+use crate::db::error::DatabaseError;
 use crate::db::repositories::Repositories;
 use crate::errors::AppError;
-use crate::models::user::GLOBAL_ROLE_ADMIN;
+use crate::middleware::client_context::ClientContext;
+use crate::middleware::request_id::REQUEST_ID_HEADER;
+use crate::models::audit::{CreateAuditLogDto, AUDIT_EVENT_IMPERSONATED_REQUEST};
+use crate::models::user::{Role, User};
 use crate::services::auth::TokenService;
 
 // Claims re-export from token service
@@ -19,15 +23,148 @@ pub use crate::services::auth::token::Claims;
 // Authentication middleware to protect routes
 pub async fn require_auth(
     State((repos, token_service)): State<(Arc<Repositories>, Arc<TokenService>)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    authenticate_request(&repos, &token_service, request, next, false).await
+}
+
+// Same as require_auth, but still accepts users whose password has expired.
+// Only for the endpoint they use to change it.
+pub async fn require_auth_allow_expired_password(
+    State((repos, token_service)): State<(Arc<Repositories>, Arc<TokenService>)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    authenticate_request(&repos, &token_service, request, next, true).await
+}
+
+async fn authenticate_request(
+    repos: &Repositories,
+    token_service: &TokenService,
     mut request: Request,
     next: Next,
+    allow_expired_password: bool,
 ) -> Result<Response, AppError> {
     // Extract the token from the Authorization header
-    let token = extract_token_from_headers(&request)
+    let token = extract_token_from_headers(request.headers())
         .ok_or_else(|| AppError::Authentication("Token not found".into()))?;
 
+    // With AUTH_STATELESS, access tokens are taken at their word: no account lookup, no
+    // revocation or session checks
+    let claims = match token_service.verify_without_lookup(&token) {
+        Some(claims) => {
+            if !allow_expired_password && token_service.is_password_expired_by_claims(&claims) {
+                return Err(AppError::PasswordExpired(
+                    "Your password has expired and must be changed".into(),
+                ));
+            }
+            claims
+        }
+        None => {
+            let (claims, user) = authenticate_token(repos, token_service, &token).await?;
+            if !allow_expired_password {
+                check_password_expiry(token_service, &claims, &user)?;
+            }
+            claims
+        }
+    };
+    token_service.check_fingerprint(
+        &claims,
+        &ClientContext::new(request.headers(), request.extensions()),
+    )?;
+
+    let impersonator = claims
+        .impersonator
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .map(Impersonator);
+
+    // Attach claims to request extensions, and the user to the request's log lines
+    tracing::Span::current().record("user_id", claims.sub.as_str());
+    request.extensions_mut().insert(claims.clone());
+
+    let Some(impersonator) = impersonator else {
+        // Continue to the handler
+        return Ok(next.run(request).await);
+    };
+
+    // Flag the request as impersonated for handlers, and record what was done
+    request.extensions_mut().insert(impersonator);
+
+    let method = request.method().clone();
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    let response = next.run(request).await;
+
+    tracing::info!(
+        request_id = ?request_id,
+        impersonator = %impersonator.0,
+        user_id = %claims.sub,
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        "Impersonated request"
+    );
+
+    // Reads are logged; anything that may change state is also written to the audit log
+    if !matches!(method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        let dto = CreateAuditLogDto {
+            event_type: AUDIT_EVENT_IMPERSONATED_REQUEST.to_string(),
+            user_id: Uuid::parse_str(&claims.sub).ok(),
+            actor_id: Some(impersonator.0),
+            success: response.status().is_success(),
+            request_id,
+            details: Some(serde_json::json!({
+                "method": method.as_str(),
+                "path": path,
+                "status": response.status().as_u16(),
+                "impersonation_id": claims.impersonation_id,
+            })),
+            ..Default::default()
+        };
+        if let Err(e) = repos.audit().create(&dto).await {
+            tracing::error!("Failed to write audit log: {}", e);
+        }
+    }
+
+    Ok(response)
+}
+
+// The admin acting on behalf of the authenticated user, if the request uses an
+// impersonation token. Inserted into request extensions by require_auth.
+#[derive(Debug, Clone, Copy)]
+pub struct Impersonator(pub Uuid);
+
+// Reject actions that must never be performed while impersonating (password
+// changes, account deletion, ...) - requires require_auth middleware to run first
+pub async fn deny_impersonation(request: Request, next: Next) -> Result<Response, AppError> {
+    if request.extensions().get::<Impersonator>().is_some() {
+        return Err(AppError::Authorization(
+            "This action is not allowed while impersonating a user".into(),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+// Validate a bearer token and check that the account behind it can still be used
+pub async fn authenticate_token(
+    repos: &Repositories,
+    token_service: &TokenService,
+    token: &str,
+) -> Result<(Claims, User), AppError> {
     // Validate the token and extract claims
-    let claims = token_service.verify_token(&token)?;
+    let claims = token_service.verify_access_token(token)?;
 
     // Check if user still exists and is active
     let user_id = Uuid::parse_str(&claims.sub)
@@ -40,14 +177,114 @@ pub async fn require_auth(
         .map_err(|_| AppError::Authentication("User not found or inactive".into()))?;
 
     if !user.is_active {
-        return Err(AppError::Authentication("Account is not active".into()));
+        return Err(AppError::AccountDisabled(
+            "Account suspended. Please contact support.".into(),
+        ));
     }
 
-    // Attach claims to request extensions
-    request.extensions_mut().insert(claims);
+    if token_service.is_token_revoked(&claims, &user) {
+        return Err(AppError::Authentication("Session has been revoked".into()));
+    }
 
-    // Continue to the handler
-    Ok(next.run(request).await)
+    if claims.impersonator.is_some() {
+        check_impersonation(repos, &claims, user_id).await?;
+    }
+
+    check_session(repos, token_service, &claims).await?;
+
+    Ok((claims, user))
+}
+
+// With SESSION_IDLE_TIMEOUT_SECS set, access tokens from a login stop working once
+// its session is ended or goes idle. Otherwise the session isn't looked up: an ended
+// session can't be refreshed, and its access tokens run out with the JWT. Tokens
+// without a session (impersonation tokens) only expire with the JWT.
+async fn check_session(
+    repos: &Repositories,
+    token_service: &TokenService,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let ended = || AppError::Authentication("Session has ended".into());
+
+    let Some(session_id) = claims.session_id.as_deref() else {
+        return Ok(());
+    };
+    if !token_service.expires_idle_sessions() {
+        return Ok(());
+    }
+    let session_id = Uuid::parse_str(session_id).map_err(|_| ended())?;
+    let session = match repos.session().find_by_id(session_id).await {
+        Ok(session) => session,
+        Err(DatabaseError::NotFound) => return Err(ended()),
+        Err(e) => return Err(AppError::Database(e)),
+    };
+
+    if !session.is_active {
+        return Err(ended());
+    }
+    if token_service.is_session_idle(&session) {
+        return Err(AppError::Authentication(
+            "Session expired due to inactivity".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Users whose password is past its maximum age must change it before doing anything
+// else. Impersonating admins can't change it for them, so they aren't held back.
+pub fn check_password_expiry(
+    token_service: &TokenService,
+    claims: &Claims,
+    user: &User,
+) -> Result<(), AppError> {
+    if claims.impersonator.is_none() && token_service.is_password_expired(user) {
+        return Err(AppError::PasswordExpired(
+            "Your password has expired and must be changed".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+// An impersonation token is only valid while its session is active and the
+// admin behind it still is one
+async fn check_impersonation(
+    repos: &Repositories,
+    claims: &Claims,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    let ended = || AppError::Authentication("Impersonation session has ended".into());
+
+    let impersonation_id = claims
+        .impersonation_id
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(ended)?;
+
+    let session = repos
+        .impersonation()
+        .find_active(impersonation_id)
+        .await
+        .map_err(|_| ended())?;
+
+    if session.user_id != user_id
+        || claims.impersonator.as_deref() != Some(session.admin_id.to_string().as_str())
+    {
+        return Err(ended());
+    }
+
+    let admin = repos
+        .user()
+        .find_by_id(session.admin_id)
+        .await
+        .map_err(|_| ended())?;
+
+    if !admin.is_active || admin.role() != Role::Admin {
+        return Err(ended());
+    }
+
+    Ok(())
 }
 
 // Email verification middleware - requires require_auth middleware to run first
@@ -92,7 +329,7 @@ pub async fn require_admin(request: Request, next: Next) -> Result<Response, App
         .ok_or_else(|| AppError::Authorization("Authentication required".into()))?;
 
     // Check if user has admin role
-    if claims.role != GLOBAL_ROLE_ADMIN {
+    if !claims.role.has_at_least(Role::Admin) {
         return Err(AppError::Authorization("Admin access required".into()));
     }
 
@@ -101,8 +338,8 @@ pub async fn require_admin(request: Request, next: Next) -> Result<Response, App
 }
 
 // Helper function to extract Bearer token from headers
-fn extract_token_from_headers(request: &Request) -> Option<String> {
-    let auth_header = request.headers().get(header::AUTHORIZATION)?;
+pub fn extract_token_from_headers(headers: &HeaderMap) -> Option<String> {
+    let auth_header = headers.get(header::AUTHORIZATION)?;
     let auth_header = auth_header.to_str().ok()?;
 
     // Check if it's a Bearer token
@@ -112,3 +349,141 @@ fn extract_token_from_headers(request: &Request) -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::LoginDto;
+    use crate::test_support::{test_client, test_config, TestApp, TEST_PASSWORD};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn idle_sessions_are_rejected(pool: PgPool) {
+        let mut config = test_config();
+        config.session_idle_timeout_secs = 900;
+        let app = TestApp::with_config(pool.clone(), config);
+        let user = app.create_user("judy").await;
+        let credentials = LoginDto {
+            email: user.email.clone(),
+            password: TEST_PASSWORD.to_string(),
+            client_id: None,
+        };
+        let token = app
+            .auth_service
+            .login(&credentials, &test_client("192.0.2.1", "test-agent"))
+            .await
+            .unwrap()
+            .token;
+        assert!(authenticate_token(&app.repos, &app.token_service, &token)
+            .await
+            .is_ok());
+
+        sqlx::query(
+            "UPDATE sessions SET last_activity_at = now() - interval '16 minutes' WHERE user_id = $1",
+        )
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(matches!(
+            authenticate_token(&app.repos, &app.token_service, &token).await,
+            Err(AppError::Authentication(_))
+        ));
+
+        // Tokens without a persisted session are unaffected
+        let (token, _) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+        assert!(authenticate_token(&app.repos, &app.token_service, &token)
+            .await
+            .is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn sessions_are_not_checked_without_an_idle_timeout(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("jack").await;
+        let credentials = LoginDto {
+            email: user.email.clone(),
+            password: TEST_PASSWORD.to_string(),
+            client_id: None,
+        };
+        let token = app
+            .auth_service
+            .login(&credentials, &test_client("192.0.2.1", "test-agent"))
+            .await
+            .unwrap()
+            .token;
+
+        sqlx::query(
+            "UPDATE sessions SET last_activity_at = now() - interval '1 day' WHERE user_id = $1",
+        )
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(authenticate_token(&app.repos, &app.token_service, &token)
+            .await
+            .is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn stateless_mode_trusts_only_short_lived_tokens(pool: PgPool) {
+        let mut config = test_config();
+        config.auth_stateless = true;
+        config.password_max_age_days = 90;
+        let app = TestApp::with_config(pool.clone(), config);
+        let user = app.create_user("kim").await;
+        let (token, refresh_token) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+        let impersonation_token = app
+            .token_service
+            .generate_impersonation_token(
+                &user,
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                chrono::Utc::now() + chrono::Duration::minutes(5),
+            )
+            .unwrap();
+
+        assert!(app.token_service.verify_without_lookup(&token).is_some());
+        assert!(app
+            .token_service
+            .verify_without_lookup(&refresh_token)
+            .is_none());
+        assert!(app
+            .token_service
+            .verify_without_lookup(&impersonation_token)
+            .is_none());
+
+        // The trade-off: a deleted account's access token works until it expires
+        app.user_management.delete_user(user.id).await.unwrap();
+        assert!(app.token_service.verify_without_lookup(&token).is_some());
+        assert!(authenticate_token(&app.repos, &app.token_service, &token)
+            .await
+            .is_err());
+
+        // Password expiry travels in the token
+        let user = app.create_user("lee").await;
+        sqlx::query!(
+            "UPDATE users SET password_changed_at = now() - interval '91 days' WHERE id = $1",
+            user.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let user = app.repos.user().find_by_id(user.id).await.unwrap();
+        let (token, _) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+        let claims = app.token_service.verify_without_lookup(&token).unwrap();
+        assert!(app.token_service.is_password_expired_by_claims(&claims));
+
+        let app = TestApp::new(pool);
+        assert!(app.token_service.verify_without_lookup(&token).is_none());
+    }
+}
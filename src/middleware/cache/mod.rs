@@ -0,0 +1,121 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+// Largest response body we are willing to buffer to compute an ETag
+const MAX_CACHEABLE_BODY_SIZE: usize = 1024 * 1024;
+
+// Cache-Control for responses to authenticated requests that didn't opt into caching
+const PRIVATE_CACHE_CONTROL: &str = "private, no-store";
+
+// Cache-Control for everything else that didn't opt into caching. Logins, sign-ups and
+// OAuth callbacks hand out tokens without an Authorization header on the request.
+const NO_STORE_CACHE_CONTROL: &str = "no-store";
+
+// Response extension marking a response as the same for every caller
+#[derive(Debug, Clone, Copy)]
+pub struct PublicCache;
+
+// Route layer for public GETs whose body doesn't depend on who is asking
+pub async fn cache_publicly(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(PublicCache);
+    response
+}
+
+// Add caching headers once the response body is final.
+// Public responses get an ETag and `Cache-Control: public`, and a matching
+// If-None-Match turns them into a 304. Nothing else is ever stored: responses to
+// requests with credentials are `private, no-store`, and the rest `no-store`, so no
+// cache keeps personalized data or the tokens a login returns.
+pub async fn cache_headers(State(max_age): State<u64>, request: Request, next: Next) -> Response {
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD);
+    let is_authenticated = request.headers().contains_key(header::AUTHORIZATION);
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let mut response = next.run(request).await;
+
+    if response.headers().contains_key(header::CACHE_CONTROL) {
+        return response;
+    }
+
+    let is_public = response.extensions().get::<PublicCache>().is_some();
+    if !(is_public && is_read && response.status() == StatusCode::OK) {
+        let cache_control = if is_authenticated {
+            PRIVATE_CACHE_CONTROL
+        } else {
+            NO_STORE_CACHE_CONTROL
+        };
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control),
+        );
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_CACHEABLE_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response body: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let etag = format!("\"{:x}\"", Sha256::digest(&bytes));
+    let headers = &mut parts.headers;
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("hex digest is a valid header value"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&format!("public, max-age={}", max_age))
+            .expect("number is a valid header value"),
+    );
+    // The envelope negotiation changes the body based on Accept
+    headers.append(header::VARY, HeaderValue::from_static("Accept"));
+
+    if if_none_match.is_some_and(|value| etag_matches(&value, &etag)) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.remove(header::CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+// Weak comparison as required for If-None-Match: `*`, or any listed tag with or without `W/`
+fn etag_matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+
+    value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let etag = "\"abc\"";
+        assert!(etag_matches(&HeaderValue::from_static("\"abc\""), etag));
+        assert!(etag_matches(&HeaderValue::from_static("W/\"abc\""), etag));
+        assert!(etag_matches(
+            &HeaderValue::from_static("\"xyz\", \"abc\""),
+            etag
+        ));
+        assert!(etag_matches(&HeaderValue::from_static("*"), etag));
+        assert!(!etag_matches(&HeaderValue::from_static("\"xyz\""), etag));
+    }
+}
@@ -0,0 +1,374 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, Extensions, HeaderMap},
+};
+use serde::Serialize;
+
+use crate::config::IpNetwork;
+
+// Client address headers set by a reverse proxy
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_REAL_IP: &str = "x-real-ip";
+
+// Country code headers set by common CDNs (Cloudflare, CloudFront, App Engine)
+const COUNTRY_HEADERS: &[&str] = &[
+    "cf-ipcountry",
+    "cloudfront-viewer-country",
+    "x-appengine-country",
+];
+
+// How client metadata is read, added to requests as an extension
+#[derive(Debug, Clone, Default)]
+pub struct ClientContextConfig {
+    // Believe proxy headers. Only safe when every request comes through a proxy
+    // that sets them, otherwise clients can claim any address.
+    pub trust_proxy: bool,
+    // The proxies in front of the server. With any listed, headers are only believed
+    // from these peers; with none, from every peer.
+    pub trusted_proxies: Vec<IpNetwork>,
+}
+
+impl ClientContextConfig {
+    // Whether the request came straight from a proxy whose headers can be believed
+    pub fn trusts_peer(&self, extensions: &Extensions) -> bool {
+        self.trust_proxy
+            && (self.trusted_proxies.is_empty() || self.is_trusted_proxy(peer_ip(extensions)))
+    }
+
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies
+            .iter()
+            .any(|network| network.contains(ip))
+    }
+}
+
+// Coarse description of the client's device, parsed from the User-Agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceInfo {
+    pub browser: Option<&'static str>,
+    pub os: Option<&'static str>,
+    pub device_type: &'static str,
+}
+
+// Who is making the request: address, user agent and what can be derived from them.
+// Take it as a handler argument instead of reading the headers directly, so proxy
+// handling is the same everywhere.
+#[derive(Debug, Clone)]
+pub struct ClientContext {
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+    pub device_info: Option<DeviceInfo>,
+    // ISO 3166 country code from a trusted CDN, when it sends one
+    pub country: Option<String>,
+    pub accept_language: Option<String>,
+}
+
+impl ClientContext {
+    pub fn new(headers: &HeaderMap, extensions: &Extensions) -> Self {
+        let proxy = trusted_proxy(extensions);
+        let user_agent = header_str(headers, header::USER_AGENT.as_str()).map(str::to_string);
+
+        Self {
+            ip: resolve_ip(headers, extensions, proxy),
+            device_info: user_agent.as_deref().map(parse_device_info),
+            user_agent,
+            country: proxy.and_then(|_| resolve_country(headers)),
+            accept_language: header_str(headers, header::ACCEPT_LANGUAGE.as_str())
+                .map(str::to_string),
+        }
+    }
+
+    // The network the client is on: the /24 around an IPv4 address or the /48 around an
+    // IPv6 one, which survive an ISP or IPv6 privacy extensions handing out a new address
+    pub fn network(&self) -> IpAddr {
+        match self.ip.to_canonical() {
+            IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(u32::from(ip) & !0xff)),
+            IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & !(u128::MAX >> 48))),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::new(&parts.headers, &parts.extensions))
+    }
+}
+
+// The client's IP, for middleware that works on the raw request
+pub fn client_ip(headers: &HeaderMap, extensions: &Extensions) -> IpAddr {
+    resolve_ip(headers, extensions, trusted_proxy(extensions))
+}
+
+// The proxy config, when the request came from a proxy it trusts
+fn trusted_proxy(extensions: &Extensions) -> Option<&ClientContextConfig> {
+    extensions
+        .get::<ClientContextConfig>()
+        .filter(|config| config.trusts_peer(extensions))
+}
+
+// Behind a trusted proxy the client is the last X-Forwarded-For hop that isn't one of
+// the trusted proxies (each appends the address it got the request from; entries to
+// the left of that hop come from the client and can't be believed), then X-Real-IP.
+// Otherwise, or when neither header is usable, it's the peer address.
+fn resolve_ip(
+    headers: &HeaderMap,
+    extensions: &Extensions,
+    proxy: Option<&ClientContextConfig>,
+) -> IpAddr {
+    let forwarded = proxy.and_then(|proxy| {
+        header_str(headers, X_FORWARDED_FOR)
+            .and_then(|value| forwarded_client(value, proxy))
+            .or_else(|| header_str(headers, X_REAL_IP).and_then(|v| v.trim().parse().ok()))
+    });
+
+    forwarded.unwrap_or_else(|| peer_ip(extensions))
+}
+
+// Walk X-Forwarded-For from the right past our own proxies. If every hop is one of
+// them, the leftmost is the best there is.
+fn forwarded_client(value: &str, proxy: &ClientContextConfig) -> Option<IpAddr> {
+    let mut client = None;
+    for hop in value.rsplit(',') {
+        let ip: IpAddr = hop.trim().parse().ok()?;
+        client = Some(ip);
+        if !proxy.is_trusted_proxy(ip) {
+            break;
+        }
+    }
+    client
+}
+
+fn peer_ip(extensions: &Extensions) -> IpAddr {
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+fn resolve_country(headers: &HeaderMap) -> Option<String> {
+    COUNTRY_HEADERS.iter().find_map(|name| {
+        header_str(headers, name)
+            .map(str::trim)
+            // Cloudflare uses XX for unknown and T1 for Tor
+            .filter(|code| {
+                code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) && *code != "XX"
+            })
+            .map(|code| code.to_ascii_uppercase())
+    })
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.trim().is_empty())
+}
+
+// Good enough to tell sessions apart ("Firefox on Windows"), not a full UA parser.
+// Order matters: many user agents mention the engines they are compatible with.
+fn parse_device_info(user_agent: &str) -> DeviceInfo {
+    let ua = user_agent;
+    let lower = ua.to_ascii_lowercase();
+
+    let device_type = if ["bot", "crawler", "spider"]
+        .iter()
+        .any(|word| lower.contains(word))
+    {
+        "bot"
+    } else if ua.contains("iPad") || (ua.contains("Android") && !ua.contains("Mobile")) {
+        "tablet"
+    } else if ua.contains("Mobi") || ua.contains("iPhone") {
+        "mobile"
+    } else {
+        "desktop"
+    };
+
+    let os = if ua.contains("Windows") {
+        Some("Windows")
+    } else if ua.contains("iPhone") || ua.contains("iPad") || ua.contains("iPod") {
+        Some("iOS")
+    } else if ua.contains("Android") {
+        Some("Android")
+    } else if ua.contains("CrOS") {
+        Some("ChromeOS")
+    } else if ua.contains("Mac OS X") || ua.contains("Macintosh") {
+        Some("macOS")
+    } else if ua.contains("Linux") {
+        Some("Linux")
+    } else {
+        None
+    };
+
+    let browser = if ua.contains("Edg/") || ua.contains("EdgA/") || ua.contains("EdgiOS/") {
+        Some("Edge")
+    } else if ua.contains("OPR/") || ua.contains("Opera") {
+        Some("Opera")
+    } else if ua.contains("Firefox/") || ua.contains("FxiOS/") {
+        Some("Firefox")
+    } else if ua.contains("Chrome/") || ua.contains("CriOS/") {
+        Some("Chrome")
+    } else if ua.contains("Safari/") {
+        Some("Safari")
+    } else {
+        None
+    };
+
+    DeviceInfo {
+        browser,
+        os,
+        device_type,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn request(headers: &[(&'static str, &'static str)], trust_proxy: bool) -> ClientContext {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(*name, HeaderValue::from_static(value));
+        }
+        let mut extensions = Extensions::new();
+        extensions.insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+        extensions.insert(ClientContextConfig {
+            trust_proxy,
+            trusted_proxies: Vec::new(),
+        });
+        ClientContext::new(&map, &extensions)
+    }
+
+    #[test]
+    fn proxy_headers_are_ignored_unless_trusted() {
+        let spoofed = [
+            ("x-forwarded-for", "6.6.6.6"),
+            ("x-real-ip", "6.6.6.6"),
+            ("cf-ipcountry", "NZ"),
+        ];
+        let client = request(&spoofed, false);
+        assert_eq!(client.ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(client.country, None);
+
+        let client = request(&spoofed, true);
+        assert_eq!(client.ip, IpAddr::from([6, 6, 6, 6]));
+        assert_eq!(client.country.as_deref(), Some("NZ"));
+    }
+
+    #[test]
+    fn networks_ignore_the_host_part() {
+        let client = request(&[("x-forwarded-for", "203.0.113.77")], true);
+        assert_eq!(client.network(), IpAddr::from([203, 0, 113, 0]));
+
+        let client = request(&[("x-forwarded-for", "2001:db8:1:2::5")], true);
+        assert_eq!(client.network(), "2001:db8:1::".parse::<IpAddr>().unwrap());
+
+        let client = request(&[("x-forwarded-for", "::ffff:198.51.100.9")], true);
+        assert_eq!(client.network(), IpAddr::from([198, 51, 100, 0]));
+    }
+
+    #[test]
+    fn trusted_proxy_uses_the_hop_it_appended() {
+        // The client sent its own X-Forwarded-For; the proxy appended the real address
+        let client = request(&[("x-forwarded-for", "6.6.6.6, 203.0.113.7")], true);
+        assert_eq!(client.ip, IpAddr::from([203, 0, 113, 7]));
+
+        let client = request(&[("x-real-ip", "2001:db8::1")], true);
+        assert_eq!(client.ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+
+        // Garbage falls back to the peer address
+        let client = request(&[("x-forwarded-for", "not-an-ip")], true);
+        assert_eq!(client.ip, IpAddr::from([10, 0, 0, 1]));
+    }
+
+    #[test]
+    fn listed_proxies_are_skipped_and_others_ignored() {
+        let config = ClientContextConfig {
+            trust_proxy: true,
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        };
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(X_FORWARDED_FOR, HeaderValue::from_static(value));
+            headers
+        };
+        let from = |peer: [u8; 4]| {
+            let mut extensions = Extensions::new();
+            extensions.insert(ConnectInfo(SocketAddr::from((peer, 4000))));
+            extensions.insert(config.clone());
+            extensions
+        };
+
+        // Two of our proxies in a row; the client's own claim stays ignored
+        let chain = headers("6.6.6.6, 203.0.113.7, 10.1.2.3");
+        assert_eq!(
+            client_ip(&chain, &from([10, 0, 0, 1])),
+            IpAddr::from([203, 0, 113, 7])
+        );
+
+        // Straight from the internet: the header is the client's word, not a proxy's
+        assert_eq!(
+            client_ip(&chain, &from([198, 51, 100, 1])),
+            IpAddr::from([198, 51, 100, 1])
+        );
+
+        // Only proxies in the chain: the leftmost is as close to the client as it gets
+        assert_eq!(
+            client_ip(&headers("10.9.9.9, 10.1.2.3"), &from([10, 0, 0, 1])),
+            IpAddr::from([10, 9, 9, 9])
+        );
+    }
+
+    #[test]
+    fn missing_headers_and_peer_address() {
+        let client = request(&[], true);
+        assert_eq!(client.ip, IpAddr::from([10, 0, 0, 1]));
+        assert!(client.user_agent.is_none() && client.device_info.is_none());
+
+        let client = ClientContext::new(&HeaderMap::new(), &Extensions::new());
+        assert_eq!(client.ip, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    }
+
+    #[test]
+    fn user_agent_is_parsed_into_device_info() {
+        let client = request(
+            &[(
+                "user-agent",
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 \
+                 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+            )],
+            false,
+        );
+        assert_eq!(
+            client.device_info,
+            Some(DeviceInfo {
+                browser: Some("Safari"),
+                os: Some("iOS"),
+                device_type: "mobile",
+            })
+        );
+
+        let edge = parse_device_info(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+             (KHTML, like Gecko) Chrome/120.0 Safari/537.36 Edg/120.0",
+        );
+        assert_eq!(edge.browser, Some("Edge"));
+        assert_eq!(edge.os, Some("Windows"));
+        assert_eq!(edge.device_type, "desktop");
+
+        assert_eq!(
+            parse_device_info("Googlebot/2.1 (+http://www.google.com/bot.html)").device_type,
+            "bot"
+        );
+    }
+}
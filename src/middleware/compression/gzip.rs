@@ -0,0 +1,257 @@
+// gzip (RFC 1952) around a single DEFLATE block (RFC 1951) using the fixed Huffman
+// codes. Not as tight as zlib's dynamic trees, but JSON is repetitive enough that the
+// LZ77 matching does most of the work.
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+// How many earlier occurrences to try for each match; more is smaller but slower
+const MAX_CHAIN: usize = 32;
+const NONE: usize = usize::MAX;
+
+const END_OF_BLOCK: u32 = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+// Compress `data` into a complete gzip member
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // Magic, deflate, no flags, no mtime, no extra flags, unknown OS
+    let mut header = Vec::with_capacity(data.len() / 4 + 32);
+    header.extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+
+    let mut writer = BitWriter {
+        out: header,
+        bits: 0,
+        count: 0,
+    };
+    deflate(data, &mut writer);
+
+    let mut out = writer.finish();
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+// Bits are packed starting at the least significant bit of each byte
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, len: u32) {
+        self.bits |= u64::from(value) << self.count;
+        self.count += len;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes go out most significant bit first
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn write_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let index = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+        self.write_symbol(257 + index as u32);
+        self.write_bits(
+            (length - LENGTH_BASE[index] as usize) as u32,
+            LENGTH_EXTRA[index] as u32,
+        );
+
+        let index = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+        self.write_code(index as u32, 5);
+        self.write_bits(
+            (distance - DISTANCE_BASE[index] as usize) as u32,
+            DISTANCE_EXTRA[index] as u32,
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+fn deflate(data: &[u8], writer: &mut BitWriter) {
+    // Final block, fixed Huffman codes
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    let mut matcher = Matcher::new();
+    let mut i = 0;
+    while i < data.len() {
+        match matcher.longest_match(data, i) {
+            Some((length, distance)) => {
+                writer.write_match(length, distance);
+                for pos in i..i + length {
+                    matcher.insert(data, pos);
+                }
+                i += length;
+            }
+            None => {
+                writer.write_symbol(data[i] as u32);
+                matcher.insert(data, i);
+                i += 1;
+            }
+        }
+    }
+
+    writer.write_symbol(END_OF_BLOCK);
+}
+
+// Earlier positions of each 3-byte sequence, for finding repeats within the window
+struct Matcher {
+    // Most recent position with each hash
+    head: Vec<usize>,
+    // The position before each one with the same hash
+    prev: Vec<usize>,
+}
+
+impl Matcher {
+    fn new() -> Self {
+        Self {
+            head: vec![NONE; 1 << HASH_BITS],
+            prev: vec![NONE; WINDOW_SIZE],
+        }
+    }
+
+    fn hash(data: &[u8], pos: usize) -> usize {
+        let key =
+            u32::from(data[pos]) << 16 | u32::from(data[pos + 1]) << 8 | u32::from(data[pos + 2]);
+        (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH <= data.len() {
+            let h = Self::hash(data, pos);
+            self.prev[pos % WINDOW_SIZE] = self.head[h];
+            self.head[h] = pos;
+        }
+    }
+
+    // The longest earlier repeat of the bytes at `pos`, as (length, distance)
+    fn longest_match(&self, data: &[u8], pos: usize) -> Option<(usize, usize)> {
+        let max_length = MAX_MATCH.min(data.len() - pos);
+        if max_length < MIN_MATCH {
+            return None;
+        }
+
+        let (mut best_length, mut best_distance) = (0, 0);
+        let mut candidate = self.head[Self::hash(data, pos)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NONE || pos - candidate > WINDOW_SIZE {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[pos..pos + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best_length {
+                best_length = length;
+                best_distance = pos - candidate;
+                if length == max_length {
+                    break;
+                }
+            }
+
+            // Slots for positions older than the window get reused; stop at one
+            let next = self.prev[candidate % WINDOW_SIZE];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+
+        (best_length >= MIN_MATCH).then_some((best_length, best_distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn gzip_frames_the_stream() {
+        // An empty input is just the end of block
+        assert_eq!(
+            gzip(b""),
+            [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff, 0x03, 0x00, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let data = br#"{"id":1,"name":"badge"},"#.repeat(200);
+        let compressed = gzip(&data);
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(
+            compressed[compressed.len() - 8..],
+            [
+                crc32(&data).to_le_bytes(),
+                (data.len() as u32).to_le_bytes()
+            ]
+            .concat()
+        );
+    }
+}
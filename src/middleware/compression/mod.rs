@@ -0,0 +1,155 @@
+mod gzip;
+
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use self::gzip::gzip;
+
+// Content types worth compressing; images and the like are compressed already
+fn is_compressible(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    mime.starts_with("text/")
+        || mime == "application/json"
+        || mime.ends_with("+json")
+        || mime == "application/javascript"
+        || mime == "application/xml"
+}
+
+// Whether Accept-Encoding allows gzip: listed (or matched by `*`) without q=0
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    let Some(accept_encoding) = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let mut gzip = None;
+    let mut wildcard = None;
+    for entry in accept_encoding.split(',') {
+        let mut params = entry.split(';').map(str::trim);
+        let coding = params.next().unwrap_or_default().to_ascii_lowercase();
+        let allowed = params
+            .filter_map(|param| param.strip_prefix("q="))
+            .all(|q| q.parse::<f32>().is_ok_and(|q| q > 0.0));
+
+        match coding.as_str() {
+            "gzip" | "x-gzip" => gzip = Some(allowed),
+            "*" => wildcard = Some(allowed),
+            _ => {}
+        }
+    }
+
+    gzip.or(wildcard).unwrap_or(false)
+}
+
+// Compress response bodies of at least `min_size` bytes with gzip for clients that
+// accept it. Streamed bodies (server-sent events, WebSocket upgrades) are left alone.
+pub async fn compress_responses(
+    State(min_size): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let wants_gzip = accepts_gzip(request.headers());
+    let is_head = request.method() == Method::HEAD;
+
+    let response = next.run(request).await;
+
+    let headers = response.headers();
+    if !is_compressible(headers) || headers.contains_key(header::CONTENT_ENCODING) {
+        return response;
+    }
+    // Only bodies already held in memory; their size is known up front
+    let Some(size) = response.body().size_hint().exact() else {
+        return response;
+    };
+    if size < min_size as u64 {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // The body now depends on Accept-Encoding, whether or not this one is compressed
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+
+    if !wants_gzip
+        || is_head
+        || matches!(
+            parts.status,
+            StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED
+        )
+    {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response body: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let compressed = gzip(&bytes);
+    // Nothing to gain on data that doesn't compress
+    if compressed.len() >= bytes.len() {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    let headers = &mut parts.headers;
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(compressed.len()));
+    // A strong ETag promises identical bytes; these differ from the uncompressed body's
+    let weak_etag = headers
+        .get(header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .map(|etag| {
+            HeaderValue::from_str(&format!("W/{}", etag)).expect("ETag is a valid header value")
+        });
+    if let Some(weak_etag) = weak_etag {
+        headers.insert(header::ETAG, weak_etag);
+    }
+
+    Response::from_parts(parts, Body::from(compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept_encoding(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn accept_encoding_is_negotiated() {
+        assert!(accepts_gzip(&accept_encoding("gzip, deflate, br")));
+        assert!(accepts_gzip(&accept_encoding("br;q=1.0, gzip;q=0.8")));
+        assert!(accepts_gzip(&accept_encoding("*")));
+        assert!(!accepts_gzip(&accept_encoding("br")));
+        assert!(!accepts_gzip(&accept_encoding("gzip;q=0")));
+        assert!(!accepts_gzip(&accept_encoding("*, gzip;q=0")));
+        assert!(!accepts_gzip(&accept_encoding("identity")));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+}
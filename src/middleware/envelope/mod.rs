@@ -0,0 +1,88 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+// Largest response body we are willing to buffer to strip the envelope
+const MAX_ENVELOPE_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+// Decide whether the client wants the ApiResponse envelope.
+// `Accept: application/json; envelope=none` asks for the bare resource and
+// `envelope=full` asks for the envelope; otherwise the configured default applies.
+fn wants_envelope(headers: &HeaderMap, default: bool) -> bool {
+    let accept = match headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) {
+        Some(accept) => accept.to_ascii_lowercase(),
+        None => return default,
+    };
+
+    let params = accept.split([',', ';']).map(str::trim);
+    for param in params {
+        match param.replace(' ', "").as_str() {
+            "envelope=none" => return false,
+            "envelope=full" => return true,
+            _ => {}
+        }
+    }
+
+    default
+}
+
+// Strip the ApiResponse envelope for clients that don't want it.
+// Successful responses become the bare `data`; errors keep their status code and
+// become `{ "code", "message" }`. Non-envelope responses pass through untouched.
+pub async fn negotiate_envelope(
+    State(envelope_by_default): State<bool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let envelope = wants_envelope(request.headers(), envelope_by_default);
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+
+    if envelope || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_ENVELOPE_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response body: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // Only touch bodies shaped like ApiResponse (GraphQL etc. have their own format)
+    let mut value: Value = match serde_json::from_slice(&bytes) {
+        Ok(Value::Object(map)) if map.get("success").is_some_and(Value::is_boolean) => {
+            Value::Object(map)
+        }
+        _ => return Response::from_parts(parts, Body::from(bytes)),
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    if value["success"] == json!(true) {
+        if parts.status == StatusCode::NO_CONTENT {
+            parts.headers.remove(header::CONTENT_TYPE);
+            return Response::from_parts(parts, Body::empty());
+        }
+        (parts, Json(value["data"].take())).into_response()
+    } else {
+        let mut error = json!({ "message": value["message"].take() });
+        if let Some(code) = value.get_mut("code").map(Value::take) {
+            error["code"] = code;
+        }
+        (parts, Json(error)).into_response()
+    }
+}
@@ -1,3 +1,11 @@
 // Middleware will be implemented later
 
 pub mod auth;
+pub mod cache;
+pub mod client_context;
+pub mod compression;
+pub mod envelope;
+pub mod rate_limit;
+pub mod request_id;
+pub mod security_headers;
+pub mod session_activity;
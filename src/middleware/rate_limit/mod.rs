@@ -0,0 +1,253 @@
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::errors::AppError;
+use crate::middleware::client_context::client_ip;
+
+mod redis;
+mod store;
+
+pub use store::{rate_limit_store, RateLimitStore};
+
+// Counts failed attempts per client IP in a fixed window and locks the client
+// out once the limit is reached, until the window expires. The counters live in
+// the store, so with a shared one the limit holds across instances.
+pub struct AttemptLimiter {
+    store: Arc<dyn RateLimitStore>,
+    name: &'static str,
+    max_failures: u32,
+    window: Duration,
+}
+
+impl AttemptLimiter {
+    // `name` keeps this limiter's counters apart from others in the same store
+    pub fn new(
+        store: Arc<dyn RateLimitStore>,
+        name: &'static str,
+        max_failures: u32,
+        window: Duration,
+    ) -> Self {
+        Self {
+            store,
+            name,
+            max_failures,
+            window,
+        }
+    }
+
+    fn key(&self, ip: IpAddr) -> String {
+        format!("{}:{}", self.name, ip)
+    }
+
+    // Returns how long the client must wait if it is currently locked out.
+    // If the store can't be reached, clients aren't locked out.
+    pub async fn check(&self, ip: IpAddr) -> Option<Duration> {
+        match self.store.get(&self.key(ip)).await {
+            Ok(count) => count
+                .filter(|count| count.hits >= self.max_failures)
+                .map(|count| count.resets_in),
+            Err(e) => {
+                tracing::error!("Failed to check {} limit: {}", self.name, e);
+                None
+            }
+        }
+    }
+
+    pub async fn record_failure(&self, ip: IpAddr) {
+        if let Err(e) = self.store.increment(&self.key(ip), self.window).await {
+            tracing::error!("Failed to record {} failure: {}", self.name, e);
+        }
+    }
+}
+
+// Lock out clients that keep submitting invalid tokens. Only failed attempts
+// count, and a success does not reset the counter so an attacker can't clear it
+// with a token of their own.
+pub async fn limit_failed_attempts(
+    State(limiter): State<Arc<AttemptLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(request.headers(), request.extensions());
+
+    if let Some(retry_after) = limiter.check(ip).await {
+        return too_many_requests(
+            retry_after,
+            "Too many failed attempts. Please try again later.",
+        );
+    }
+
+    let response = next.run(request).await;
+
+    if matches!(
+        response.status(),
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::NOT_FOUND
+    ) {
+        limiter.record_failure(ip).await;
+    }
+
+    response
+}
+
+// Registrations are counted per minute
+const REGISTRATION_WINDOW: Duration = Duration::from_secs(60);
+
+// Caps how many accounts can be created through self-registration per minute, across
+// everyone and per client subnet, to ride out signup floods without shutting
+// registration off. Like AttemptLimiter, it counts in the store and fails open.
+pub struct RegistrationThrottle {
+    store: Arc<dyn RateLimitStore>,
+    global_limit: u32, // 0 disables
+    subnet_limit: u32, // 0 disables
+}
+
+impl RegistrationThrottle {
+    pub fn new(store: Arc<dyn RateLimitStore>, global_limit: u32, subnet_limit: u32) -> Self {
+        Self {
+            store,
+            global_limit,
+            subnet_limit,
+        }
+    }
+
+    // The counters a registration from `ip` counts against, with their limits
+    fn counters(&self, ip: IpAddr) -> Vec<(String, u32)> {
+        let mut counters = Vec::with_capacity(2);
+        if self.global_limit > 0 {
+            counters.push(("registrations:global".to_string(), self.global_limit));
+        }
+        if self.subnet_limit > 0 {
+            counters.push((
+                format!("registrations:subnet:{}", subnet(ip)),
+                self.subnet_limit,
+            ));
+        }
+        counters
+    }
+
+    // Returns how long to wait if a limit has been reached
+    pub async fn check(&self, ip: IpAddr) -> Option<Duration> {
+        let mut retry_after = None;
+        for (key, limit) in self.counters(ip) {
+            match self.store.get(&key).await {
+                Ok(Some(count)) if count.hits >= limit => {
+                    retry_after = retry_after.max(Some(count.resets_in));
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to check registration limit: {}", e),
+            }
+        }
+        retry_after
+    }
+
+    pub async fn record(&self, ip: IpAddr) {
+        for (key, _) in self.counters(ip) {
+            if let Err(e) = self.store.increment(&key, REGISTRATION_WINDOW).await {
+                tracing::error!("Failed to record registration: {}", e);
+            }
+        }
+    }
+}
+
+// The network a client is in: its /24 for IPv4 and /64 for IPv6, the usual
+// allocation to a single customer
+fn subnet(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, d, ..] = ip.segments();
+            format!("{}/64", Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0))
+        }
+    }
+}
+
+// Turn away self-registrations once too many accounts have been created recently.
+// Only successful registrations count; admins creating users don't go through here.
+pub async fn throttle_registrations(
+    State(throttle): State<Arc<RegistrationThrottle>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(request.headers(), request.extensions());
+
+    if let Some(retry_after) = throttle.check(ip).await {
+        tracing::warn!(ip = %ip, "Registration throttled");
+        return too_many_requests(
+            retry_after,
+            "Too many accounts are being created. Please try again later.",
+        );
+    }
+
+    let response = next.run(request).await;
+
+    if response.status().is_success() {
+        throttle.record(ip).await;
+    }
+
+    response
+}
+
+// A 429 telling the client when to come back
+fn too_many_requests(retry_after: Duration, message: &str) -> Response {
+    (
+        [(
+            header::RETRY_AFTER,
+            retry_after.as_secs().max(1).to_string(),
+        )],
+        AppError::TooManyRequests(message.into()),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use store::MemoryRateLimitStore;
+
+    #[test]
+    fn clients_are_grouped_by_subnet() {
+        assert_eq!(subnet("203.0.113.57".parse().unwrap()), "203.0.113.0/24");
+        assert_eq!(
+            subnet("::ffff:203.0.113.57".parse().unwrap()),
+            "203.0.113.0/24"
+        );
+        assert_eq!(
+            subnet("2001:db8:1:2:3:4:5:6".parse().unwrap()),
+            "2001:db8:1:2::/64"
+        );
+    }
+
+    #[tokio::test]
+    async fn registrations_are_throttled_per_subnet_and_globally() {
+        let throttle = RegistrationThrottle::new(Arc::new(MemoryRateLimitStore::default()), 3, 2);
+        let ip: IpAddr = "198.51.100.1".parse().unwrap();
+        let neighbour: IpAddr = "198.51.100.200".parse().unwrap();
+        let elsewhere: IpAddr = "192.0.2.1".parse().unwrap();
+
+        throttle.record(ip).await;
+        assert!(throttle.check(neighbour).await.is_none());
+        throttle.record(neighbour).await;
+
+        // The subnet is full, other networks can still register
+        assert!(throttle.check(ip).await.is_some());
+        assert!(throttle.check(elsewhere).await.is_none());
+
+        // Until the global limit is reached too
+        throttle.record(elsewhere).await;
+        assert!(throttle
+            .check("192.0.2.99".parse().unwrap())
+            .await
+            .is_some());
+    }
+}
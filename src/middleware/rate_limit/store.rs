@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+
+use crate::config::RateLimitStoreConfig;
+use crate::errors::AppError;
+
+use super::redis::RedisRateLimitStore;
+
+// Prune expired entries once the table grows past this many keys
+const PRUNE_THRESHOLD: usize = 1024;
+
+// Hits counted in a key's current window, and how long until the window ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowCount {
+    pub hits: u32,
+    pub resets_in: Duration,
+}
+
+// Where rate limit counters live. Windows are fixed: they start with the first hit
+// and everything is forgotten once they end.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    // Count a hit against the key, starting a new window if none is open
+    async fn increment(&self, key: &str, window: Duration) -> Result<WindowCount, AppError>;
+
+    // The key's open window, if it has one
+    async fn get(&self, key: &str) -> Result<Option<WindowCount>, AppError>;
+}
+
+// The store selected by RATE_LIMIT_STORE
+pub fn rate_limit_store(config: &RateLimitStoreConfig) -> Arc<dyn RateLimitStore> {
+    match config {
+        RateLimitStoreConfig::Memory => Arc::new(MemoryRateLimitStore::default()),
+        RateLimitStoreConfig::Redis { url } => Arc::new(
+            RedisRateLimitStore::new(url).unwrap_or_else(|e| panic!("Invalid REDIS_URL: {}", e)),
+        ),
+    }
+}
+
+struct MemoryWindow {
+    hits: u32,
+    started_at: Instant,
+    window: Duration,
+}
+
+impl MemoryWindow {
+    fn resets_in(&self) -> Option<Duration> {
+        self.window.checked_sub(self.started_at.elapsed())
+    }
+}
+
+// Counters kept in this process. Each instance behind a load balancer has its own.
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    entries: Mutex<HashMap<String, MemoryWindow>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn increment(&self, key: &str, window: Duration) -> Result<WindowCount, AppError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        if entries.len() > PRUNE_THRESHOLD {
+            entries.retain(|_, entry| entry.resets_in().is_some());
+        }
+
+        let entry = entries
+            .entry(key.to_string())
+            .or_insert_with(|| MemoryWindow {
+                hits: 0,
+                started_at: Instant::now(),
+                window,
+            });
+
+        // Start a fresh window once the previous one has expired
+        if entry.resets_in().is_none() {
+            entry.hits = 0;
+            entry.started_at = Instant::now();
+            entry.window = window;
+        }
+
+        entry.hits += 1;
+
+        Ok(WindowCount {
+            hits: entry.hits,
+            resets_in: entry.resets_in().unwrap_or_default(),
+        })
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<WindowCount>, AppError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(entries.get(key).and_then(|entry| {
+            entry.resets_in().map(|resets_in| WindowCount {
+                hits: entry.hits,
+                resets_in,
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_store_counts_hits_until_the_window_ends() {
+        let store = MemoryRateLimitStore::default();
+        assert_eq!(store.get("ip").await.unwrap(), None);
+
+        let window = Duration::from_millis(50);
+        store.increment("ip", window).await.unwrap();
+        let count = store.increment("ip", window).await.unwrap();
+        assert_eq!(count.hits, 2);
+        assert!(count.resets_in <= window);
+        assert_eq!(store.get("ip").await.unwrap().unwrap().hits, 2);
+        assert_eq!(store.get("other").await.unwrap(), None);
+
+        tokio::time::sleep(window).await;
+        assert_eq!(store.get("ip").await.unwrap(), None);
+        assert_eq!(store.increment("ip", window).await.unwrap().hits, 1);
+    }
+}
@@ -0,0 +1,79 @@
+use std::convert::Infallible;
+use std::fmt;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tracing::Instrument;
+
+// Header used to carry the request ID between clients, proxies and this service
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Layer that assigns a UUID request ID to requests that don't already carry one
+pub fn set_request_id_layer() -> SetRequestIdLayer<MakeRequestUuid> {
+    SetRequestIdLayer::x_request_id(MakeRequestUuid)
+}
+
+// Layer that copies the request ID onto the response
+pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
+    PropagateRequestIdLayer::x_request_id()
+}
+
+// Handle the request inside a span carrying its ID, so every log line it leads to has
+// it. require_auth fills in user_id once the caller is known. Only the path is logged:
+// query strings carry OAuth codes and verification tokens.
+pub async fn request_span(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("-");
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        user_id = tracing::field::Empty,
+    );
+
+    next.run(request).instrument(span).await
+}
+
+// Extractor for the current request ID (set by `set_request_id_layer`)
+#[derive(Debug, Clone, Default)]
+pub struct RequestId(pub Option<String>);
+
+impl RequestId {
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str().unwrap_or("-"))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestId
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let request_id = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+
+        Ok(RequestId(request_id))
+    }
+}
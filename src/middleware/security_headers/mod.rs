@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::SecurityHeadersConfig;
+use crate::middleware::client_context::ClientContextConfig;
+
+// Protocol the client used, as reported by a reverse proxy that terminates TLS
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+// The headers to add, checked once at startup
+pub struct SecurityHeaders {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    hsts: Option<HeaderValue>,
+    // Whose X-Forwarded-Proto to believe, the same proxies ClientContext takes addresses from
+    proxy: ClientContextConfig,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig, proxy: ClientContextConfig) -> Self {
+        let value = |name: &str, value: &str| {
+            HeaderValue::from_str(value)
+                .unwrap_or_else(|_| panic!("{} is not a valid header value", name))
+        };
+
+        let mut headers = vec![(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        )];
+        if let Some(csp) = &config.content_security_policy {
+            headers.push((
+                header::CONTENT_SECURITY_POLICY,
+                value("CONTENT_SECURITY_POLICY", csp),
+            ));
+        }
+        if let Some(frame_options) = &config.frame_options {
+            headers.push((
+                header::X_FRAME_OPTIONS,
+                value("X_FRAME_OPTIONS", frame_options),
+            ));
+        }
+        if let Some(referrer_policy) = &config.referrer_policy {
+            headers.push((
+                header::REFERRER_POLICY,
+                value("REFERRER_POLICY", referrer_policy),
+            ));
+        }
+
+        Self {
+            headers,
+            hsts: (config.hsts_max_age > 0).then(|| {
+                HeaderValue::from_str(&format!("max-age={}", config.hsts_max_age))
+                    .expect("number is a valid header value")
+            }),
+            proxy,
+        }
+    }
+
+    // Add the headers a handler hasn't set itself. HSTS is only meaningful (and only
+    // honoured by browsers) over HTTPS.
+    fn apply(&self, headers: &mut HeaderMap, is_https: bool) {
+        for (name, value) in &self.headers {
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+
+        if let Some(hsts) = self.hsts.as_ref().filter(|_| is_https) {
+            headers.insert(header::STRICT_TRANSPORT_SECURITY, hsts.clone());
+        }
+    }
+
+    fn is_https(&self, request: &Request) -> bool {
+        request.uri().scheme_str() == Some("https")
+            || (self.proxy.trusts_peer(request.extensions())
+                && request
+                    .headers()
+                    .get(X_FORWARDED_PROTO)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https")))
+    }
+}
+
+// Add security headers to every response, errors and redirects included
+pub async fn security_headers(
+    State(security_headers): State<Arc<SecurityHeaders>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_https = security_headers.is_https(&request);
+    let mut response = next.run(request).await;
+    security_headers.apply(response.headers_mut(), is_https);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secure_defaults_and_handler_overrides() {
+        let security_headers = SecurityHeaders::new(
+            &SecurityHeadersConfig::default(),
+            ClientContextConfig::default(),
+        );
+
+        let mut headers = HeaderMap::new();
+        security_headers.apply(&mut headers, false);
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+        assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+
+        // A handler's own policy is kept
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'self'"),
+        );
+        security_headers.apply(&mut headers, true);
+        assert_eq!(
+            headers[header::CONTENT_SECURITY_POLICY],
+            "default-src 'self'"
+        );
+        assert_eq!(
+            headers[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000"
+        );
+    }
+
+    #[test]
+    fn headers_can_be_turned_off() {
+        let config = SecurityHeadersConfig {
+            content_security_policy: None,
+            frame_options: None,
+            referrer_policy: Some("same-origin".to_string()),
+            hsts_max_age: 0,
+        };
+        let mut headers = HeaderMap::new();
+        SecurityHeaders::new(&config, ClientContextConfig::default()).apply(&mut headers, true);
+
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(!headers.contains_key(header::X_FRAME_OPTIONS));
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+        assert_eq!(headers[header::REFERRER_POLICY], "same-origin");
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+
+use uuid::Uuid;
+
+use crate::db::repositories::Repositories;
+use crate::middleware::auth::extract_token_from_headers;
+use crate::services::auth::TokenService;
+
+// Prune stale entries once the table grows past this many sessions
+const PRUNE_THRESHOLD: usize = 1024;
+
+// Keeps sessions' last_activity_at current without writing on every request:
+// each session is written at most once per interval by this instance, and the
+// update itself skips sessions another instance touched within the interval
+pub struct SessionActivityTracker {
+    repos: Arc<Repositories>,
+    token_service: Arc<TokenService>,
+    interval: Duration,
+    last_touched: Mutex<HashMap<Uuid, Instant>>,
+}
+
+impl SessionActivityTracker {
+    pub fn new(
+        repos: Arc<Repositories>,
+        token_service: Arc<TokenService>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            repos,
+            token_service,
+            interval,
+            last_touched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // The session behind an access token, if it's due for an update. Every token the
+    // login led to counts towards the same session.
+    fn due_session(&self, token: &str) -> Option<Uuid> {
+        let claims = self.token_service.verify_access_token(token).ok()?;
+        let session_id = Uuid::parse_str(claims.session_id.as_deref()?).ok()?;
+        self.is_due(session_id).then_some(session_id)
+    }
+
+    // Whether the session is due for an update; if so, counts it as updated now
+    fn is_due(&self, session_id: Uuid) -> bool {
+        let mut last_touched = self.last_touched.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(touched_at) = last_touched.get(&session_id) {
+            if touched_at.elapsed() < self.interval {
+                return false;
+            }
+        }
+
+        if last_touched.len() > PRUNE_THRESHOLD {
+            let interval = self.interval;
+            last_touched.retain(|_, touched_at| touched_at.elapsed() < interval);
+        }
+
+        last_touched.insert(session_id, Instant::now());
+        true
+    }
+
+    async fn record_activity(&self, session_id: Uuid) {
+        let min_interval = self.interval.as_secs() as i64;
+        if let Err(err) = self.repos.session().touch(session_id, min_interval).await {
+            tracing::warn!("Failed to record session activity: {}", err);
+        }
+    }
+}
+
+// Record activity on the session behind the request's bearer token.
+// The write happens in the background after the response, so it never slows a request down.
+pub async fn track_session_activity(
+    State(tracker): State<Arc<SessionActivityTracker>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = extract_token_from_headers(request.headers());
+
+    let response = next.run(request).await;
+
+    // Rejected tokens don't count as activity
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return response;
+    }
+
+    if let Some(session_id) = token.and_then(|token| tracker.due_session(&token)) {
+        tokio::spawn(async move { tracker.record_activity(session_id).await });
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::LoginDto;
+    use crate::test_support::{test_client, TestApp, TEST_PASSWORD};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn activity_is_recorded_on_the_login_session(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("nora").await;
+        let credentials = LoginDto {
+            email: user.email.clone(),
+            password: TEST_PASSWORD.to_string(),
+            client_id: None,
+        };
+        let client = test_client("192.0.2.1", "test-agent");
+        let auth = app.auth_service.login(&credentials, &client).await.unwrap();
+        let tracker = SessionActivityTracker::new(
+            app.repos.clone(),
+            app.token_service.clone(),
+            Duration::from_secs(60),
+        );
+
+        let session_id = tracker.due_session(&auth.token).unwrap();
+        // Once per interval, whichever of the session's tokens is used
+        let (_, refreshed) = app
+            .auth_service
+            .refresh_token(&auth.refresh_token, &client)
+            .await
+            .unwrap();
+        assert!(tracker.due_session(&refreshed).is_none());
+        assert!(tracker.due_session(&auth.refresh_token).is_none());
+
+        sqlx::query(
+            "UPDATE sessions SET last_activity_at = now() - interval '1 hour' WHERE id = $1",
+        )
+        .bind(session_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        tracker.record_activity(session_id).await;
+        let session = app.repos.session().find_by_id(session_id).await.unwrap();
+        assert!(session.last_activity_at > chrono::Utc::now() - chrono::Duration::minutes(1));
+    }
+}
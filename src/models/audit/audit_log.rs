@@ -0,0 +1,122 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub event_type: String,
+    pub user_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub success: bool,
+    pub request_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct CreateAuditLogDto {
+    pub event_type: String,
+    pub user_id: Option<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub success: bool,
+    pub request_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Option<serde_json::Value>,
+}
+
+// Filters for GET /audit/export. Every bound is optional; from and to are inclusive.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditExportQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub user_id: Option<Uuid>,
+    // An event type, e.g. login or admin_deactivate
+    pub action: Option<String>,
+    #[serde(default)]
+    pub format: AuditExportFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditExportFormat {
+    #[default]
+    Csv,
+    // One JSON object per line
+    Jsonl,
+}
+
+impl AuditExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            AuditExportFormat::Csv => "text/csv; charset=utf-8",
+            AuditExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            AuditExportFormat::Csv => "csv",
+            AuditExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+// How often one kind of auth event succeeded and failed
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AuthEventCount {
+    pub success: u64,
+    pub failure: u64,
+}
+
+// Auth events recorded by this instance since it started, per kind
+#[derive(Debug, Serialize)]
+pub struct AuthEventCountsResponse {
+    pub login: AuthEventCount,
+    pub oauth_login: AuthEventCount,
+    pub refresh: AuthEventCount,
+    pub logout: AuthEventCount,
+}
+
+// Authentication events tracked by the audit service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthEventKind {
+    Login,
+    OAuthLogin,
+    Refresh,
+    Logout,
+}
+
+impl AuthEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventKind::Login => AUDIT_EVENT_LOGIN,
+            AuthEventKind::OAuthLogin => AUDIT_EVENT_OAUTH_LOGIN,
+            AuthEventKind::Refresh => AUDIT_EVENT_REFRESH,
+            AuthEventKind::Logout => AUDIT_EVENT_LOGOUT,
+        }
+    }
+}
+
+// Audit event type constants
+pub const AUDIT_EVENT_LOGIN: &str = "login";
+pub const AUDIT_EVENT_OAUTH_LOGIN: &str = "oauth_login";
+pub const AUDIT_EVENT_REFRESH: &str = "refresh";
+pub const AUDIT_EVENT_LOGOUT: &str = "logout";
+pub const AUDIT_EVENT_ADMIN_VERIFY_EMAIL: &str = "admin_verify_email";
+pub const AUDIT_EVENT_ADMIN_DEACTIVATE: &str = "admin_deactivate";
+pub const AUDIT_EVENT_ADMIN_REACTIVATE: &str = "admin_reactivate";
+pub const AUDIT_EVENT_ADMIN_FORCE_LOGOUT: &str = "admin_force_logout";
+pub const AUDIT_EVENT_ADMIN_MERGE: &str = "admin_merge";
+pub const AUDIT_EVENT_ADMIN_RESEND_VERIFICATION: &str = "admin_resend_verification";
+pub const AUDIT_EVENT_ADMIN_SEND_PASSWORD_RESET: &str = "admin_send_password_reset";
+pub const AUDIT_EVENT_ADMIN_REVOKE_BADGE: &str = "admin_revoke_badge";
+pub const AUDIT_EVENT_DORMANCY_NOTICE: &str = "dormancy_notice";
+pub const AUDIT_EVENT_DORMANCY_DEACTIVATE: &str = "dormancy_deactivate";
+pub const AUDIT_EVENT_IMPERSONATION_START: &str = "impersonation_start";
+pub const AUDIT_EVENT_IMPERSONATION_END: &str = "impersonation_end";
+pub const AUDIT_EVENT_IMPERSONATED_REQUEST: &str = "impersonated_request";
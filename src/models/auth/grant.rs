@@ -0,0 +1,178 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+
+pub const GRANT_TYPE_PASSWORD: &str = "password";
+pub const GRANT_TYPE_REFRESH_TOKEN: &str = "refresh_token";
+
+// A form-encoded request to the OAuth2 token endpoint (RFC 6749 sections 4.3 and 6).
+// `username` is the account's email. client_id picks the access token lifetime for the
+// password grant; refreshed tokens keep their client. Other parameters, such as scope,
+// are ignored.
+#[derive(Debug, Deserialize)]
+pub struct TokenGrantDto {
+    pub grant_type: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub refresh_token: Option<String>,
+    pub client_id: Option<String>,
+}
+
+// A successful token response (RFC 6749 section 5.1). Refreshing doesn't issue a new
+// refresh token, so it's only included for the password grant.
+#[derive(Debug, Serialize)]
+pub struct TokenGrantResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+impl TokenGrantResponse {
+    pub fn bearer(access_token: String, refresh_token: Option<String>, expires_in: i64) -> Self {
+        Self {
+            access_token,
+            token_type: "Bearer",
+            expires_in,
+            refresh_token,
+        }
+    }
+}
+
+impl IntoResponse for TokenGrantResponse {
+    fn into_response(self) -> Response {
+        no_store(StatusCode::OK, Json(self))
+    }
+}
+
+// An error response from the token endpoint (RFC 6749 section 5.2)
+#[derive(Debug, Serialize)]
+pub struct TokenGrantError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub error: &'static str,
+    pub error_description: String,
+}
+
+impl TokenGrantError {
+    pub fn invalid_request(description: String) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: "invalid_request",
+            error_description: description,
+        }
+    }
+
+    pub fn unsupported_grant_type(grant_type: &str) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: "unsupported_grant_type",
+            error_description: format!(
+                "grant_type '{}' is not supported; use {} or {}",
+                grant_type, GRANT_TYPE_PASSWORD, GRANT_TYPE_REFRESH_TOKEN
+            ),
+        }
+    }
+}
+
+impl From<AppError> for TokenGrantError {
+    fn from(error: AppError) -> Self {
+        match error {
+            AppError::Validation(msg) => Self::invalid_request(msg),
+            // Wrong credentials, a bad, expired or revoked refresh token, or an account
+            // that can't sign in: the grant itself is no good
+            AppError::Authentication(msg)
+            | AppError::InvalidToken(msg)
+            | AppError::AccountDisabled(msg)
+            | AppError::EmailNotVerified(msg) => Self {
+                status: StatusCode::BAD_REQUEST,
+                error: "invalid_grant",
+                error_description: msg,
+            },
+            error => {
+                let code = error.code();
+                Self {
+                    status: code.status(),
+                    error: if code.status().is_server_error() {
+                        "server_error"
+                    } else {
+                        "invalid_request"
+                    },
+                    error_description: code.default_message().to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl IntoResponse for TokenGrantError {
+    fn into_response(self) -> Response {
+        no_store(self.status, Json(self))
+    }
+}
+
+// Token responses must not be cached (RFC 6749 section 5.1)
+fn no_store(status: StatusCode, body: impl IntoResponse) -> Response {
+    (
+        status,
+        [
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+            (header::PRAGMA, HeaderValue::from_static("no-cache")),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::error::DatabaseError;
+
+    #[test]
+    fn app_errors_become_oauth_errors() {
+        let cases = [
+            (
+                AppError::Validation(String::new()),
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+            ),
+            (
+                AppError::Authentication(String::new()),
+                StatusCode::BAD_REQUEST,
+                "invalid_grant",
+            ),
+            (
+                AppError::AccountDisabled(String::new()),
+                StatusCode::BAD_REQUEST,
+                "invalid_grant",
+            ),
+            (
+                AppError::EmailNotVerified(String::new()),
+                StatusCode::BAD_REQUEST,
+                "invalid_grant",
+            ),
+            (
+                AppError::TooManyRequests(String::new()),
+                StatusCode::TOO_MANY_REQUESTS,
+                "invalid_request",
+            ),
+            (
+                AppError::Database(DatabaseError::TransactionError(String::new())),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+            ),
+        ];
+
+        for (error, status, code) in cases {
+            let error = TokenGrantError::from(error);
+            assert_eq!((error.status, error.error), (status, code));
+        }
+    }
+}
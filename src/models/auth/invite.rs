@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::models::user::Role;
+use crate::services::validation::validate_email;
+
+// How long an invite can be redeemed unless the admin says otherwise
+pub const INVITE_TTL_HOURS: i64 = 7 * 24; // 7 days
+
+// An admin's invitation for one email address to register. Single-use; the
+// account it creates starts out verified with `global_role`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invite {
+    pub id: Uuid,
+    pub email: String,
+    pub global_role: String,
+    pub invited_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Invite {
+    // Like User::role, anything but a known role gets the least privileges
+    pub fn role(&self) -> Role {
+        self.global_role.parse().unwrap_or(Role::User)
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateInviteDto {
+    #[validate(custom = "validate_email")]
+    pub email: String,
+
+    // Defaults to USER
+    pub global_role: Option<String>,
+
+    #[validate(range(
+        min = 1,
+        max = 720,
+        message = "Invites must expire within 1 to 720 hours"
+    ))]
+    pub expires_in_hours: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InviteResponse {
+    pub id: Uuid,
+    pub email: String,
+    pub global_role: String,
+    pub invited_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    // Only returned when the invite is created; it's stored hashed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<Invite> for InviteResponse {
+    fn from(invite: Invite) -> Self {
+        Self {
+            id: invite.id,
+            email: invite.email,
+            global_role: invite.global_role,
+            invited_by: invite.invited_by,
+            expires_at: invite.expires_at,
+            created_at: invite.created_at,
+            token: None,
+        }
+    }
+}
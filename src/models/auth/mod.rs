@@ -1,3 +1,6 @@
+pub mod grant;
+pub mod impersonation;
+pub mod invite;
 pub mod oauth;
 pub mod session;
 pub mod token;
@@ -1,7 +1,15 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::FromRow;
 use uuid::Uuid;
+use validator::Validate;
+
+use crate::models::common::patch;
+use crate::models::user::UserResponse;
+use crate::services::validation::validate_provider_url;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct OAuthProvider {
@@ -18,11 +26,72 @@ pub struct OAuthProvider {
     pub scope: String,
     pub is_active: bool,
     pub icon_url: Option<String>,
+    pub field_map: Option<Json<OAuthFieldMap>>,
+    pub extra_auth_params: Option<Json<OAuthAuthParams>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
+// Where each user field lives in a provider's user info response, as dotted paths
+// ("id", "data.user.email", "emails.0.value"). A leading "$." is accepted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthFieldMap {
+    pub id: String,
+    pub email: String,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+    // Stands in for a missing name, and for a missing email as <username>@<provider>.user
+    pub username: Option<String>,
+    // Whether the provider has verified the email. Logins are refused when it says no;
+    // leave it unset for providers that only return verified addresses.
+    #[serde(default)]
+    pub email_verified: Option<String>,
+}
+
+impl OAuthFieldMap {
+    // Mappings for providers supported before field maps were configurable
+    pub fn builtin(provider_name: &str) -> Option<Self> {
+        match provider_name.to_lowercase().as_str() {
+            "google" => Some(Self {
+                id: "id".to_string(),
+                email: "email".to_string(),
+                name: Some("name".to_string()),
+                avatar: Some("picture".to_string()),
+                username: None,
+                email_verified: Some("verified_email".to_string()),
+            }),
+            "github" => Some(Self {
+                id: "id".to_string(),
+                email: "email".to_string(),
+                name: Some("name".to_string()),
+                avatar: Some("avatar_url".to_string()),
+                username: Some("login".to_string()),
+                // GitHub only shows verified addresses as the public email
+                email_verified: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Extra query parameters sent to the provider's authorization URL
+pub type OAuthAuthParams = BTreeMap<String, String>;
+
+// Parameters the authorization URL gets unless the provider configures its own.
+// Google only issues a refresh token with offline access, and only on consent.
+pub fn builtin_auth_params(provider_name: &str) -> OAuthAuthParams {
+    let params: &[(&str, &str)] = match provider_name.to_lowercase().as_str() {
+        "google" => &[("access_type", "offline"), ("prompt", "consent")],
+        _ => &[],
+    };
+
+    params
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct UserOAuthConnection {
     pub id: Uuid,
@@ -36,11 +105,70 @@ pub struct UserOAuthConnection {
     pub refresh_token: Option<String>,
     pub expires_at: Option<DateTime<Utc>>,
     pub raw_user_info: Option<serde_json::Value>,
+    // The client of the last login through this connection
+    pub user_agent: Option<String>,
+    pub device_info: Option<serde_json::Value>,
+    pub last_used_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
+// What an OAuth login stores on the user's connection to the provider. Profile fields
+// and tokens left as None keep the connection's current values.
+#[derive(Debug, Default)]
+pub struct CreateOAuthConnectionDto {
+    pub user_id: Uuid,
+    pub provider_id: Uuid,
+    pub provider_user_id: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub raw_user_info: Option<serde_json::Value>,
+    // The client logging in
+    pub user_agent: Option<String>,
+    pub device_info: Option<serde_json::Value>,
+}
+
+// A linked login as users and admins see it; tokens and the raw profile stay private
+#[derive(Debug, Serialize, FromRow)]
+pub struct OAuthConnectionResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider_id: Uuid,
+    pub provider_name: String,
+    pub provider_display_name: String,
+    pub provider_user_id: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    // Browser, OS and device type of the last OAuth login through this connection
+    pub device_info: Option<serde_json::Value>,
+    // Last OAuth login through this connection
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// What re-syncing a linked login did: the connection with the provider's latest
+// profile, the user as now stored and which of the user's fields followed it
+#[derive(Debug, Serialize)]
+pub struct OAuthProfileSyncResponse {
+    pub user: UserResponse,
+    pub connection: OAuthConnectionResponse,
+    pub updated_fields: Vec<&'static str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OAuthConnectionListQuery {
+    // Only connections through this provider, by name (e.g. github)
+    pub provider: Option<String>,
+    pub user_id: Option<Uuid>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct OAuthProviderResponse {
     pub id: Uuid,
@@ -53,31 +181,75 @@ pub struct OAuthProviderResponse {
 }
 
 #[derive(Debug, Deserialize)]
+pub struct OAuthProviderListQuery {
+    #[serde(default)]
+    pub include_inactive: bool,
+}
+
+#[derive(Debug, Deserialize, Validate)]
 pub struct CreateOAuthProviderDto {
+    #[validate(length(min = 1, max = 50, message = "Must be between 1 and 50 characters"))]
     pub provider_name: String,
+    #[validate(length(min = 1, max = 100, message = "Must be between 1 and 100 characters"))]
     pub display_name: String,
+    #[validate(length(min = 1, message = "Must not be empty"))]
     pub client_id: String,
+    #[validate(length(min = 1, message = "Must not be empty"))]
     pub client_secret: String,
+    #[validate(custom = "validate_provider_url")]
     pub auth_url: String,
+    #[validate(custom = "validate_provider_url")]
     pub token_url: String,
+    #[validate(custom = "validate_provider_url")]
     pub user_info_url: String,
+    #[validate(custom = "validate_provider_url")]
     pub redirect_url: String,
     pub scope: String,
+    #[validate(custom = "validate_provider_url")]
     pub icon_url: Option<String>,
+    // Required unless the provider has a built-in mapping (google, github)
+    pub field_map: Option<OAuthFieldMap>,
+    // Omitted uses the built-in parameters for the provider (google), if any
+    pub extra_auth_params: Option<OAuthAuthParams>,
 }
 
-#[derive(Debug, Deserialize)]
+// A merge patch of a provider: fields left out are kept. Null clears icon_url, and
+// puts field_map and extra_auth_params back to the provider's built-in ones.
+#[derive(Debug, Default, Deserialize, Validate)]
 pub struct UpdateOAuthProviderDto {
+    #[serde(default, deserialize_with = "patch::non_null")]
+    #[validate(length(min = 1, max = 100, message = "Must be between 1 and 100 characters"))]
     pub display_name: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
+    #[validate(length(min = 1, message = "Must not be empty"))]
     pub client_id: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
+    #[validate(length(min = 1, message = "Must not be empty"))]
     pub client_secret: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
+    #[validate(custom = "validate_provider_url")]
     pub auth_url: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
+    #[validate(custom = "validate_provider_url")]
     pub token_url: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
+    #[validate(custom = "validate_provider_url")]
     pub user_info_url: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
+    #[validate(custom = "validate_provider_url")]
     pub redirect_url: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
     pub scope: Option<String>,
+    #[serde(default, deserialize_with = "patch::non_null")]
     pub is_active: Option<bool>,
-    pub icon_url: Option<String>,
+    #[serde(default, deserialize_with = "patch::nullable")]
+    #[validate(custom = "validate_provider_url")]
+    pub icon_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "patch::nullable")]
+    pub field_map: Option<Option<OAuthFieldMap>>,
+    // {} sends no parameters at all, not even the built-in ones
+    #[serde(default, deserialize_with = "patch::nullable")]
+    pub extra_auth_params: Option<Option<OAuthAuthParams>>,
 }
 
 #[derive(Debug, Deserialize)]
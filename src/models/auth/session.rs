@@ -20,6 +20,20 @@ pub struct Session {
     pub updated_at: DateTime<Utc>,
 }
 
+// A session started by a login. The id is chosen up front so the tokens can carry it.
+#[derive(Debug)]
+pub struct CreateSessionDto {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub refresh_token_expires_at: Option<DateTime<Utc>>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub device_info: Option<serde_json::Value>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SessionResponse {
     pub id: Uuid,
@@ -23,6 +23,53 @@ pub struct VerificationTokenResponse {
     pub expires_at: DateTime<Utc>,
 }
 
+// What support can see of an emailed token, to tell why a link doesn't work. Never
+// includes the token itself.
+#[derive(Debug, Serialize)]
+pub struct VerificationTokenStatusResponse {
+    pub id: Uuid,
+    pub token_type: String,
+    pub status: VerificationTokenStatus,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+// Where an emailed token stands:
+// - active: the link still works
+// - used: it was used, or replaced when a newer one was sent or all of the user's
+//   sessions were revoked
+// - expired: it outlived its lifetime unused. Expired tokens are deleted periodically,
+//   so an old link can also have no token at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationTokenStatus {
+    Active,
+    Used,
+    Expired,
+}
+
+impl From<VerificationToken> for VerificationTokenStatusResponse {
+    fn from(token: VerificationToken) -> Self {
+        let status = if token.used_at.is_some() {
+            VerificationTokenStatus::Used
+        } else if token.expires_at <= Utc::now() {
+            VerificationTokenStatus::Expired
+        } else {
+            VerificationTokenStatus::Active
+        };
+
+        Self {
+            id: token.id,
+            token_type: token.token_type,
+            status,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            used_at: token.used_at,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateVerificationTokenDto {
     pub user_id: Option<Uuid>,
@@ -30,15 +77,55 @@ pub struct CreateVerificationTokenDto {
     pub expires_in: i64, // seconds
 }
 
+// An emailed token, passed as a query parameter or in a POST body
+#[derive(Debug, Deserialize)]
+pub struct EmailTokenDto {
+    pub token: String,
+}
+
+// An emailed token that can still be used. Checking it doesn't use it up, so links
+// pre-fetched by email scanners keep working; the action itself takes a POST.
+#[derive(Debug, Serialize)]
+pub struct PendingTokenResponse {
+    pub token_type: String,
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VerifyTokenDto {
     pub token: String,
     pub token_type: String,
 }
 
+// Where a refresh token stands, as reported on logout:
+// - active: it can still be exchanged for an access token
+// - expired: it has outlived its lifetime
+// - revoked: it was ended early, by logging out its session, a sign-out of every
+//   session, or the account being suspended or deleted
+// - invalid: it isn't a refresh token we issued
+// Tokens without a persisted session are stateless, so logging out can't end them and
+// they stay active until they expire or the user's tokens are revoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshTokenState {
+    Active,
+    Expired,
+    Revoked,
+    Invalid,
+}
+
 // Token type constants
 pub const TOKEN_TYPE_EMAIL_VERIFICATION: &str = "email_verification";
 pub const TOKEN_TYPE_PASSWORD_RESET: &str = "password_reset";
+pub const TOKEN_TYPE_SECONDARY_EMAIL_VERIFICATION: &str = "secondary_email_verification";
+
+// Token lifetimes in seconds
+pub const EMAIL_VERIFICATION_TOKEN_TTL: i64 = 24 * 60 * 60; // 24 hours
+pub const PASSWORD_RESET_TOKEN_TTL: i64 = 60 * 60; // 1 hour
+
+// 32 alphanumeric characters from the OS CSPRNG is ~190 bits of entropy
+pub const VERIFICATION_TOKEN_LENGTH: usize = 32;
 
 // Implementation of From trait for converting from VerificationToken to VerificationTokenResponse
 impl From<VerificationToken> for VerificationTokenResponse {
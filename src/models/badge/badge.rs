@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::models::common::fields::SparseFields;
+use crate::models::common::patch;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Badge {
     pub id: Uuid,
@@ -27,8 +30,11 @@ pub struct CreateBadgeDto {
     pub image_url: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+// A merge patch of a badge: fields left out are kept, and null clears description and
+// image_url
+#[derive(Debug, Default, Deserialize, Validate)]
 pub struct UpdateBadgeDto {
+    #[serde(default, deserialize_with = "patch::non_null")]
     #[validate(length(
         min = 1,
         max = 100,
@@ -36,11 +42,13 @@ pub struct UpdateBadgeDto {
     ))]
     pub name: Option<String>,
 
-    pub description: Option<String>,
-    pub image_url: Option<String>,
+    #[serde(default, deserialize_with = "patch::nullable")]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "patch::nullable")]
+    pub image_url: Option<Option<String>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
 pub struct BadgeResponse {
     pub id: Uuid,
     pub name: String,
@@ -49,6 +57,11 @@ pub struct BadgeResponse {
     pub created_at: DateTime<Utc>,
 }
 
+impl SparseFields for BadgeResponse {
+    const FIELDS: &'static [&'static str] =
+        &["id", "name", "description", "image_url", "created_at"];
+}
+
 // Implementation of From trait for converting from Badge to BadgeResponse
 impl From<Badge> for BadgeResponse {
     fn from(badge: Badge) -> Self {
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// The claims of a badge credential JWT: an Open Badges 3.0 credential in the JWT
+// encoding of the W3C Verifiable Credentials data model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BadgeCredentialClaims {
+    pub iss: String, // The issuer
+    pub sub: String, // The recipient, as in credentialSubject.id
+    pub jti: String, // The credential, as in vc.id
+    pub iat: i64,    // When the credential was signed
+    pub nbf: i64,    // When the badge was awarded
+    pub vc: OpenBadgeCredential,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenBadgeCredential {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    pub id: String,
+    pub name: String,
+    pub issuer: CredentialIssuer,
+    pub issuance_date: DateTime<Utc>,
+    pub credential_subject: AchievementSubject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialIssuer {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+}
+
+// The recipient and what they achieved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementSubject {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    pub identifier: Vec<IdentityObject>,
+    pub achievement: Achievement,
+}
+
+// The recipient's email address, salted and hashed so the credential doesn't disclose
+// it but whoever knows it can check that it matches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityObject {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub identity_type: String,
+    pub identity_hash: String, // "sha256$" and the hex SHA-256 of the address and salt
+    pub hashed: bool,
+    pub salt: String,
+}
+
+// The badge itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    pub name: String,
+    pub description: String,
+    pub criteria: AchievementCriteria,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<CredentialImage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementCriteria {
+    pub narrative: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialImage {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+// What GET /users/:id/badges/:badge_id/credential returns. The credential verifies
+// against the key with `key_id` in the JWKS at /.well-known/jwks.json.
+#[derive(Debug, Serialize)]
+pub struct BadgeCredentialResponse {
+    pub credential: String,
+    pub key_id: String,
+}
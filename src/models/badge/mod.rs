@@ -1,3 +1,5 @@
 pub mod badge;
+pub mod credential;
 
 pub use self::badge::*;
+pub use self::credential::*;
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Query parameters accepted by bulk admin operations
+#[derive(Debug, Deserialize, Default)]
+pub struct BulkOperationQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// Why a single item of a bulk operation was (or would be) skipped
+#[derive(Debug, Serialize)]
+pub struct BulkSkippedItem {
+    pub id: Uuid,
+    pub reason: String,
+}
+
+// Outcome of a bulk operation. In a dry run `succeeded` lists the ids that
+// would have been changed and nothing is written.
+#[derive(Debug, Serialize, Default)]
+pub struct BulkOperationResult {
+    pub dry_run: bool,
+    pub requested: usize,
+    pub succeeded: Vec<Uuid>,
+    pub skipped: Vec<BulkSkippedItem>,
+}
+
+impl BulkOperationResult {
+    pub fn new(dry_run: bool, requested: usize) -> Self {
+        Self {
+            dry_run,
+            requested,
+            ..Default::default()
+        }
+    }
+
+    pub fn skip(&mut self, id: Uuid, reason: &str) {
+        self.skipped.push(BulkSkippedItem {
+            id,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+// Bulk skip reasons
+pub const BULK_SKIP_USER_NOT_FOUND: &str = "user_not_found";
+pub const BULK_SKIP_DUPLICATE: &str = "duplicate";
+pub const BULK_SKIP_ALREADY_HAS_BADGE: &str = "already_has_badge";
+pub const BULK_SKIP_DOES_NOT_HAVE_BADGE: &str = "does_not_have_badge";
+pub const BULK_SKIP_ALREADY_INACTIVE: &str = "already_inactive";
+pub const BULK_SKIP_SELF: &str = "cannot_deactivate_self";
@@ -0,0 +1,12 @@
+use serde::Serialize;
+
+use crate::config::FeatureFlags;
+
+// What GET /config/features returns: the configured flags plus the OAuth providers
+// that can be used to sign in right now
+#[derive(Debug, Serialize)]
+pub struct FeaturesResponse {
+    #[serde(flatten)]
+    pub flags: FeatureFlags,
+    pub oauth_providers: Vec<String>,
+}
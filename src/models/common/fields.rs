@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::AppError;
+use crate::models::common::response::PaginatedResponse;
+
+// A response type clients can trim with ?fields=. FIELDS lists what may be asked for,
+// which is never more than the type already serializes, so a selection can only hide
+// data the caller would otherwise see.
+pub trait SparseFields: Serialize {
+    const FIELDS: &'static [&'static str];
+}
+
+// ?fields=id,username,avatar_url keeps only those top-level fields of each item.
+// Without it the response is complete.
+#[derive(Debug, Default, Deserialize)]
+pub struct FieldsQuery {
+    pub fields: Option<String>,
+}
+
+impl FieldsQuery {
+    // The requested fields, checked against the type's allowlist. None means all.
+    fn selected<T: SparseFields>(&self) -> Result<Option<Vec<&str>>, AppError> {
+        let Some(fields) = &self.fields else {
+            return Ok(None);
+        };
+
+        let selected: Vec<&str> = fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .collect();
+        let unknown = selected.iter().find(|field| !T::FIELDS.contains(field));
+        if selected.is_empty() || unknown.is_some() {
+            return Err(AppError::Validation(format!(
+                "fields must be a comma-separated list of {}",
+                T::FIELDS.join(", ")
+            )));
+        }
+
+        Ok(Some(selected))
+    }
+
+    pub fn select<T: SparseFields>(&self, item: &T) -> Result<Value, AppError> {
+        let selected = self.selected::<T>()?;
+        project(item, selected.as_deref())
+    }
+
+    // Same as select, for each item of a page
+    pub fn select_page<T: SparseFields>(
+        &self,
+        page: PaginatedResponse<T>,
+    ) -> Result<PaginatedResponse<Value>, AppError> {
+        let selected = self.selected::<T>()?;
+
+        Ok(PaginatedResponse {
+            data: page
+                .data
+                .iter()
+                .map(|item| project(item, selected.as_deref()))
+                .collect::<Result<_, _>>()?,
+            total: page.total,
+            page: page.page,
+            limit: page.limit,
+            total_pages: page.total_pages,
+            max_limit: page.max_limit,
+            links: page.links,
+        })
+    }
+}
+
+fn project<T: Serialize>(item: &T, selected: Option<&[&str]>) -> Result<Value, AppError> {
+    let mut value = serde_json::to_value(item).map_err(|e| {
+        tracing::error!("Failed to serialize response: {}", e);
+        AppError::Internal("Failed to serialize response".into())
+    })?;
+    if let (Some(selected), Value::Object(map)) = (selected, &mut value) {
+        map.retain(|key, _| selected.contains(&key.as_str()));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::{AccountStatus, UserResponse};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn user() -> UserResponse {
+        UserResponse {
+            id: Uuid::new_v4(),
+            email: "ann@example.com".to_string(),
+            username: "ann".to_string(),
+            full_name: None,
+            avatar_url: Some("https://example.com/ann.png".to_string()),
+            global_role: "USER".to_string(),
+            is_email_verified: true,
+            is_active: true,
+            status: AccountStatus::Active,
+            created_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    fn query(fields: &str) -> FieldsQuery {
+        FieldsQuery {
+            fields: Some(fields.to_string()),
+        }
+    }
+
+    #[test]
+    fn only_allowed_fields_are_selected() {
+        let user = user();
+        let selected = query("id, avatar_url,username").select(&user).unwrap();
+        let keys: Vec<_> = selected.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(keys.len(), 3);
+        assert!(["id", "username", "avatar_url"]
+            .iter()
+            .all(|key| keys.iter().any(|k| k == key)));
+
+        let full = FieldsQuery::default().select(&user).unwrap();
+        assert_eq!(full, serde_json::to_value(&user).unwrap());
+
+        for fields in ["password_hash", "id,nope", ",", ""] {
+            assert!(matches!(
+                query(fields).select(&user),
+                Err(AppError::Validation(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::errors::AppError;
+
+// Limit a list to records created in a window. Both ends are RFC 3339 timestamps and
+// inclusive; either can be left out.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct CreatedRangeQuery {
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+impl CreatedRangeQuery {
+    // Reject a window that ends before it starts
+    pub fn checked(self) -> Result<Self, AppError> {
+        match (self.created_after, self.created_before) {
+            (Some(after), Some(before)) if after > before => Err(AppError::Validation(
+                "created_after must not be later than created_before".to_string(),
+            )),
+            _ => Ok(self),
+        }
+    }
+}
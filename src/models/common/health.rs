@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    // Up, but something users depend on (email, an OAuth provider) isn't working
+    Degraded,
+    // The database can't be reached; nothing works
+    Unavailable,
+}
+
+// ?deep=true also probes SMTP and the OAuth providers, which is slower and goes off
+// the machine, so load balancer checks should leave it out
+#[derive(Debug, Default, Deserialize)]
+pub struct ReadinessQuery {
+    #[serde(default)]
+    pub deep: bool,
+}
+
+// One dependency's probe. Errors are only logged: the endpoint is public.
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+}
+
+// What GET /health/ready returns
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub checks: Vec<DependencyHealth>,
+}
+
+// What GET /health returns: the process is up and answering, nothing more
+#[derive(Debug, Serialize)]
+pub struct LivenessResponse {
+    pub status: HealthStatus,
+}
@@ -1,4 +1,14 @@
+pub mod bulk;
+pub mod features;
+pub mod fields;
+pub mod filter;
+pub mod health;
 pub mod pagination;
+pub mod patch;
 pub mod response;
 
+pub use bulk::*;
+pub use fields::*;
+pub use filter::*;
 pub use pagination::*;
+pub use patch::NullablePatch;
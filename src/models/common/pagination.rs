@@ -1,12 +1,40 @@
 use serde::Deserialize;
 
+// Page size ceiling when MAX_PAGE_SIZE isn't configured
+pub const DEFAULT_MAX_PAGE_SIZE: i64 = 100;
+
 /// Pagination query parameters used throughout the API
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 pub struct PaginationQuery {
     #[serde(default = "default_page")]
     pub page: i64,
     #[serde(default = "default_limit")]
     pub limit: i64,
+    // The ceiling `limit` was clamped to; set by the extractor, never by the client
+    #[serde(skip, default = "default_max_limit")]
+    pub max_limit: i64,
+}
+
+impl PaginationQuery {
+    // Bring page and limit into range, capping limit at `max_limit`
+    pub fn clamped(self, max_limit: i64) -> Self {
+        let max_limit = max_limit.max(1);
+        Self {
+            page: self.page.max(1),
+            limit: self.limit.clamp(1, max_limit),
+            max_limit,
+        }
+    }
+}
+
+impl Default for PaginationQuery {
+    fn default() -> Self {
+        Self {
+            page: default_page(),
+            limit: default_limit(),
+            max_limit: default_max_limit(),
+        }
+    }
 }
 
 fn default_page() -> i64 {
@@ -16,3 +44,7 @@ fn default_page() -> i64 {
 fn default_limit() -> i64 {
     10
 }
+
+fn default_max_limit() -> i64 {
+    DEFAULT_MAX_PAGE_SIZE
+}
@@ -0,0 +1,79 @@
+use serde::de::{Deserialize, Deserializer, Error};
+
+// Partial updates follow JSON Merge Patch (RFC 7396): a field left out is kept, null
+// clears it, and a value replaces it. Fields that can be cleared are Option<Option<T>>
+// with `#[serde(default, deserialize_with = "patch::nullable")]`; the rest are Option<T>
+// with `#[serde(default, deserialize_with = "patch::non_null")]`, which turns null into
+// an error instead of silently keeping the value.
+
+// Deserialize a field that can be cleared, so that null is Some(None) rather than None
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Deserialize a field that can't be cleared, rejecting null
+pub fn non_null<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer)?
+        .map(Some)
+        .ok_or_else(|| D::Error::custom("cannot be null"))
+}
+
+// Binds for a nullable column in an UPDATE, written as
+// `column = CASE WHEN $1 THEN $2 ELSE column END` with `is_patched()` and `patched()`
+pub trait NullablePatch<T> {
+    // Whether the patch mentions the field at all
+    fn is_patched(&self) -> bool;
+    // The field's new value; None when it's cleared (or not patched)
+    fn patched(&self) -> Option<&T>;
+}
+
+impl<T> NullablePatch<T> for Option<Option<T>> {
+    fn is_patched(&self) -> bool {
+        self.is_some()
+    }
+
+    fn patched(&self) -> Option<&T> {
+        self.as_ref().and_then(Option::as_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::common::patch;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Patch {
+        #[serde(default, deserialize_with = "patch::nullable")]
+        note: Option<Option<String>>,
+        #[serde(default, deserialize_with = "patch::non_null")]
+        name: Option<String>,
+    }
+
+    #[test]
+    fn null_clears_or_is_rejected() {
+        let patch: Patch = serde_json::from_str("{}").unwrap();
+        assert!(!patch.note.is_patched());
+        assert_eq!(patch.name, None);
+
+        let patch: Patch = serde_json::from_str(r#"{"note": null, "name": "n"}"#).unwrap();
+        assert!(patch.note.is_patched());
+        assert_eq!(patch.note.patched(), None);
+        assert_eq!(patch.name.as_deref(), Some("n"));
+
+        let patch: Patch = serde_json::from_str(r#"{"note": "hi"}"#).unwrap();
+        assert_eq!(patch.note.patched().map(String::as_str), Some("hi"));
+
+        let error = serde_json::from_str::<Patch>(r#"{"name": null}"#).unwrap_err();
+        assert!(error.to_string().contains("cannot be null"));
+    }
+}
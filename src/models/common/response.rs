@@ -1,21 +1,11 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode, Uri},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SuccessResponse<T> {
-    pub status: String,
-    pub data: T,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ErrorResponse {
-    pub status: String,
-    pub message: String,
-}
+use crate::errors::AppError;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PaginatedResponse<T> {
@@ -24,47 +14,163 @@ pub struct PaginatedResponse<T> {
     pub page: i64,
     pub limit: i64,
     pub total_pages: i64,
+    // The largest limit the endpoint accepts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<PaginationLinks>,
 }
 
+// Ready-to-follow navigation links for a paginated response
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ApiResponse {
+pub struct PaginationLinks {
+    pub first: String,
+    pub last: String,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+impl<T> PaginatedResponse<T> {
+    pub fn with_max_limit(mut self, max_limit: i64) -> Self {
+        self.max_limit = Some(max_limit);
+        self
+    }
+
+    // Build navigation links from the request URI, keeping any other query parameters.
+    // Handlers should pass the OriginalUri so links include the nesting prefix.
+    pub fn with_links(mut self, uri: &Uri) -> Self {
+        let other_params: Vec<&str> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or_default();
+                !pair.is_empty() && key != "page" && key != "limit"
+            })
+            .collect();
+
+        let link = |page: i64| {
+            let mut params = other_params.clone();
+            let pagination = format!("page={}&limit={}", page, self.limit);
+            params.push(&pagination);
+            format!("{}?{}", uri.path(), params.join("&"))
+        };
+
+        // An empty result set still has a single (empty) page
+        let last_page = self.total_pages.max(1);
+
+        self.links = Some(PaginationLinks {
+            first: link(1),
+            last: link(last_page),
+            prev: (self.page > 1).then(|| link((self.page - 1).min(last_page))),
+            next: (self.page < last_page).then(|| link(self.page + 1)),
+        });
+        self
+    }
+}
+
+// The single response envelope used by every REST endpoint.
+// Clients can opt out of it, see middleware::envelope.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiResponse<T = ()> {
     pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
     pub message: Option<String>,
-    pub data: Option<serde_json::Value>,
+    pub data: Option<T>,
 }
 
-impl ApiResponse {
-    pub fn success<T: Serialize>(status_code: StatusCode, data: T) -> Response {
+impl<T: Serialize> ApiResponse<T> {
+    pub fn success(status_code: StatusCode, data: T) -> Response {
         let response = Self {
             success: true,
+            code: None,
             message: None,
-            data: Some(serde_json::to_value(data).unwrap()),
+            data: Some(data),
         };
 
-        (status_code, Json(response)).into_response()
+        // Serialize up front so a bad response type becomes a 500 instead of a panic
+        match serde_json::to_vec(&response) {
+            Ok(body) => (
+                status_code,
+                [(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                )],
+                body,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Failed to serialize response: {}", e);
+                AppError::Internal("Failed to serialize response".into()).into_response()
+            }
+        }
     }
 
-    pub fn created<T: Serialize>(data: T) -> Response {
+    pub fn created(data: T) -> Response {
         Self::success(StatusCode::CREATED, data)
     }
+}
 
+impl ApiResponse {
     pub fn no_content() -> Response {
-        let response = Self {
-            success: true,
-            message: None,
-            data: None,
-        };
+        Self::without_data(StatusCode::NO_CONTENT, true, None, None)
+    }
 
-        (StatusCode::NO_CONTENT, Json(response)).into_response()
+    pub fn error_with_code(status_code: StatusCode, code: &str, message: String) -> Response {
+        Self::without_data(status_code, false, Some(code.to_string()), Some(message))
     }
 
-    pub fn error(status_code: StatusCode, message: String) -> Response {
+    fn without_data(
+        status_code: StatusCode,
+        success: bool,
+        code: Option<String>,
+        message: Option<String>,
+    ) -> Response {
         let response = Self {
-            success: false,
-            message: Some(message),
+            success,
+            code,
+            message,
             data: None,
         };
 
         (status_code, Json(response)).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use serde::Serializer;
+
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("deliberately failing"))
+        }
+    }
+
+    #[tokio::test]
+    async fn success_with_unserializable_data_returns_internal_error() {
+        let response = ApiResponse::success(StatusCode::OK, Unserializable);
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["success"], false);
+        assert_eq!(body["message"], "Failed to serialize response");
+    }
+
+    #[tokio::test]
+    async fn success_serializes_data_into_envelope() {
+        let response = ApiResponse::success(StatusCode::OK, vec![1, 2, 3]);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["success"], true);
+        assert_eq!(body["data"], serde_json::json!([1, 2, 3]));
+    }
+}
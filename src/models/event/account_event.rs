@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::user::Role;
+
+// What happened to the account
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountEventKind {
+    BadgeAwarded { badge_id: Uuid, badge_name: String },
+    NewLogin { method: String },
+    RoleChanged { role: Role },
+    EmailVerified,
+}
+
+// An event pushed to the affected user's live connections
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountEvent {
+    pub user_id: Uuid,
+    #[serde(flatten)]
+    pub kind: AccountEventKind,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl AccountEvent {
+    pub fn new(user_id: Uuid, kind: AccountEventKind) -> Self {
+        Self {
+            user_id,
+            kind,
+            occurred_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod account_event;
+
+pub use self::account_event::*;
@@ -1,4 +1,6 @@
+pub mod audit;
 pub mod auth;
 pub mod badge;
 pub mod common;
+pub mod event;
 pub mod user;
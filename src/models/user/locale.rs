@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+// A null locale clears the preference, leaving it to the client's Accept-Language
+#[derive(Debug, Deserialize)]
+pub struct UpdateLocaleDto {
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LocaleResponse {
+    // The language the user is addressed in for this request
+    pub locale: String,
+    // The one they chose, if any
+    pub preferred: Option<String>,
+}
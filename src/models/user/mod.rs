@@ -1,5 +1,13 @@
+pub mod locale;
+pub mod permissions;
+pub mod role;
 pub mod user;
 pub mod user_badge;
+pub mod user_email;
 
+pub use self::locale::{LocaleResponse, UpdateLocaleDto};
+pub use self::permissions::UserPermissionsResponse;
+pub use self::role::{Permission, Role};
 pub use self::user::*;
 pub use self::user_badge::*;
+pub use self::user_email::*;
@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+use super::{Permission, Role, User};
+
+// Permissions an impersonating admin doesn't get, matching the deny_impersonation routes
+const SELF_ONLY_PERMISSIONS: &[Permission] = &[
+    Permission::PasswordChange,
+    Permission::EmailsManage,
+    Permission::UsersDelete,
+    Permission::UsersImpersonate,
+];
+
+#[derive(Debug, Serialize)]
+pub struct UserPermissionsResponse {
+    pub role: Role,
+    pub capabilities: Vec<&'static str>,
+    pub is_email_verified: bool,
+    pub has_password: bool,
+    pub has_2fa: bool,
+    pub password_expired: bool,
+    pub is_impersonated: bool,
+}
+
+impl UserPermissionsResponse {
+    pub fn for_user(user: &User, password_expired: bool, is_impersonated: bool) -> Self {
+        Self {
+            role: user.role(),
+            capabilities: capabilities(user, password_expired, is_impersonated),
+            is_email_verified: user.is_email_verified,
+            has_password: !user.password_hash.is_empty(),
+            // Two-factor authentication isn't supported yet
+            has_2fa: false,
+            password_expired,
+            is_impersonated,
+        }
+    }
+}
+
+// Capabilities reported to clients so they can show or hide UI.
+// The routes still enforce access on their own; these only describe it.
+fn capabilities(user: &User, password_expired: bool, is_impersonated: bool) -> Vec<&'static str> {
+    // Everything else is behind email verification
+    if !user.is_email_verified {
+        return Vec::new();
+    }
+
+    let mut permissions = if password_expired && !is_impersonated {
+        vec![Permission::PasswordChange]
+    } else {
+        let role = user.role();
+        Permission::ALL
+            .into_iter()
+            .filter(|&permission| role.can(permission))
+            .collect()
+    };
+
+    if is_impersonated {
+        permissions.retain(|permission| !SELF_ONLY_PERMISSIONS.contains(permission));
+    }
+
+    permissions.into_iter().map(Permission::as_str).collect()
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+
+// A user's global role. Stored (in users, invites and tokens) as its name, so renaming a
+// variant means migrating data; declared from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 2] = [Role::User, Role::Admin];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "USER",
+            Role::Admin => "ADMIN",
+        }
+    }
+
+    pub fn has_at_least(self, role: Role) -> bool {
+        self >= role
+    }
+
+    pub fn can(self, permission: Permission) -> bool {
+        self.has_at_least(permission.required_role())
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Role::ALL.iter().map(|role| role.as_str()).collect();
+                AppError::Validation(format!("Role must be one of {}", names.join(", ")))
+            })
+    }
+}
+
+// Things a role allows. Their names are the capabilities reported to clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ProfileUpdate,
+    PasswordChange,
+    EmailsManage,
+    UsersManage,
+    UsersDelete,
+    UsersImpersonate,
+    BadgesManage,
+    OAuthProvidersManage,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 8] = [
+        Permission::ProfileUpdate,
+        Permission::PasswordChange,
+        Permission::EmailsManage,
+        Permission::UsersManage,
+        Permission::UsersDelete,
+        Permission::UsersImpersonate,
+        Permission::BadgesManage,
+        Permission::OAuthProvidersManage,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::ProfileUpdate => "profile:update",
+            Permission::PasswordChange => "password:change",
+            Permission::EmailsManage => "emails:manage",
+            Permission::UsersManage => "users:manage",
+            Permission::UsersDelete => "users:delete",
+            Permission::UsersImpersonate => "users:impersonate",
+            Permission::BadgesManage => "badges:manage",
+            Permission::OAuthProvidersManage => "oauth_providers:manage",
+        }
+    }
+
+    // The least privileged role that has this permission
+    fn required_role(self) -> Role {
+        match self {
+            Permission::ProfileUpdate | Permission::PasswordChange | Permission::EmailsManage => {
+                Role::User
+            }
+            Permission::UsersManage
+            | Permission::UsersDelete
+            | Permission::UsersImpersonate
+            | Permission::BadgesManage
+            | Permission::OAuthProvidersManage => Role::Admin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_round_trip_and_rank() {
+        for role in Role::ALL {
+            assert_eq!(role.to_string().parse::<Role>().unwrap(), role);
+        }
+        assert!(matches!(
+            "admin".parse::<Role>(),
+            Err(AppError::Validation(_))
+        ));
+
+        assert!(Role::Admin.has_at_least(Role::User));
+        assert!(!Role::User.has_at_least(Role::Admin));
+        assert!(Role::User.can(Permission::PasswordChange));
+        assert!(!Role::User.can(Permission::UsersManage));
+        assert!(Permission::ALL.into_iter().all(|p| Role::Admin.can(p)));
+    }
+}
@@ -3,6 +3,10 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
+use super::Role;
+use crate::config::AvatarConfig;
+use crate::models::common::fields::SparseFields;
+use crate::models::common::patch;
 use crate::services::validation::{validate_email, validate_password_strength, validate_username};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,13 +22,22 @@ pub struct User {
     pub is_email_verified: bool,
     pub is_active: bool,
     pub last_login_at: Option<DateTime<Utc>>,
+    pub password_changed_at: DateTime<Utc>,
+    // Tokens issued at or before this are no longer accepted
+    #[serde(skip_serializing)]
+    pub tokens_revoked_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
-pub const GLOBAL_ROLE_ADMIN: &str = "ADMIN";
-pub const GLOBAL_ROLE_USER: &str = "USER";
+impl User {
+    // Roles are only written from `Role`, so anything else means the row was edited by
+    // hand; treat it as the least privileged role rather than failing the request
+    pub fn role(&self) -> Role {
+        self.global_role.parse().unwrap_or(Role::User)
+    }
+}
 
 #[derive(Debug, Clone, Deserialize, Validate)]
 pub struct CreateUserDto {
@@ -39,16 +52,30 @@ pub struct CreateUserDto {
 
     pub full_name: Option<String>,
     pub avatar_url: Option<String>,
+
+    // Only used by self-registration; an invite creates the account pre-verified
+    #[serde(default)]
+    pub invite_token: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+// A merge patch of a user: fields left out are kept, and null clears full_name and
+// avatar_url
+#[derive(Debug, Default, Deserialize, Validate)]
 pub struct UpdateUserDto {
+    #[serde(default, deserialize_with = "patch::non_null")]
     #[validate(custom = "validate_username")]
-    pub username: String,
+    pub username: Option<String>,
 
-    pub full_name: Option<String>,
-    pub avatar_url: Option<String>,
-    pub is_active: Option<bool>,
+    #[serde(default, deserialize_with = "patch::nullable")]
+    pub full_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "patch::nullable")]
+    pub avatar_url: Option<Option<String>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeUsernameDto {
+    #[validate(custom = "validate_username")]
+    pub username: String,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -58,6 +85,10 @@ pub struct LoginDto {
 
     #[validate(length(min = 1, message = "Password cannot be empty"))]
     pub password: String,
+
+    // The client app logging in, for its own access token lifetime (JWT_CLIENT_EXPIRATIONS)
+    #[serde(default)]
+    pub client_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -90,7 +121,41 @@ pub struct UpdatePasswordDto {
     pub new_password: String,
 }
 
-#[derive(Debug, Serialize)]
+// Admin lookups that can also return soft-deleted accounts
+#[derive(Debug, Deserialize)]
+pub struct IncludeDeletedQuery {
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AccountStatusDto {
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "Reason must be between 1 and 500 characters"
+    ))]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct BulkAccountStatusDto {
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "Between 1 and 500 user IDs are required"
+    ))]
+    pub user_ids: Vec<Uuid>,
+
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "Reason must be between 1 and 500 characters"
+    ))]
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
 pub struct UserResponse {
     pub id: Uuid,
     pub email: String,
@@ -99,20 +164,116 @@ pub struct UserResponse {
     pub avatar_url: Option<String>,
     pub global_role: String,
     pub is_email_verified: bool,
+    pub is_active: bool,
+    pub status: AccountStatus,
     pub created_at: DateTime<Utc>,
+    // Only set when an admin looks up a deleted account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[graphql(skip)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl SparseFields for UserResponse {
+    const FIELDS: &'static [&'static str] = &[
+        "id",
+        "email",
+        "username",
+        "full_name",
+        "avatar_url",
+        "global_role",
+        "is_email_verified",
+        "is_active",
+        "status",
+        "created_at",
+        "deleted_at",
+    ];
+}
+
+// One field for clients to switch on instead of combining is_active, is_email_verified
+// and deleted_at themselves. The first that applies wins, in the order listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, async_graphql::Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    Deleted,
+    Suspended,
+    Unverified,
+    Active,
+}
+
+impl AccountStatus {
+    pub fn of(user: &User) -> Self {
+        if user.deleted_at.is_some() {
+            Self::Deleted
+        } else if !user.is_active {
+            Self::Suspended
+        } else if !user.is_email_verified {
+            Self::Unverified
+        } else {
+            Self::Active
+        }
+    }
+}
+
+// A new account, and whether a verification email is on its way. When it isn't, the
+// user can ask for another, unless email is disabled and the account came verified.
+#[derive(Debug, Serialize)]
+pub struct RegisterResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub verification_email_sent: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub user: UserResponse,
     pub token: String,
+    // Left out when it's set in the refresh token cookie instead
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub refresh_token: String,
+    pub login_method: &'static str,
+    // OAuth logins only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_new_user: Option<bool>,
+}
+
+// What was moved when one account was merged into another. When both accounts had
+// the same badge or a connection to the same provider, the target's is kept.
+#[derive(Debug, Default, Serialize)]
+pub struct UserMergeResult {
+    pub badges_moved: u64,
+    pub badges_dropped: u64,
+    pub connections_moved: u64,
+    pub connections_dropped: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserMergeResponse {
+    pub source_id: Uuid,
+    pub target: UserResponse,
+    #[serde(flatten)]
+    pub merged: UserMergeResult,
+}
+
+pub const LOGIN_METHOD_PASSWORD: &str = "password";
+pub const LOGIN_METHOD_OAUTH: &str = "oauth";
+
+impl UserResponse {
+    // Fill in the configured fallback for users without an avatar of their own
+    pub fn with_default_avatar(mut self, avatars: &AvatarConfig) -> Self {
+        if self.avatar_url.is_none() {
+            self.avatar_url = avatars.fallback_for(&self.email);
+        }
+        self
+    }
 }
 
 // Implementation of From trait for converting from User to UserResponse
 impl From<User> for UserResponse {
     fn from(user: User) -> Self {
         Self {
+            status: AccountStatus::of(&user),
             id: user.id,
             email: user.email,
             username: user.username,
@@ -120,7 +281,9 @@ impl From<User> for UserResponse {
             avatar_url: user.avatar_url,
             global_role: user.global_role,
             is_email_verified: user.is_email_verified,
+            is_active: user.is_active,
             created_at: user.created_at,
+            deleted_at: user.deleted_at,
         }
     }
 }
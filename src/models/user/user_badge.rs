@@ -4,6 +4,7 @@ use uuid::Uuid;
 use validator::Validate;
 
 use crate::models::badge::BadgeResponse;
+use crate::models::common::fields::SparseFields;
 use crate::models::user::UserResponse;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +23,44 @@ pub struct AwardBadgeDto {
     pub badge_id: Uuid,
 }
 
+// With `idempotent`, awarding a badge the user already holds succeeds instead of
+// being rejected, for callers that only need it to be there
+#[derive(Debug, Deserialize, Default)]
+pub struct AwardBadgeQuery {
+    #[serde(default)]
+    pub idempotent: bool,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct BulkAwardBadgeDto {
+    pub badge_id: Uuid,
+
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "Between 1 and 500 user IDs are required"
+    ))]
+    pub user_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct BulkRevokeBadgeDto {
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "Between 1 and 500 user IDs are required"
+    ))]
+    pub user_ids: Vec<Uuid>,
+}
+
+// Outcome of removing a badge from all its holders. In a dry run `revoked` is how
+// many would have lost it and nothing is written.
+#[derive(Debug, Serialize)]
+pub struct RevokeAllBadgeResponse {
+    pub dry_run: bool,
+    pub revoked: usize,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserBadgeResponse {
     pub id: Uuid,
@@ -30,13 +69,41 @@ pub struct UserBadgeResponse {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
 pub struct UserWithBadgesResponse {
     pub user: UserResponse,
     pub badges: Vec<BadgeResponse>,
 }
 
-#[derive(Debug, Serialize)]
+// What anyone may see of a user, for public profile pages. Leaves out the email
+// address and account state that UserResponse carries.
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
+pub struct PublicUserResponse {
+    pub id: Uuid,
+    pub username: String,
+    pub full_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub badges: Vec<BadgeResponse>,
+}
+
+impl SparseFields for PublicUserResponse {
+    const FIELDS: &'static [&'static str] =
+        &["id", "username", "full_name", "avatar_url", "badges"];
+}
+
+impl From<UserWithBadgesResponse> for PublicUserResponse {
+    fn from(profile: UserWithBadgesResponse) -> Self {
+        Self {
+            id: profile.user.id,
+            username: profile.user.username,
+            full_name: profile.user.full_name,
+            avatar_url: profile.user.avatar_url,
+            badges: profile.badges,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, async_graphql::SimpleObject)]
 pub struct BadgeWithUsersResponse {
     pub badge: BadgeResponse,
     pub users: Vec<UserResponse>,
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::services::validation::validate_email;
+
+// An additional address on an account. The primary address stays on the user row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserEmail {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+    pub is_verified: bool,
+    pub verification_token_id: Option<Uuid>,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// Maximum number of secondary addresses per account
+pub const MAX_SECONDARY_EMAILS: i64 = 5;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AddUserEmailDto {
+    #[validate(custom = "validate_email")]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserEmailResponse {
+    pub id: Uuid,
+    pub email: String,
+    pub is_primary: bool,
+    pub is_verified: bool,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<UserEmail> for UserEmailResponse {
+    fn from(email: UserEmail) -> Self {
+        Self {
+            id: email.id,
+            email: email.email,
+            is_primary: false,
+            is_verified: email.is_verified,
+            verified_at: email.verified_at,
+            created_at: email.created_at,
+        }
+    }
+}
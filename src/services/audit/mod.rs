@@ -0,0 +1,4 @@
+mod export;
+pub mod service;
+
+pub use service::AuditService;
@@ -0,0 +1,246 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::db::repositories::Repositories;
+use crate::errors::AppError;
+use crate::middleware::client_context::ClientContext;
+use crate::middleware::request_id::RequestId;
+use crate::models::audit::{
+    AuthEventCount, AuthEventCountsResponse, AuthEventKind, CreateAuditLogDto,
+};
+use crate::models::event::{AccountEvent, AccountEventKind};
+use crate::services::events::EventBus;
+
+// Success/failure counters for a single kind of auth event
+#[derive(Default)]
+struct EventCounter {
+    success: AtomicU64,
+    failure: AtomicU64,
+}
+
+impl EventCounter {
+    fn count(&self) -> AuthEventCount {
+        AuthEventCount {
+            success: self.success.load(Ordering::Relaxed),
+            failure: self.failure.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub struct AuditService {
+    pub(super) repos: Arc<Repositories>,
+    events: Arc<EventBus>,
+    login: EventCounter,
+    oauth_login: EventCounter,
+    refresh: EventCounter,
+    logout: EventCounter,
+}
+
+impl AuditService {
+    pub fn new(repos: Arc<Repositories>, events: Arc<EventBus>) -> Self {
+        Self {
+            repos,
+            events,
+            login: EventCounter::default(),
+            oauth_login: EventCounter::default(),
+            refresh: EventCounter::default(),
+            logout: EventCounter::default(),
+        }
+    }
+
+    // Record the outcome of an auth flow (login, OAuth login, refresh, logout).
+    // This is the single place that bumps the counters, updates last-login,
+    // writes the audit row and logs the event, so every auth path is observable
+    // the same way. Failures here are logged and never fail the request.
+    pub async fn record_auth_event<T>(
+        &self,
+        kind: AuthEventKind,
+        user_id: Option<Uuid>,
+        outcome: &Result<T, AppError>,
+        request_id: &RequestId,
+        client: &ClientContext,
+        details: Option<serde_json::Value>,
+    ) {
+        let counter = self.counter(kind);
+        let (successes, failures) = match outcome {
+            Ok(_) => (
+                counter.success.fetch_add(1, Ordering::Relaxed) + 1,
+                counter.failure.load(Ordering::Relaxed),
+            ),
+            Err(_) => (
+                counter.success.load(Ordering::Relaxed),
+                counter.failure.fetch_add(1, Ordering::Relaxed) + 1,
+            ),
+        };
+
+        match outcome {
+            Ok(_) => tracing::info!(
+                request_id = %request_id,
+                event = kind.as_str(),
+                user_id = ?user_id,
+                successes,
+                failures,
+                "Auth event succeeded"
+            ),
+            Err(e) => tracing::warn!(
+                request_id = %request_id,
+                event = kind.as_str(),
+                user_id = ?user_id,
+                successes,
+                failures,
+                error = %e,
+                "Auth event failed"
+            ),
+        }
+
+        // Update last login timestamp and notify the user's other devices of successful logins
+        if let (Ok(_), Some(user_id)) = (outcome, user_id) {
+            if matches!(kind, AuthEventKind::Login | AuthEventKind::OAuthLogin) {
+                if let Err(e) = self.repos.user().update_last_login(user_id).await {
+                    tracing::error!(
+                        request_id = %request_id,
+                        "Failed to update last login timestamp: {}",
+                        e
+                    );
+                }
+
+                self.events.publish(AccountEvent::new(
+                    user_id,
+                    AccountEventKind::NewLogin {
+                        method: kind.as_str().to_string(),
+                    },
+                ));
+            }
+        }
+
+        // Attach the failure reason and the client's device to the audit details
+        let mut details = details.unwrap_or_else(|| json!({}));
+        if let Err(e) = outcome {
+            details["error"] = json!(e.to_string());
+        }
+        if let Some(device) = &client.device_info {
+            details["device"] = json!(device);
+        }
+        if let Some(country) = &client.country {
+            details["country"] = json!(country);
+        }
+
+        let dto = CreateAuditLogDto {
+            event_type: kind.as_str().to_string(),
+            user_id,
+            success: outcome.is_ok(),
+            request_id: request_id.as_str().map(|s| s.to_string()),
+            ip_address: Some(client.ip.to_string()),
+            user_agent: client.user_agent.clone(),
+            details: Some(details),
+            ..Default::default()
+        };
+
+        if let Err(e) = self.repos.audit().create(&dto).await {
+            tracing::error!(request_id = %request_id, "Failed to write audit log: {}", e);
+        }
+    }
+
+    // Record an action an admin performed on a user's account
+    pub async fn record_admin_action(
+        &self,
+        event_type: &str,
+        actor_id: Uuid,
+        user_id: Uuid,
+        request_id: &RequestId,
+        details: Option<serde_json::Value>,
+    ) {
+        tracing::info!(
+            request_id = %request_id,
+            event = event_type,
+            actor_id = %actor_id,
+            user_id = %user_id,
+            "Admin action performed"
+        );
+
+        let dto = CreateAuditLogDto {
+            event_type: event_type.to_string(),
+            user_id: Some(user_id),
+            actor_id: Some(actor_id),
+            success: true,
+            request_id: request_id.as_str().map(|s| s.to_string()),
+            details,
+            ..Default::default()
+        };
+
+        if let Err(e) = self.repos.audit().create(&dto).await {
+            tracing::error!(request_id = %request_id, "Failed to write audit log: {}", e);
+        }
+    }
+
+    // The counters record_auth_event keeps, for monitoring
+    pub fn auth_event_counts(&self) -> AuthEventCountsResponse {
+        AuthEventCountsResponse {
+            login: self.login.count(),
+            oauth_login: self.oauth_login.count(),
+            refresh: self.refresh.count(),
+            logout: self.logout.count(),
+        }
+    }
+
+    fn counter(&self, kind: AuthEventKind) -> &EventCounter {
+        match kind {
+            AuthEventKind::Login => &self.login,
+            AuthEventKind::OAuthLogin => &self.oauth_login,
+            AuthEventKind::Refresh => &self.refresh,
+            AuthEventKind::Logout => &self.logout,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_client, TestApp};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn auth_events_are_counted_per_kind(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("lena").await;
+        let client = test_client("192.0.2.1", "test");
+        let request_id = RequestId(None);
+
+        let failed: Result<(), AppError> = Err(AppError::Authentication("Invalid".into()));
+        for outcome in [Ok(()), failed] {
+            app.audit_service
+                .record_auth_event(
+                    AuthEventKind::Login,
+                    Some(user.id),
+                    &outcome,
+                    &request_id,
+                    &client,
+                    None,
+                )
+                .await;
+        }
+
+        let counts = app.audit_service.auth_event_counts();
+        assert_eq!((counts.login.success, counts.login.failure), (1, 1));
+        assert_eq!((counts.refresh.success, counts.refresh.failure), (0, 0));
+
+        // Failed logins name the account they were for, and only when there is one
+        assert_eq!(
+            app.auth_service.login_account_id("lena@example.com").await,
+            Some(user.id)
+        );
+        assert_eq!(
+            app.auth_service.login_account_id("lena").await,
+            Some(user.id)
+        );
+        assert_eq!(
+            app.auth_service
+                .login_account_id("nobody@example.com")
+                .await,
+            None
+        );
+    }
+}
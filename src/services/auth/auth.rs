@@ -1,42 +1,69 @@
+use chrono::{DateTime, Duration, Utc};
 use std::sync::Arc;
-use tokio::task;
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::db::error::DatabaseError;
+use crate::db::repositories::SessionRepository;
 use crate::db::repositories::TokenRepository;
 use crate::db::repositories::UserRepository;
 use crate::errors::AppError;
+use crate::middleware::client_context::ClientContext;
+use crate::models::auth::oauth::{
+    CreateOAuthProviderDto, OAuthConnectionResponse, OAuthProfileSyncResponse, OAuthProvider,
+    OAuthProviderResponse, UpdateOAuthProviderDto,
+};
+use crate::models::auth::session::CreateSessionDto;
 use crate::models::auth::token::{
-    CreateVerificationTokenDto, TOKEN_TYPE_EMAIL_VERIFICATION, TOKEN_TYPE_PASSWORD_RESET,
+    CreateVerificationTokenDto, PendingTokenResponse, RefreshTokenState,
+    VerificationTokenStatusResponse, PASSWORD_RESET_TOKEN_TTL, TOKEN_TYPE_EMAIL_VERIFICATION,
+    TOKEN_TYPE_PASSWORD_RESET, VERIFICATION_TOKEN_LENGTH,
+};
+use crate::models::common::response::PaginatedResponse;
+use crate::models::user::{
+    AuthResponse, LoginDto, User, UserPermissionsResponse, UserResponse, LOGIN_METHOD_OAUTH,
+    LOGIN_METHOD_PASSWORD,
 };
-use crate::models::user::{AuthResponse, LoginDto, UserResponse};
 use crate::services::auth::oauth::OAuthService;
-use crate::services::auth::token::TokenService;
+use crate::services::auth::token::{generate_secure_token, TokenService};
+use crate::services::email::EmailService;
 use crate::services::user::UserManagementService;
 use crate::services::validation::validation_err_to_app_error;
 
 pub struct AuthService {
     user_repo: UserRepository,
     token_repo: TokenRepository,
+    session_repo: SessionRepository,
     token_service: Arc<TokenService>,
     user_management: Arc<UserManagementService>,
     oauth_service: Option<Arc<OAuthService>>,
+    email_service: Option<Arc<EmailService>>,
+    password_reset_cooldown: Duration,
+    invalidate_reset_on_login: bool,
+    require_verified_email: bool,
+    login_verification_resend: Duration,
 }
 
 impl AuthService {
     pub fn new(
         user_repo: UserRepository,
         token_repo: TokenRepository,
+        session_repo: SessionRepository,
         token_service: Arc<TokenService>,
         user_management: Arc<UserManagementService>,
     ) -> Self {
         Self {
             user_repo,
             token_repo,
+            session_repo,
             token_service,
             user_management,
             oauth_service: None,
+            email_service: None,
+            password_reset_cooldown: Duration::zero(),
+            invalidate_reset_on_login: false,
+            require_verified_email: false,
+            login_verification_resend: Duration::zero(),
         }
     }
 
@@ -46,92 +73,564 @@ impl AuthService {
         self
     }
 
+    // Set email service, used to send emails on a user's behalf
+    pub fn with_email_service(mut self, email_service: Arc<EmailService>) -> Self {
+        self.email_service = Some(email_service);
+        self
+    }
+
+    // Set the minimum time between password reset emails requested for one user
+    pub fn with_password_reset_cooldown(mut self, cooldown_secs: u64) -> Self {
+        self.password_reset_cooldown = Duration::seconds(cooldown_secs as i64);
+        self
+    }
+
+    // Void the user's outstanding password reset links when they log in with their password
+    pub fn with_reset_invalidation_on_login(mut self, invalidate: bool) -> Self {
+        self.invalidate_reset_on_login = invalidate;
+        self
+    }
+
+    // Refuse password logins to accounts whose email isn't verified, resending the
+    // verification link at most once per resend_secs (0 never resends)
+    pub fn with_verified_email_login(mut self, required: bool, resend_secs: u64) -> Self {
+        self.require_verified_email = required;
+        self.login_verification_resend = Duration::seconds(resend_secs as i64);
+        self
+    }
+
     // Login with username/email and password
-    pub async fn login(&self, credentials: &LoginDto) -> Result<AuthResponse, AppError> {
+    pub async fn login(
+        &self,
+        credentials: &LoginDto,
+        client: &ClientContext,
+    ) -> Result<AuthResponse, AppError> {
         // Validate login data
         credentials
             .validate()
             .map_err(validation_err_to_app_error)?;
 
-        // Get user by email or username
-        let user = if credentials.email.contains('@') {
-            self.user_repo
-                .find_by_email(&credentials.email)
-                .await
-                .map_err(|e| match e {
-                    DatabaseError::NotFound => {
-                        AppError::Authentication("Invalid credentials".into())
-                    }
-                    _ => AppError::Database(e),
-                })?
-        } else {
-            self.user_repo
-                .find_by_username(&credentials.email)
-                .await
-                .map_err(|e| match e {
-                    DatabaseError::NotFound => {
-                        AppError::Authentication("Invalid credentials".into())
-                    }
-                    _ => AppError::Database(e),
-                })?
-        };
+        // Until the password has been checked, nothing may depend on whether the account
+        // exists: an unknown account, one without a password (OAuth only) and a wrong
+        // password all cost one password check and fail with the same error. What the
+        // account's state calls for (suspension, and a 2FA challenge once supported) is
+        // only revealed to someone who knows the password.
+        let user = self
+            .find_login_user(&credentials.email)
+            .await?
+            .filter(|user| !user.password_hash.is_empty());
 
         // Verify password
+        let Some(user) = user else {
+            self.user_management
+                .verify_dummy_password(&credentials.password);
+            return Err(invalid_credentials());
+        };
         self.user_management
-            .verify_password(&credentials.password, &user.password_hash)?;
+            .verify_password(&credentials.password, &user.password_hash)
+            .map_err(|e| match e {
+                AppError::Authentication(_) => invalid_credentials(),
+                e => e,
+            })?;
 
         // Check if user is active
         if !user.is_active {
-            return Err(AppError::Authentication(
-                "Account is disabled. Please contact support.".into(),
+            return Err(AppError::AccountDisabled(
+                "Account suspended. Please contact support.".into(),
             ));
         }
 
-        // Clone user for the response
-        let response_user = user.clone();
+        if self.require_verified_email && !user.is_email_verified {
+            let message = if self.resend_login_verification(&user).await {
+                "Your email address isn't verified yet. We've sent a new verification link to your inbox."
+            } else {
+                "Your email address isn't verified yet. Use the link in the verification email to verify it."
+            };
+            return Err(AppError::EmailNotVerified(message.into()));
+        }
 
-        // Generate tokens
-        let (token, refresh_token) = self.token_service.generate_tokens(&response_user)?;
+        // A second factor, when there is one, is asked for here: after the password and
+        // the account state, before any tokens are issued
 
-        // Update last login timestamp asynchronously
-        let user_repo = self.user_repo.clone();
-        let user_id = user.id;
-        tokio::spawn(async move {
-            if let Err(e) = user_repo.update_last_login(user_id).await {
-                tracing::error!("Failed to update last login timestamp: {}", e);
-            }
-        });
+        let (token, refresh_token) = self
+            .start_session(&user, credentials.client_id.as_deref(), client)
+            .await?;
 
-        // Create response
+        // They remembered the password, so a reset link they asked for is no longer needed
+        if self.invalidate_reset_on_login {
+            self.token_repo
+                .invalidate_by_user_and_type(user.id, TOKEN_TYPE_PASSWORD_RESET)
+                .await
+                .map_err(AppError::Database)?;
+        }
+
+        // Create response (last login is updated when the auth event is recorded)
         let auth_response = AuthResponse {
-            user: UserResponse::from(response_user),
+            user: self.user_management.user_response(user),
             token,
             refresh_token,
+            login_method: LOGIN_METHOD_PASSWORD,
+            provider: None,
+            is_new_user: None,
         };
 
         Ok(auth_response)
     }
 
-    // Logout user (can be extended for token blacklisting, etc.)
-    pub async fn logout(&self, user_id: Uuid) -> Result<(), AppError> {
-        // For simple implementation, just verify that the user exists
-        let _user = self
+    // Persist a session for a login and issue its tokens, which carry the session's ID.
+    // Ending the session (logging out, signing other devices out) ends them.
+    async fn start_session(
+        &self,
+        user: &User,
+        client_id: Option<&str>,
+        client: &ClientContext,
+    ) -> Result<(String, String), AppError> {
+        let session_id = Uuid::new_v4();
+        let (token, refresh_token) =
+            self.token_service
+                .generate_tokens(user, Some(session_id), client_id, Some(client))?;
+
+        let expiry = |token: &str| {
+            let claims = self.token_service.verify_token(token)?;
+            DateTime::from_timestamp(claims.exp, 0)
+                .ok_or_else(|| AppError::Internal("Token expiry out of range".into()))
+        };
+        let session = CreateSessionDto {
+            id: session_id,
+            user_id: user.id,
+            token: token.clone(),
+            refresh_token: Some(refresh_token.clone()),
+            expires_at: expiry(&token)?,
+            refresh_token_expires_at: Some(expiry(&refresh_token)?),
+            ip_address: Some(client.ip.to_string()),
+            user_agent: client.user_agent.clone(),
+            device_info: client
+                .device_info
+                .as_ref()
+                .and_then(|device| serde_json::to_value(device).ok()),
+        };
+        self.session_repo
+            .create(&session)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok((token, refresh_token))
+    }
+
+    // The account a login identifier (email or username) names, if any
+    async fn find_login_user(&self, identifier: &str) -> Result<Option<User>, AppError> {
+        let user = if identifier.contains('@') {
+            self.user_repo.find_by_email(identifier).await
+        } else {
+            self.user_repo.find_by_username(identifier).await
+        };
+        match user {
+            Ok(user) => Ok(Some(user)),
+            Err(DatabaseError::NotFound) => Ok(None),
+            Err(e) => Err(AppError::Database(e)),
+        }
+    }
+
+    // The ID of the account a failed login was for, so the attempt can be recorded
+    // against it. Never shown to the client.
+    pub async fn login_account_id(&self, identifier: &str) -> Option<Uuid> {
+        self.find_login_user(identifier)
+            .await
+            .ok()
+            .flatten()
+            .map(|user| user.id)
+    }
+
+    // Issue a new access token from a refresh token, as long as the account is still active
+    pub async fn refresh_token(
+        &self,
+        refresh_token: &str,
+        client: &ClientContext,
+    ) -> Result<(Uuid, String), AppError> {
+        let claims = self.token_service.verify_refresh_token(refresh_token)?;
+        let user_id = Uuid::parse_str(&claims.sub)
+            .map_err(|_| AppError::Authentication("Token contains invalid user ID".into()))?;
+
+        let user = self
             .user_repo
             .find_by_id(user_id)
             .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::Authentication("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        if !user.is_active {
+            return Err(AppError::AccountDisabled(
+                "Account suspended. Please contact support.".into(),
+            ));
+        }
+
+        if self.token_service.is_token_revoked(&claims, &user) {
+            return Err(AppError::Authentication("Session has been revoked".into()));
+        }
+        self.token_service.check_fingerprint(&claims, client)?;
+
+        // A refresh token whose session was ended (by logging out, for one) or has gone
+        // idle is done, as is one its session has since replaced.
+        let session = match self
+            .session_repo
+            .find_by_refresh_token_including_inactive(refresh_token)
+            .await
+        {
+            Ok(session) if !session.is_active => {
+                return Err(AppError::Authentication("Session has ended".into()))
+            }
+            Ok(session) => Some(session),
+            Err(DatabaseError::NotFound) if claims.session_id.is_some() => {
+                return Err(AppError::Authentication("Session has ended".into()))
+            }
+            Err(DatabaseError::NotFound) => None,
+            Err(e) => return Err(AppError::Database(e)),
+        };
+        if session
+            .as_ref()
+            .is_some_and(|session| self.token_service.is_session_idle(session))
+        {
+            return Err(AppError::Authentication(
+                "Session expired due to inactivity".into(),
+            ));
+        }
+
+        let new_token = self.token_service.refresh_token(refresh_token, &user)?;
+
+        // The session moves to the new access token, so it's checked against the session too
+        if let Some(session) = session {
+            let access_claims = self.token_service.verify_token(&new_token)?;
+            let access_expires_at = DateTime::from_timestamp(access_claims.exp, 0)
+                .ok_or_else(|| AppError::Authentication("Invalid token".into()))?;
+            self.session_repo
+                .refresh(
+                    session.id,
+                    &new_token,
+                    session.refresh_token.as_deref(),
+                    access_expires_at,
+                    session.refresh_token_expires_at,
+                )
+                .await
+                .map_err(AppError::Database)?;
+        }
+
+        Ok((user_id, new_token))
+    }
+
+    // Swap a refresh token for a new one after it's been used to get `access_token`,
+    // moving its session, if it has one, over to the new tokens. The old refresh token is
+    // refused from then on. Returns the new refresh token and when it expires, which is
+    // when the old one would have.
+    pub async fn rotate_refresh_token(
+        &self,
+        refresh_token: &str,
+        access_token: &str,
+    ) -> Result<(String, DateTime<Utc>), AppError> {
+        let (rotated, expires_at) = self.token_service.rotate_refresh_token(refresh_token)?;
+
+        match self.session_repo.find_by_refresh_token(refresh_token).await {
+            Ok(session) => {
+                let access_claims = self.token_service.verify_token(access_token)?;
+                let access_expires_at = DateTime::from_timestamp(access_claims.exp, 0)
+                    .ok_or_else(|| AppError::Authentication("Invalid token".into()))?;
+                self.session_repo
+                    .refresh(
+                        session.id,
+                        access_token,
+                        Some(&rotated),
+                        access_expires_at,
+                        Some(expires_at),
+                    )
+                    .await
+                    .map_err(AppError::Database)?;
+            }
+            Err(DatabaseError::NotFound) => {}
+            Err(e) => return Err(AppError::Database(e)),
+        }
+
+        // Also when it had no session, so a copy of it left behind (a stolen cookie) is
+        // useless. A token rotated twice was used twice, and the second use is refused.
+        let user_id = self.token_service.get_user_id_from_token(refresh_token)?;
+        self.session_repo
+            .end_refresh_token(user_id, refresh_token, expires_at)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::Duplicate(_) => AppError::Authentication("Session has ended".into()),
+                e => AppError::Database(e),
+            })?;
+
+        Ok((rotated, expires_at))
+    }
+
+    // Invalidate all sessions, every access and refresh token issued so far, and
+    // outstanding verification/reset tokens for a user. Tokens issued before stay
+    // rejected after the account is reactivated.
+    pub async fn revoke_all_sessions(&self, user_id: Uuid) -> Result<(), AppError> {
+        self.user_repo
+            .revoke_tokens(user_id)
+            .await
             .map_err(|e| match e {
                 DatabaseError::NotFound => AppError::NotFound("User not found".into()),
                 _ => AppError::Database(e),
             })?;
 
-        // Here you could add logic such as:
-        // - Adding refresh token to blacklist
-        // - Removing user session from database
-        // - Logging logout activity, etc.
+        self.session_repo
+            .deactivate_all_for_user(user_id)
+            .await
+            .map_err(AppError::Database)?;
+
+        for token_type in [TOKEN_TYPE_EMAIL_VERIFICATION, TOKEN_TYPE_PASSWORD_RESET] {
+            self.token_repo
+                .invalidate_by_user_and_type(user_id, token_type)
+                .await
+                .map_err(AppError::Database)?;
+        }
 
         Ok(())
     }
 
+    // Sign the user out everywhere: end their sessions and reject every access and
+    // refresh token issued so far. Returns how many open sessions were ended
+    pub async fn force_logout(&self, user_id: Uuid) -> Result<i64, AppError> {
+        self.user_repo
+            .revoke_tokens(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        self.session_repo
+            .deactivate_all_for_user(user_id)
+            .await
+            .map_err(AppError::Database)
+    }
+
+    // Number of sessions the user currently has open
+    pub async fn count_active_sessions(&self, user_id: Uuid) -> Result<i64, AppError> {
+        self.session_repo
+            .count_active_for_user(user_id)
+            .await
+            .map_err(AppError::Database)
+    }
+
+    // Sign the user out everywhere except `current_session`, the session of the request.
+    // Returns how many sessions were ended
+    pub async fn revoke_other_sessions(
+        &self,
+        user_id: Uuid,
+        current_session: Option<Uuid>,
+    ) -> Result<i64, AppError> {
+        match current_session {
+            Some(current_session) => {
+                self.session_repo
+                    .deactivate_all_for_user_except(user_id, current_session)
+                    .await
+            }
+            None => self.session_repo.deactivate_all_for_user(user_id).await,
+        }
+        .map_err(AppError::Database)
+    }
+
+    // Where a refresh token stands, checked the same way refresh_token checks it
+    pub async fn refresh_token_state(
+        &self,
+        refresh_token: &str,
+    ) -> Result<RefreshTokenState, AppError> {
+        let claims = match self.token_service.verify_refresh_token(refresh_token) {
+            Ok(claims) => claims,
+            Err(_) if self.token_service.is_token_expired(refresh_token) => {
+                return Ok(RefreshTokenState::Expired)
+            }
+            Err(_) => return Ok(RefreshTokenState::Invalid),
+        };
+
+        // Impersonation tokens can't be refreshed
+        let user_id = match Uuid::parse_str(&claims.sub) {
+            Ok(user_id) if claims.impersonator.is_none() => user_id,
+            _ => return Ok(RefreshTokenState::Invalid),
+        };
+
+        let user = match self.user_repo.find_by_id(user_id).await {
+            Ok(user) => user,
+            Err(DatabaseError::NotFound) => return Ok(RefreshTokenState::Revoked),
+            Err(e) => return Err(AppError::Database(e)),
+        };
+        if !user.is_active || self.token_service.is_token_revoked(&claims, &user) {
+            return Ok(RefreshTokenState::Revoked);
+        }
+
+        match self
+            .session_repo
+            .find_by_refresh_token_including_inactive(refresh_token)
+            .await
+        {
+            Ok(session) if !session.is_active => Ok(RefreshTokenState::Revoked),
+            Ok(_) => Ok(RefreshTokenState::Active),
+            // A login's refresh token that its session no longer holds was replaced
+            Err(DatabaseError::NotFound) if claims.session_id.is_some() => {
+                Ok(RefreshTokenState::Revoked)
+            }
+            Err(DatabaseError::NotFound) => Ok(RefreshTokenState::Active),
+            Err(e) => Err(AppError::Database(e)),
+        }
+    }
+
+    // End the session behind a refresh token. Returns the token's owner, when it can
+    // still be read from the token, and the state the token was in beforehand, so
+    // clients can tell a logout from one that had nothing left to do.
+    pub async fn logout(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(Option<Uuid>, RefreshTokenState), AppError> {
+        let state = self.refresh_token_state(refresh_token).await?;
+        match state {
+            RefreshTokenState::Invalid => {
+                return Err(AppError::Authentication("Invalid token".into()))
+            }
+            RefreshTokenState::Expired => return Ok((None, state)),
+            RefreshTokenState::Active | RefreshTokenState::Revoked => {}
+        }
+
+        let claims = self.token_service.verify_refresh_token(refresh_token)?;
+        let user_id = self.token_service.get_user_id_from_token(refresh_token)?;
+
+        if state == RefreshTokenState::Active {
+            match self.session_repo.find_by_refresh_token(refresh_token).await {
+                Ok(session) => {
+                    self.session_repo
+                        .deactivate(session.id)
+                        .await
+                        .map_err(AppError::Database)?;
+                }
+                // A token with no session is ended the way a rotated one is
+                Err(DatabaseError::NotFound) => {
+                    let expires_at = DateTime::from_timestamp(claims.exp, 0)
+                        .ok_or_else(|| AppError::Authentication("Invalid token".into()))?;
+                    match self
+                        .session_repo
+                        .end_refresh_token(user_id, refresh_token, expires_at)
+                        .await
+                    {
+                        // Ended by a concurrent logout or refresh
+                        Ok(()) | Err(DatabaseError::Duplicate(_)) => {}
+                        Err(e) => return Err(AppError::Database(e)),
+                    }
+                }
+                Err(e) => return Err(AppError::Database(e)),
+            }
+        }
+
+        Ok((Some(user_id), state))
+    }
+
+    // The user's emailed tokens and where each stands, for support looking into a link
+    // that doesn't work
+    pub async fn list_verification_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<VerificationTokenStatusResponse>, AppError> {
+        self.user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        let tokens = self
+            .token_repo
+            .find_by_user(user_id)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(tokens.into_iter().map(Into::into).collect())
+    }
+
+    // What the user can currently do, for clients deciding which UI to show
+    pub async fn get_permissions(
+        &self,
+        user_id: Uuid,
+        is_impersonated: bool,
+    ) -> Result<UserPermissionsResponse, AppError> {
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        let password_expired = self.token_service.is_password_expired(&user);
+
+        Ok(UserPermissionsResponse::for_user(
+            &user,
+            password_expired,
+            is_impersonated,
+        ))
+    }
+
+    // Check an email verification token without using it
+    pub async fn check_email_verification_token(
+        &self,
+        token: &str,
+    ) -> Result<PendingTokenResponse, AppError> {
+        self.check_token(
+            token,
+            TOKEN_TYPE_EMAIL_VERIFICATION,
+            "Invalid or expired verification token",
+        )
+        .await
+    }
+
+    // Check a password reset token without using it, so the reset page can tell the
+    // user their link is dead before they pick a new password
+    pub async fn check_password_reset_token(
+        &self,
+        token: &str,
+    ) -> Result<PendingTokenResponse, AppError> {
+        self.check_token(
+            token,
+            TOKEN_TYPE_PASSWORD_RESET,
+            "Invalid or expired reset token",
+        )
+        .await
+    }
+
+    async fn check_token(
+        &self,
+        token: &str,
+        token_type: &str,
+        invalid_message: &str,
+    ) -> Result<PendingTokenResponse, AppError> {
+        let verification_token = self
+            .token_repo
+            .verify_token(token, token_type)
+            .await
+            .map_err(|_| AppError::InvalidToken(invalid_message.into()))?;
+
+        let user_id = verification_token
+            .user_id
+            .ok_or_else(|| AppError::InvalidToken("Token is not associated with a user".into()))?;
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        Ok(PendingTokenResponse {
+            token_type: verification_token.token_type,
+            email: user.email,
+            expires_at: verification_token.expires_at,
+        })
+    }
+
     // Email verification
     pub async fn verify_email_token(&self, token: &str) -> Result<UserResponse, AppError> {
         // Verify the token
@@ -160,12 +659,12 @@ impl AuthService {
         if !user.is_email_verified {
             // Update the user's email verification status synchronously
             let updated_user = self
-                .user_repo
-                .update_email_verification(user_id, true)
+                .user_management
+                .verify_email(user_id)
                 .await
                 .map_err(|e| {
                     tracing::error!("Failed to update email verification status: {}", e);
-                    AppError::Database(e)
+                    e
                 })?;
 
             // Mark the token as used synchronously
@@ -178,48 +677,152 @@ impl AuthService {
                 })?;
 
             // Return updated user
-            return Ok(UserResponse::from(updated_user));
+            return Ok(updated_user);
         }
 
         // If already verified, just return the user
-        Ok(UserResponse::from(user))
+        Ok(self.user_management.user_response(user))
     }
 
-    // Password reset request
-    pub async fn request_password_reset(&self, email: &str) -> Result<String, AppError> {
-        // Find user by email
-        let user = self
-            .user_repo
-            .find_by_email(email)
+    // Password reset request. Returns the user, the address to email and the token, or
+    // None when there is nobody to email: the address is unknown, or a link went out
+    // within the cooldown. Callers must respond the same way either way, or the endpoint
+    // reveals accounts.
+    pub async fn request_password_reset(
+        &self,
+        email: &str,
+    ) -> Result<Option<(User, String, String)>, AppError> {
+        let user = match self.user_repo.find_by_email(email).await {
+            Ok(user) => user,
+            Err(DatabaseError::NotFound) => return Ok(None),
+            Err(e) => return Err(AppError::Database(e)),
+        };
+
+        if !self.password_reset_cooldown.is_zero() {
+            let last_sent = self
+                .token_repo
+                .latest_unused_created_at(user.id, TOKEN_TYPE_PASSWORD_RESET)
+                .await
+                .map_err(AppError::Database)?;
+            if last_sent.is_some_and(|sent| Utc::now() - sent < self.password_reset_cooldown) {
+                return Ok(None);
+            }
+        }
+
+        // The link goes to the address it was asked for with, which find_by_email matched
+        // exactly: the primary or a verified secondary one, so an account whose primary
+        // mailbox is lost can still be recovered
+        let token = self.create_password_reset_token(user.id).await?;
+        Ok(Some((user, email.to_string(), token)))
+    }
+
+    // Send a fresh verification link to a user whose login was refused, unless one went
+    // out within login_verification_resend. Failures are only logged: the login is
+    // refused either way.
+    async fn resend_login_verification(&self, user: &User) -> bool {
+        let Some(email_service) = self
+            .email_service
+            .as_ref()
+            .filter(|email_service| email_service.is_enabled())
+        else {
+            return false;
+        };
+        if self.login_verification_resend.is_zero() {
+            return false;
+        }
+
+        match self
+            .token_repo
+            .latest_unused_created_at(user.id, TOKEN_TYPE_EMAIL_VERIFICATION)
+            .await
+        {
+            Ok(Some(sent)) if Utc::now() - sent < self.login_verification_resend => return false,
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!(
+                    "Failed to look up verification emails for {}: {}",
+                    user.id,
+                    e
+                );
+                return false;
+            }
+        }
+
+        match email_service
+            .send_verification_email(user.id, &user.email, &user.username)
+            .await
+        {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!("Failed to send verification email to {}: {}", user.id, e);
+                false
+            }
+        }
+    }
+
+    // Send a user a fresh verification link on their behalf (admin support)
+    pub async fn resend_verification_for_user(&self, user_id: Uuid) -> Result<(), AppError> {
+        let email_service = self.email_service()?;
+        let user = self.find_user(user_id).await?;
+
+        if user.is_email_verified {
+            return Err(AppError::Validation(
+                "Email is already verified".to_string(),
+            ));
+        }
+
+        email_service
+            .send_verification_email(user.id, &user.email, &user.username)
+            .await
+    }
+
+    // Send a user a password reset link on their behalf (admin support)
+    pub async fn send_password_reset_for_user(&self, user_id: Uuid) -> Result<(), AppError> {
+        let email_service = self.email_service()?;
+        let user = self.find_user(user_id).await?;
+        let token = self.create_password_reset_token(user.id).await?;
+
+        email_service
+            .send_password_reset_email(&user.email, &user.username, &token)
+            .await
+    }
+
+    async fn find_user(&self, user_id: Uuid) -> Result<User, AppError> {
+        self.user_repo
+            .find_by_id(user_id)
             .await
             .map_err(|e| match e {
-                // Don't reveal if the email exists for security reasons
-                DatabaseError::NotFound => {
-                    AppError::NotFound("If the email exists, a reset link will be sent".into())
-                }
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
                 _ => AppError::Database(e),
-            })?;
+            })
+    }
+
+    fn email_service(&self) -> Result<&EmailService, AppError> {
+        self.email_service
+            .as_deref()
+            .ok_or_else(|| AppError::Configuration("Email service not configured".into()))
+    }
 
+    async fn create_password_reset_token(&self, user_id: Uuid) -> Result<String, AppError> {
         // Generate a random token
-        let token_string = self.generate_random_token(32)?;
+        let token_string = generate_secure_token(VERIFICATION_TOKEN_LENGTH);
 
         // Create a password reset token
         let token_dto = CreateVerificationTokenDto {
-            user_id: Some(user.id),
+            user_id: Some(user_id),
             token_type: TOKEN_TYPE_PASSWORD_RESET.to_string(),
-            expires_in: 24 * 60 * 60, // 24 hours in seconds
+            expires_in: PASSWORD_RESET_TOKEN_TTL,
         };
 
         // Create token in database
-        let token = self
-            .token_repo
+        self.token_repo
             .create(&token_dto, &token_string)
             .await
             .map_err(AppError::Database)?;
 
         // In a real application, you would send an email with the reset link
         // Return the token for demo purposes
-        Ok(token.token)
+        Ok(token_string)
     }
 
     // Reset password
@@ -240,26 +843,25 @@ impl AuthService {
             .user_id
             .ok_or_else(|| AppError::InvalidToken("Token is not associated with a user".into()))?;
 
-        // Hash the new password
-        let password_hash = self.user_management.hash_password(new_password)?;
-
-        // Update password and mark token as used asynchronously
-        let user_repo = self.user_repo.clone();
-        let token_repo = self.token_repo.clone();
-        let token_id = verification_token.id;
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
 
-        // Spawn task to handle database updates
-        task::spawn(async move {
-            // Update the user's password
-            if let Err(e) = user_repo.update_password(user_id, &password_hash).await {
-                tracing::error!("Failed to update password: {}", e);
-            }
+        // Update the password first so a reused password leaves the token usable for another try
+        self.user_management
+            .set_password(&user, new_password)
+            .await?;
 
-            // Mark the token as used
-            if let Err(e) = token_repo.mark_as_used(token_id).await {
-                tracing::error!("Failed to mark token as used: {}", e);
-            }
-        });
+        // Mark the token as used
+        self.token_repo
+            .mark_as_used(verification_token.id)
+            .await
+            .map_err(AppError::Database)?;
 
         Ok(())
     }
@@ -279,25 +881,979 @@ impl AuthService {
         &self,
         provider: &str,
         code: &str,
+        client: &ClientContext,
     ) -> Result<AuthResponse, AppError> {
+        let Some(oauth_service) = &self.oauth_service else {
+            return Err(AppError::Configuration(
+                "OAuth service not configured".into(),
+            ));
+        };
+        let (user, is_new_user) = oauth_service
+            .handle_oauth_callback(provider, code, client)
+            .await?;
+
+        let (token, refresh_token) = self.start_session(&user, None, client).await?;
+
+        Ok(AuthResponse {
+            user: self.user_management.user_response(user),
+            token,
+            refresh_token,
+            login_method: LOGIN_METHOD_OAUTH,
+            provider: Some(provider.to_lowercase()),
+            is_new_user: Some(is_new_user),
+        })
+    }
+
+    // Provider listing and management to use the new OAuthService
+    pub async fn list_active_oauth_providers(
+        &self,
+    ) -> Result<Vec<OAuthProviderResponse>, AppError> {
+        match &self.oauth_service {
+            Some(oauth_service) => oauth_service.list_active_providers().await,
+            None => Err(AppError::Configuration(
+                "OAuth service not configured".into(),
+            )),
+        }
+    }
+
+    pub async fn list_oauth_providers(
+        &self,
+        include_inactive: bool,
+    ) -> Result<Vec<OAuthProvider>, AppError> {
+        match &self.oauth_service {
+            Some(oauth_service) => oauth_service.list_providers(include_inactive).await,
+            None => Err(AppError::Configuration(
+                "OAuth service not configured".into(),
+            )),
+        }
+    }
+
+    pub async fn list_oauth_connections(
+        &self,
+        user_id: Option<Uuid>,
+        provider_name: Option<&str>,
+        page: i64,
+        limit: i64,
+    ) -> Result<PaginatedResponse<OAuthConnectionResponse>, AppError> {
+        match &self.oauth_service {
+            Some(oauth_service) => {
+                oauth_service
+                    .list_connections(user_id, provider_name, page, limit)
+                    .await
+            }
+            None => Err(AppError::Configuration(
+                "OAuth service not configured".into(),
+            )),
+        }
+    }
+
+    pub async fn refresh_oauth_connection_profile(
+        &self,
+        user_id: Uuid,
+        provider: &str,
+    ) -> Result<OAuthProfileSyncResponse, AppError> {
+        match &self.oauth_service {
+            Some(oauth_service) => {
+                oauth_service
+                    .refresh_connection_profile(user_id, provider)
+                    .await
+            }
+            None => Err(AppError::Configuration(
+                "OAuth service not configured".into(),
+            )),
+        }
+    }
+
+    pub async fn create_oauth_provider(
+        &self,
+        dto: CreateOAuthProviderDto,
+    ) -> Result<OAuthProvider, AppError> {
         match &self.oauth_service {
-            Some(oauth_service) => oauth_service.handle_oauth_callback(provider, code).await,
+            Some(oauth_service) => oauth_service.create_provider(dto).await,
             None => Err(AppError::Configuration(
                 "OAuth service not configured".into(),
             )),
         }
     }
 
-    // Helper to generate random token
-    fn generate_random_token(&self, length: usize) -> Result<String, AppError> {
-        use rand::{distributions::Alphanumeric, Rng};
+    pub async fn update_oauth_provider(
+        &self,
+        id: Uuid,
+        dto: UpdateOAuthProviderDto,
+    ) -> Result<OAuthProvider, AppError> {
+        match &self.oauth_service {
+            Some(oauth_service) => oauth_service.update_provider(id, dto).await,
+            None => Err(AppError::Configuration(
+                "OAuth service not configured".into(),
+            )),
+        }
+    }
+}
+
+// The one error login gives for any account, password or lack of either
+fn invalid_credentials() -> AppError {
+    AppError::Authentication("Invalid credentials".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::auth::token::VerificationTokenStatus;
+    use crate::models::auth::token::EMAIL_VERIFICATION_TOKEN_TTL;
+    use crate::test_support::{test_client, TestApp, TEST_PASSWORD};
+    use sqlx::PgPool;
+
+    fn client() -> ClientContext {
+        test_client("192.0.2.1", "test-agent")
+    }
+
+    // Log in with the password, starting a session like any other login
+    async fn log_in(app: &TestApp, user: &User) -> AuthResponse {
+        let credentials = LoginDto {
+            email: user.email.clone(),
+            password: TEST_PASSWORD.to_string(),
+            client_id: None,
+        };
+        app.auth_service
+            .login(&credentials, &client())
+            .await
+            .unwrap()
+    }
+
+    fn session_id(app: &TestApp, token: &str) -> Uuid {
+        let claims = app.token_service.verify_token(token).unwrap();
+        Uuid::parse_str(&claims.session_id.unwrap()).unwrap()
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn register_verify_login_refresh_logout(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("alice").await;
+        assert!(!user.is_email_verified);
+
+        // Verify the email with a token issued the same way the email service does
+        let token = generate_secure_token(VERIFICATION_TOKEN_LENGTH);
+        app.repos
+            .token()
+            .create(
+                &CreateVerificationTokenDto {
+                    user_id: Some(user.id),
+                    token_type: TOKEN_TYPE_EMAIL_VERIFICATION.to_string(),
+                    expires_in: EMAIL_VERIFICATION_TOKEN_TTL,
+                },
+                &token,
+            )
+            .await
+            .unwrap();
+        // Checking the link, as an email scanner would, doesn't use it up
+        for _ in 0..2 {
+            let pending = app
+                .auth_service
+                .check_email_verification_token(&token)
+                .await
+                .unwrap();
+            assert_eq!(pending.email, "alice@example.com");
+        }
+
+        let verified = app.auth_service.verify_email_token(&token).await.unwrap();
+        assert!(verified.is_email_verified);
+
+        // Verification tokens are single-use
+        assert!(app.auth_service.verify_email_token(&token).await.is_err());
+        assert!(app
+            .auth_service
+            .check_email_verification_token(&token)
+            .await
+            .is_err());
+
+        let credentials = LoginDto {
+            email: "alice@example.com".to_string(),
+            password: TEST_PASSWORD.to_string(),
+            client_id: None,
+        };
+        let auth = app
+            .auth_service
+            .login(&credentials, &client())
+            .await
+            .unwrap();
+        assert_eq!(auth.user.id, user.id);
+        assert_eq!(auth.login_method, LOGIN_METHOD_PASSWORD);
+        assert!(auth.provider.is_none() && auth.is_new_user.is_none());
+        assert_eq!(
+            app.token_service.verify_token(&auth.token).unwrap().sub,
+            user.id.to_string()
+        );
+
+        let (user_id, new_token) = app
+            .auth_service
+            .refresh_token(&auth.refresh_token, &client())
+            .await
+            .unwrap();
+        assert_eq!(user_id, user.id);
+        assert!(app.token_service.verify_token(&new_token).is_ok());
+
+        // Logging in started a session, which logging out ends
+        assert_eq!(
+            app.auth_service.logout(&auth.refresh_token).await.unwrap(),
+            (Some(user.id), RefreshTokenState::Active)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn force_logout_ends_sessions_and_rejects_issued_tokens(pool: PgPool) {
+        use crate::middleware::auth::authenticate_token;
+
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("heidi").await;
+        let auth = log_in(&app, &user).await;
+        let laptop = log_in(&app, &user).await;
+        let phone = log_in(&app, &user).await;
+        // Past the point it could be resumed, so not counted as ended
+        sqlx::query(
+            "UPDATE sessions SET refresh_token_expires_at = now() - interval '1 minute' WHERE id = $1",
+        )
+        .bind(session_id(&app, &phone.token))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            app.auth_service
+                .refresh_token_state(&auth.refresh_token)
+                .await
+                .unwrap(),
+            RefreshTokenState::Active
+        );
+
+        assert_eq!(app.auth_service.force_logout(user.id).await.unwrap(), 2);
+        assert_eq!(
+            app.auth_service
+                .count_active_sessions(user.id)
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert!(matches!(
+            authenticate_token(&app.repos, &app.token_service, &auth.token).await,
+            Err(AppError::Authentication(_))
+        ));
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&auth.refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&laptop.refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+        assert_eq!(
+            app.auth_service.logout(&auth.refresh_token).await.unwrap(),
+            (Some(user.id), RefreshTokenState::Revoked)
+        );
+        assert!(matches!(
+            app.auth_service.logout("not-a-token").await,
+            Err(AppError::Authentication(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn logout_reports_whether_the_session_was_still_active(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("ivy").await;
+        let refresh_token = log_in(&app, &user).await.refresh_token;
+
+        assert_eq!(
+            app.auth_service.logout(&refresh_token).await.unwrap().1,
+            RefreshTokenState::Active
+        );
+        // Logging out twice is visible to the client
+        assert_eq!(
+            app.auth_service.logout(&refresh_token).await.unwrap().1,
+            RefreshTokenState::Revoked
+        );
+        assert_eq!(
+            app.auth_service
+                .refresh_token_state(&refresh_token)
+                .await
+                .unwrap(),
+            RefreshTokenState::Revoked
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn logout_ends_refresh_tokens_without_a_session(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("jade").await;
+        let (_, refresh_token) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+
+        assert_eq!(
+            app.auth_service.logout(&refresh_token).await.unwrap(),
+            (Some(user.id), RefreshTokenState::Active)
+        );
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+        assert_eq!(
+            app.auth_service.logout(&refresh_token).await.unwrap(),
+            (Some(user.id), RefreshTokenState::Revoked)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn replaced_refresh_tokens_log_out_as_revoked(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("jana").await;
+        let auth = log_in(&app, &user).await;
+        let (_, access_token) = app
+            .auth_service
+            .refresh_token(&auth.refresh_token, &client())
+            .await
+            .unwrap();
+        let (rotated, _) = app
+            .auth_service
+            .rotate_refresh_token(&auth.refresh_token, &access_token)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            app.auth_service
+                .logout(&auth.refresh_token)
+                .await
+                .unwrap()
+                .1,
+            RefreshTokenState::Revoked
+        );
+        // The session carries on under the new token until that is logged out
+        assert_eq!(
+            app.auth_service.logout(&rotated).await.unwrap().1,
+            RefreshTokenState::Active
+        );
+        assert_eq!(
+            app.auth_service
+                .count_active_sessions(user.id)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn refresh_is_refused_after_logout(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("jill").await;
+        let refresh_token = log_in(&app, &user).await.refresh_token;
+
+        assert!(app
+            .auth_service
+            .refresh_token(&refresh_token, &client())
+            .await
+            .is_ok());
+        app.auth_service.logout(&refresh_token).await.unwrap();
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn access_and_refresh_tokens_are_not_interchangeable(pool: PgPool) {
+        use crate::middleware::auth::authenticate_token;
+
+        let app = TestApp::new(pool);
+        let user = app.create_user("jules").await;
+        let (token, refresh_token) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+
+        assert!(matches!(
+            app.auth_service.refresh_token(&token, &client()).await,
+            Err(AppError::Authentication(_))
+        ));
+        assert!(app.token_service.rotate_refresh_token(&token).is_err());
+        assert_eq!(
+            app.auth_service.refresh_token_state(&token).await.unwrap(),
+            RefreshTokenState::Invalid
+        );
+        assert!(matches!(
+            authenticate_token(&app.repos, &app.token_service, &refresh_token).await,
+            Err(AppError::Authentication(_))
+        ));
+
+        assert!(authenticate_token(&app.repos, &app.token_service, &token)
+            .await
+            .is_ok());
+        assert!(app
+            .auth_service
+            .refresh_token(&refresh_token, &client())
+            .await
+            .is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn refreshing_keeps_tokens_tied_to_their_session(pool: PgPool) {
+        use crate::middleware::auth::authenticate_token;
+
+        let mut config = crate::test_support::test_config();
+        config.session_idle_timeout_secs = 900;
+        let app = TestApp::with_config(pool.clone(), config);
+        let user = app.create_user("kurt").await;
+        let auth = log_in(&app, &user).await;
+        let refresh_token = auth.refresh_token;
+
+        // The new access token takes the session over
+        let (_, new_token) = app
+            .auth_service
+            .refresh_token(&refresh_token, &client())
+            .await
+            .unwrap();
+        assert_eq!(session_id(&app, &new_token), session_id(&app, &auth.token));
+        let session = app
+            .repos
+            .session()
+            .find_by_id(session_id(&app, &auth.token))
+            .await
+            .unwrap();
+        assert_eq!(session.token, new_token);
+
+        sqlx::query(
+            "UPDATE sessions SET last_activity_at = now() - interval '16 minutes' WHERE id = $1",
+        )
+        .bind(session.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        // Tokens from before the refresh belong to the same session
+        for token in [&new_token, &auth.token] {
+            assert!(matches!(
+                authenticate_token(&app.repos, &app.token_service, token).await,
+                Err(AppError::Authentication(_))
+            ));
+        }
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn revoking_other_sessions_signs_other_devices_out(pool: PgPool) {
+        use crate::middleware::auth::authenticate_token;
+
+        // Access tokens are only checked against their session with an idle timeout
+        let mut config = crate::test_support::test_config();
+        config.session_idle_timeout_secs = 900;
+        let app = TestApp::with_config(pool, config);
+        let user = app.create_user("lena").await;
+        let laptop = log_in(&app, &user).await;
+        let phone = log_in(&app, &user).await;
+
+        assert_eq!(
+            app.auth_service
+                .revoke_other_sessions(user.id, Some(session_id(&app, &laptop.token)))
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(
+            authenticate_token(&app.repos, &app.token_service, &laptop.token)
+                .await
+                .is_ok()
+        );
+        assert!(matches!(
+            authenticate_token(&app.repos, &app.token_service, &phone.token).await,
+            Err(AppError::Authentication(_))
+        ));
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&phone.refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn only_open_sessions_are_counted(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("ines").await;
+        let laptop = log_in(&app, &user).await;
+        let phone = log_in(&app, &user).await;
+        let tablet = log_in(&app, &user).await;
+        assert_eq!(
+            app.auth_service
+                .count_active_sessions(user.id)
+                .await
+                .unwrap(),
+            3
+        );
+
+        app.auth_service.logout(&phone.refresh_token).await.unwrap();
+        // A session can no longer be resumed once its refresh token has lapsed
+        sqlx::query(
+            "UPDATE sessions SET refresh_token_expires_at = now() - interval '1 minute' WHERE id = $1",
+        )
+        .bind(session_id(&app, &tablet.token))
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            app.auth_service
+                .count_active_sessions(user.id)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(app
+            .auth_service
+            .refresh_token(&laptop.refresh_token, &client())
+            .await
+            .is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn rotated_refresh_tokens_are_refused(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("mona").await;
+        let auth = log_in(&app, &user).await;
+        let refresh_token = auth.refresh_token;
+
+        let (_, access_token) = app
+            .auth_service
+            .refresh_token(&refresh_token, &client())
+            .await
+            .unwrap();
+        let (rotated, _) = app
+            .auth_service
+            .rotate_refresh_token(&refresh_token, &access_token)
+            .await
+            .unwrap();
+        assert_ne!(rotated, refresh_token);
+        let session = app
+            .repos
+            .session()
+            .find_by_id(session_id(&app, &auth.token))
+            .await
+            .unwrap();
+        assert_eq!(session.refresh_token.as_deref(), Some(rotated.as_str()));
+
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+        assert!(matches!(
+            app.auth_service
+                .rotate_refresh_token(&refresh_token, &access_token)
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+        assert!(app
+            .auth_service
+            .refresh_token(&rotated, &client())
+            .await
+            .is_ok());
+
+        // Refresh tokens without a session are refused after rotation as well
+        let other = app.create_user("nina").await;
+        let (_, sessionless) = app
+            .token_service
+            .generate_tokens(&other, None, None, None)
+            .unwrap();
+        let (_, access_token) = app
+            .auth_service
+            .refresh_token(&sessionless, &client())
+            .await
+            .unwrap();
+        app.auth_service
+            .rotate_refresh_token(&sessionless, &access_token)
+            .await
+            .unwrap();
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&sessionless, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn tokens_from_before_a_suspension_stay_revoked(pool: PgPool) {
+        use crate::middleware::auth::authenticate_token;
+
+        let app = TestApp::new(pool);
+        let user = app.create_user("olga").await;
+        let (token, refresh_token) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+
+        // What deactivating an account does
+        app.user_management
+            .set_user_active(user.id, false)
+            .await
+            .unwrap();
+        app.auth_service.revoke_all_sessions(user.id).await.unwrap();
+        assert!(matches!(
+            authenticate_token(&app.repos, &app.token_service, &token).await,
+            Err(AppError::AccountDisabled(_))
+        ));
+
+        app.user_management
+            .set_user_active(user.id, true)
+            .await
+            .unwrap();
+        assert!(matches!(
+            authenticate_token(&app.repos, &app.token_service, &token).await,
+            Err(AppError::Authentication(_))
+        ));
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&refresh_token, &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+
+        // Signing in again works; revocation is compared to the second
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let auth = app
+            .auth_service
+            .login(
+                &LoginDto {
+                    email: "olga@example.com".to_string(),
+                    password: TEST_PASSWORD.to_string(),
+                    client_id: None,
+                },
+                &client(),
+            )
+            .await
+            .unwrap();
+        assert!(
+            authenticate_token(&app.repos, &app.token_service, &auth.token)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn password_reset_link_is_only_used_by_the_reset(pool: PgPool) {
+        let app = TestApp::new(pool);
+        app.create_user("judy").await;
+        let (_, _, token) = app
+            .auth_service
+            .request_password_reset("judy@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let pending = app
+            .auth_service
+            .check_password_reset_token(&token)
+            .await
+            .unwrap();
+        assert_eq!(pending.token_type, TOKEN_TYPE_PASSWORD_RESET);
+
+        app.auth_service
+            .reset_password(&token, "Rotated1!")
+            .await
+            .unwrap();
+        assert!(matches!(
+            app.auth_service.check_password_reset_token(&token).await,
+            Err(AppError::InvalidToken(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn password_reset_emails_wait_for_the_cooldown(pool: PgPool) {
+        let mut config = crate::test_support::test_config();
+        config.password_reset_cooldown_secs = 300;
+        let app = TestApp::with_config(pool, config);
+        app.create_user("karl").await;
+        let auth = &app.auth_service;
+
+        assert!(auth
+            .request_password_reset("nobody@example.com")
+            .await
+            .unwrap()
+            .is_none());
+        let (_, _, token) = auth
+            .request_password_reset("karl@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(auth
+            .request_password_reset("karl@example.com")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Resetting to the current password is refused and leaves the link usable
+        assert!(matches!(
+            auth.reset_password(&token, TEST_PASSWORD).await,
+            Err(AppError::Validation(_))
+        ));
+
+        // Once the link is used, the next one doesn't have to wait
+        auth.reset_password(&token, "Rotated1!").await.unwrap();
+        let user = app
+            .repos
+            .user()
+            .find_by_email("karl@example.com")
+            .await
+            .unwrap();
+        let tokens = auth.list_verification_tokens(user.id).await.unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type, TOKEN_TYPE_PASSWORD_RESET);
+        assert_eq!(tokens[0].status, VerificationTokenStatus::Used);
+        assert!(serde_json::to_value(&tokens[0])
+            .unwrap()
+            .get("token")
+            .is_none());
+        assert!(auth
+            .request_password_reset("karl@example.com")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn emails_can_be_sent_on_a_users_behalf(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("mallory").await;
+
+        app.auth_service
+            .resend_verification_for_user(user.id)
+            .await
+            .unwrap();
+        app.auth_service
+            .send_password_reset_for_user(user.id)
+            .await
+            .unwrap();
+        let issued: Vec<String> = sqlx::query_scalar(
+            "SELECT type FROM verification_tokens WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user.id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            issued,
+            [TOKEN_TYPE_EMAIL_VERIFICATION, TOKEN_TYPE_PASSWORD_RESET]
+        );
+
+        app.repos
+            .user()
+            .update_email_verification(user.id, true)
+            .await
+            .unwrap();
+        assert!(matches!(
+            app.auth_service.resend_verification_for_user(user.id).await,
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            app.auth_service
+                .send_password_reset_for_user(Uuid::new_v4())
+                .await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn login_rejects_wrong_password_and_inactive_accounts(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("bob").await;
+
+        let wrong_password = LoginDto {
+            email: "bob@example.com".to_string(),
+            password: "Wr0ngPassword!".to_string(),
+            client_id: None,
+        };
+        assert!(matches!(
+            app.auth_service.login(&wrong_password, &client()).await,
+            Err(AppError::Authentication(_))
+        ));
+
+        app.user_management
+            .set_user_active(user.id, false)
+            .await
+            .unwrap();
+        let credentials = LoginDto {
+            email: "bob@example.com".to_string(),
+            password: TEST_PASSWORD.to_string(),
+            client_id: None,
+        };
+        assert!(matches!(
+            app.auth_service.login(&credentials, &client()).await,
+            Err(AppError::AccountDisabled(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn login_reveals_nothing_about_an_account_before_the_password(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("heidi").await;
+        app.create_user("ivan").await;
+        // An account that only signs in through OAuth
+        sqlx::query!("UPDATE users SET password_hash = '' WHERE username = 'ivan'",)
+            .execute(&pool)
+            .await
+            .unwrap();
+        app.user_management
+            .set_user_active(user.id, false)
+            .await
+            .unwrap();
+
+        let login = |email: &str, password: &str| LoginDto {
+            email: email.to_string(),
+            password: password.to_string(),
+            client_id: None,
+        };
+        let failures = [
+            login("nobody@example.com", TEST_PASSWORD),
+            login("ivan@example.com", TEST_PASSWORD),
+            // Suspended, but that is only for someone with the password to learn
+            login("heidi@example.com", "Wr0ngPassword!"),
+        ];
+        for credentials in &failures {
+            match app.auth_service.login(credentials, &client()).await {
+                Err(AppError::Authentication(message)) => {
+                    assert_eq!(message, "Invalid credentials")
+                }
+                other => panic!("{}: {:?}", credentials.email, other.map(|_| ())),
+            }
+        }
+
+        assert!(matches!(
+            app.auth_service
+                .login(&login("heidi@example.com", TEST_PASSWORD), &client())
+                .await,
+            Err(AppError::AccountDisabled(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn unverified_accounts_can_be_kept_from_logging_in(pool: PgPool) {
+        let mut config = crate::test_support::test_config();
+        config.require_verified_email_for_login = true;
+        let app = TestApp::with_config(pool, config);
+        let user = app.create_user("judy").await;
 
-        let token: String = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(length)
-            .map(char::from)
-            .collect();
+        let login = |password: &str| LoginDto {
+            email: "judy@example.com".to_string(),
+            password: password.to_string(),
+            client_id: None,
+        };
+        // Only someone with the password learns the account isn't verified
+        assert!(matches!(
+            app.auth_service
+                .login(&login("Wr0ngPassword!"), &client())
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+        let refused = app
+            .auth_service
+            .login(&login(TEST_PASSWORD), &client())
+            .await
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(refused.code().as_str(), "EMAIL_NOT_VERIFIED");
 
-        Ok(token)
+        app.user_management.verify_email(user.id).await.unwrap();
+        assert!(app
+            .auth_service
+            .login(&login(TEST_PASSWORD), &client())
+            .await
+            .is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn password_logins_can_void_outstanding_reset_links(pool: PgPool) {
+        for (username, invalidate) in [("judy", false), ("kate", true)] {
+            let mut config = crate::test_support::test_config();
+            config.invalidate_reset_on_login = invalidate;
+            let app = TestApp::with_config(pool.clone(), config);
+            let user = app.create_user(username).await;
+
+            let (_, _, token) = app
+                .auth_service
+                .request_password_reset(&user.email)
+                .await
+                .unwrap()
+                .unwrap();
+            let login = LoginDto {
+                email: user.email,
+                password: TEST_PASSWORD.to_string(),
+                client_id: None,
+            };
+            app.auth_service.login(&login, &client()).await.unwrap();
+
+            // Left alone unless the option is on
+            assert_eq!(
+                app.auth_service
+                    .check_password_reset_token(&token)
+                    .await
+                    .is_ok(),
+                !invalidate
+            );
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn permissions_follow_verification_role_and_impersonation(pool: PgPool) {
+        use crate::models::user::Permission;
+
+        let app = TestApp::new(pool);
+        let user = app.create_user("grace").await;
+
+        let permissions = app
+            .auth_service
+            .get_permissions(user.id, false)
+            .await
+            .unwrap();
+        assert!(permissions.capabilities.is_empty());
+        assert!(!permissions.is_email_verified);
+
+        app.user_management.verify_email(user.id).await.unwrap();
+        let permissions = app
+            .auth_service
+            .get_permissions(user.id, false)
+            .await
+            .unwrap();
+        assert!(permissions
+            .capabilities
+            .contains(&Permission::ProfileUpdate.as_str()));
+        assert!(permissions
+            .capabilities
+            .contains(&Permission::PasswordChange.as_str()));
+        assert!(!permissions
+            .capabilities
+            .contains(&Permission::UsersManage.as_str()));
+
+        // An impersonating admin can't change the user's password
+        let permissions = app
+            .auth_service
+            .get_permissions(user.id, true)
+            .await
+            .unwrap();
+        assert!(permissions
+            .capabilities
+            .contains(&Permission::ProfileUpdate.as_str()));
+        assert!(!permissions
+            .capabilities
+            .contains(&Permission::PasswordChange.as_str()));
     }
 }
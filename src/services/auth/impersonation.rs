@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::config::AvatarConfig;
+use crate::db::error::DatabaseError;
+use crate::db::repositories::Repositories;
+use crate::errors::AppError;
+use crate::models::auth::impersonation::{
+    ImpersonateUserDto, ImpersonationResponse, ImpersonationSession,
+};
+use crate::models::user::{Role, UserResponse};
+use crate::services::auth::token::Claims;
+use crate::services::auth::TokenService;
+use crate::services::validation::validation_err_to_app_error;
+
+// Lets support admins act as a user through a short-lived, revocable token
+pub struct ImpersonationService {
+    repos: Arc<Repositories>,
+    token_service: Arc<TokenService>,
+    token_expiration: i64, // in seconds
+    avatars: AvatarConfig,
+}
+
+impl ImpersonationService {
+    pub fn new(
+        repos: Arc<Repositories>,
+        token_service: Arc<TokenService>,
+        token_expiration: i64,
+    ) -> Self {
+        Self {
+            repos,
+            token_service,
+            token_expiration,
+            avatars: AvatarConfig::default(),
+        }
+    }
+
+    // Fall back to a default avatar for the impersonated user in responses
+    pub fn with_avatars(mut self, avatars: AvatarConfig) -> Self {
+        self.avatars = avatars;
+        self
+    }
+
+    // Start impersonating a user and issue a token for it
+    pub async fn start(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        dto: &ImpersonateUserDto,
+    ) -> Result<ImpersonationResponse, AppError> {
+        dto.validate().map_err(validation_err_to_app_error)?;
+
+        if admin_id == user_id {
+            return Err(AppError::Validation(
+                "You cannot impersonate yourself".into(),
+            ));
+        }
+
+        let user = self
+            .repos
+            .user()
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        // Acting as another admin would hand out admin rights under someone else's name
+        if user.role() == Role::Admin {
+            return Err(AppError::Authorization(
+                "Admin accounts cannot be impersonated".into(),
+            ));
+        }
+
+        if !user.is_active {
+            return Err(AppError::Validation(
+                "Inactive accounts cannot be impersonated".into(),
+            ));
+        }
+
+        let expires_at = Utc::now() + Duration::seconds(self.token_expiration);
+        let session = self
+            .repos
+            .impersonation()
+            .create(admin_id, user_id, &dto.reason, expires_at)
+            .await
+            .map_err(AppError::Database)?;
+
+        let token = self
+            .token_service
+            .generate_impersonation_token(&user, admin_id, session.id, expires_at)?;
+
+        Ok(ImpersonationResponse {
+            impersonation_id: session.id,
+            token,
+            expires_at,
+            user: UserResponse::from(user).with_default_avatar(&self.avatars),
+        })
+    }
+
+    // End the impersonation session behind an impersonation token
+    pub async fn end(&self, claims: &Claims) -> Result<ImpersonationSession, AppError> {
+        let impersonation_id = claims
+            .impersonation_id
+            .as_deref()
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or_else(|| AppError::Validation("Not an impersonation token".into()))?;
+
+        self.repos
+            .impersonation()
+            .end(impersonation_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => {
+                    AppError::NotFound("Impersonation session not found".into())
+                }
+                _ => AppError::Database(e),
+            })
+    }
+}
@@ -1,7 +1,10 @@
 pub mod auth;
+pub mod impersonation;
 pub mod oauth;
+pub mod retry;
 pub mod token;
 
 pub use auth::AuthService;
+pub use impersonation::ImpersonationService;
 pub use oauth::OAuthService;
 pub use token::TokenService;
@@ -1,137 +1,334 @@
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::Utc;
 use oauth2::{
-    basic::BasicClient, AuthUrl, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
-    TokenResponse, TokenUrl,
+    basic::BasicClient, AuthUrl, ClientId, ClientSecret, CsrfToken, RedirectUrl, RefreshToken,
+    RequestTokenError, Scope, TokenResponse, TokenUrl,
 };
-use reqwest::Client as HttpClient;
+use reqwest::{Client as HttpClient, StatusCode};
 use serde_json::Value;
+use sqlx::types::Json;
+use uuid::Uuid;
+use validator::Validate;
 
 use crate::config::AppConfig;
 use crate::db::error::DatabaseError;
 use crate::db::repositories::{OAuthRepository, UserRepository};
 use crate::errors::AppError;
-use crate::models::auth::oauth::OAuthProvider;
-use crate::models::user::{AuthResponse, CreateUserDto};
-use crate::services::auth::token::TokenService;
+use crate::middleware::client_context::ClientContext;
+use crate::models::auth::oauth::{
+    builtin_auth_params, CreateOAuthConnectionDto, CreateOAuthProviderDto, OAuthAuthParams,
+    OAuthConnectionResponse, OAuthFieldMap, OAuthProfileSyncResponse, OAuthProvider,
+    OAuthProviderResponse, UpdateOAuthProviderDto, UserOAuthConnection,
+};
+use crate::models::common::response::PaginatedResponse;
+use crate::models::user::{CreateUserDto, UpdateUserDto, User};
+use crate::services::auth::retry::RetryPolicy;
+use crate::services::auth::token::generate_secure_token;
 use crate::services::user::UserManagementService;
+use crate::services::validation::{check_email_domain_allowed, validation_err_to_app_error};
+use crate::utils::redact::redact;
 
 pub struct OAuthService {
     user_repo: UserRepository,
     oauth_repo: OAuthRepository,
-    token_service: Arc<TokenService>,
     user_management: Arc<UserManagementService>,
     config: AppConfig,
+    http_client: HttpClient,
+    retry_policy: RetryPolicy,
 }
 
 impl OAuthService {
     pub fn new(
         user_repo: UserRepository,
         oauth_repo: OAuthRepository,
-        token_service: Arc<TokenService>,
         user_management: Arc<UserManagementService>,
         config: AppConfig,
     ) -> Self {
+        let http_client = HttpClient::builder()
+            .timeout(Duration::from_secs(config.oauth.http_timeout))
+            .build()
+            .expect("Failed to build OAuth HTTP client");
+        let retry_policy = RetryPolicy::from_config(&config.oauth);
+
         Self {
             user_repo,
             oauth_repo,
-            token_service,
             user_management,
             config,
+            http_client,
+            retry_policy,
+        }
+    }
+
+    // Providers users can sign in with
+    pub async fn list_active_providers(&self) -> Result<Vec<OAuthProviderResponse>, AppError> {
+        let providers = self
+            .oauth_repo
+            .find_all_providers(true)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(providers
+            .into_iter()
+            .map(OAuthProviderResponse::from)
+            .collect())
+    }
+
+    // Full provider records for administration
+    pub async fn list_providers(
+        &self,
+        include_inactive: bool,
+    ) -> Result<Vec<OAuthProvider>, AppError> {
+        self.oauth_repo
+            .find_all_providers(!include_inactive)
+            .await
+            .map_err(AppError::Database)
+    }
+
+    // A page of linked logins, optionally for one user and/or one provider
+    pub async fn list_connections(
+        &self,
+        user_id: Option<Uuid>,
+        provider_name: Option<&str>,
+        page: i64,
+        limit: i64,
+    ) -> Result<PaginatedResponse<OAuthConnectionResponse>, AppError> {
+        let offset = (page - 1) * limit;
+        let connections = self
+            .oauth_repo
+            .find_connections(user_id, provider_name, limit, offset)
+            .await
+            .map_err(AppError::Database)?;
+        let total = self
+            .oauth_repo
+            .count_connections(user_id, provider_name)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(PaginatedResponse {
+            data: connections,
+            total,
+            page,
+            limit,
+            total_pages: (total as f64 / limit as f64).ceil() as i64,
+            max_limit: None,
+            links: None,
+        })
+    }
+
+    // Register a new OAuth provider (URLs and scope are checked here rather than at login)
+    pub async fn create_provider(
+        &self,
+        mut dto: CreateOAuthProviderDto,
+    ) -> Result<OAuthProvider, AppError> {
+        for url in [
+            &mut dto.auth_url,
+            &mut dto.token_url,
+            &mut dto.user_info_url,
+            &mut dto.redirect_url,
+        ] {
+            *url = url.trim().to_string();
+        }
+        dto.validate().map_err(validation_err_to_app_error)?;
+        dto.scope = normalize_scope(&dto.scope)?;
+        match &dto.field_map {
+            Some(field_map) => validate_field_map(field_map)?,
+            None if OAuthFieldMap::builtin(&dto.provider_name).is_none() => {
+                return Err(AppError::Validation(
+                    "field_map: Required for providers without a built-in mapping".into(),
+                ))
+            }
+            None => {}
+        }
+        if let Some(params) = &dto.extra_auth_params {
+            validate_auth_params(params)?;
+        }
+
+        self.oauth_repo
+            .create_provider(&dto)
+            .await
+            .map_err(AppError::Database)
+    }
+
+    // Update an OAuth provider from a merge patch
+    pub async fn update_provider(
+        &self,
+        id: Uuid,
+        mut dto: UpdateOAuthProviderDto,
+    ) -> Result<OAuthProvider, AppError> {
+        for url in [
+            &mut dto.auth_url,
+            &mut dto.token_url,
+            &mut dto.user_info_url,
+            &mut dto.redirect_url,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *url = url.trim().to_string();
+        }
+        dto.validate().map_err(validation_err_to_app_error)?;
+        if let Some(scope) = &dto.scope {
+            dto.scope = Some(normalize_scope(scope)?);
         }
+        match &dto.field_map {
+            Some(Some(field_map)) => validate_field_map(field_map)?,
+            // Only providers with a built-in mapping can go back to it
+            Some(None) => {
+                let provider =
+                    self.oauth_repo
+                        .find_provider_by_id(id)
+                        .await
+                        .map_err(|e| match e {
+                            DatabaseError::NotFound => {
+                                AppError::NotFound("OAuth provider not found".into())
+                            }
+                            _ => AppError::Database(e),
+                        })?;
+                if OAuthFieldMap::builtin(&provider.provider_name).is_none() {
+                    return Err(AppError::Validation(
+                        "field_map: Required for providers without a built-in mapping".into(),
+                    ));
+                }
+            }
+            None => {}
+        }
+        if let Some(Some(params)) = &dto.extra_auth_params {
+            validate_auth_params(params)?;
+        }
+
+        self.oauth_repo
+            .update_provider(id, &dto)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("OAuth provider not found".into()),
+                _ => AppError::Database(e),
+            })
     }
 
     // Get OAuth redirect URL
     pub async fn get_oauth_redirect_url(&self, provider: &str) -> Result<String, AppError> {
-        // Try to get provider from database
-        let provider_result = self.oauth_repo.find_provider_by_name(provider).await;
-
-        match provider_result {
-            Ok(provider_config) => {
+        match self.find_login_provider(provider).await? {
+            Some(provider_config) => {
                 // Create an OAuth client with the stored configuration
                 let client = self.create_oauth_client_from_config(&provider_config)?;
 
-                // Generate the authorization URL
-                let (auth_url, _csrf_token) = client
-                    .authorize_url(CsrfToken::new_random)
-                    .add_scope(Scope::new(provider_config.scope))
-                    .url();
+                let extra_params = match provider_config.extra_auth_params {
+                    Some(Json(params)) => params,
+                    None => builtin_auth_params(&provider_config.provider_name),
+                };
 
                 // In a real application, you'd store the CSRF token in a session or cookie
                 // For simplicity, we're not handling CSRF protection here
-
-                Ok(auth_url.to_string())
+                Ok(authorization_url(
+                    &client,
+                    vec![Scope::new(provider_config.scope)],
+                    extra_params,
+                ))
             }
-            Err(_) => {
+            None => {
                 // Fall back to hardcoded configuration
                 self.create_oauth_redirect_url_fallback(provider)
             }
         }
     }
 
-    // Handle OAuth callback
+    // Handle OAuth callback: find or create the account the provider vouches for.
+    // Returns it and whether it was just created; signing in is up to the caller.
     pub async fn handle_oauth_callback(
         &self,
         provider: &str,
         code: &str,
-    ) -> Result<AuthResponse, AppError> {
+        client: &ClientContext,
+    ) -> Result<(User, bool), AppError> {
         // Get provider from database or use fallback
-        let oauth_client = match self.oauth_repo.find_provider_by_name(provider).await {
-            Ok(provider_config) => self.create_oauth_client_from_config(&provider_config)?,
-            Err(_) => self.create_oauth_client_fallback(provider)?,
+        let provider_config = self.find_login_provider(provider).await?;
+        let oauth_client = match &provider_config {
+            Some(provider_config) => self.create_oauth_client_from_config(provider_config)?,
+            None => self.create_oauth_client_fallback(provider)?,
         };
 
         // Exchange the authorization code for an access token
         let token_result = oauth_client
             .exchange_code(oauth2::AuthorizationCode::new(code.to_string()))
-            .request_async(oauth2::reqwest::async_http_client)
+            .request_async(|request| self.retry_policy.oauth2_http_client(request))
             .await
-            .map_err(|e| AppError::Authentication(format!("Failed to exchange code: {}", e)))?;
+            .map_err(|e| match e {
+                RequestTokenError::Request(oauth2::reqwest::Error::Reqwest(e))
+                    if e.is_timeout() =>
+                {
+                    provider_request_error("exchange code", e)
+                }
+                e => AppError::Authentication(format!(
+                    "Failed to exchange code: {}",
+                    redact(&e.to_string())
+                )),
+            })?;
 
         // Get the access token
         let access_token = token_result.access_token().secret();
 
         // Fetch user info from the provider using the access token
-        let (provider_user_id, email, name, avatar) =
-            match self.oauth_repo.find_provider_by_name(provider).await {
-                Ok(provider_config) => {
-                    self.get_oauth_user_info_from_config(&provider_config, access_token)
-                        .await?
-                }
-                Err(_) => {
-                    self.get_oauth_user_info_fallback(provider, access_token)
-                        .await?
-                }
+        let ProviderUserInfo {
+            provider_user_id,
+            email,
+            name,
+            avatar,
+            email_verified,
+        } = match &provider_config {
+            Some(provider_config) => {
+                self.get_oauth_user_info_from_config(provider_config, access_token)
+                    .await?
+            }
+            None => {
+                self.get_oauth_user_info_fallback(provider, access_token)
+                    .await?
+            }
+        };
+
+        // Accounts are matched and created by email, so an address the provider hasn't
+        // verified would let anyone sign in as its owner
+        if !email_verified {
+            let display_name = match &provider_config {
+                Some(provider_config) => provider_config.display_name.as_str(),
+                None => fallback_display_name(provider),
             };
+            return Err(AppError::OAuthEmailUnverified(format!(
+                "Your {} email address ({}) is not verified. Verify it with {}, or sign in another way, and try again.",
+                display_name, email, display_name
+            )));
+        }
 
         // Check if user exists with this email
-        let user = match self.user_repo.find_by_email(&email).await {
-            Ok(user) => {
-                // User exists, update their last login
-                self.user_repo
-                    .update_last_login(user.id)
-                    .await
-                    .map_err(AppError::from)?
-            }
+        let (user, is_new_user) = match self.user_repo.find_by_email(&email).await {
+            // User exists (last login is updated when the auth event is recorded)
+            Ok(user) => (user, false),
             Err(DatabaseError::NotFound) => {
+                // Signing in with a provider must not get around the registration mode
+                self.config.registration_mode.check(false)?;
+                check_email_domain_allowed(&email, &self.config.allowed_email_domains)?;
+
                 // Create a new user
                 let mut create_user_dto = CreateUserDto {
                     email: email.clone(),
                     username: email.split('@').next().unwrap_or("user").to_string(),
-                    password: self.generate_random_token(32)?, // Random password
+                    password: generate_secure_token(32), // Random password
                     full_name: Some(name.clone()),
                     avatar_url: avatar.clone(),
+                    invite_token: None,
                 };
 
                 // Ensure username is unique by adding random characters if needed
                 let username_base = create_user_dto.username.clone();
                 let mut attempt = 0;
 
-                while self
+                while !self
                     .user_repo
-                    .find_by_username(&create_user_dto.username)
+                    .is_username_available(&create_user_dto.username, None)
                     .await
-                    .is_ok()
+                    .map_err(AppError::Database)?
                 {
                     attempt += 1;
                     create_user_dto.username = format!("{}_{}", username_base.clone(), attempt);
@@ -159,13 +356,13 @@ impl OAuthService {
                     .await
                     .map_err(AppError::Database)?;
 
-                user
+                (user, true)
             }
             Err(e) => return Err(AppError::Database(e)),
         };
 
         // Store the OAuth connection if provider was found in database
-        if let Ok(provider_config) = self.oauth_repo.find_provider_by_name(provider).await {
+        if let Some(provider_config) = &provider_config {
             let refresh_token = token_result.refresh_token().map(|rt| rt.secret().clone());
             let expires_in = token_result.expires_in().map(|d| {
                 let now = chrono::Utc::now();
@@ -175,32 +372,195 @@ impl OAuthService {
 
             // Store or update the OAuth connection
             self.oauth_repo
-                .upsert_connection(
-                    user.id,
-                    provider_config.id,
-                    &provider_user_id,
-                    Some(&email),
-                    Some(&name),
-                    avatar.as_deref(),
-                    Some(access_token),
-                    refresh_token.as_deref(),
-                    expires_in,
-                    None, // We could store the raw user info here
-                )
+                .upsert_connection(&CreateOAuthConnectionDto {
+                    user_id: user.id,
+                    provider_id: provider_config.id,
+                    provider_user_id,
+                    email: Some(email),
+                    name: Some(name),
+                    avatar_url: avatar,
+                    access_token: Some(access_token.to_string()),
+                    refresh_token,
+                    expires_at: expires_in,
+                    raw_user_info: None, // We could store the raw user info here
+                    user_agent: client.user_agent.clone(),
+                    device_info: client
+                        .device_info
+                        .as_ref()
+                        .and_then(|device| serde_json::to_value(device).ok()),
+                })
                 .await
                 .map_err(AppError::Database)?;
         }
 
-        // Generate JWT tokens
-        let token_pair = self.token_service.generate_tokens(&user)?;
+        Ok((user, is_new_user))
+    }
+
+    // Re-fetch the user's profile from a linked provider and keep it on the connection.
+    // The user's own name and avatar only follow while they still match what the
+    // provider last gave us, so anything the user set themselves is kept.
+    pub async fn refresh_connection_profile(
+        &self,
+        user_id: Uuid,
+        provider: &str,
+    ) -> Result<OAuthProfileSyncResponse, AppError> {
+        let not_linked = || AppError::NotFound(format!("No {} account is linked", provider));
+        // Connections are only stored for providers in the database
+        let provider_config = self
+            .find_login_provider(provider)
+            .await?
+            .ok_or_else(not_linked)?;
+        let connection = self
+            .oauth_repo
+            .find_connection_by_user_and_provider(user_id, provider_config.id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => not_linked(),
+                _ => AppError::Database(e),
+            })?;
+
+        let access_token = self
+            .connection_access_token(&provider_config, &connection)
+            .await?;
+        let info = self
+            .get_oauth_user_info_from_config(&provider_config, &access_token)
+            .await?;
+        if info.provider_user_id != connection.provider_user_id {
+            return Err(reauth_required(&provider_config));
+        }
 
-        let auth_response = AuthResponse {
-            user: user.into(),
-            token: token_pair.0,
-            refresh_token: token_pair.1,
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+        let full_name = synced_value(
+            user.full_name.as_deref(),
+            connection.name.as_deref(),
+            Some(&info.name),
+        );
+        let avatar_url = synced_value(
+            user.avatar_url.as_deref(),
+            connection.avatar_url.as_deref(),
+            info.avatar.as_deref(),
+        );
+        let updated_fields = [("full_name", &full_name), ("avatar_url", &avatar_url)]
+            .into_iter()
+            .filter(|(_, value)| value.is_some())
+            .map(|(field, _)| field)
+            .collect::<Vec<_>>();
+        let user = if updated_fields.is_empty() {
+            user
+        } else {
+            let dto = UpdateUserDto {
+                full_name: full_name.map(Some),
+                avatar_url: avatar_url.map(Some),
+                ..Default::default()
+            };
+            self.user_repo
+                .update(user_id, &dto)
+                .await
+                .map_err(AppError::Database)?
         };
 
-        Ok(auth_response)
+        let connection = self
+            .oauth_repo
+            .update_connection_profile(
+                connection.id,
+                Some(&info.email),
+                Some(&info.name),
+                info.avatar.as_deref(),
+            )
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(OAuthProfileSyncResponse {
+            user: self.user_management.user_response(user),
+            connection: OAuthConnectionResponse {
+                id: connection.id,
+                user_id: connection.user_id,
+                provider_id: provider_config.id,
+                provider_name: provider_config.provider_name,
+                provider_display_name: provider_config.display_name,
+                provider_user_id: connection.provider_user_id,
+                email: connection.email,
+                name: connection.name,
+                avatar_url: connection.avatar_url,
+                device_info: connection.device_info,
+                last_used_at: connection.last_used_at,
+                created_at: connection.created_at,
+                updated_at: connection.updated_at,
+            },
+            updated_fields,
+        })
+    }
+
+    // An access token for the connection: the stored one until it expires, then a new
+    // one from the refresh token, which is stored right away. Without either, the user
+    // has to sign in again.
+    async fn connection_access_token(
+        &self,
+        provider: &OAuthProvider,
+        connection: &UserOAuthConnection,
+    ) -> Result<String, AppError> {
+        let expired = connection
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now());
+        if let (Some(access_token), false) = (&connection.access_token, expired) {
+            return Ok(access_token.clone());
+        }
+        let refresh_token = connection
+            .refresh_token
+            .as_ref()
+            .ok_or_else(|| reauth_required(provider))?;
+
+        let token_result = self
+            .create_oauth_client_from_config(provider)?
+            .exchange_refresh_token(&RefreshToken::new(refresh_token.clone()))
+            .request_async(|request| self.retry_policy.oauth2_http_client(request))
+            .await
+            .map_err(|e| match e {
+                RequestTokenError::Request(oauth2::reqwest::Error::Reqwest(e))
+                    if e.is_timeout() =>
+                {
+                    provider_request_error("refresh the access token", e)
+                }
+                // The refresh token was revoked or has expired
+                RequestTokenError::ServerResponse(_) => reauth_required(provider),
+                e => AppError::Unexpected(format!(
+                    "Failed to refresh the access token: {}",
+                    redact(&e.to_string())
+                )),
+            })?;
+
+        let access_token = token_result.access_token().secret().clone();
+        self.oauth_repo
+            .update_connection_tokens(
+                connection.id,
+                &access_token,
+                token_result.refresh_token().map(|rt| rt.secret().as_str()),
+                token_result.expires_in().map(|d| Utc::now() + d),
+            )
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(access_token)
+    }
+
+    // Look up a stored provider for login. None means it isn't in the database and the
+    // hardcoded configuration applies; a stored but disabled provider never falls back.
+    async fn find_login_provider(&self, provider: &str) -> Result<Option<OAuthProvider>, AppError> {
+        match self.oauth_repo.find_provider_by_name(provider).await {
+            Ok(provider_config) if !provider_config.is_active => Err(AppError::Validation(
+                "Provider is currently disabled".into(),
+            )),
+            Ok(provider_config) => Ok(Some(provider_config)),
+            Err(DatabaseError::NotFound) => Ok(None),
+            Err(e) => Err(AppError::Database(e)),
+        }
     }
 
     // Helper function to create OAuth client from database configuration
@@ -267,14 +627,14 @@ impl OAuthService {
     fn create_oauth_redirect_url_fallback(&self, provider: &str) -> Result<String, AppError> {
         let client = self.create_oauth_client_fallback(provider)?;
 
-        // Generate the authorization URL
-        let (auth_url, _csrf_token) = client
-            .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new("email".to_string()))
-            .add_scope(Scope::new("profile".to_string()))
-            .url();
-
-        Ok(auth_url.to_string())
+        Ok(authorization_url(
+            &client,
+            vec![
+                Scope::new("email".to_string()),
+                Scope::new("profile".to_string()),
+            ],
+            builtin_auth_params(provider),
+        ))
     }
 
     // Get user info from OAuth provider
@@ -282,84 +642,48 @@ impl OAuthService {
         &self,
         provider: &OAuthProvider,
         access_token: &str,
-    ) -> Result<(String, String, String, Option<String>), AppError> {
-        let client = HttpClient::new();
-
+    ) -> Result<ProviderUserInfo, AppError> {
         // Make the request to the user info endpoint
-        let response = client
-            .get(&provider.user_info_url)
-            .header("Authorization", format!("Bearer {}", access_token))
-            .header("Accept", "application/json")
-            .header("User-Agent", "Safatanc-Connect")
-            .send()
+        let response = self
+            .retry_policy
+            .send(|| {
+                self.http_client
+                    .get(&provider.user_info_url)
+                    .header("Authorization", format!("Bearer {}", access_token))
+                    .header("Accept", "application/json")
+                    .header("User-Agent", "Safatanc-Connect")
+            })
             .await
-            .map_err(|e| AppError::Unexpected(format!("Failed to fetch user info: {}", e)))?;
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| match e.status() {
+                // The token has expired or been revoked
+                Some(StatusCode::UNAUTHORIZED) => reauth_required(provider),
+                _ => provider_request_error("fetch user info", e),
+            })?;
 
         // Parse the response
         let user_info: Value = response
             .json()
             .await
-            .map_err(|e| AppError::Unexpected(format!("Failed to parse user info: {}", e)))?;
-
-        // Extract info based on provider
-        match provider.provider_name.to_lowercase().as_str() {
-            "google" => {
-                let provider_user_id = user_info["id"]
-                    .as_str()
-                    .ok_or_else(|| {
-                        AppError::Authentication("User ID not provided by OAuth provider".into())
-                    })?
-                    .to_string();
-
-                let email = user_info["email"]
-                    .as_str()
-                    .ok_or_else(|| {
-                        AppError::Authentication("Email not provided by OAuth provider".into())
-                    })?
-                    .to_string();
-
-                let name = user_info["name"]
-                    .as_str()
-                    .unwrap_or("Google User")
-                    .to_string();
-
-                let avatar = user_info["picture"].as_str().map(|s| s.to_string());
-
-                Ok((provider_user_id, email, name, avatar))
-            }
-            "github" => {
-                let provider_user_id = match user_info["id"].as_str() {
-                    Some(id) => id.to_string(),
-                    None => user_info["id"].to_string().trim_matches('"').to_string(),
-                };
-
-                let email = match user_info["email"].as_str() {
-                    Some(email) => email.to_string(),
-                    None => {
-                        // GitHub might not provide email in the initial response
-                        // In a real app, you'd make a separate request to fetch emails
-                        // For simplicity, we'll generate a placeholder email
-                        let username = user_info["login"].as_str().ok_or_else(|| {
-                            AppError::Authentication("Username not provided by GitHub".into())
-                        })?;
-                        format!("{}@github.user", username)
-                    }
-                };
-
-                let name = user_info["name"]
-                    .as_str()
-                    .unwrap_or_else(|| user_info["login"].as_str().unwrap_or("GitHub User"))
-                    .to_string();
+            .map_err(|e| provider_request_error("parse user info", e))?;
 
-                let avatar = user_info["avatar_url"].as_str().map(|s| s.to_string());
+        // A stored mapping wins over the built-in one for the same provider name
+        let field_map = match &provider.field_map {
+            Some(field_map) => field_map.0.clone(),
+            None => OAuthFieldMap::builtin(&provider.provider_name).ok_or_else(|| {
+                AppError::Validation(format!(
+                    "No user info field mapping configured for OAuth provider: {}",
+                    provider.provider_name
+                ))
+            })?,
+        };
 
-                Ok((provider_user_id, email, name, avatar))
-            }
-            _ => Err(AppError::Validation(format!(
-                "Unsupported OAuth provider: {}",
-                provider.provider_name
-            ))),
-        }
+        extract_user_info(
+            &user_info,
+            &field_map,
+            &provider.provider_name,
+            &provider.display_name,
+        )
     }
 
     // Fallback method for hardcoded providers
@@ -367,8 +691,7 @@ impl OAuthService {
         &self,
         provider: &str,
         access_token: &str,
-    ) -> Result<(String, String, String, Option<String>), AppError> {
-        let client = HttpClient::new();
+    ) -> Result<ProviderUserInfo, AppError> {
         let url = match provider.to_lowercase().as_str() {
             "google" => &self.config.oauth.google_user_info_url,
             "github" => &self.config.oauth.github_user_info_url,
@@ -380,96 +703,447 @@ impl OAuthService {
             }
         };
 
-        let mut req = client
-            .get(url)
-            .header("Authorization", format!("Bearer {}", access_token));
+        let build_request = || {
+            let req = self
+                .http_client
+                .get(url)
+                .header("Authorization", format!("Bearer {}", access_token));
 
-        // Add special headers for GitHub
-        if provider.to_lowercase() == "github" {
-            req = req
-                .header("Accept", "application/json")
-                .header("User-Agent", "Safatanc-Connect");
-        }
+            // Add special headers for GitHub
+            if provider.to_lowercase() == "github" {
+                req.header("Accept", "application/json")
+                    .header("User-Agent", "Safatanc-Connect")
+            } else {
+                req
+            }
+        };
 
         // Make the request
-        let response = req
-            .send()
+        let response = self
+            .retry_policy
+            .send(build_request)
             .await
-            .map_err(|e| AppError::Unexpected(format!("Failed to fetch user info: {}", e)))?;
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| provider_request_error("fetch user info", e))?;
 
         // Parse the response
         let user_info: Value = response
             .json()
             .await
-            .map_err(|e| AppError::Unexpected(format!("Failed to parse user info: {}", e)))?;
+            .map_err(|e| provider_request_error("parse user info", e))?;
 
-        match provider.to_lowercase().as_str() {
-            "google" => {
-                let provider_user_id = user_info["id"]
-                    .as_str()
-                    .ok_or_else(|| {
-                        AppError::Authentication("User ID not provided by OAuth provider".into())
-                    })?
-                    .to_string();
-
-                let email = user_info["email"]
-                    .as_str()
-                    .ok_or_else(|| {
-                        AppError::Authentication("Email not provided by OAuth provider".into())
-                    })?
-                    .to_string();
-
-                let name = user_info["name"]
-                    .as_str()
-                    .unwrap_or("Google User")
-                    .to_string();
-
-                let avatar = user_info["picture"].as_str().map(|s| s.to_string());
-
-                Ok((provider_user_id, email, name, avatar))
+        let field_map = OAuthFieldMap::builtin(provider).ok_or_else(|| {
+            AppError::Validation(format!("Unsupported OAuth provider: {}", provider))
+        })?;
+
+        extract_user_info(
+            &user_info,
+            &field_map,
+            provider,
+            fallback_display_name(provider),
+        )
+    }
+}
+
+// The provider's authorization URL with the given scopes and extra parameters
+fn authorization_url(client: &BasicClient, scopes: Vec<Scope>, params: OAuthAuthParams) -> String {
+    let mut request = client
+        .authorize_url(CsrfToken::new_random)
+        .add_scopes(scopes);
+    for (key, value) in params {
+        request = request.add_extra_param(key, value);
+    }
+
+    let (auth_url, _csrf_token) = request.url();
+    auth_url.to_string()
+}
+
+// What we need from a provider's user info response
+struct ProviderUserInfo {
+    provider_user_id: String,
+    email: String,
+    name: String,
+    avatar: Option<String>,
+    // False only when the provider says the email is unverified
+    email_verified: bool,
+}
+
+// A failed call to a provider. Timeouts are reported as such, so clients know the
+// provider was slow rather than that we failed, and can retry. The URL is left out, as
+// it can carry the code or tokens we were sending.
+fn provider_request_error(action: &str, e: reqwest::Error) -> AppError {
+    let e = e.without_url();
+    if e.is_timeout() {
+        AppError::Timeout(format!(
+            "The OAuth provider timed out: failed to {}",
+            action
+        ))
+    } else {
+        AppError::Unexpected(format!("Failed to {}: {}", action, e))
+    }
+}
+
+// The provider won't accept the tokens we hold for the user any more
+fn reauth_required(provider: &OAuthProvider) -> AppError {
+    AppError::OAuthReauthRequired(format!(
+        "Your {} sign-in has expired. Sign in with {} again to reconnect it.",
+        provider.display_name, provider.display_name
+    ))
+}
+
+// The value a user's profile field takes from the provider, if it should change: only
+// while it is unset or still what the provider last gave us
+fn synced_value(
+    current: Option<&str>,
+    last_synced: Option<&str>,
+    fresh: Option<&str>,
+) -> Option<String> {
+    let fresh = fresh.filter(|fresh| !fresh.is_empty() && Some(*fresh) != current)?;
+    (current.is_none() || current == last_synced).then(|| fresh.to_string())
+}
+
+fn fallback_display_name(provider: &str) -> &'static str {
+    match provider.to_lowercase().as_str() {
+        "github" => "GitHub",
+        _ => "Google",
+    }
+}
+
+// Pull the user's id, email, name, avatar and email verification out of a user info response
+fn extract_user_info(
+    user_info: &Value,
+    field_map: &OAuthFieldMap,
+    provider_name: &str,
+    display_name: &str,
+) -> Result<ProviderUserInfo, AppError> {
+    let lookup = |path: &Option<String>| {
+        path.as_deref()
+            .and_then(|path| lookup_field(user_info, path))
+    };
+
+    // Some providers (e.g. GitHub) send numeric IDs
+    let provider_user_id = lookup_field(user_info, &field_map.id)
+        .ok_or_else(|| AppError::Authentication("User ID not provided by OAuth provider".into()))?;
+
+    let username = lookup(&field_map.username);
+
+    let email = match lookup_field(user_info, &field_map.email) {
+        Some(email) => email,
+        // Without an email in the response, fall back to a placeholder based on the username.
+        // In a real app, you'd make a separate request to fetch emails
+        None => match &username {
+            Some(username) => format!("{}@{}.user", username, provider_name.to_lowercase()),
+            None => {
+                return Err(AppError::Authentication(
+                    "Email not provided by OAuth provider".into(),
+                ))
             }
-            "github" => {
-                let provider_user_id = user_info["id"].to_string().trim_matches('"').to_string();
-
-                let email = match user_info["email"].as_str() {
-                    Some(email) => email.to_string(),
-                    None => {
-                        // GitHub might not provide email in the initial response
-                        // In a real app, you'd make a separate request to fetch emails
-                        // For simplicity, we'll generate a placeholder email
-                        let username = user_info["login"].as_str().ok_or_else(|| {
-                            AppError::Authentication("Username not provided by GitHub".into())
-                        })?;
-                        format!("{}@github.user", username)
-                    }
-                };
+        },
+    };
 
-                let name = user_info["name"]
-                    .as_str()
-                    .unwrap_or_else(|| user_info["login"].as_str().unwrap_or("GitHub User"))
-                    .to_string();
+    let name = lookup(&field_map.name)
+        .or(username)
+        .unwrap_or_else(|| format!("{} User", display_name));
 
-                let avatar = user_info["avatar_url"].as_str().map(|s| s.to_string());
+    let avatar = lookup(&field_map.avatar);
 
-                Ok((provider_user_id, email, name, avatar))
+    // Some providers send the flag as a string
+    let email_verified = lookup(&field_map.email_verified)
+        .is_none_or(|verified| verified.eq_ignore_ascii_case("true"));
+
+    Ok(ProviderUserInfo {
+        provider_user_id,
+        email,
+        name,
+        avatar,
+        email_verified,
+    })
+}
+
+// Follow a dotted path through objects and arrays to a string, number or boolean
+fn lookup_field(value: &Value, path: &str) -> Option<String> {
+    let path = path.strip_prefix("$.").unwrap_or(path);
+
+    let mut current = value;
+    for segment in path.split('.') {
+        current = match current {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => current.get(segment)?,
+        };
+    }
+
+    match current {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn validate_field_map(field_map: &OAuthFieldMap) -> Result<(), AppError> {
+    let paths = [
+        ("id", Some(&field_map.id)),
+        ("email", Some(&field_map.email)),
+        ("name", field_map.name.as_ref()),
+        ("avatar", field_map.avatar.as_ref()),
+        ("username", field_map.username.as_ref()),
+        ("email_verified", field_map.email_verified.as_ref()),
+    ];
+
+    for (field, path) in paths {
+        if let Some(path) = path {
+            let path = path.strip_prefix("$.").unwrap_or(path);
+            if path.split('.').any(str::is_empty) {
+                return Err(AppError::Validation(format!(
+                    "field_map.{}: Must be a dotted path such as \"data.email\"",
+                    field
+                )));
             }
-            _ => Err(AppError::Validation(format!(
-                "Unsupported OAuth provider: {}",
-                provider
-            ))),
         }
     }
 
-    // Helper to generate random token
-    fn generate_random_token(&self, length: usize) -> Result<String, AppError> {
-        use rand::{distributions::Alphanumeric, Rng};
+    Ok(())
+}
+
+// The OAuth client sets these itself; letting them be overridden would break the flow
+const RESERVED_AUTH_PARAMS: &[&str] = &[
+    "response_type",
+    "client_id",
+    "redirect_uri",
+    "scope",
+    "state",
+    "code_challenge",
+    "code_challenge_method",
+];
+
+fn validate_auth_params(params: &OAuthAuthParams) -> Result<(), AppError> {
+    for key in params.keys() {
+        if key.trim().is_empty() {
+            return Err(AppError::Validation(
+                "extra_auth_params: Parameter names must not be empty".into(),
+            ));
+        }
+        if RESERVED_AUTH_PARAMS.contains(&key.to_lowercase().as_str()) {
+            return Err(AppError::Validation(format!(
+                "extra_auth_params.{}: Set by the server and can't be overridden",
+                key
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// Providers disagree on the scope separator; store scopes space-separated without duplicates
+fn normalize_scope(scope: &str) -> Result<String, AppError> {
+    let mut scopes: Vec<&str> = Vec::new();
+    for part in scope.split(|c: char| c == ',' || c.is_whitespace()) {
+        if !part.is_empty() && !scopes.contains(&part) {
+            scopes.push(part);
+        }
+    }
+
+    if scopes.is_empty() {
+        return Err(AppError::Validation("scope: Must not be empty".into()));
+    }
+
+    let scope = scopes.join(" ");
+    if scope.len() > 255 {
+        return Err(AppError::Validation(
+            "scope: Must be at most 255 characters".into(),
+        ));
+    }
+
+    Ok(scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn user_info_is_extracted_through_the_field_map() {
+        let field_map = OAuthFieldMap {
+            id: "$.data.id".to_string(),
+            email: "data.emails.0.value".to_string(),
+            name: Some("data.display_name".to_string()),
+            avatar: None,
+            username: None,
+            email_verified: None,
+        };
+        let user_info = json!({
+            "data": { "id": 42, "emails": [{ "value": "kim@example.com" }] }
+        });
+
+        let info = extract_user_info(&user_info, &field_map, "example", "Example").unwrap();
+        assert_eq!(info.provider_user_id, "42");
+        assert_eq!(info.email, "kim@example.com");
+        assert_eq!(info.name, "Example User");
+        assert_eq!(info.avatar, None);
+        assert!(info.email_verified);
+
+        // GitHub users without a public email get a placeholder from their login
+        let github = OAuthFieldMap::builtin("github").unwrap();
+        let info = extract_user_info(
+            &json!({ "id": 7, "login": "kim" }),
+            &github,
+            "github",
+            "GitHub",
+        )
+        .unwrap();
+        assert_eq!(info.email, "kim@github.user");
+        assert_eq!(info.name, "kim");
+    }
+
+    #[test]
+    fn authorization_url_carries_extra_params() {
+        let client = BasicClient::new(
+            ClientId::new("client-id".to_string()),
+            None,
+            AuthUrl::new("https://accounts.example.com/authorize".to_string()).unwrap(),
+            None,
+        );
+
+        let url = authorization_url(
+            &client,
+            vec![Scope::new("email".to_string())],
+            builtin_auth_params("Google"),
+        );
+        assert!(url.contains("access_type=offline"));
+        assert!(url.contains("prompt=consent"));
+        assert!(url.contains("scope=email"));
+
+        assert!(builtin_auth_params("github").is_empty());
+        let reserved = OAuthAuthParams::from([("state".to_string(), "x".to_string())]);
+        assert!(validate_auth_params(&reserved).is_err());
+        let hosted_domain = OAuthAuthParams::from([("hd".to_string(), "example.com".to_string())]);
+        assert!(validate_auth_params(&hosted_domain).is_ok());
+    }
+
+    #[test]
+    fn email_verification_flag_is_read_when_mapped() {
+        let google = OAuthFieldMap::builtin("google").unwrap();
+        let extract = |user_info| {
+            extract_user_info(&user_info, &google, "google", "Google")
+                .unwrap()
+                .email_verified
+        };
+
+        assert!(extract(
+            json!({ "id": "1", "email": "kim@example.com", "verified_email": true })
+        ));
+        assert!(!extract(
+            json!({ "id": "1", "email": "kim@example.com", "verified_email": false })
+        ));
+        assert!(!extract(
+            json!({ "id": "1", "email": "kim@example.com", "verified_email": "false" })
+        ));
+        // Mapped but missing from the response: nothing says it's unverified
+        assert!(extract(json!({ "id": "1", "email": "kim@example.com" })));
+    }
+
+    #[test]
+    fn only_profile_fields_the_user_kept_follow_the_provider() {
+        // Never set, or still what the provider gave us last time
+        assert_eq!(
+            synced_value(None, None, Some("Kim Lee")),
+            Some("Kim Lee".to_string())
+        );
+        assert_eq!(
+            synced_value(Some("Kim"), Some("Kim"), Some("Kim Lee")),
+            Some("Kim Lee".to_string())
+        );
+        // Changed by the user, or nothing new
+        assert_eq!(
+            synced_value(Some("K. Lee"), Some("Kim"), Some("Kim Lee")),
+            None
+        );
+        assert_eq!(synced_value(Some("Kim"), Some("Kim"), Some("Kim")), None);
+        assert_eq!(synced_value(Some("Kim"), Some("Kim"), None), None);
+        assert_eq!(synced_value(None, None, Some("")), None);
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn expired_connections_ask_the_user_to_sign_in_again(pool: sqlx::PgPool) {
+        let app = crate::test_support::TestApp::new(pool);
+        let user = app.create_user("kim").await;
+        let oauth_repo = app.repos.oauth();
+
+        let not_linked = app
+            .auth_service
+            .refresh_oauth_connection_profile(user.id, "example")
+            .await
+            .unwrap_err();
+        assert!(matches!(not_linked, AppError::NotFound(_)));
+
+        let provider = oauth_repo
+            .create_provider(&CreateOAuthProviderDto {
+                provider_name: "example".to_string(),
+                display_name: "Example".to_string(),
+                client_id: "client-id".to_string(),
+                client_secret: "client-secret".to_string(),
+                auth_url: "https://accounts.example.com/authorize".to_string(),
+                token_url: "https://accounts.example.com/token".to_string(),
+                user_info_url: "https://api.example.com/me".to_string(),
+                redirect_url: "https://connect.example.com/auth/oauth/example/callback".to_string(),
+                scope: "email profile".to_string(),
+                icon_url: None,
+                field_map: None,
+                extra_auth_params: None,
+            })
+            .await
+            .unwrap();
+        // The access token has expired and there's no refresh token to replace it with
+        oauth_repo
+            .upsert_connection(&CreateOAuthConnectionDto {
+                user_id: user.id,
+                provider_id: provider.id,
+                provider_user_id: "42".to_string(),
+                email: Some("kim@example.com".to_string()),
+                name: Some("Kim".to_string()),
+                access_token: Some("expired-token".to_string()),
+                expires_at: Some(chrono::Utc::now() - chrono::Duration::minutes(5)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let error = app
+            .auth_service
+            .refresh_oauth_connection_profile(user.id, "example")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::OAuthReauthRequired(_)));
+        assert_eq!(error.code().as_str(), "OAUTH_REAUTH_REQUIRED");
+    }
+
+    #[tokio::test]
+    async fn slow_providers_are_timeouts() {
+        // Accepts the connection but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/userinfo", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move { listener.accept().await });
 
-        let token: String = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(length)
-            .map(char::from)
-            .collect();
+        let client = HttpClient::builder()
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let error = client.get(&url).send().await.unwrap_err();
+        let error = provider_request_error("fetch user info", error);
+        assert!(matches!(error, AppError::Timeout(_)));
+        assert_eq!(
+            error.code().status(),
+            axum::http::StatusCode::GATEWAY_TIMEOUT
+        );
+        server.abort();
+        let _ = server.await;
 
-        Ok(token)
+        // Nothing listening any more: a plain failure, not a timeout
+        let error = client.get(&url).send().await.unwrap_err();
+        assert!(matches!(
+            provider_request_error("fetch user info", error),
+            AppError::Unexpected(_)
+        ));
     }
 }
@@ -0,0 +1,146 @@
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use oauth2::{HttpRequest, HttpResponse};
+use rand::Rng;
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
+
+use crate::config::OAuthConfig;
+
+// Bounded, jittered exponential backoff for calls to OAuth providers. Only
+// transient failures (429, 5xx, timeouts and connection errors) are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+// Why an attempt failed, and whether the provider asked for a specific delay
+enum Outcome<T, E> {
+    Done(Result<T, E>),
+    Retry(Result<T, E>, Option<Duration>),
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            max_delay,
+        }
+    }
+
+    pub fn from_config(config: &OAuthConfig) -> Self {
+        Self::new(
+            config.http_max_attempts,
+            Duration::from_millis(config.http_retry_base_delay_ms),
+            Duration::from_millis(config.http_retry_max_delay_ms),
+        )
+    }
+
+    // Send a reqwest request, rebuilding it for every attempt
+    pub async fn send<F>(&self, build: F) -> Result<reqwest::Response, reqwest::Error>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        self.run(|| async {
+            match build().send().await {
+                Ok(response) if is_retryable_status(response.status()) => {
+                    let retry_after = parse_retry_after(response.headers());
+                    Outcome::Retry(Ok(response), retry_after)
+                }
+                Err(e) if e.is_timeout() || e.is_connect() => Outcome::Retry(Err(e), None),
+                result => Outcome::Done(result),
+            }
+        })
+        .await
+    }
+
+    // HTTP client for oauth2's `request_async` (token exchange) with the same retry rules
+    pub async fn oauth2_http_client(
+        &self,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, oauth2::reqwest::Error<reqwest::Error>> {
+        self.run(|| async {
+            match oauth2::reqwest::async_http_client(request.clone()).await {
+                Ok(response) if is_retryable_status(response.status_code) => {
+                    let retry_after = parse_retry_after(&response.headers);
+                    Outcome::Retry(Ok(response), retry_after)
+                }
+                Err(oauth2::reqwest::Error::Reqwest(e)) if e.is_timeout() || e.is_connect() => {
+                    Outcome::Retry(Err(oauth2::reqwest::Error::Reqwest(e)), None)
+                }
+                result => Outcome::Done(result),
+            }
+        })
+        .await
+    }
+
+    async fn run<T, E, F, Fut>(&self, attempt: F) -> Result<T, E>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Outcome<T, E>>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let (result, retry_after) = match attempt().await {
+                Outcome::Done(result) => return result,
+                Outcome::Retry(result, retry_after) => (result, retry_after),
+            };
+
+            if attempts >= self.max_attempts {
+                return result;
+            }
+
+            // A provider asking for longer than we're willing to hold the login open gets no retry
+            let delay = match retry_after {
+                Some(delay) if delay > self.max_delay => return result,
+                Some(delay) => delay,
+                None => self.backoff(attempts),
+            };
+
+            tracing::warn!(
+                attempt = attempts,
+                delay_ms = delay.as_millis() as u64,
+                "Transient OAuth provider failure, retrying"
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    // Exponential backoff with "equal jitter": half the delay is fixed, half is random
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        let half = exp / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// Retry-After is either a number of seconds or an HTTP date
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
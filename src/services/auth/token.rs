@@ -1,11 +1,27 @@
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation,
+};
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, TokenBinding};
 use crate::errors::AppError;
-use crate::models::user::User;
+use crate::middleware::client_context::ClientContext;
+use crate::models::auth::session::Session;
+use crate::models::user::{Role, User};
+use crate::services::validation::field_validation_error;
+
+// What a JWT may be used for, so a refresh token can't stand in for an access token or
+// the other way round
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenUse {
+    Access,
+    Refresh,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -13,7 +29,40 @@ pub struct Claims {
     pub exp: i64,      // Expiration time
     pub iat: i64,      // Issued at
     pub email: String, // User email
-    pub role: String,  // User role
+    pub role: Role,    // User role
+    // Required: tokens issued before the claim existed are refused
+    pub token_use: TokenUse,
+    // Set only on impersonation tokens: the acting admin and the impersonation session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonation_id: Option<String>,
+    // Not before: the token is rejected until this time (scheduled activation)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    // When the password reaches PASSWORD_MAX_AGE_DAYS, for checks that don't load the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_expires_at: Option<i64>,
+    // With TOKEN_BINDING, the client the token was issued to (see client_fingerprint)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    // The client (JWT_CLIENT_EXPIRATIONS) the tokens were issued to, which sets how long
+    // access tokens live, refreshed ones included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    // The persisted session a login started, which every token it leads to carries.
+    // Impersonation tokens have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+// Generate a random alphanumeric secret straight from the OS CSPRNG
+pub fn generate_secure_token(length: usize) -> String {
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
 }
 
 pub struct TokenService {
@@ -25,11 +74,170 @@ impl TokenService {
         Self { config }
     }
 
-    // Generate token and refresh token for user
-    pub fn generate_tokens(&self, user: &User) -> Result<(String, String), AppError> {
+    // Whether sessions expire after SESSION_IDLE_TIMEOUT_SECS without activity
+    pub fn expires_idle_sessions(&self) -> bool {
+        self.config.session_idle_timeout_secs > 0
+    }
+
+    // Whether a persisted session has gone unused for longer than SESSION_IDLE_TIMEOUT_SECS
+    pub fn is_session_idle(&self, session: &Session) -> bool {
+        self.expires_idle_sessions()
+            && session.last_activity_at
+                + Duration::seconds(self.config.session_idle_timeout_secs as i64)
+                < Utc::now()
+    }
+
+    // Whether the account's password is older than PASSWORD_MAX_AGE_DAYS allows
+    pub fn is_password_expired(&self, user: &User) -> bool {
+        self.config.password_max_age_days > 0
+            && user.password_changed_at + Duration::days(self.config.password_max_age_days)
+                < Utc::now()
+    }
+
+    // With AUTH_STATELESS, the claims of an access token that can be trusted without
+    // looking the account up. Only tokens that live no longer than JWT_EXPIRATION
+    // qualify; refresh and impersonation tokens are always checked.
+    pub fn verify_without_lookup(&self, token: &str) -> Option<Claims> {
+        if !self.config.auth_stateless {
+            return None;
+        }
+
+        let claims = self.verify_access_token(token).ok()?;
+        let lifetime = claims.exp - claims.nbf.unwrap_or(claims.iat);
+        (claims.impersonator.is_none() && lifetime <= self.config.jwt_expiration).then_some(claims)
+    }
+
+    fn password_expires_at(&self, user: &User) -> Option<i64> {
+        (self.config.password_max_age_days > 0).then(|| {
+            (user.password_changed_at + Duration::days(self.config.password_max_age_days))
+                .timestamp()
+        })
+    }
+
+    // is_password_expired for when only the token's claims are at hand
+    pub fn is_password_expired_by_claims(&self, claims: &Claims) -> bool {
+        claims
+            .password_expires_at
+            .is_some_and(|expires_at| expires_at < Utc::now().timestamp())
+    }
+
+    // Whether the token was issued before the user's tokens were revoked.
+    // iat has second precision, so a token from the same second counts as revoked.
+    pub fn is_token_revoked(&self, claims: &Claims, user: &User) -> bool {
+        user.tokens_revoked_at
+            .is_some_and(|revoked_at| claims.iat <= revoked_at.timestamp())
+    }
+
+    // With TOKEN_BINDING on, a fingerprint of the client: keyed hashes of its network
+    // and of its user agent. Keyed with the JWT secret so the claim doesn't reveal them.
+    pub fn client_fingerprint(&self, client: &ClientContext) -> Option<String> {
+        if self.config.token_binding == TokenBinding::Off {
+            return None;
+        }
+
+        let hash = |kind: &str, value: &str| {
+            let digest = Sha256::new()
+                .chain_update(self.config.jwt_secret.as_bytes())
+                .chain_update([0])
+                .chain_update(kind.as_bytes())
+                .chain_update([0])
+                .chain_update(value.as_bytes())
+                .finalize();
+            format!("{:x}", digest)[..16].to_string()
+        };
+        Some(format!(
+            "{}.{}",
+            hash("network", &client.network().to_string()),
+            hash(
+                "user_agent",
+                client.user_agent.as_deref().unwrap_or_default()
+            )
+        ))
+    }
+
+    // Check that a bound token is used by the client it was issued to. Only a change of
+    // both network and user agent counts; either one alone happens all the time.
+    pub fn check_fingerprint(
+        &self,
+        claims: &Claims,
+        client: &ClientContext,
+    ) -> Result<(), AppError> {
+        let (Some(issued), Some(current)) = (
+            claims.fingerprint.as_deref(),
+            self.client_fingerprint(client),
+        ) else {
+            return Ok(());
+        };
+        let (Some((issued_network, issued_agent)), Some((network, agent))) =
+            (issued.split_once('.'), current.split_once('.'))
+        else {
+            return Ok(());
+        };
+        if issued_network == network || issued_agent == agent {
+            return Ok(());
+        }
+
+        tracing::warn!(
+            user_id = %claims.sub,
+            ip = %client.ip,
+            enforced = self.config.token_binding == TokenBinding::Enforce,
+            "Token used from a different client than it was issued to"
+        );
+        if self.config.token_binding == TokenBinding::Enforce {
+            return Err(AppError::Authentication(
+                "Token was issued to a different client".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // How long access tokens issued to `client_id` live: its JWT_CLIENT_EXPIRATIONS entry,
+    // or JWT_EXPIRATION without a client id. Unknown clients are rejected.
+    pub fn access_token_expiration(&self, client_id: Option<&str>) -> Result<i64, AppError> {
+        match client_id {
+            None => Ok(self.config.jwt_expiration),
+            Some(client_id) => self
+                .config
+                .jwt_client_expirations
+                .get(client_id)
+                .copied()
+                .ok_or_else(|| field_validation_error("client_id", "unknown_client")),
+        }
+    }
+
+    // Generate token and refresh token for user, for the persisted session `session_id`
+    // and the client app `client_id` if given. With TOKEN_BINDING on, they're bound to
+    // `client`, the one they're issued to.
+    pub fn generate_tokens(
+        &self,
+        user: &User,
+        session_id: Option<Uuid>,
+        client_id: Option<&str>,
+        client: Option<&ClientContext>,
+    ) -> Result<(String, String), AppError> {
+        self.generate_tokens_not_before(user, session_id, None, client_id, client)
+    }
+
+    // Generate tokens that only become valid at `not_before`, if given. Their lifetimes
+    // start from then rather than from now.
+    pub fn generate_tokens_not_before(
+        &self,
+        user: &User,
+        session_id: Option<Uuid>,
+        not_before: Option<DateTime<Utc>>,
+        client_id: Option<&str>,
+        client: Option<&ClientContext>,
+    ) -> Result<(String, String), AppError> {
         let now = Utc::now();
-        let token_exp = now + Duration::seconds(self.config.jwt_expiration);
-        let refresh_token_exp = now + Duration::seconds(self.config.refresh_token_expiration);
+        let valid_from = not_before.map_or(now, |not_before| not_before.max(now));
+        let nbf = not_before.map(|_| valid_from.timestamp());
+        let token_exp = valid_from + Duration::seconds(self.access_token_expiration(client_id)?);
+        let refresh_token_exp =
+            valid_from + Duration::seconds(self.config.refresh_token_expiration);
+        let password_expires_at = self.password_expires_at(user);
+        let fingerprint = client.and_then(|client| self.client_fingerprint(client));
+        let session_id = session_id.map(|id| id.to_string());
 
         // Claims for the main token
         let claims = Claims {
@@ -37,7 +245,15 @@ impl TokenService {
             exp: token_exp.timestamp(),
             iat: now.timestamp(),
             email: user.email.clone(),
-            role: user.global_role.clone(),
+            role: user.role(),
+            token_use: TokenUse::Access,
+            impersonator: None,
+            impersonation_id: None,
+            nbf,
+            password_expires_at,
+            fingerprint: fingerprint.clone(),
+            client_id: client_id.map(str::to_string),
+            session_id: session_id.clone(),
         };
 
         // Claims for refresh token (same, but with different expiry)
@@ -46,7 +262,15 @@ impl TokenService {
             exp: refresh_token_exp.timestamp(),
             iat: now.timestamp(),
             email: user.email.clone(),
-            role: user.global_role.clone(),
+            role: user.role(),
+            token_use: TokenUse::Refresh,
+            impersonator: None,
+            impersonation_id: None,
+            nbf,
+            password_expires_at,
+            fingerprint,
+            client_id: client_id.map(str::to_string),
+            session_id,
         };
 
         // Encode token
@@ -70,28 +294,107 @@ impl TokenService {
 
     // Verify token and return claims
     pub fn verify_token(&self, token: &str) -> Result<Claims, AppError> {
-        let decoded = decode::<Claims>(
+        self.decode_claims(token).map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => AppError::Authentication("Token has expired".into()),
+            ErrorKind::ImmatureSignature => {
+                AppError::Authentication("Token is not valid yet".into())
+            }
+            _ => AppError::Authentication("Invalid token".into()),
+        })
+    }
+
+    // verify_token for tokens presented as access tokens
+    pub fn verify_access_token(&self, token: &str) -> Result<Claims, AppError> {
+        self.verify_token_for(token, TokenUse::Access)
+    }
+
+    // verify_token for tokens presented as refresh tokens
+    pub fn verify_refresh_token(&self, token: &str) -> Result<Claims, AppError> {
+        self.verify_token_for(token, TokenUse::Refresh)
+    }
+
+    fn verify_token_for(&self, token: &str, token_use: TokenUse) -> Result<Claims, AppError> {
+        let claims = self.verify_token(token)?;
+        if claims.token_use != token_use {
+            return Err(AppError::Authentication("Invalid token".into()));
+        }
+        Ok(claims)
+    }
+
+    // Whether the token is one we issued that has since expired
+    pub fn is_token_expired(&self, token: &str) -> bool {
+        matches!(
+            self.decode_claims(token),
+            Err(e) if matches!(e.kind(), ErrorKind::ExpiredSignature)
+        )
+    }
+
+    fn decode_claims(&self, token: &str) -> jsonwebtoken::errors::Result<Claims> {
+        // nbf is only checked on tokens that carry it. No leeway: the default minute would
+        // accept scheduled tokens early and expired ones late.
+        let mut validation = Validation::default();
+        validation.validate_nbf = true;
+        validation.leeway = 0;
+
+        decode::<Claims>(
             token,
             &DecodingKey::from_secret(self.config.jwt_secret.as_bytes()),
-            &Validation::default(),
+            &validation,
         )
-        .map_err(|e| match e.kind() {
-            jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
-                AppError::Authentication("Token has expired".into())
-            }
-            _ => AppError::Authentication("Invalid token".into()),
-        })?;
+        .map(|decoded| decoded.claims)
+    }
 
-        Ok(decoded.claims)
+    // Generate a short-lived, non-refreshable token for an admin acting as `user`
+    pub fn generate_impersonation_token(
+        &self,
+        user: &User,
+        admin_id: Uuid,
+        impersonation_id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, AppError> {
+        let claims = Claims {
+            sub: user.id.to_string(),
+            exp: expires_at.timestamp(),
+            iat: Utc::now().timestamp(),
+            email: user.email.clone(),
+            role: user.role(),
+            token_use: TokenUse::Access,
+            impersonator: Some(admin_id.to_string()),
+            impersonation_id: Some(impersonation_id.to_string()),
+            nbf: None,
+            // Impersonating admins aren't held to the user's password expiry
+            password_expires_at: None,
+            fingerprint: None,
+            client_id: None,
+            session_id: None,
+        };
+
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.config.jwt_secret.as_bytes()),
+        )
+        .map_err(|e| AppError::Internal(format!("Failed to generate impersonation token: {}", e)))
     }
 
-    // Refresh token to get a new token
-    pub fn refresh_token(&self, refresh_token: &str) -> Result<String, AppError> {
-        let claims = self.verify_token(refresh_token)?;
+    // Refresh token to get a new token for `user`, the account it was issued to
+    pub fn refresh_token(&self, refresh_token: &str, user: &User) -> Result<String, AppError> {
+        let claims = self.verify_refresh_token(refresh_token)?;
+
+        // Impersonation must end when its token expires
+        if claims.impersonator.is_some() {
+            return Err(AppError::Authentication(
+                "Impersonation tokens cannot be refreshed".into(),
+            ));
+        }
 
-        // Create a new token with same claims but new expiry
+        // Create a new token with same claims but new expiry. A client that has since
+        // been removed from the config gets the default lifetime.
         let now = Utc::now();
-        let token_exp = now + Duration::seconds(self.config.jwt_expiration);
+        let expiration = self
+            .access_token_expiration(claims.client_id.as_deref())
+            .unwrap_or(self.config.jwt_expiration);
+        let token_exp = now + Duration::seconds(expiration);
 
         let new_claims = Claims {
             sub: claims.sub,
@@ -99,6 +402,16 @@ impl TokenService {
             iat: now.timestamp(),
             email: claims.email,
             role: claims.role,
+            token_use: TokenUse::Access,
+            impersonator: None,
+            impersonation_id: None,
+            nbf: None,
+            password_expires_at: self.password_expires_at(user),
+            // Access tokens stay bound to the client the refresh token was issued to
+            fingerprint: claims.fingerprint,
+            client_id: claims.client_id,
+            // Refreshed tokens belong to the same session
+            session_id: claims.session_id,
         };
 
         let new_token = encode(
@@ -111,6 +424,34 @@ impl TokenService {
         Ok(new_token)
     }
 
+    // Reissue a refresh token for the refresh cookie. The new one keeps the old one's
+    // claims and expiry, so rotating doesn't extend the session. Returns the token and
+    // when it expires.
+    pub fn rotate_refresh_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(String, DateTime<Utc>), AppError> {
+        let mut claims = self.verify_refresh_token(refresh_token)?;
+        if claims.impersonator.is_some() {
+            return Err(AppError::Authentication(
+                "Impersonation tokens cannot be refreshed".into(),
+            ));
+        }
+        // Always later than the old token, so the two differ even within the same second
+        claims.iat = Utc::now().timestamp().max(claims.iat + 1);
+
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.config.jwt_secret.as_bytes()),
+        )
+        .map_err(|e| AppError::Internal(format!("Failed to generate refresh token: {}", e)))?;
+        let expires_at = DateTime::from_timestamp(claims.exp, 0)
+            .ok_or_else(|| AppError::Authentication("Invalid token".into()))?;
+
+        Ok((token, expires_at))
+    }
+
     // Extract user ID from token
     pub fn get_user_id_from_token(&self, token: &str) -> Result<Uuid, AppError> {
         let claims = self.verify_token(token)?;
@@ -121,3 +462,156 @@ impl TokenService {
         Ok(user_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_client, test_config, TestApp};
+    use chrono::{Duration, Utc};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn tokens_are_rejected_before_nbf(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("liam").await;
+        let tokens = &app.token_service;
+
+        let (scheduled, _) = tokens
+            .generate_tokens_not_before(
+                &user,
+                None,
+                Some(Utc::now() + Duration::hours(1)),
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(tokens.verify_token(&scheduled).is_err());
+
+        // Not even a few seconds early
+        let (soon, _) = tokens
+            .generate_tokens_not_before(
+                &user,
+                None,
+                Some(Utc::now() + Duration::seconds(30)),
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(tokens.verify_token(&soon).is_err());
+
+        let (active, _) = tokens
+            .generate_tokens_not_before(
+                &user,
+                None,
+                Some(Utc::now() - Duration::minutes(1)),
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(tokens.verify_token(&active).unwrap().nbf.is_some());
+
+        // Tokens without the claim are unaffected
+        let (token, _) = tokens.generate_tokens(&user, None, None, None).unwrap();
+        assert!(tokens.verify_token(&token).unwrap().nbf.is_none());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn expired_tokens_get_no_grace_period(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("lena").await;
+
+        let token = app
+            .token_service
+            .generate_impersonation_token(
+                &user,
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                Utc::now() - Duration::seconds(30),
+            )
+            .unwrap();
+        assert!(app.token_service.verify_token(&token).is_err());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn clients_get_their_own_access_token_lifetime(pool: PgPool) {
+        let mut config = test_config();
+        config.jwt_client_expirations = [("mobile".to_string(), 86400)].into();
+        let app = TestApp::with_config(pool, config.clone());
+        let user = app.create_user("milo").await;
+        let tokens = &app.token_service;
+        let lifetime = |token: &str| {
+            let claims = tokens.verify_token(token).unwrap();
+            claims.exp - claims.iat
+        };
+
+        let (token, refresh_token) = tokens
+            .generate_tokens(&user, None, Some("mobile"), None)
+            .unwrap();
+        assert_eq!(lifetime(&token), 86400);
+        let refreshed = tokens.refresh_token(&refresh_token, &user).unwrap();
+        assert_eq!(lifetime(&refreshed), 86400);
+        let claims = tokens.verify_token(&refreshed).unwrap();
+        assert_eq!(claims.client_id.as_deref(), Some("mobile"));
+
+        let (token, _) = tokens.generate_tokens(&user, None, None, None).unwrap();
+        assert_eq!(lifetime(&token), config.jwt_expiration);
+        assert!(matches!(
+            tokens.generate_tokens(&user, None, Some("desktop"), None),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn rotated_refresh_tokens_keep_their_expiry(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("mira").await;
+        let tokens = &app.token_service;
+
+        let (_, refresh_token) = tokens.generate_tokens(&user, None, None, None).unwrap();
+        let original = tokens.verify_token(&refresh_token).unwrap();
+
+        let (rotated, expires_at) = tokens.rotate_refresh_token(&refresh_token).unwrap();
+        let claims = tokens.verify_token(&rotated).unwrap();
+        assert_eq!((claims.sub, claims.exp), (original.sub, original.exp));
+        assert_eq!(expires_at.timestamp(), original.exp);
+        assert!(tokens.rotate_refresh_token("not-a-token").is_err());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn bound_tokens_only_survive_a_partial_client_change(pool: PgPool) {
+        let mut config = test_config();
+        config.token_binding = TokenBinding::Enforce;
+        let app = TestApp::with_config(pool, config);
+        let user = app.create_user("mia").await;
+        let tokens = &app.token_service;
+
+        let issued_to = test_client("203.0.113.10", "Firefox/128");
+        let (token, refresh_token) = tokens
+            .generate_tokens(&user, None, None, Some(&issued_to))
+            .unwrap();
+        let claims = tokens.verify_token(&token).unwrap();
+
+        // A new address on the same network, or a browser update, is the same client
+        for client in [
+            test_client("198.51.100.7", "Firefox/128"),
+            test_client("203.0.113.10", "Firefox/129"),
+        ] {
+            assert!(tokens.check_fingerprint(&claims, &client).is_ok());
+        }
+        let elsewhere = test_client("198.51.100.7", "curl/8.5");
+        assert!(matches!(
+            tokens.check_fingerprint(&claims, &elsewhere),
+            Err(AppError::Authentication(_))
+        ));
+
+        // Refreshed access tokens keep the binding
+        let refreshed = tokens.refresh_token(&refresh_token, &user).unwrap();
+        let refreshed = tokens.verify_token(&refreshed).unwrap();
+        assert_eq!(refreshed.fingerprint, claims.fingerprint);
+
+        // Tokens issued without a client aren't bound
+        let (unbound, _) = tokens.generate_tokens(&user, None, None, None).unwrap();
+        let unbound = tokens.verify_token(&unbound).unwrap();
+        assert!(tokens.check_fingerprint(&unbound, &elsewhere).is_ok());
+    }
+}
@@ -1,21 +1,55 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::config::AvatarConfig;
+use crate::db::error::DatabaseError;
 use crate::db::repositories::Repositories;
 use crate::errors::AppError;
 use crate::models::badge::{Badge, BadgeResponse, CreateBadgeDto, UpdateBadgeDto};
+use crate::models::common::bulk::{
+    BulkOperationResult, BULK_SKIP_ALREADY_HAS_BADGE, BULK_SKIP_DOES_NOT_HAVE_BADGE,
+    BULK_SKIP_DUPLICATE, BULK_SKIP_USER_NOT_FOUND,
+};
+use crate::models::common::filter::CreatedRangeQuery;
 use crate::models::common::response::PaginatedResponse;
-use crate::models::user::{AwardBadgeDto, BadgeWithUsersResponse, UserWithBadgesResponse};
+use crate::models::event::{AccountEvent, AccountEventKind};
+use crate::models::user::{
+    AwardBadgeDto, BadgeWithUsersResponse, BulkAwardBadgeDto, BulkRevokeBadgeDto,
+    PublicUserResponse, UserWithBadgesResponse,
+};
+use crate::services::badge::CredentialSigner;
+use crate::services::events::EventBus;
 use crate::services::validation::validation_err_to_app_error;
 use validator::Validate;
 
 pub struct BadgeService {
-    repos: Arc<Repositories>,
+    pub(super) repos: Arc<Repositories>,
+    events: Arc<EventBus>,
+    avatars: AvatarConfig,
+    pub(super) credentials: Option<CredentialSigner>,
 }
 
 impl BadgeService {
-    pub fn new(repos: Arc<Repositories>) -> Self {
-        Self { repos }
+    pub fn new(repos: Arc<Repositories>, events: Arc<EventBus>) -> Self {
+        Self {
+            repos,
+            events,
+            avatars: AvatarConfig::default(),
+            credentials: None,
+        }
+    }
+
+    // Fall back to a default avatar for the users in badge responses
+    pub fn with_avatars(mut self, avatars: AvatarConfig) -> Self {
+        self.avatars = avatars;
+        self
+    }
+
+    // Sign awarded badges as verifiable credentials; None leaves that off
+    pub fn with_credentials(mut self, signer: Option<CredentialSigner>) -> Self {
+        self.credentials = signer;
+        self
     }
 
     // Create a new badge
@@ -46,10 +80,11 @@ impl BadgeService {
         &self,
         page: i64,
         limit: i64,
+        created: &CreatedRangeQuery,
     ) -> Result<PaginatedResponse<BadgeResponse>, AppError> {
         let offset = (page - 1) * limit;
-        let badges = self.repos.badge().find_all(limit, offset).await?;
-        let total = self.repos.badge().count().await?;
+        let badges = self.repos.badge().find_all(limit, offset, created).await?;
+        let total = self.repos.badge().count(created).await?;
 
         let badge_responses: Vec<BadgeResponse> = badges.into_iter().map(Badge::into).collect();
 
@@ -59,6 +94,8 @@ impl BadgeService {
             page,
             limit,
             total_pages: (total as f64 / limit as f64).ceil() as i64,
+            max_limit: None,
+            links: None,
         })
     }
 
@@ -83,8 +120,20 @@ impl BadgeService {
         Ok(())
     }
 
-    // Award a badge to a user
+    // Award a badge to a user, failing if they already have it
     pub async fn award_badge(&self, dto: AwardBadgeDto) -> Result<(), AppError> {
+        if self.ensure_badge(dto).await? {
+            Ok(())
+        } else {
+            Err(AppError::Validation(
+                "User already has this badge".to_string(),
+            ))
+        }
+    }
+
+    // Award a badge unless the user already has it, which also counts as success.
+    // Returns whether it was newly awarded.
+    pub async fn ensure_badge(&self, dto: AwardBadgeDto) -> Result<bool, AppError> {
         // Validate the DTO
         dto.validate().map_err(validation_err_to_app_error)?;
 
@@ -92,25 +141,138 @@ impl BadgeService {
         self.repos.user().find_by_id(dto.user_id).await?;
 
         // Check if badge exists
+        let badge = self.repos.badge().find_by_id(dto.badge_id).await?;
+
+        // Award badge to user; nothing to announce if they already had it
+        if self.repos.user_badge().award_badge(&dto).await?.is_none() {
+            return Ok(false);
+        }
+
+        self.events.publish(AccountEvent::new(
+            dto.user_id,
+            AccountEventKind::BadgeAwarded {
+                badge_id: badge.id,
+                badge_name: badge.name,
+            },
+        ));
+
+        Ok(true)
+    }
+
+    // Award a badge to many users at once. With dry_run, runs the same checks and
+    // reports which users would receive the badge without writing anything.
+    pub async fn bulk_award_badge(
+        &self,
+        dto: BulkAwardBadgeDto,
+        dry_run: bool,
+    ) -> Result<BulkOperationResult, AppError> {
+        // Validate the DTO
+        dto.validate().map_err(validation_err_to_app_error)?;
+
+        // Nothing can be awarded if the badge doesn't exist
         self.repos.badge().find_by_id(dto.badge_id).await?;
 
-        // Check if user already has this badge
-        let has_badge = self
+        let mut result = BulkOperationResult::new(dry_run, dto.user_ids.len());
+        let mut seen = HashSet::new();
+
+        for user_id in dto.user_ids {
+            if !seen.insert(user_id) {
+                result.skip(user_id, BULK_SKIP_DUPLICATE);
+                continue;
+            }
+
+            match self.repos.user().find_by_id(user_id).await {
+                Ok(_) => {}
+                Err(DatabaseError::NotFound) => {
+                    result.skip(user_id, BULK_SKIP_USER_NOT_FOUND);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            // A badge awarded concurrently also shows up as already held
+            let awarded = if dry_run {
+                !self
+                    .repos
+                    .user_badge()
+                    .has_badge(user_id, dto.badge_id)
+                    .await?
+            } else {
+                self.ensure_badge(AwardBadgeDto {
+                    user_id,
+                    badge_id: dto.badge_id,
+                })
+                .await?
+            };
+            if !awarded {
+                result.skip(user_id, BULK_SKIP_ALREADY_HAS_BADGE);
+                continue;
+            }
+
+            result.succeeded.push(user_id);
+        }
+
+        Ok(result)
+    }
+
+    // Remove a badge from many users at once. With dry_run, reports which users would
+    // lose the badge without writing anything.
+    pub async fn bulk_revoke_badge(
+        &self,
+        badge_id: Uuid,
+        dto: BulkRevokeBadgeDto,
+        dry_run: bool,
+    ) -> Result<BulkOperationResult, AppError> {
+        // Validate the DTO
+        dto.validate().map_err(validation_err_to_app_error)?;
+
+        self.repos.badge().find_by_id(badge_id).await?;
+
+        let mut result = BulkOperationResult::new(dry_run, dto.user_ids.len());
+        let mut seen = HashSet::new();
+        let mut user_ids = Vec::new();
+        for user_id in dto.user_ids {
+            if seen.insert(user_id) {
+                user_ids.push(user_id);
+            } else {
+                result.skip(user_id, BULK_SKIP_DUPLICATE);
+            }
+        }
+
+        let revoked: HashSet<Uuid> = self
             .repos
             .user_badge()
-            .has_badge(dto.user_id, dto.badge_id)
-            .await?;
-
-        if has_badge {
-            return Err(AppError::Validation(
-                "User already has this badge".to_string(),
-            ));
+            .remove_badge_from_users(badge_id, &user_ids, dry_run)
+            .await?
+            .into_iter()
+            .collect();
+
+        // Unknown users don't hold the badge either
+        for user_id in user_ids {
+            if revoked.contains(&user_id) {
+                result.succeeded.push(user_id);
+            } else {
+                result.skip(user_id, BULK_SKIP_DOES_NOT_HAVE_BADGE);
+            }
         }
 
-        // Award badge to user
-        self.repos.user_badge().award_badge(&dto).await?;
+        Ok(result)
+    }
 
-        Ok(())
+    // Remove a badge from everyone who holds it, e.g. after it was awarded in error.
+    // Returns the users it was (or in a dry run, would be) removed from.
+    pub async fn revoke_badge_from_all(
+        &self,
+        badge_id: Uuid,
+        dry_run: bool,
+    ) -> Result<Vec<Uuid>, AppError> {
+        self.repos.badge().find_by_id(badge_id).await?;
+
+        Ok(self
+            .repos
+            .user_badge()
+            .remove_badge_from_all(badge_id, dry_run)
+            .await?)
     }
 
     // Remove a badge from a user
@@ -130,15 +292,21 @@ impl BadgeService {
         self.repos.user().find_by_id(user_id).await?;
 
         // Get user with all badges
-        let user_with_badges = self
+        let mut user_with_badges = self
             .repos
             .user_badge()
             .get_user_with_badges(user_id)
             .await?;
+        user_with_badges.user = user_with_badges.user.with_default_avatar(&self.avatars);
 
         Ok(user_with_badges)
     }
 
+    // A user's public profile: their badges and nothing that identifies or describes the account
+    pub async fn get_public_profile(&self, user_id: Uuid) -> Result<PublicUserResponse, AppError> {
+        Ok(self.get_user_badges(user_id).await?.into())
+    }
+
     // Get all users who have a specific badge
     pub async fn get_badge_users(
         &self,
@@ -148,11 +316,16 @@ impl BadgeService {
         self.repos.badge().find_by_id(badge_id).await?;
 
         // Get badge with all users
-        let badge_with_users = self
+        let mut badge_with_users = self
             .repos
             .user_badge()
             .get_badge_with_users(badge_id)
             .await?;
+        badge_with_users.users = badge_with_users
+            .users
+            .into_iter()
+            .map(|user| user.with_default_avatar(&self.avatars))
+            .collect();
 
         Ok(badge_with_users)
     }
@@ -171,3 +344,304 @@ impl BadgeService {
         Ok(has_badge)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn award_and_remove_badge(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("carol").await;
+        let badge = app
+            .badge_service
+            .create_badge(CreateBadgeDto {
+                name: "Early Adopter".to_string(),
+                description: None,
+                image_url: None,
+            })
+            .await
+            .unwrap();
+        let award = || AwardBadgeDto {
+            user_id: user.id,
+            badge_id: badge.id,
+        };
+
+        app.badge_service.award_badge(award()).await.unwrap();
+        assert!(app
+            .badge_service
+            .check_user_badge(user.id, badge.id)
+            .await
+            .unwrap());
+
+        // A badge can only be held once
+        assert!(matches!(
+            app.badge_service.award_badge(award()).await,
+            Err(AppError::Validation(_))
+        ));
+        assert!(!app.badge_service.ensure_badge(award()).await.unwrap());
+
+        let holders = app.badge_service.get_badge_users(badge.id).await.unwrap();
+        assert_eq!(holders.users.len(), 1);
+
+        app.badge_service
+            .remove_badge(user.id, badge.id)
+            .await
+            .unwrap();
+        let user_badges = app.badge_service.get_user_badges(user.id).await.unwrap();
+        assert!(user_badges.badges.is_empty());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn public_profiles_leave_out_the_account(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("oscar").await;
+        let badge = app
+            .badge_service
+            .create_badge(CreateBadgeDto {
+                name: "Contributor".to_string(),
+                description: None,
+                image_url: None,
+            })
+            .await
+            .unwrap();
+        app.badge_service
+            .award_badge(AwardBadgeDto {
+                user_id: user.id,
+                badge_id: badge.id,
+            })
+            .await
+            .unwrap();
+
+        let profile = app.badge_service.get_public_profile(user.id).await.unwrap();
+        assert_eq!(profile.username, "oscar");
+        assert_eq!(profile.badges.len(), 1);
+
+        let json = serde_json::to_value(&profile).unwrap();
+        for field in [
+            "email",
+            "is_email_verified",
+            "is_active",
+            "global_role",
+            "status",
+        ] {
+            assert!(json.get(field).is_none(), "{} is public", field);
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn concurrent_awards_give_the_badge_once(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("dan").await;
+        let badge = app
+            .badge_service
+            .create_badge(CreateBadgeDto {
+                name: "Contributor".to_string(),
+                description: None,
+                image_url: None,
+            })
+            .await
+            .unwrap();
+        let award = || AwardBadgeDto {
+            user_id: user.id,
+            badge_id: badge.id,
+        };
+
+        let (first, second) = tokio::join!(
+            app.badge_service.ensure_badge(award()),
+            app.badge_service.ensure_badge(award())
+        );
+        assert!(first.unwrap() ^ second.unwrap());
+        let holders = app.badge_service.get_badge_users(badge.id).await.unwrap();
+        assert_eq!(holders.users.len(), 1);
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn removed_badge_is_restored_when_awarded_again(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("erin").await;
+        let badge = app
+            .badge_service
+            .create_badge(CreateBadgeDto {
+                name: "Returning".to_string(),
+                description: None,
+                image_url: None,
+            })
+            .await
+            .unwrap();
+        let award = || AwardBadgeDto {
+            user_id: user.id,
+            badge_id: badge.id,
+        };
+
+        for _ in 0..2 {
+            app.badge_service.award_badge(award()).await.unwrap();
+            app.badge_service
+                .remove_badge(user.id, badge.id)
+                .await
+                .unwrap();
+        }
+        app.badge_service.award_badge(award()).await.unwrap();
+        assert!(app
+            .badge_service
+            .check_user_badge(user.id, badge.id)
+            .await
+            .unwrap());
+
+        // The original row came back each time
+        let rows = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM user_badges WHERE user_id = $1 AND badge_id = $2",
+            user.id,
+            badge.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(rows, Some(1));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn deleting_a_badge_takes_it_from_its_holders(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("fay").await;
+        let badge = app
+            .badge_service
+            .create_badge(CreateBadgeDto {
+                name: "Retired".to_string(),
+                description: None,
+                image_url: None,
+            })
+            .await
+            .unwrap();
+        app.badge_service
+            .award_badge(AwardBadgeDto {
+                user_id: user.id,
+                badge_id: badge.id,
+            })
+            .await
+            .unwrap();
+
+        app.badge_service.delete_badge(badge.id).await.unwrap();
+        assert!(matches!(
+            app.badge_service.delete_badge(badge.id).await,
+            Err(AppError::Database(DatabaseError::NotFound))
+        ));
+
+        let held = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM user_badges WHERE badge_id = $1 AND deleted_at IS NULL",
+            badge.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(held, Some(0));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn badges_are_revoked_in_bulk(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let badge = app
+            .badge_service
+            .create_badge(CreateBadgeDto {
+                name: "Mistake".to_string(),
+                description: None,
+                image_url: None,
+            })
+            .await
+            .unwrap();
+        let mut holders = Vec::new();
+        for name in ["fay", "gus", "hal"] {
+            let user = app.create_user(name).await;
+            app.badge_service
+                .award_badge(AwardBadgeDto {
+                    user_id: user.id,
+                    badge_id: badge.id,
+                })
+                .await
+                .unwrap();
+            holders.push(user.id);
+        }
+        let stranger = app.create_user("ivy").await;
+        let revoke = || BulkRevokeBadgeDto {
+            user_ids: vec![holders[0], holders[0], stranger.id],
+        };
+
+        // A dry run reports the same outcome and changes nothing
+        let preview = app
+            .badge_service
+            .bulk_revoke_badge(badge.id, revoke(), true)
+            .await
+            .unwrap();
+        assert_eq!(preview.succeeded, vec![holders[0]]);
+        let reasons: Vec<_> = preview.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            [BULK_SKIP_DUPLICATE, BULK_SKIP_DOES_NOT_HAVE_BADGE]
+        );
+        let users = app.badge_service.get_badge_users(badge.id).await.unwrap();
+        assert_eq!(users.users.len(), 3);
+
+        let result = app
+            .badge_service
+            .bulk_revoke_badge(badge.id, revoke(), false)
+            .await
+            .unwrap();
+        assert_eq!(result.succeeded, vec![holders[0]]);
+        assert!(!app
+            .badge_service
+            .check_user_badge(holders[0], badge.id)
+            .await
+            .unwrap());
+
+        assert_eq!(
+            app.badge_service
+                .revoke_badge_from_all(badge.id, true)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        let mut revoked = app
+            .badge_service
+            .revoke_badge_from_all(badge.id, false)
+            .await
+            .unwrap();
+        revoked.sort();
+        let mut expected = holders[1..].to_vec();
+        expected.sort();
+        assert_eq!(revoked, expected);
+        let users = app.badge_service.get_badge_users(badge.id).await.unwrap();
+        assert!(users.users.is_empty());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn pages_do_not_overlap_when_timestamps_tie(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        for i in 0..5 {
+            app.badge_service
+                .create_badge(CreateBadgeDto {
+                    name: format!("Badge {}", i),
+                    description: None,
+                    image_url: None,
+                })
+                .await
+                .unwrap();
+        }
+        sqlx::query!("UPDATE badges SET created_at = now()")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut seen = HashSet::new();
+        for page in 1..=5 {
+            let badges = app
+                .badge_service
+                .get_badges(page, 1, &CreatedRangeQuery::default())
+                .await
+                .unwrap();
+            assert!(seen.insert(badges.data[0].id));
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod badge;
+mod credential;
 
 pub use badge::*;
+pub use credential::CredentialSigner;
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use lettre::{
     message::{header::ContentType, MultiPart, SinglePart},
     transport::smtp::authentication::Credentials,
@@ -11,7 +12,11 @@ use uuid::Uuid;
 use crate::config::EmailConfig;
 use crate::db::repositories::TokenRepository;
 use crate::errors::AppError;
-use crate::models::auth::token::{CreateVerificationTokenDto, TOKEN_TYPE_EMAIL_VERIFICATION};
+use crate::models::auth::token::{
+    CreateVerificationTokenDto, EMAIL_VERIFICATION_TOKEN_TTL, TOKEN_TYPE_EMAIL_VERIFICATION,
+    VERIFICATION_TOKEN_LENGTH,
+};
+use crate::services::auth::token::generate_secure_token;
 use crate::services::email::template::TemplateManager;
 
 pub struct EmailService {
@@ -27,6 +32,21 @@ impl EmailService {
         }
     }
 
+    // Whether emails are sent at all (EMAIL_ENABLED)
+    pub fn is_enabled(&self) -> bool {
+        self.email_config.enabled
+    }
+
+    fn ensure_enabled(&self) -> Result<(), AppError> {
+        if !self.email_config.enabled {
+            return Err(AppError::EmailDisabled(
+                "Email is not enabled on this server".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
     // Create SMTP transport
     fn create_transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, AppError> {
         let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&self.email_config.smtp_host)
@@ -41,6 +61,25 @@ impl EmailService {
         Ok(transport)
     }
 
+    // Connect to the SMTP server and say hello without sending anything, to tell
+    // whether emails would go out
+    pub async fn check_connection(&self) -> Result<(), AppError> {
+        self.ensure_enabled()?;
+
+        let connected = self
+            .create_transport()?
+            .test_connection()
+            .await
+            .map_err(|e| AppError::Internal(format!("SMTP server unreachable: {}", e)))?;
+        if !connected {
+            return Err(AppError::Internal(
+                "SMTP server refused the connection".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
     // Send verification email to user
     pub async fn send_verification_email(
         &self,
@@ -75,9 +114,42 @@ impl EmailService {
             subject.to_string(),
             html_content,
             text_content,
+        )
+    }
+
+    // Send a verification link for a secondary email address
+    pub async fn send_secondary_email_verification(
+        &self,
+        email: &str,
+        username: &str,
+        token: &str,
+    ) -> Result<(), AppError> {
+        // Create verification URL
+        let verification_url = format!(
+            "{}/auth/verify-secondary-email/{}",
+            self.email_config.frontend_url, token
         );
 
-        Ok(())
+        // Create template parameters
+        let mut params = HashMap::new();
+        params.insert("username", username);
+        params.insert("verification_url", &verification_url);
+
+        // Render the email templates
+        let html_content =
+            TemplateManager::render_html("secondary_email_verification", params.clone());
+        let text_content = TemplateManager::render_text("secondary_email_verification", params);
+
+        // Email subject
+        let subject = "Confirm Your Backup Email Address";
+
+        // Send the email asynchronously
+        self.send_email_async(
+            email.to_string(),
+            subject.to_string(),
+            html_content,
+            text_content,
+        )
     }
 
     // Send password reset email
@@ -111,98 +183,117 @@ impl EmailService {
             subject.to_string(),
             html_content,
             text_content,
+        )
+    }
+
+    // Send an invite link to someone who doesn't have an account yet
+    pub async fn send_invite_email(
+        &self,
+        email: &str,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        // Create registration URL
+        let register_url = format!(
+            "{}/auth/register?invite={}",
+            self.email_config.frontend_url, token
         );
+        let expires_at = expires_at.format("%B %-d, %Y %H:%M UTC").to_string();
 
-        Ok(())
+        // Create template parameters
+        let mut params = HashMap::new();
+        params.insert("register_url", register_url.as_str());
+        params.insert("expires_at", &expires_at);
+
+        // Render the email templates
+        let html_content = TemplateManager::render_html("invite", params.clone());
+        let text_content = TemplateManager::render_text("invite", params);
+
+        // Email subject
+        let subject = "You're Invited to Safatanc Connect";
+
+        // Send the email asynchronously
+        self.send_email_async(
+            email.to_string(),
+            subject.to_string(),
+            html_content,
+            text_content,
+        )
+    }
+
+    // Let a dormant user know their account is still here, and when it will be
+    // deactivated if they don't sign in (if deactivation is enabled)
+    pub async fn send_dormancy_notice_email(
+        &self,
+        email: &str,
+        username: &str,
+        deactivate_in_days: Option<i64>,
+    ) -> Result<(), AppError> {
+        let login_url = format!("{}/auth/login", self.email_config.frontend_url);
+
+        let deactivation_notice = match deactivate_in_days {
+            Some(days) => format!(
+                "To keep your account active, please sign in within the next {} days. \
+                 After that, inactive accounts are deactivated.",
+                days
+            ),
+            None => "Signing in once is all it takes to keep your account active.".to_string(),
+        };
+
+        // Create template parameters
+        let mut params = HashMap::new();
+        params.insert("username", username);
+        params.insert("login_url", &login_url);
+        params.insert("deactivation_notice", &deactivation_notice);
+
+        // Render the email templates
+        let html_content = TemplateManager::render_html("dormancy_notice", params.clone());
+        let text_content = TemplateManager::render_text("dormancy_notice", params);
+
+        // Email subject
+        let subject = "We Miss You at Safatanc Connect";
+
+        // Send the email asynchronously
+        self.send_email_async(
+            email.to_string(),
+            subject.to_string(),
+            html_content,
+            text_content,
+        )
     }
 
-    // Send email asynchronously in a separate task
+    // Send an email in the background. Only delivery happens after returning: a
+    // message that can't be built, or email being disabled, is reported to the caller.
     fn send_email_async(
         &self,
         to_email: String,
         subject: String,
         html_content: String,
         text_content: String,
-    ) {
-        // Clone necessary data for the task
-        let email_config = self.email_config.clone();
+    ) -> Result<(), AppError> {
+        self.ensure_enabled()?;
+        let transport = self.create_transport()?;
+        let email = self.build_message(&to_email, &subject, html_content, text_content)?;
 
-        // Spawn a new task to send the email
         task::spawn(async move {
-            // Create transport inside the task
-            let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&email_config.smtp_host)
-                .unwrap_or_else(|e| {
-                    tracing::error!("Failed to create SMTP transport: {}", e);
-                    panic!("Failed to create SMTP transport: {}", e);
-                })
-                .port(email_config.smtp_port)
-                .credentials(Credentials::new(
-                    email_config.smtp_username,
-                    email_config.smtp_password,
-                ))
-                .build();
-
-            // Build email message
-            let email_result = Message::builder()
-                .from(
-                    format!(
-                        "{} <{}>",
-                        email_config.sender_name, email_config.sender_email
-                    )
-                    .parse()
-                    .unwrap_or_else(|e| {
-                        tracing::error!("Invalid sender email: {}", e);
-                        panic!("Invalid sender email: {}", e);
-                    }),
-                )
-                .to(to_email.parse().unwrap_or_else(|e| {
-                    tracing::error!("Invalid recipient email: {}", e);
-                    panic!("Invalid recipient email: {}", e);
-                }))
-                .subject(subject)
-                .multipart(
-                    MultiPart::alternative()
-                        .singlepart(
-                            SinglePart::builder()
-                                .header(ContentType::TEXT_PLAIN)
-                                .body(text_content),
-                        )
-                        .singlepart(
-                            SinglePart::builder()
-                                .header(ContentType::TEXT_HTML)
-                                .body(html_content),
-                        ),
-                );
-
-            match email_result {
-                Ok(email) => {
-                    // Send the email
-                    if let Err(e) = transport.send(email).await {
-                        tracing::error!("Failed to send email: {}", e);
-                    } else {
-                        tracing::info!("Email sent successfully");
-                    }
-                }
-                Err(e) => {
-                    tracing::error!("Failed to build email: {}", e);
-                }
+            if let Err(e) = transport.send(email).await {
+                tracing::error!("Failed to send email to {}: {}", to_email, e);
+            } else {
+                tracing::info!("Email sent successfully");
             }
         });
+
+        Ok(())
     }
 
-    // Synchronous version of send_email (for cases where you want to wait for the email to be sent)
-    async fn send_email(
+    fn build_message(
         &self,
         to_email: &str,
         subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<(), AppError> {
-        // Create transport
-        let transport = self.create_transport()?;
-
-        // Build email message
-        let email = Message::builder()
+        html_content: String,
+        text_content: String,
+    ) -> Result<Message, AppError> {
+        Message::builder()
             .from(
                 format!(
                     "{} <{}>",
@@ -220,15 +311,37 @@ impl EmailService {
                     .singlepart(
                         SinglePart::builder()
                             .header(ContentType::TEXT_PLAIN)
-                            .body(text_content.to_string()),
+                            .body(text_content),
                     )
                     .singlepart(
                         SinglePart::builder()
                             .header(ContentType::TEXT_HTML)
-                            .body(html_content.to_string()),
+                            .body(html_content),
                     ),
             )
-            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))
+    }
+
+    // Synchronous version of send_email (for cases where you want to wait for the email to be sent)
+    async fn send_email(
+        &self,
+        to_email: &str,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), AppError> {
+        self.ensure_enabled()?;
+
+        // Create transport
+        let transport = self.create_transport()?;
+
+        // Build email message
+        let email = self.build_message(
+            to_email,
+            subject,
+            html_content.to_string(),
+            text_content.to_string(),
+        )?;
 
         // Send the email
         transport
@@ -241,14 +354,17 @@ impl EmailService {
 
     // Generate a verification token for email verification
     async fn generate_verification_token(&self, user_id: Uuid) -> Result<String, AppError> {
+        // No point in a token that can't be delivered
+        self.ensure_enabled()?;
+
         // Generate a random token
-        let token_string = self.generate_random_token(32)?;
+        let token_string = generate_secure_token(VERIFICATION_TOKEN_LENGTH);
 
         // Create token DTO
         let token_dto = CreateVerificationTokenDto {
             user_id: Some(user_id),
             token_type: TOKEN_TYPE_EMAIL_VERIFICATION.to_string(),
-            expires_in: 24 * 60 * 60, // 24 hours in seconds
+            expires_in: EMAIL_VERIFICATION_TOKEN_TTL,
         };
 
         // Create token in the database
@@ -259,17 +375,36 @@ impl EmailService {
 
         Ok(token_string)
     }
+}
 
-    // Helper to generate random token
-    fn generate_random_token(&self, length: usize) -> Result<String, AppError> {
-        use rand::{distributions::Alphanumeric, Rng};
-
-        let token: String = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(length)
-            .map(char::from)
-            .collect();
-
-        Ok(token)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_config, TestApp};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn disabled_email_sends_nothing_and_says_so(pool: PgPool) {
+        let mut config = test_config();
+        config.email.enabled = false;
+        let app = TestApp::with_config(pool.clone(), config);
+        let user = app.create_user("nina").await;
+
+        assert!(!app.email_service.is_enabled());
+        assert!(matches!(
+            app.email_service
+                .send_verification_email(user.id, &user.email, &user.username)
+                .await,
+            Err(AppError::EmailDisabled(_))
+        ));
+
+        // No token was issued for an email that never went out
+        let tokens: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM verification_tokens WHERE user_id = $1")
+                .bind(user.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(tokens, 0);
     }
 }
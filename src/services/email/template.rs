@@ -7,11 +7,20 @@ const EMAIL_STYLE_CSS: &str = include_str!("../../../templates/email/styles.html
 // Email templates - HTML versions
 const VERIFICATION_EMAIL_HTML: &str = include_str!("../../../templates/email/verification.html");
 const PASSWORD_RESET_HTML: &str = include_str!("../../../templates/email/password_reset.html");
+const SECONDARY_EMAIL_VERIFICATION_HTML: &str =
+    include_str!("../../../templates/email/secondary_email_verification.html");
+const DORMANCY_NOTICE_HTML: &str = include_str!("../../../templates/email/dormancy_notice.html");
+const INVITE_HTML: &str = include_str!("../../../templates/email/invite.html");
 
 // Email templates - Text versions
 const VERIFICATION_EMAIL_TEXT: &str =
     include_str!("../../../templates/email/verification_text.txt");
 const PASSWORD_RESET_TEXT: &str = include_str!("../../../templates/email/password_reset_text.txt");
+const SECONDARY_EMAIL_VERIFICATION_TEXT: &str =
+    include_str!("../../../templates/email/secondary_email_verification_text.txt");
+const DORMANCY_NOTICE_TEXT: &str =
+    include_str!("../../../templates/email/dormancy_notice_text.txt");
+const INVITE_TEXT: &str = include_str!("../../../templates/email/invite_text.txt");
 
 pub struct TemplateManager;
 
@@ -21,6 +30,9 @@ impl TemplateManager {
         let title = match template_name {
             "verification" => "Email Verification - Safatanc Connect",
             "password_reset" => "Password Reset - Safatanc Connect",
+            "secondary_email_verification" => "Confirm Your Email - Safatanc Connect",
+            "dormancy_notice" => "We Miss You - Safatanc Connect",
+            "invite" => "You're Invited - Safatanc Connect",
             _ => "Safatanc Connect",
         };
 
@@ -28,6 +40,9 @@ impl TemplateManager {
         let content_template = match template_name {
             "verification" => VERIFICATION_EMAIL_HTML,
             "password_reset" => PASSWORD_RESET_HTML,
+            "secondary_email_verification" => SECONDARY_EMAIL_VERIFICATION_HTML,
+            "dormancy_notice" => DORMANCY_NOTICE_HTML,
+            "invite" => INVITE_HTML,
             _ => panic!("Unknown template: {}", template_name),
         };
 
@@ -50,6 +65,9 @@ impl TemplateManager {
         let text_template = match template_name {
             "verification" => VERIFICATION_EMAIL_TEXT,
             "password_reset" => PASSWORD_RESET_TEXT,
+            "secondary_email_verification" => SECONDARY_EMAIL_VERIFICATION_TEXT,
+            "dormancy_notice" => DORMANCY_NOTICE_TEXT,
+            "invite" => INVITE_TEXT,
             _ => panic!("Unknown template: {}", template_name),
         };
 
@@ -0,0 +1,35 @@
+use tokio::sync::broadcast;
+
+use crate::models::event::AccountEvent;
+
+// Buffered events per subscriber before it starts lagging
+const EVENT_BUS_CAPACITY: usize = 256;
+
+// In-process fan-out of account events to live connections.
+// Events are only delivered to subscribers on this instance.
+pub struct EventBus {
+    sender: broadcast::Sender<AccountEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+
+    // Publish an event; having no connected subscribers is not an error
+    pub fn publish(&self, event: AccountEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    // Subscribe to all events; callers filter by user
+    pub fn subscribe(&self) -> broadcast::Receiver<AccountEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,3 @@
+pub mod event_bus;
+
+pub use event_bus::EventBus;
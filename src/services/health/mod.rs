@@ -0,0 +1,3 @@
+pub mod readiness;
+
+pub use readiness::HealthService;
@@ -0,0 +1,189 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::{redirect, Client as HttpClient};
+use sqlx::PgPool;
+
+use crate::db::pool::check_connection;
+use crate::models::common::health::{DependencyHealth, HealthStatus, ReadinessResponse};
+use crate::services::auth::AuthService;
+use crate::services::email::EmailService;
+
+// Longest a single probe may take before its dependency counts as degraded
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+// How long deep probe results are reused, so frequent checks don't hammer providers
+const DEEP_PROBE_TTL: Duration = Duration::from_secs(30);
+
+pub struct HealthService {
+    pool: PgPool,
+    email_service: Arc<EmailService>,
+    auth_service: Arc<AuthService>,
+    http_client: HttpClient,
+    deep_probes: Mutex<Option<(Instant, Vec<DependencyHealth>)>>,
+}
+
+impl HealthService {
+    pub fn new(
+        pool: PgPool,
+        email_service: Arc<EmailService>,
+        auth_service: Arc<AuthService>,
+    ) -> Self {
+        let http_client = HttpClient::builder()
+            .timeout(PROBE_TIMEOUT)
+            // Any answer from the provider itself will do
+            .redirect(redirect::Policy::none())
+            .build()
+            .expect("Failed to build health check HTTP client");
+
+        Self {
+            pool,
+            email_service,
+            auth_service,
+            http_client,
+            deep_probes: Mutex::new(None),
+        }
+    }
+
+    // Whether the service can take traffic. The database is always checked; a deep
+    // check adds SMTP (when email is enabled) and every active OAuth provider.
+    pub async fn readiness(&self, deep: bool) -> ReadinessResponse {
+        let database = probe("database", check_connection(&self.pool)).await;
+        let database_up = database.status == HealthStatus::Healthy;
+
+        let mut checks = vec![database];
+        if deep {
+            checks.extend(self.deep_probes().await);
+        }
+
+        let status = if !database_up {
+            HealthStatus::Unavailable
+        } else if checks
+            .iter()
+            .any(|check| check.status != HealthStatus::Healthy)
+        {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+
+        ReadinessResponse { status, checks }
+    }
+
+    async fn deep_probes(&self) -> Vec<DependencyHealth> {
+        if let Some((probed_at, probes)) = self.deep_probes.lock().unwrap().as_ref() {
+            if probed_at.elapsed() < DEEP_PROBE_TTL {
+                return probes.clone();
+            }
+        }
+
+        // Each provider in its own task, so a slow one doesn't hold up the rest
+        let providers: Vec<_> = self
+            .auth_service
+            .list_active_oauth_providers()
+            .await
+            // Not configured, or the database is down, which is reported already
+            .unwrap_or_default()
+            .into_iter()
+            .map(|provider| {
+                let client = self.http_client.clone();
+                tokio::spawn(probe(
+                    format!("oauth:{}", provider.provider_name),
+                    check_url(client, provider.auth_url),
+                ))
+            })
+            .collect();
+
+        let mut probes = Vec::new();
+        if self.email_service.is_enabled() {
+            probes.push(probe("smtp", self.email_service.check_connection()).await);
+        }
+        for provider in providers {
+            if let Ok(health) = provider.await {
+                probes.push(health);
+            }
+        }
+
+        *self.deep_probes.lock().unwrap() = Some((Instant::now(), probes.clone()));
+        probes
+    }
+}
+
+// Reachable when the server answers at all, except with a server error. Authorization
+// URLs often reject a HEAD without parameters, which still shows they're up.
+async fn check_url(client: HttpClient, url: String) -> Result<(), String> {
+    let response = client.head(&url).send().await.map_err(|e| e.to_string())?;
+    if response.status().is_server_error() {
+        return Err(format!("responded with {}", response.status()));
+    }
+    Ok(())
+}
+
+// Run a check within PROBE_TIMEOUT, timing it
+async fn probe<E: Display>(
+    name: impl Into<String>,
+    check: impl Future<Output = Result<(), E>>,
+) -> DependencyHealth {
+    let name = name.into();
+    let started = Instant::now();
+    let status = match tokio::time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(Ok(())) => HealthStatus::Healthy,
+        Ok(Err(e)) => {
+            tracing::warn!("Health check {} failed: {}", name, e);
+            HealthStatus::Degraded
+        }
+        Err(_) => {
+            tracing::warn!("Health check {} timed out after {:?}", name, PROBE_TIMEOUT);
+            HealthStatus::Degraded
+        }
+    };
+
+    DependencyHealth {
+        name,
+        status,
+        latency_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn readiness_checks_the_database_and_optionally_the_rest(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        let health = HealthService::new(pool, app.email_service, app.auth_service);
+
+        let readiness = health.readiness(false).await;
+        assert_eq!(readiness.status, HealthStatus::Healthy);
+        assert_eq!(readiness.checks.len(), 1);
+        assert_eq!(readiness.checks[0].name, "database");
+
+        // Nothing listens on the test SMTP port; no OAuth provider is set up
+        let readiness = health.readiness(true).await;
+        assert_eq!(readiness.status, HealthStatus::Degraded);
+        let smtp = &readiness.checks[1];
+        assert_eq!(
+            (smtp.name.as_str(), smtp.status),
+            ("smtp", HealthStatus::Degraded)
+        );
+        assert_eq!(readiness.checks.len(), 2);
+
+        // Probed again only once the results are stale
+        let again = health.readiness(true).await;
+        assert_eq!(again.checks[1].latency_ms, smtp.latency_ms);
+    }
+
+    #[tokio::test]
+    async fn unreachable_dependencies_are_degraded() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/authorize", listener.local_addr().unwrap());
+        drop(listener);
+
+        let health = probe("oauth:test", check_url(HttpClient::new(), url)).await;
+        assert_eq!(health.name, "oauth:test");
+        assert_eq!(health.status, HealthStatus::Degraded);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod resolver;
+
+pub use resolver::LocaleService;
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::config::LocaleConfig;
+use crate::db::repositories::Repositories;
+use crate::errors::AppError;
+
+// Decides which language to address a user in, so emails and responses agree on it
+pub struct LocaleService {
+    repos: Arc<Repositories>,
+    config: LocaleConfig,
+}
+
+impl LocaleService {
+    pub fn new(repos: Arc<Repositories>, config: LocaleConfig) -> Self {
+        Self { repos, config }
+    }
+
+    // The user's saved preference, then the client's Accept-Language, then the default.
+    // A saved locale that is no longer supported is skipped rather than an error.
+    pub async fn resolve(
+        &self,
+        user_id: Option<Uuid>,
+        accept_language: Option<&str>,
+    ) -> Result<String, AppError> {
+        let preferred = match user_id {
+            Some(user_id) => self.repos.user().find_locale(user_id).await?,
+            None => None,
+        };
+
+        let locale = preferred
+            .as_deref()
+            .and_then(|locale| self.config.find(locale))
+            .or_else(|| accept_language.and_then(|header| self.config.negotiate(header)))
+            .unwrap_or(&self.config.default);
+
+        Ok(locale.to_string())
+    }
+
+    // The locale the user chose, if any
+    pub async fn get_preference(&self, user_id: Uuid) -> Result<Option<String>, AppError> {
+        Ok(self.repos.user().find_locale(user_id).await?)
+    }
+
+    // Save the user's preferred locale, stored as the supported locale it matches, or
+    // clear it with None
+    pub async fn set_preference(
+        &self,
+        user_id: Uuid,
+        locale: Option<&str>,
+    ) -> Result<Option<String>, AppError> {
+        let locale = locale
+            .map(|locale| {
+                self.config.find(locale).ok_or_else(|| {
+                    AppError::Validation(format!(
+                        "Locale must be one of {}",
+                        self.config.supported.join(", ")
+                    ))
+                })
+            })
+            .transpose()?;
+
+        self.repos.user().update_locale(user_id, locale).await?;
+
+        Ok(locale.map(str::to_string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_config, TestApp};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn saved_locale_wins_over_the_client(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("joan").await;
+        let locales = LocaleService::new(app.repos.clone(), test_config().locale);
+
+        let resolve = |user_id, header| locales.resolve(user_id, header);
+        assert_eq!(resolve(None, None).await.unwrap(), "en");
+        assert_eq!(resolve(None, Some("id-ID,en;q=0.5")).await.unwrap(), "id");
+        assert_eq!(resolve(Some(user.id), Some("fr")).await.unwrap(), "en");
+
+        // Stored as the supported spelling
+        assert_eq!(
+            locales
+                .set_preference(user.id, Some("ID-id"))
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("id")
+        );
+        assert_eq!(resolve(Some(user.id), Some("en")).await.unwrap(), "id");
+
+        assert!(matches!(
+            locales.set_preference(user.id, Some("fr")).await,
+            Err(AppError::Validation(_))
+        ));
+
+        locales.set_preference(user.id, None).await.unwrap();
+        assert_eq!(locales.get_preference(user.id).await.unwrap(), None);
+        assert_eq!(resolve(Some(user.id), Some("en")).await.unwrap(), "en");
+    }
+}
@@ -1,8 +1,12 @@
 // Services will be implemented later
 
+pub mod audit;
 pub mod auth;
 pub mod badge;
 pub mod email;
+pub mod events;
+pub mod health;
+pub mod locale;
 pub mod scheduler;
 pub mod user;
 pub mod validation;
@@ -1,16 +1,51 @@
+use chrono::Utc;
+use serde_json::json;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
 
+use crate::config::DormancyConfig;
 use crate::db::repositories::Repositories;
+use crate::models::audit::{
+    CreateAuditLogDto, AUDIT_EVENT_DORMANCY_DEACTIVATE, AUDIT_EVENT_DORMANCY_NOTICE,
+};
+use crate::services::email::EmailService;
+
+// Maximum number of accounts handled per dormancy stage on each run
+const DORMANCY_BATCH_SIZE: i64 = 500;
+
+// Upper bound on how often idle sessions are swept
+const IDLE_SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct SchedulerService {
     repos: Arc<Repositories>,
+    dormancy: Option<(DormancyConfig, Arc<EmailService>)>,
+    session_idle_timeout: Option<Duration>,
 }
 
 impl SchedulerService {
     pub fn new(repos: Arc<Repositories>) -> Self {
-        Self { repos }
+        Self {
+            repos,
+            dormancy: None,
+            session_idle_timeout: None,
+        }
+    }
+
+    // Enable the dormant-account notice/deactivation task (if configured)
+    pub fn with_dormancy(
+        mut self,
+        config: DormancyConfig,
+        email_service: Arc<EmailService>,
+    ) -> Self {
+        self.dormancy = Some((config, email_service));
+        self
+    }
+
+    // Enable deactivating sessions that have been idle for the given number of seconds (0 disables)
+    pub fn with_session_idle_timeout(mut self, timeout_secs: u64) -> Self {
+        self.session_idle_timeout = (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs));
+        self
     }
 
     // Start background tasks
@@ -19,6 +54,27 @@ impl SchedulerService {
         tokio::spawn(async move {
             Self::run_token_cleanup(repos_clone).await;
         });
+
+        if let Some(timeout) = self.session_idle_timeout {
+            let repos_clone = self.repos.clone();
+            tokio::spawn(async move {
+                Self::run_idle_session_cleanup(repos_clone, timeout).await;
+            });
+        }
+
+        if let Some((config, email_service)) = self.dormancy.clone() {
+            if config.notice_enabled() {
+                let repos_clone = self.repos.clone();
+                tokio::spawn(async move {
+                    Self::run_dormancy_checks(repos_clone, config, email_service).await;
+                });
+            } else if config.deactivate_after_days > 0 {
+                tracing::warn!(
+                    "DORMANCY_DEACTIVATE_AFTER_DAYS is set but dormancy notices are disabled; \
+                     accounts are never deactivated without a notice"
+                );
+            }
+        }
     }
 
     // Periodically clean up expired tokens
@@ -38,4 +94,136 @@ impl SchedulerService {
             }
         }
     }
+
+    // Periodically deactivate sessions that have been idle for longer than the timeout.
+    // Requests already reject them; this keeps the sessions table in line.
+    async fn run_idle_session_cleanup(repos: Arc<Repositories>, timeout: Duration) {
+        let mut interval = time::interval(IDLE_SESSION_CHECK_INTERVAL.min(timeout));
+        loop {
+            interval.tick().await;
+            let idle_since = Utc::now()
+                - chrono::Duration::from_std(timeout).unwrap_or_else(|_| chrono::Duration::zero());
+            match repos.session().deactivate_idle(idle_since).await {
+                Ok(result) => {
+                    if result.rows_affected() > 0 {
+                        tracing::info!("Deactivated {} idle sessions", result.rows_affected());
+                    }
+                }
+                Err(err) => {
+                    tracing::error!("Error deactivating idle sessions: {:?}", err);
+                }
+            }
+        }
+    }
+
+    // Periodically warn dormant accounts, then deactivate them once the grace period has passed
+    async fn run_dormancy_checks(
+        repos: Arc<Repositories>,
+        config: DormancyConfig,
+        email_service: Arc<EmailService>,
+    ) {
+        let mut interval = time::interval(Duration::from_secs(config.check_interval));
+        loop {
+            interval.tick().await;
+            if config.deactivation_enabled() {
+                Self::deactivate_dormant_accounts(&repos, &config).await;
+            }
+            Self::send_dormancy_notices(&repos, &config, &email_service).await;
+        }
+    }
+
+    async fn send_dormancy_notices(
+        repos: &Repositories,
+        config: &DormancyConfig,
+        email_service: &EmailService,
+    ) {
+        let inactive_since = Utc::now() - chrono::Duration::days(config.notice_after_days);
+        let users = match repos
+            .user()
+            .find_dormant_without_notice(inactive_since, DORMANCY_BATCH_SIZE)
+            .await
+        {
+            Ok(users) => users,
+            Err(err) => {
+                tracing::error!("Error finding dormant accounts: {:?}", err);
+                return;
+            }
+        };
+
+        let deactivate_in_days = config
+            .deactivation_enabled()
+            .then_some(config.deactivate_after_days);
+
+        for user in &users {
+            if let Err(err) = email_service
+                .send_dormancy_notice_email(&user.email, &user.username, deactivate_in_days)
+                .await
+            {
+                tracing::error!("Failed to send dormancy notice to {}: {}", user.id, err);
+                continue;
+            }
+
+            // The audit row also marks the notice as sent, which starts the grace period
+            let dto = CreateAuditLogDto {
+                event_type: AUDIT_EVENT_DORMANCY_NOTICE.to_string(),
+                user_id: Some(user.id),
+                success: true,
+                details: Some(json!({
+                    "last_login_at": user.last_login_at,
+                    "deactivate_in_days": deactivate_in_days,
+                })),
+                ..Default::default()
+            };
+            if let Err(err) = repos.audit().create(&dto).await {
+                tracing::error!("Failed to write audit log: {}", err);
+            }
+        }
+
+        if !users.is_empty() {
+            tracing::info!("Sent dormancy notices to {} accounts", users.len());
+        }
+    }
+
+    async fn deactivate_dormant_accounts(repos: &Repositories, config: &DormancyConfig) {
+        let notified_before = Utc::now() - chrono::Duration::days(config.deactivate_after_days);
+        let users = match repos
+            .user()
+            .find_dormant_notified_before(notified_before, DORMANCY_BATCH_SIZE)
+            .await
+        {
+            Ok(users) => users,
+            Err(err) => {
+                tracing::error!("Error finding dormant accounts to deactivate: {:?}", err);
+                return;
+            }
+        };
+
+        let mut deactivated = 0;
+        for user in &users {
+            if let Err(err) = repos.user().update_active_status(user.id, false).await {
+                tracing::error!("Failed to deactivate dormant account {}: {}", user.id, err);
+                continue;
+            }
+            deactivated += 1;
+
+            if let Err(err) = repos.session().deactivate_all_for_user(user.id).await {
+                tracing::error!("Failed to revoke sessions for {}: {}", user.id, err);
+            }
+
+            let dto = CreateAuditLogDto {
+                event_type: AUDIT_EVENT_DORMANCY_DEACTIVATE.to_string(),
+                user_id: Some(user.id),
+                success: true,
+                details: Some(json!({ "last_login_at": user.last_login_at })),
+                ..Default::default()
+            };
+            if let Err(err) = repos.audit().create(&dto).await {
+                tracing::error!("Failed to write audit log: {}", err);
+            }
+        }
+
+        if deactivated > 0 {
+            tracing::info!("Deactivated {} dormant accounts", deactivated);
+        }
+    }
 }
@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::config::RegistrationMode;
+use crate::db::error::DatabaseError;
+use crate::db::repositories::Repositories;
+use crate::errors::AppError;
+use crate::models::auth::invite::{CreateInviteDto, InviteResponse, INVITE_TTL_HOURS};
+use crate::models::auth::token::VERIFICATION_TOKEN_LENGTH;
+use crate::models::user::{CreateUserDto, Role, User};
+use crate::services::auth::token::generate_secure_token;
+use crate::services::email::EmailService;
+use crate::services::user::UserManagementService;
+use crate::services::validation::{check_email_domain_allowed, validation_err_to_app_error};
+
+// Admin-issued invites and the self-registration that redeems them
+pub struct InviteService {
+    repos: Arc<Repositories>,
+    user_management: Arc<UserManagementService>,
+    email_service: Arc<EmailService>,
+    registration_mode: RegistrationMode,
+    allowed_email_domains: Vec<String>,
+}
+
+impl InviteService {
+    pub fn new(
+        repos: Arc<Repositories>,
+        user_management: Arc<UserManagementService>,
+        email_service: Arc<EmailService>,
+        registration_mode: RegistrationMode,
+    ) -> Self {
+        Self {
+            repos,
+            user_management,
+            email_service,
+            registration_mode,
+            allowed_email_domains: Vec::new(),
+        }
+    }
+
+    // Limit registration without an invite to these email domains
+    pub fn with_allowed_email_domains(mut self, domains: Vec<String>) -> Self {
+        self.allowed_email_domains = domains;
+        self
+    }
+
+    // Invite an email address and send it the registration link.
+    // The response is the only place the plaintext token is returned.
+    pub async fn create_invite(
+        &self,
+        admin_id: Uuid,
+        dto: CreateInviteDto,
+    ) -> Result<InviteResponse, AppError> {
+        dto.validate().map_err(validation_err_to_app_error)?;
+
+        let role = match dto.global_role.as_deref() {
+            Some(role) => role.parse()?,
+            None => Role::User,
+        };
+
+        if self.repos.user().find_by_email(&dto.email).await.is_ok() {
+            return Err(AppError::Database(DatabaseError::Duplicate(
+                "Email already exists".to_string(),
+            )));
+        }
+
+        let token = generate_secure_token(VERIFICATION_TOKEN_LENGTH);
+        let expires_at =
+            Utc::now() + Duration::hours(dto.expires_in_hours.unwrap_or(INVITE_TTL_HOURS));
+        let invite = self
+            .repos
+            .invite()
+            .create(&dto.email, &token, role.as_str(), admin_id, expires_at)
+            .await
+            .map_err(AppError::Database)?;
+
+        self.email_service
+            .send_invite_email(&invite.email, &token, invite.expires_at)
+            .await?;
+
+        Ok(InviteResponse {
+            token: Some(token),
+            ..InviteResponse::from(invite)
+        })
+    }
+
+    // Invites that haven't been used or expired yet
+    pub async fn list_pending(&self) -> Result<Vec<InviteResponse>, AppError> {
+        let invites = self
+            .repos
+            .invite()
+            .find_pending()
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(invites.into_iter().map(InviteResponse::from).collect())
+    }
+
+    // Withdraw an invite that hasn't been used
+    pub async fn revoke_invite(&self, id: Uuid) -> Result<(), AppError> {
+        self.repos
+            .invite()
+            .delete_pending(id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("Invite not found".into()),
+                _ => AppError::Database(e),
+            })
+    }
+
+    // Self-registration, as allowed by the registration mode. With an invite the
+    // account is created verified and with the invite's role; invites are an admin's
+    // decision, so they aren't held to the email domain allowlist.
+    pub async fn register(&self, dto: CreateUserDto) -> Result<User, AppError> {
+        self.registration_mode.check(dto.invite_token.is_some())?;
+
+        let Some(token) = dto.invite_token.clone() else {
+            check_email_domain_allowed(&dto.email, &self.allowed_email_domains)?;
+            return self.user_management.register_user(dto).await;
+        };
+
+        // Claim the invite first so two registrations can't both redeem it
+        let invite = self
+            .repos
+            .invite()
+            .claim(&token)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => {
+                    AppError::Validation("Invite is invalid or has expired".into())
+                }
+                _ => AppError::Database(e),
+            })?;
+
+        let registered = if invite.email.eq_ignore_ascii_case(&dto.email) {
+            self.user_management.register_user(dto).await
+        } else {
+            Err(AppError::Validation(
+                "This invite was issued for a different email address".into(),
+            ))
+        };
+        let user = match registered {
+            Ok(user) => user,
+            Err(e) => {
+                // Let the invitee try again
+                if let Err(release_err) = self.repos.invite().release(invite.id).await {
+                    tracing::error!("Failed to release invite {}: {}", invite.id, release_err);
+                }
+                return Err(e);
+            }
+        };
+
+        if invite.role() != user.role() {
+            self.user_management
+                .set_global_role(user.id, invite.role())
+                .await?;
+        }
+
+        // The invite went to this address, which proves the user controls it
+        self.repos
+            .user()
+            .update_email_verification(user.id, true)
+            .await
+            .map_err(AppError::Database)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::test_support::{test_config, TestApp, TEST_PASSWORD};
+    use sqlx::PgPool;
+
+    fn registration(username: &str, invite_token: Option<String>) -> CreateUserDto {
+        CreateUserDto {
+            email: format!("{}@example.com", username),
+            username: username.to_string(),
+            password: TEST_PASSWORD.to_string(),
+            full_name: None,
+            avatar_url: None,
+            invite_token,
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn invite_only_registration_requires_a_matching_unused_invite(pool: PgPool) {
+        let app = TestApp::with_config(
+            pool,
+            AppConfig {
+                registration_mode: RegistrationMode::InviteOnly,
+                ..test_config()
+            },
+        );
+        let admin = app.create_user("admin").await;
+
+        assert!(matches!(
+            app.invite_service.register(registration("eve", None)).await,
+            Err(AppError::RegistrationDisabled(_))
+        ));
+
+        let invite = app
+            .invite_service
+            .create_invite(
+                admin.id,
+                CreateInviteDto {
+                    email: "dave@example.com".to_string(),
+                    global_role: Some(Role::Admin.to_string()),
+                    expires_in_hours: None,
+                },
+            )
+            .await
+            .unwrap();
+        let token = invite.token.unwrap();
+
+        // Issued for someone else; the invite stays usable
+        assert!(matches!(
+            app.invite_service
+                .register(registration("eve", Some(token.clone())))
+                .await,
+            Err(AppError::Validation(_))
+        ));
+
+        let user = app
+            .invite_service
+            .register(registration("dave", Some(token.clone())))
+            .await
+            .unwrap();
+        assert!(user.is_email_verified);
+        assert_eq!(user.role(), Role::Admin);
+
+        // Single-use
+        assert!(app.invite_service.list_pending().await.unwrap().is_empty());
+        assert!(matches!(
+            app.invite_service
+                .register(registration("dave2", Some(token)))
+                .await,
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn closed_registration_refuses_even_invites(pool: PgPool) {
+        let app = TestApp::with_config(
+            pool,
+            AppConfig {
+                registration_mode: RegistrationMode::Closed,
+                ..test_config()
+            },
+        );
+        let admin = app.create_user("admin").await;
+        let invite = app
+            .invite_service
+            .create_invite(
+                admin.id,
+                CreateInviteDto {
+                    email: "dave@example.com".to_string(),
+                    global_role: None,
+                    expires_in_hours: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            app.invite_service
+                .register(registration("dave", invite.token))
+                .await,
+            Err(AppError::RegistrationDisabled(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn registration_is_limited_to_allowed_email_domains(pool: PgPool) {
+        let app = TestApp::with_config(
+            pool,
+            AppConfig {
+                allowed_email_domains: vec!["example.com".to_string()],
+                ..test_config()
+            },
+        );
+
+        let mut outsider = registration("mallory", None);
+        outsider.email = "mallory@example.org".to_string();
+        assert!(matches!(
+            app.invite_service.register(outsider).await,
+            Err(AppError::Validation(_))
+        ));
+
+        let mut insider = registration("carol", None);
+        insider.email = "carol@EXAMPLE.com".to_string();
+        assert!(app.invite_service.register(insider).await.is_ok());
+    }
+}
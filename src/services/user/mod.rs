@@ -1,3 +1,7 @@
+pub mod invite;
+pub mod user_email;
 pub mod user_management;
 
+pub use invite::InviteService;
+pub use user_email::UserEmailService;
 pub use user_management::UserManagementService;
@@ -0,0 +1,353 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::db::error::DatabaseError;
+use crate::db::repositories::Repositories;
+use crate::errors::AppError;
+use crate::models::auth::token::{
+    CreateVerificationTokenDto, PendingTokenResponse, VerificationToken,
+    EMAIL_VERIFICATION_TOKEN_TTL, TOKEN_TYPE_SECONDARY_EMAIL_VERIFICATION,
+    VERIFICATION_TOKEN_LENGTH,
+};
+use crate::models::user::{
+    AddUserEmailDto, User, UserEmail, UserEmailResponse, MAX_SECONDARY_EMAILS,
+};
+use crate::services::auth::token::generate_secure_token;
+use crate::services::email::EmailService;
+use crate::services::validation::validation_err_to_app_error;
+
+// Manages the secondary (backup) email addresses of an account
+pub struct UserEmailService {
+    repos: Arc<Repositories>,
+    email_service: Arc<EmailService>,
+}
+
+impl UserEmailService {
+    pub fn new(repos: Arc<Repositories>, email_service: Arc<EmailService>) -> Self {
+        Self {
+            repos,
+            email_service,
+        }
+    }
+
+    // List the account's addresses, primary first
+    pub async fn list_emails(&self, user_id: Uuid) -> Result<Vec<UserEmailResponse>, AppError> {
+        let user = self.find_user(user_id).await?;
+        let secondary = self
+            .repos
+            .user_email()
+            .find_by_user_id(user_id)
+            .await
+            .map_err(AppError::Database)?;
+
+        let mut emails = vec![UserEmailResponse {
+            id: user.id,
+            email: user.email,
+            is_primary: true,
+            is_verified: user.is_email_verified,
+            verified_at: None,
+            created_at: user.created_at,
+        }];
+        emails.extend(secondary.into_iter().map(UserEmailResponse::from));
+
+        Ok(emails)
+    }
+
+    // Add a secondary address and send it a verification link
+    pub async fn add_email(
+        &self,
+        user_id: Uuid,
+        dto: AddUserEmailDto,
+    ) -> Result<UserEmailResponse, AppError> {
+        dto.validate().map_err(validation_err_to_app_error)?;
+
+        let user = self.find_user(user_id).await?;
+
+        // The address must not already belong to any account, including this one
+        match self.repos.user().find_by_email(&dto.email).await {
+            Ok(owner) if owner.id == user_id => {
+                return Err(AppError::Validation(
+                    "Email is already added to this account".to_string(),
+                ))
+            }
+            Ok(_) => {
+                return Err(AppError::Database(DatabaseError::Duplicate(
+                    "Email already exists".to_string(),
+                )))
+            }
+            Err(DatabaseError::NotFound) => {}
+            Err(e) => return Err(AppError::Database(e)),
+        }
+
+        let count = self
+            .repos
+            .user_email()
+            .count_for_user(user_id)
+            .await
+            .map_err(AppError::Database)?;
+        if count >= MAX_SECONDARY_EMAILS {
+            return Err(AppError::Validation(format!(
+                "An account can have at most {} secondary emails",
+                MAX_SECONDARY_EMAILS
+            )));
+        }
+
+        let email = self
+            .repos
+            .user_email()
+            .create(user_id, &dto.email)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::Duplicate(msg) => AppError::Validation(msg),
+                _ => AppError::Database(e),
+            })?;
+
+        self.send_verification(&user, &email).await?;
+
+        Ok(UserEmailResponse::from(email))
+    }
+
+    // Send a fresh verification link for an unverified secondary address
+    pub async fn resend_verification(&self, user_id: Uuid, email_id: Uuid) -> Result<(), AppError> {
+        let user = self.find_user(user_id).await?;
+        let email = self.find_email(user_id, email_id).await?;
+
+        if email.is_verified {
+            return Err(AppError::Validation(
+                "Email is already verified".to_string(),
+            ));
+        }
+
+        self.send_verification(&user, &email).await
+    }
+
+    // Check a secondary address's verification token without using it
+    pub async fn check_verification_token(
+        &self,
+        token: &str,
+    ) -> Result<PendingTokenResponse, AppError> {
+        let (verification_token, email) = self.find_pending_verification(token).await?;
+
+        Ok(PendingTokenResponse {
+            token_type: verification_token.token_type,
+            email: email.email,
+            expires_at: verification_token.expires_at,
+        })
+    }
+
+    // Confirm a secondary address from the token in its verification link
+    pub async fn verify_email(&self, token: &str) -> Result<UserEmailResponse, AppError> {
+        let (verification_token, email) = self.find_pending_verification(token).await?;
+
+        // Someone else may have claimed the address while this one was pending
+        match self.repos.user().find_by_email(&email.email).await {
+            Ok(owner) if owner.id != email.user_id => {
+                return Err(AppError::Database(DatabaseError::Duplicate(
+                    "Email already exists".to_string(),
+                )))
+            }
+            Ok(_) | Err(DatabaseError::NotFound) => {}
+            Err(e) => return Err(AppError::Database(e)),
+        }
+
+        let email = self
+            .repos
+            .user_email()
+            .mark_verified(email.id)
+            .await
+            .map_err(AppError::Database)?;
+
+        self.repos
+            .token()
+            .mark_as_used(verification_token.id)
+            .await
+            .map_err(AppError::Database)?;
+
+        Ok(UserEmailResponse::from(email))
+    }
+
+    // The token and the address it verifies. Only the most recently issued link for
+    // an address is valid.
+    async fn find_pending_verification(
+        &self,
+        token: &str,
+    ) -> Result<(VerificationToken, UserEmail), AppError> {
+        let invalid_token =
+            || AppError::InvalidToken("Invalid or expired verification token".into());
+
+        let verification_token = self
+            .repos
+            .token()
+            .verify_token(token, TOKEN_TYPE_SECONDARY_EMAIL_VERIFICATION)
+            .await
+            .map_err(|_| invalid_token())?;
+
+        let email = self
+            .repos
+            .user_email()
+            .find_by_verification_token(verification_token.id)
+            .await
+            .map_err(|_| invalid_token())?;
+
+        Ok((verification_token, email))
+    }
+
+    // Remove a secondary address from the account
+    pub async fn remove_email(&self, user_id: Uuid, email_id: Uuid) -> Result<(), AppError> {
+        let email = self.find_email(user_id, email_id).await?;
+
+        self.repos
+            .user_email()
+            .delete(email.id)
+            .await
+            .map_err(AppError::Database)
+    }
+
+    async fn send_verification(&self, user: &User, email: &UserEmail) -> Result<(), AppError> {
+        let token_string = generate_secure_token(VERIFICATION_TOKEN_LENGTH);
+        let token_dto = CreateVerificationTokenDto {
+            user_id: Some(user.id),
+            token_type: TOKEN_TYPE_SECONDARY_EMAIL_VERIFICATION.to_string(),
+            expires_in: EMAIL_VERIFICATION_TOKEN_TTL,
+        };
+
+        let token = self
+            .repos
+            .token()
+            .create(&token_dto, &token_string)
+            .await
+            .map_err(AppError::Database)?;
+
+        self.repos
+            .user_email()
+            .set_verification_token(email.id, token.id)
+            .await
+            .map_err(AppError::Database)?;
+
+        self.email_service
+            .send_secondary_email_verification(&email.email, &user.username, &token_string)
+            .await
+    }
+
+    async fn find_user(&self, user_id: Uuid) -> Result<User, AppError> {
+        self.repos
+            .user()
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })
+    }
+
+    async fn find_email(&self, user_id: Uuid, email_id: Uuid) -> Result<UserEmail, AppError> {
+        self.repos
+            .user_email()
+            .find_by_id_for_user(email_id, user_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("Email not found".into()),
+                _ => AppError::Database(e),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn verified_secondary_addresses_recover_the_account(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("pia").await;
+        let service = &app.user_email_service;
+
+        let added = service
+            .add_email(
+                user.id,
+                AddUserEmailDto {
+                    email: "pia.backup@example.com".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        assert!(!added.is_verified);
+
+        // Unverified addresses don't find the account or receive reset links
+        assert!(matches!(
+            app.repos
+                .user()
+                .find_by_email("pia.backup@example.com")
+                .await,
+            Err(DatabaseError::NotFound)
+        ));
+        assert!(app
+            .auth_service
+            .request_password_reset("pia.backup@example.com")
+            .await
+            .unwrap()
+            .is_none());
+
+        // The emailed link's token isn't returned, so issue a known one the same way
+        let token = "secondary-verification-token";
+        let verification_token = app
+            .repos
+            .token()
+            .create(
+                &CreateVerificationTokenDto {
+                    user_id: Some(user.id),
+                    token_type: TOKEN_TYPE_SECONDARY_EMAIL_VERIFICATION.to_string(),
+                    expires_in: EMAIL_VERIFICATION_TOKEN_TTL,
+                },
+                token,
+            )
+            .await
+            .unwrap();
+        app.repos
+            .user_email()
+            .set_verification_token(added.id, verification_token.id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            service.check_verification_token(token).await.unwrap().email,
+            "pia.backup@example.com"
+        );
+        let verified = service.verify_email(token).await.unwrap();
+        assert!(verified.is_verified);
+        assert!(matches!(
+            service.verify_email(token).await,
+            Err(AppError::InvalidToken(_))
+        ));
+
+        let owner = app
+            .repos
+            .user()
+            .find_by_email("pia.backup@example.com")
+            .await
+            .unwrap();
+        assert_eq!(owner.id, user.id);
+
+        // The reset link goes to the secondary address it was asked for with
+        let (reset_user, address, reset_token) = app
+            .auth_service
+            .request_password_reset("pia.backup@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reset_user.id, user.id);
+        assert_eq!(address, "pia.backup@example.com");
+        app.auth_service
+            .reset_password(&reset_token, "Recovered1!")
+            .await
+            .unwrap();
+
+        let emails = service.list_emails(user.id).await.unwrap();
+        assert_eq!(emails.len(), 2);
+        assert!(emails[0].is_primary && !emails[1].is_primary);
+    }
+}
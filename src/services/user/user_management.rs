@@ -2,22 +2,75 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::config::{AvatarConfig, BootstrapAdminConfig};
 use crate::db::error::DatabaseError;
 use crate::db::repositories::UserRepository;
 use crate::errors::AppError;
-use crate::models::user::{CreateUserDto, UpdateUserDto, User, UserResponse};
-use crate::services::validation::validation_err_to_app_error;
+use crate::models::common::bulk::{
+    BulkOperationResult, BULK_SKIP_ALREADY_INACTIVE, BULK_SKIP_DUPLICATE, BULK_SKIP_SELF,
+    BULK_SKIP_USER_NOT_FOUND,
+};
+use crate::models::common::filter::CreatedRangeQuery;
+use crate::models::event::{AccountEvent, AccountEventKind};
+use crate::models::user::{
+    ChangeUsernameDto, CreateUserDto, Role, UpdateUserDto, User, UserMergeResponse, UserResponse,
+};
+use crate::services::events::EventBus;
+use crate::services::validation::{
+    check_username_not_reserved, field_validation_error, validation_err_to_app_error,
+};
 
 pub struct UserManagementService {
     user_repo: UserRepository,
+    events: Arc<EventBus>,
+    password_history_size: usize,
+    avatars: AvatarConfig,
+    username_change_cooldown_days: i64,
+    username_reservation_days: i64,
 }
 
 impl UserManagementService {
-    pub fn new(user_repo: UserRepository) -> Self {
-        Self { user_repo }
+    pub fn new(user_repo: UserRepository, events: Arc<EventBus>) -> Self {
+        Self {
+            user_repo,
+            events,
+            password_history_size: 0,
+            avatars: AvatarConfig::default(),
+            username_change_cooldown_days: 0,
+            username_reservation_days: 0,
+        }
+    }
+
+    // Reject new passwords matching any of the last `size` (including the current one)
+    pub fn with_password_history(mut self, size: usize) -> Self {
+        self.password_history_size = size;
+        self
+    }
+
+    // Fall back to a default avatar in responses for users without one
+    pub fn with_avatars(mut self, avatars: AvatarConfig) -> Self {
+        self.avatars = avatars;
+        self
+    }
+
+    // Let users change their own username once per `cooldown_days`, keeping the old one
+    // from others for `reservation_days` so it can't be taken to pose as them
+    pub fn with_username_policy(mut self, cooldown_days: i64, reservation_days: i64) -> Self {
+        self.username_change_cooldown_days = cooldown_days;
+        self.username_reservation_days = reservation_days;
+        self
+    }
+
+    // How a user is shown to API clients
+    pub fn user_response(&self, user: User) -> UserResponse {
+        UserResponse::from(user).with_default_avatar(&self.avatars)
     }
 
     // Register new user
@@ -25,6 +78,25 @@ impl UserManagementService {
         // Validate DTO
         dto.validate().map_err(validation_err_to_app_error)?;
 
+        // The address may already be a verified secondary email on another account
+        if self.user_repo.find_by_email(&dto.email).await.is_ok() {
+            return Err(AppError::Database(DatabaseError::Duplicate(
+                "Email already exists".to_string(),
+            )));
+        }
+
+        // Another user may still be holding on to a username they gave up
+        if !self
+            .user_repo
+            .is_username_available(&dto.username, None)
+            .await
+            .map_err(AppError::Database)?
+        {
+            return Err(AppError::Database(DatabaseError::Duplicate(
+                "Username already exists".to_string(),
+            )));
+        }
+
         // Hash password using Argon2
         let password_hash = self.hash_password(&dto.password)?;
 
@@ -38,6 +110,65 @@ impl UserManagementService {
         Ok(user)
     }
 
+    // Create the first admin account (verified) unless an admin already exists.
+    // Returns the new admin, or None when there was nothing to do.
+    pub async fn bootstrap_admin(
+        &self,
+        config: &BootstrapAdminConfig,
+    ) -> Result<Option<User>, AppError> {
+        if self
+            .user_repo
+            .exists_with_role(Role::Admin.as_str())
+            .await
+            .map_err(AppError::Database)?
+        {
+            return Ok(None);
+        }
+
+        let admin = self
+            .create_admin(CreateUserDto {
+                email: config.email.clone(),
+                username: config.username.clone(),
+                password: config.password.clone(),
+                full_name: None,
+                avatar_url: None,
+                invite_token: None,
+            })
+            .await?;
+
+        Ok(Some(admin))
+    }
+
+    // Register an account that starts out as a verified admin
+    pub async fn create_admin(&self, dto: CreateUserDto) -> Result<User, AppError> {
+        let user = self.register_user(dto).await?;
+
+        self.set_global_role(user.id, Role::Admin).await?;
+        self.user_repo
+            .update_email_verification(user.id, true)
+            .await
+            .map_err(AppError::Database)
+    }
+
+    // Change a user's global role
+    pub async fn set_global_role(&self, id: Uuid, role: Role) -> Result<UserResponse, AppError> {
+        let user = self
+            .user_repo
+            .update_role(id, role.as_str())
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        self.events.publish(AccountEvent::new(
+            id,
+            AccountEventKind::RoleChanged { role },
+        ));
+
+        Ok(self.user_response(user))
+    }
+
     // Get user data by ID
     pub async fn get_user_by_id(&self, id: Uuid) -> Result<UserResponse, AppError> {
         let user = self.user_repo.find_by_id(id).await.map_err(|e| match e {
@@ -45,21 +176,24 @@ impl UserManagementService {
             _ => AppError::Database(e),
         })?;
 
-        Ok(UserResponse::from(user))
+        Ok(self.user_response(user))
     }
 
-    // Get user data by email
-    pub async fn get_user_by_email(&self, email: &str) -> Result<UserResponse, AppError> {
+    // Get user data by ID even if the account was deleted (admin only)
+    pub async fn get_user_by_id_including_deleted(
+        &self,
+        id: Uuid,
+    ) -> Result<UserResponse, AppError> {
         let user = self
             .user_repo
-            .find_by_email(email)
+            .find_by_id_including_deleted(id)
             .await
             .map_err(|e| match e {
                 DatabaseError::NotFound => AppError::NotFound("User not found".into()),
                 _ => AppError::Database(e),
             })?;
 
-        Ok(UserResponse::from(user))
+        Ok(self.user_response(user))
     }
 
     // Get all users with pagination
@@ -67,6 +201,8 @@ impl UserManagementService {
         &self,
         page: i64,
         limit: i64,
+        include_deleted: bool,
+        created: &CreatedRangeQuery,
     ) -> Result<(Vec<UserResponse>, u64), AppError> {
         // Calculate offset from page
         let offset = (page - 1) * limit;
@@ -74,15 +210,22 @@ impl UserManagementService {
         // Get users
         let users = self
             .user_repo
-            .find_all(limit, offset)
+            .find_all(limit, offset, include_deleted, created)
             .await
             .map_err(AppError::Database)?;
 
         // Get total count
-        let total = self.user_repo.count().await.map_err(AppError::Database)? as u64;
+        let total = self
+            .user_repo
+            .count(include_deleted, created)
+            .await
+            .map_err(AppError::Database)? as u64;
 
         // Convert to UserResponse
-        let user_responses = users.into_iter().map(UserResponse::from).collect();
+        let user_responses = users
+            .into_iter()
+            .map(|user| self.user_response(user))
+            .collect();
 
         Ok((user_responses, total))
     }
@@ -96,6 +239,18 @@ impl UserManagementService {
         // Validate DTO
         dto.validate().map_err(validation_err_to_app_error)?;
 
+        // Another user may still be holding on to a username they gave up
+        if let Some(username) = &dto.username {
+            if !self
+                .user_repo
+                .is_username_available(username, Some(id))
+                .await
+                .map_err(AppError::Database)?
+            {
+                return Err(AppError::Validation("Username already exists".into()));
+            }
+        }
+
         // Update user in database
         let user = self.user_repo.update(id, &dto).await.map_err(|e| match e {
             DatabaseError::NotFound => AppError::NotFound("User not found".into()),
@@ -103,7 +258,101 @@ impl UserManagementService {
             _ => AppError::Database(e),
         })?;
 
-        Ok(UserResponse::from(user))
+        Ok(self.user_response(user))
+    }
+
+    // Update the user's own profile. A new username goes through change_username, so
+    // its cooldown applies here too; admins editing others use update_user.
+    pub async fn update_profile(
+        &self,
+        id: Uuid,
+        dto: UpdateUserDto,
+    ) -> Result<UserResponse, AppError> {
+        dto.validate().map_err(validation_err_to_app_error)?;
+
+        let user = self.user_repo.find_by_id(id).await.map_err(|e| match e {
+            DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+            _ => AppError::Database(e),
+        })?;
+        if let Some(username) = dto.username.clone().filter(|u| *u != user.username) {
+            self.change_username(id, ChangeUsernameDto { username })
+                .await?;
+        }
+
+        self.update_user(id, dto).await
+    }
+
+    // Change the user's own username, at most once per cooldown
+    pub async fn change_username(
+        &self,
+        id: Uuid,
+        dto: ChangeUsernameDto,
+    ) -> Result<UserResponse, AppError> {
+        dto.validate().map_err(validation_err_to_app_error)?;
+        check_username_not_reserved(&dto.username)?;
+
+        let user = self.user_repo.find_by_id(id).await.map_err(|e| match e {
+            DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+            _ => AppError::Database(e),
+        })?;
+        if dto.username == user.username {
+            return Ok(self.user_response(user));
+        }
+
+        let changed_at = self
+            .user_repo
+            .find_username_changed_at(id)
+            .await
+            .map_err(AppError::Database)?;
+        if let Some(available_at) = self.username_change_available_at(changed_at) {
+            return Err(AppError::UsernameChangeCooldown(available_at));
+        }
+
+        if !self
+            .user_repo
+            .is_username_available(&dto.username, Some(id))
+            .await
+            .map_err(AppError::Database)?
+        {
+            return Err(AppError::Database(DatabaseError::Duplicate(
+                "Username already exists".to_string(),
+            )));
+        }
+
+        let now = Utc::now();
+        let reserve_until = (self.username_reservation_days > 0)
+            .then(|| now + Duration::days(self.username_reservation_days));
+        let changed = self
+            .user_repo
+            .change_username(
+                id,
+                &dto.username,
+                now - Duration::days(self.username_change_cooldown_days),
+                reserve_until,
+            )
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        match changed {
+            Some(user) => Ok(self.user_response(user)),
+            // Changed by another request since the check above
+            None => Err(AppError::UsernameChangeCooldown(
+                now + Duration::days(self.username_change_cooldown_days),
+            )),
+        }
+    }
+
+    // When a username last changed at `changed_at` may be changed again, if not yet
+    fn username_change_available_at(
+        &self,
+        changed_at: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        changed_at
+            .map(|changed_at| changed_at + Duration::days(self.username_change_cooldown_days))
+            .filter(|available_at| *available_at > Utc::now())
     }
 
     // Update user password
@@ -126,16 +375,7 @@ impl UserManagementService {
         // Verify current password
         self.verify_password(current_password, &user.password_hash)?;
 
-        // Hash new password
-        let new_password_hash = self.hash_password(new_password)?;
-
-        // Update password in database
-        self.user_repo
-            .update_password(id, &new_password_hash)
-            .await
-            .map_err(AppError::Database)?;
-
-        Ok(())
+        self.set_password(&user, new_password).await
     }
 
     // Update user password directly (for admin)
@@ -144,18 +384,67 @@ impl UserManagementService {
         crate::services::validation::validate_password_strength(new_password)
             .map_err(|e| AppError::Validation(e.to_string()))?;
 
+        let user = self.user_repo.find_by_id(id).await.map_err(|e| match e {
+            DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+            _ => AppError::Database(e),
+        })?;
+
+        self.set_password(&user, new_password).await
+    }
+
+    // Store a new (already validated) password, enforcing and updating the password history
+    pub async fn set_password(&self, user: &User, new_password: &str) -> Result<(), AppError> {
+        // Checked against the hash, so it also covers resets and admins setting a password
+        if self
+            .verify_password(new_password, &user.password_hash)
+            .is_ok()
+        {
+            return Err(field_validation_error("new_password", "password_unchanged"));
+        }
+
+        // The current password, already checked, is the first of the last N
+        if self.password_history_size > 1 {
+            let previous = self
+                .user_repo
+                .find_password_history(user.id, self.password_history_size as i64 - 1)
+                .await
+                .map_err(AppError::Database)?;
+
+            if previous
+                .iter()
+                .any(|hash| self.verify_password(new_password, hash).is_ok())
+            {
+                return Err(AppError::PasswordReused(format!(
+                    "New password must differ from your last {} passwords",
+                    self.password_history_size
+                )));
+            }
+        }
+
         // Hash new password
         let new_password_hash = self.hash_password(new_password)?;
 
         // Update password in database
         self.user_repo
-            .update_password(id, &new_password_hash)
+            .update_password(user.id, &new_password_hash)
             .await
             .map_err(|e| match e {
                 DatabaseError::NotFound => AppError::NotFound("User not found".into()),
                 _ => AppError::Database(e),
             })?;
 
+        // The replaced password joins the history; the current one is always checked directly
+        if self.password_history_size > 1 {
+            self.user_repo
+                .add_password_history(
+                    user.id,
+                    &user.password_hash,
+                    self.password_history_size as i64 - 1,
+                )
+                .await
+                .map_err(AppError::Database)?;
+        }
+
         Ok(())
     }
 
@@ -169,6 +458,99 @@ impl UserManagementService {
         Ok(())
     }
 
+    // Fold a duplicate account into the one the person keeps using. The source is
+    // soft-deleted and signed out; its badges and OAuth connections go to the target.
+    pub async fn merge_users(
+        &self,
+        source_id: Uuid,
+        target_id: Uuid,
+    ) -> Result<UserMergeResponse, AppError> {
+        if source_id == target_id {
+            return Err(AppError::Validation(
+                "Cannot merge an account into itself".into(),
+            ));
+        }
+
+        let merged = self
+            .user_repo
+            .merge_into(source_id, target_id)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        Ok(UserMergeResponse {
+            source_id,
+            target: self.get_user_by_id(target_id).await?,
+            merged,
+        })
+    }
+
+    // Activate or deactivate a user account
+    pub async fn set_user_active(
+        &self,
+        id: Uuid,
+        is_active: bool,
+    ) -> Result<UserResponse, AppError> {
+        let user = self
+            .user_repo
+            .update_active_status(id, is_active)
+            .await
+            .map_err(|e| match e {
+                DatabaseError::NotFound => AppError::NotFound("User not found".into()),
+                _ => AppError::Database(e),
+            })?;
+
+        Ok(self.user_response(user))
+    }
+
+    // Deactivate many accounts at once on behalf of an admin. With dry_run, runs the
+    // same checks and reports which accounts would be deactivated without writing anything.
+    pub async fn bulk_deactivate_users(
+        &self,
+        user_ids: &[Uuid],
+        actor_id: Uuid,
+        dry_run: bool,
+    ) -> Result<BulkOperationResult, AppError> {
+        let mut result = BulkOperationResult::new(dry_run, user_ids.len());
+        let mut seen = HashSet::new();
+
+        for &user_id in user_ids {
+            if !seen.insert(user_id) {
+                result.skip(user_id, BULK_SKIP_DUPLICATE);
+                continue;
+            }
+
+            if user_id == actor_id {
+                result.skip(user_id, BULK_SKIP_SELF);
+                continue;
+            }
+
+            let user = match self.user_repo.find_by_id(user_id).await {
+                Ok(user) => user,
+                Err(DatabaseError::NotFound) => {
+                    result.skip(user_id, BULK_SKIP_USER_NOT_FOUND);
+                    continue;
+                }
+                Err(e) => return Err(AppError::Database(e)),
+            };
+
+            if !user.is_active {
+                result.skip(user_id, BULK_SKIP_ALREADY_INACTIVE);
+                continue;
+            }
+
+            if !dry_run {
+                self.set_user_active(user_id, false).await?;
+            }
+
+            result.succeeded.push(user_id);
+        }
+
+        Ok(result)
+    }
+
     // Verify user email
     pub async fn verify_email(&self, id: Uuid) -> Result<UserResponse, AppError> {
         let user = self
@@ -180,7 +562,10 @@ impl UserManagementService {
                 _ => AppError::Database(e),
             })?;
 
-        Ok(UserResponse::from(user))
+        self.events
+            .publish(AccountEvent::new(id, AccountEventKind::EmailVerified));
+
+        Ok(self.user_response(user))
     }
 
     // Helper function to hash password
@@ -194,6 +579,17 @@ impl UserManagementService {
             .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
     }
 
+    // Spend as long as verify_password would when there is no hash to check against,
+    // so how long a login takes doesn't tell whether the account exists
+    pub fn verify_dummy_password(&self, password: &str) {
+        static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+        let hash = DUMMY_HASH.get_or_init(|| {
+            self.hash_password("not anyone's password")
+                .expect("hashing a constant cannot fail")
+        });
+        let _ = self.verify_password(password, hash);
+    }
+
     // Helper function to verify password
     pub fn verify_password(&self, password: &str, hash: &str) -> Result<(), AppError> {
         let parsed_hash = PasswordHash::new(hash)
@@ -204,3 +600,473 @@ impl UserManagementService {
             .map_err(|_| AppError::Authentication("Email or password incorrect".into()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_client, TestApp, TEST_PASSWORD};
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn bootstrap_admin_runs_only_once(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let config = BootstrapAdminConfig {
+            email: "root@example.com".to_string(),
+            password: TEST_PASSWORD.to_string(),
+            username: "admin".to_string(),
+        };
+
+        let admin = app
+            .user_management
+            .bootstrap_admin(&config)
+            .await
+            .unwrap()
+            .expect("admin should be created");
+        assert_eq!(admin.role(), Role::Admin);
+        assert!(admin.is_email_verified);
+
+        // Once an admin exists, later startups leave everything alone
+        let second = BootstrapAdminConfig {
+            email: "other@example.com".to_string(),
+            ..config
+        };
+        assert!(app
+            .user_management
+            .bootstrap_admin(&second)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(app
+            .repos
+            .user()
+            .find_by_email("other@example.com")
+            .await
+            .is_err());
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn role_changes_are_pushed_to_the_user(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("gwen").await;
+        let mut events = app.events.subscribe();
+
+        app.user_management
+            .set_global_role(user.id, Role::Admin)
+            .await
+            .unwrap();
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.user_id, user.id);
+        assert!(matches!(
+            event.kind,
+            AccountEventKind::RoleChanged { role: Role::Admin }
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn password_history_rejects_recent_passwords(pool: PgPool) {
+        let mut config = crate::test_support::test_config();
+        config.password_history_size = 3;
+        let app = TestApp::with_config(pool, config);
+        let user = app.create_user("erin").await;
+        let service = &app.user_management;
+
+        // Keeping the current password isn't a change at all
+        assert!(matches!(
+            service
+                .update_password(user.id, TEST_PASSWORD, TEST_PASSWORD)
+                .await,
+            Err(AppError::Validation(msg)) if msg.contains("different from the current")
+        ));
+
+        service
+            .update_password(user.id, TEST_PASSWORD, "Second1!")
+            .await
+            .unwrap();
+        service
+            .update_password(user.id, "Second1!", "Third33!")
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.update_user_password(user.id, TEST_PASSWORD).await,
+            Err(AppError::PasswordReused(_))
+        ));
+
+        // A fourth change pushes the original password out of the window
+        service
+            .update_password(user.id, "Third33!", "Fourth4!")
+            .await
+            .unwrap();
+        service
+            .update_user_password(user.id, TEST_PASSWORD)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn changing_an_expired_password_restarts_its_age(pool: PgPool) {
+        let mut config = crate::test_support::test_config();
+        config.password_max_age_days = 90;
+        let app = TestApp::with_config(pool.clone(), config);
+        let user = app.create_user("frank").await;
+        assert!(!app.token_service.is_password_expired(&user));
+
+        sqlx::query(
+            "UPDATE users SET password_changed_at = now() - interval '91 days' WHERE id = $1",
+        )
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let user = app.repos.user().find_by_id(user.id).await.unwrap();
+        assert!(app.token_service.is_password_expired(&user));
+
+        app.user_management
+            .update_password(user.id, TEST_PASSWORD, "Rotated1!")
+            .await
+            .unwrap();
+        let user = app.repos.user().find_by_id(user.id).await.unwrap();
+        assert!(!app.token_service.is_password_expired(&user));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn users_without_an_avatar_get_the_default(pool: PgPool) {
+        let mut config = crate::test_support::test_config();
+        config.avatars.default_url = Some("https://cdn.example.com/avatar.png".to_string());
+        let app = TestApp::with_config(pool, config);
+        let user = app.create_user("grace").await;
+
+        let response = app.user_management.get_user_by_id(user.id).await.unwrap();
+        assert_eq!(
+            response.avatar_url.as_deref(),
+            Some("https://cdn.example.com/avatar.png")
+        );
+        // Only responses get the default; nothing is stored
+        let user = app.repos.user().find_by_id(user.id).await.unwrap();
+        assert!(user.avatar_url.is_none());
+
+        let response = app
+            .user_management
+            .update_user(
+                user.id,
+                UpdateUserDto {
+                    avatar_url: Some(Some("https://example.com/grace.png".to_string())),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.avatar_url.as_deref(),
+            Some("https://example.com/grace.png")
+        );
+
+        // Clearing it brings the default back
+        let response = app
+            .user_management
+            .update_user(
+                user.id,
+                UpdateUserDto {
+                    avatar_url: Some(None),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.avatar_url.as_deref(),
+            Some("https://cdn.example.com/avatar.png")
+        );
+        assert_eq!(response.username, "grace");
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn updates_leave_the_account_active(pool: PgPool) {
+        let app = TestApp::new(pool);
+        let user = app.create_user("ida").await;
+
+        // Suspending goes through deactivate_user, which also signs the user out
+        let patch: UpdateUserDto =
+            serde_json::from_str(r#"{"full_name": "Ida", "is_active": false}"#).unwrap();
+        app.user_management
+            .update_user(user.id, patch)
+            .await
+            .unwrap();
+
+        let user = app.repos.user().find_by_id(user.id).await.unwrap();
+        assert_eq!(user.full_name.as_deref(), Some("Ida"));
+        assert!(user.is_active);
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn username_changes_wait_for_the_cooldown(pool: PgPool) {
+        let mut config = crate::test_support::test_config();
+        config.username_change_cooldown_days = 30;
+        config.username_reservation_days = 7;
+        let app = TestApp::with_config(pool, config);
+        let service = &app.user_management;
+        let grace = app.create_user("grace").await;
+        let henry = app.create_user("henry").await;
+        let rename = |username: &str| ChangeUsernameDto {
+            username: username.to_string(),
+        };
+
+        let renamed = service
+            .change_username(grace.id, rename("grace_h"))
+            .await
+            .unwrap();
+        assert_eq!(renamed.username, "grace_h");
+
+        // Not again until the cooldown is over, through either endpoint
+        assert!(matches!(
+            service.change_username(grace.id, rename("grace_x")).await,
+            Err(AppError::UsernameChangeCooldown(available_at))
+                if available_at > Utc::now() + Duration::days(29)
+        ));
+        let profile = UpdateUserDto {
+            username: Some("grace_x".to_string()),
+            full_name: Some(Some("Grace".to_string())),
+            ..Default::default()
+        };
+        assert!(matches!(
+            service.update_profile(grace.id, profile).await,
+            Err(AppError::UsernameChangeCooldown(_))
+        ));
+
+        // The old username is still held for grace, and some are never available
+        assert!(matches!(
+            service.change_username(henry.id, rename("grace")).await,
+            Err(AppError::Database(DatabaseError::Duplicate(_)))
+        ));
+        assert!(matches!(
+            service.change_username(henry.id, rename("Admin")).await,
+            Err(AppError::Validation(_))
+        ));
+        assert_eq!(
+            service.get_user_by_id(henry.id).await.unwrap().username,
+            "henry"
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn deleted_users_are_only_visible_when_asked_for(pool: PgPool) {
+        use crate::models::user::AccountStatus;
+
+        let app = TestApp::new(pool);
+        let service = &app.user_management;
+        let user = app.create_user("heidi").await;
+        assert_eq!(
+            UserResponse::from(user.clone()).status,
+            AccountStatus::Unverified
+        );
+        let suspended = service.set_user_active(user.id, false).await.unwrap();
+        assert_eq!(suspended.status, AccountStatus::Suspended);
+        service.delete_user(user.id).await.unwrap();
+
+        assert!(matches!(
+            service.get_user_by_id(user.id).await,
+            Err(AppError::NotFound(_))
+        ));
+        let deleted = service
+            .get_user_by_id_including_deleted(user.id)
+            .await
+            .unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert_eq!(deleted.status, AccountStatus::Deleted);
+
+        let (users, total) = service
+            .get_all_users(1, 10, false, &CreatedRangeQuery::default())
+            .await
+            .unwrap();
+        assert!(users.is_empty() && total == 0);
+        let (users, total) = service
+            .get_all_users(1, 10, true, &CreatedRangeQuery::default())
+            .await
+            .unwrap();
+        assert_eq!((users.len(), total), (1, 1));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn users_can_be_listed_by_signup_date(pool: PgPool) {
+        let app = TestApp::new(pool.clone());
+        for (name, signed_up) in [
+            ("may", "2025-01-10T00:00:00Z"),
+            ("ned", "2025-02-10T00:00:00Z"),
+            ("ola", "2025-03-10T00:00:00Z"),
+        ] {
+            let user = app.create_user(name).await;
+            let signed_up: DateTime<Utc> = signed_up.parse().unwrap();
+            sqlx::query!(
+                "UPDATE users SET created_at = $1 WHERE id = $2",
+                signed_up,
+                user.id
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let list = |created_after: Option<&str>, created_before: Option<&str>| {
+            let created = CreatedRangeQuery {
+                created_after: created_after.map(|t| t.parse().unwrap()),
+                created_before: created_before.map(|t| t.parse().unwrap()),
+            };
+            let service = &app.user_management;
+            async move {
+                let (users, total) = service.get_all_users(1, 10, false, &created).await.unwrap();
+                let names: Vec<_> = users.into_iter().map(|u| u.username).collect();
+                assert_eq!(names.len() as u64, total);
+                names
+            }
+        };
+
+        assert_eq!(list(None, None).await.len(), 3);
+        assert_eq!(
+            list(Some("2025-02-10T00:00:00Z"), None).await,
+            ["ola", "ned"]
+        );
+        assert_eq!(
+            list(None, Some("2025-02-10T00:00:00Z")).await,
+            ["ned", "may"]
+        );
+        assert_eq!(
+            list(Some("2025-02-01T00:00:00Z"), Some("2025-02-28T00:00:00Z")).await,
+            ["ned"]
+        );
+
+        let inverted = CreatedRangeQuery {
+            created_after: Some(Utc::now()),
+            created_before: Some(Utc::now() - Duration::days(1)),
+        };
+        assert!(matches!(inverted.checked(), Err(AppError::Validation(_))));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn deleting_a_user_ends_everything_that_signs_them_in(pool: PgPool) {
+        use crate::models::auth::token::{CreateVerificationTokenDto, TOKEN_TYPE_PASSWORD_RESET};
+
+        let app = TestApp::new(pool.clone());
+        let user = app.create_user("ivan").await;
+        let (token, refresh_token) = app
+            .token_service
+            .generate_tokens(&user, None, None, None)
+            .unwrap();
+        sqlx::query!(
+            "INSERT INTO sessions (user_id, token, expires_at) VALUES ($1, $2, now() + interval '1 hour')",
+            user.id,
+            token
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        app.repos
+            .token()
+            .create(
+                &CreateVerificationTokenDto {
+                    user_id: Some(user.id),
+                    token_type: TOKEN_TYPE_PASSWORD_RESET.to_string(),
+                    expires_in: 3600,
+                },
+                "reset-token",
+            )
+            .await
+            .unwrap();
+
+        app.user_management.delete_user(user.id).await.unwrap();
+
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM sessions WHERE user_id = $1 AND is_active) +
+                (SELECT COUNT(*) FROM verification_tokens WHERE user_id = $1 AND used_at IS NULL)
+                AS "count!"
+            "#,
+            user.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(active, 0);
+        // Tokens already issued stay revoked even if the account is brought back
+        let deleted = app
+            .repos
+            .user()
+            .find_by_id_including_deleted(user.id)
+            .await
+            .unwrap();
+        let claims = app.token_service.verify_token(&token).unwrap();
+        assert!(app.token_service.is_token_revoked(&claims, &deleted));
+        assert!(matches!(
+            app.auth_service
+                .refresh_token(&refresh_token, &test_client("192.0.2.1", "test"))
+                .await,
+            Err(AppError::Authentication(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn merging_moves_badges_and_deletes_the_source(pool: PgPool) {
+        use crate::models::badge::CreateBadgeDto;
+        use crate::models::user::AwardBadgeDto;
+
+        let app = TestApp::new(pool);
+        let source = app.create_user("ivan").await;
+        let target = app.create_user("ivan2").await;
+
+        let mut badges = Vec::new();
+        for name in ["Early adopter", "Contributor"] {
+            let badge = app
+                .repos
+                .badge()
+                .create(&CreateBadgeDto {
+                    name: name.to_string(),
+                    description: None,
+                    image_url: None,
+                })
+                .await
+                .unwrap();
+            badges.push(badge.id);
+        }
+        let award = |user_id, badge_id| AwardBadgeDto { user_id, badge_id };
+        for dto in [
+            award(source.id, badges[0]),
+            award(source.id, badges[1]),
+            award(target.id, badges[0]),
+        ] {
+            app.repos.user_badge().award_badge(&dto).await.unwrap();
+        }
+
+        assert!(matches!(
+            app.user_management.merge_users(source.id, source.id).await,
+            Err(AppError::Validation(_))
+        ));
+
+        let merge = app
+            .user_management
+            .merge_users(source.id, target.id)
+            .await
+            .unwrap();
+        assert_eq!(merge.target.id, target.id);
+        assert_eq!(
+            (merge.merged.badges_moved, merge.merged.badges_dropped),
+            (1, 1)
+        );
+
+        let kept = app
+            .repos
+            .user_badge()
+            .find_badges_by_user_id(target.id)
+            .await
+            .unwrap();
+        assert_eq!(kept.len(), 2);
+        assert!(matches!(
+            app.user_management.get_user_by_id(source.id).await,
+            Err(AppError::NotFound(_))
+        ));
+
+        // The source is gone, so it can't be merged again
+        assert!(matches!(
+            app.user_management.merge_users(source.id, target.id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
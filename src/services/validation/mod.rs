@@ -1,4 +1,5 @@
 use crate::errors::AppError;
+use oauth2::url::{Host, Url};
 use regex::Regex;
 use validator::ValidationError;
 
@@ -52,23 +53,111 @@ pub fn validate_username(username: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+// Names users can't switch to, since they'd pass for the service or its staff
+const RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "root",
+    "system",
+    "support",
+    "help",
+    "security",
+    "staff",
+    "moderator",
+    "official",
+    "api",
+    "auth",
+    "oauth",
+    "users",
+    "settings",
+    "null",
+    "undefined",
+];
+
+pub fn check_username_not_reserved(username: &str) -> Result<(), AppError> {
+    if RESERVED_USERNAMES
+        .iter()
+        .any(|reserved| username.eq_ignore_ascii_case(reserved))
+    {
+        return Err(AppError::Validation(format!(
+            "The username {} is reserved",
+            username
+        )));
+    }
+
+    Ok(())
+}
+
+// Validate an OAuth provider endpoint: an absolute HTTPS URL (plain HTTP is
+// only accepted for loopback hosts, for local development)
+pub fn validate_provider_url(url: &str) -> Result<(), ValidationError> {
+    let url = Url::parse(url).map_err(|_| ValidationError::new("invalid_provider_url"))?;
+
+    let is_loopback = match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost",
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => return Err(ValidationError::new("invalid_provider_url")),
+    };
+
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if is_loopback => Ok(()),
+        _ => Err(ValidationError::new("invalid_provider_url")),
+    }
+}
+
+// Check a new account's email against ALLOWED_EMAIL_DOMAINS (lowercase, without `@`).
+// An empty list allows every domain.
+pub fn check_email_domain_allowed(email: &str, allowed_domains: &[String]) -> Result<(), AppError> {
+    if allowed_domains.is_empty() {
+        return Ok(());
+    }
+
+    let allowed = email.rsplit_once('@').is_some_and(|(_, domain)| {
+        allowed_domains
+            .iter()
+            .any(|allowed| domain.eq_ignore_ascii_case(allowed))
+    });
+    if allowed {
+        return Ok(());
+    }
+
+    Err(AppError::Validation(format!(
+        "Registration is limited to email addresses at {}",
+        allowed_domains.join(", ")
+    )))
+}
+
+// Messages for the codes of the validation rules above, listed in the error catalog
+pub const VALIDATION_MESSAGES: &[(&str, &str)] = &[
+    ("password_too_short", "Password must be at least 8 characters"),
+    ("password_no_uppercase", "Password must contain at least one uppercase letter"),
+    ("password_no_number", "Password must contain at least one number"),
+    ("password_no_special_char", "Password must contain at least one special character"),
+    ("password_unchanged", "New password must be different from the current password"),
+    ("invalid_email_format", "Invalid email format"),
+    ("invalid_username_format", "Username must be 3-30 characters and contain only letters, numbers, underscores, or hyphens"),
+    ("invalid_provider_url", "Must be an absolute HTTPS URL"),
+    ("unknown_client", "Is not a known client"),
+];
+
 // Helper function to convert validation errors to AppError
 pub fn validation_err_to_app_error(error: validator::ValidationErrors) -> AppError {
     let mut error_messages = String::new();
 
     for (field, errors) in error.field_errors() {
         for error in errors {
-            let message = match error.code.as_ref() {
-                "password_too_short" => "Password must be at least 8 characters",
-                "password_no_uppercase" => "Password must contain at least one uppercase letter",
-                "password_no_number" => "Password must contain at least one number",
-                "password_no_special_char" => "Password must contain at least one special character",
-                "invalid_email_format" => "Invalid email format",
-                "invalid_username_format" => "Username must be 3-30 characters and contain only letters, numbers, underscores, or hyphens",
-                _ => error.message.as_ref().map_or(
-                    error.code.as_ref(), |m| m.as_ref()
-                ),
-            };
+            let message = VALIDATION_MESSAGES
+                .iter()
+                .find(|(code, _)| *code == error.code)
+                .map(|(_, message)| *message)
+                .unwrap_or_else(|| {
+                    error
+                        .message
+                        .as_ref()
+                        .map_or(error.code.as_ref(), |m| m.as_ref())
+                });
 
             if !error_messages.is_empty() {
                 error_messages.push_str("; ");
@@ -83,3 +172,13 @@ pub fn validation_err_to_app_error(error: validator::ValidationErrors) -> AppErr
 
     AppError::Validation(error_messages)
 }
+
+// A validation error for one field found outside a DTO's rules, worded the same way
+pub fn field_validation_error(field: &str, code: &str) -> AppError {
+    let message = VALIDATION_MESSAGES
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map_or(code, |(_, message)| *message);
+
+    AppError::Validation(format!("{}: {}", field, message))
+}
@@ -0,0 +1,215 @@
+// Shared setup for integration tests. Each `#[sqlx::test(migrations = "./migrations")]`
+// test gets its own freshly migrated database (DATABASE_URL must point at a Postgres
+// server the tests can create databases on); `TestApp::new` wires the services on top.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+use crate::config::{
+    AppConfig, AvatarConfig, CredentialConfig, DatabaseConfig, DormancyConfig, EmailConfig,
+    LocaleConfig, OAuthConfig, RateLimitStoreConfig, RegistrationMode, SecurityHeadersConfig,
+    TokenBinding,
+};
+use crate::db::repositories::{
+    OAuthRepository, Repositories, SessionRepository, TokenRepository, UserRepository,
+};
+use crate::middleware::client_context::ClientContext;
+use crate::models::user::{CreateUserDto, User};
+use crate::services::audit::AuditService;
+use crate::services::auth::{AuthService, OAuthService, TokenService};
+use crate::services::badge::{BadgeService, CredentialSigner};
+use crate::services::email::EmailService;
+use crate::services::events::EventBus;
+use crate::services::user::{InviteService, UserEmailService, UserManagementService};
+
+pub const TEST_PASSWORD: &str = "Passw0rd!";
+
+// Configuration for tests; nothing here is read from required environment variables
+pub fn test_config() -> AppConfig {
+    AppConfig {
+        database: DatabaseConfig {
+            connection_string: String::new(),
+            max_connections: 5,
+            slow_query_ms: 0,
+        },
+        email: EmailConfig {
+            enabled: true,
+            smtp_host: "localhost".to_string(),
+            smtp_port: 2525,
+            smtp_username: "test".to_string(),
+            smtp_password: "test".to_string(),
+            sender_email: "noreply@example.com".to_string(),
+            sender_name: "Safatanc Connect".to_string(),
+            frontend_url: "http://localhost:3000".to_string(),
+        },
+        oauth: OAuthConfig::from_env(),
+        dormancy: DormancyConfig::from_env(),
+        bootstrap_admin: None,
+        server_host: "127.0.0.1".to_string(),
+        server_port: 0,
+        jwt_secret: "test-secret".to_string(),
+        jwt_expiration: 3600,
+        jwt_client_expirations: HashMap::new(),
+        refresh_token_expiration: 604800,
+        impersonation_token_expiration: 900,
+        auth_stateless: false,
+        token_binding: TokenBinding::Off,
+        refresh_token_cookie: false,
+        cors_allowed_origins: vec!["*".to_string()],
+        graphql_playground_enabled: false,
+        response_envelope: true,
+        api_base_path: String::new(),
+        token_attempt_limit: 10,
+        token_attempt_window: 900,
+        rate_limit_store: RateLimitStoreConfig::Memory,
+        registration_limit_global: 0,
+        registration_limit_per_subnet: 0,
+        password_history_size: 0,
+        password_max_age_days: 0,
+        json_max_depth: 32,
+        graphql_max_depth: 10,
+        graphql_max_complexity: 500,
+        track_session_activity: false,
+        session_activity_interval: 60,
+        session_idle_timeout_secs: 0,
+        max_page_size: 100,
+        registration_mode: RegistrationMode::Open,
+        allowed_email_domains: Vec::new(),
+        trust_proxy: false,
+        trusted_proxies: Vec::new(),
+        cache_max_age: 60,
+        security_headers: SecurityHeadersConfig::default(),
+        compression_enabled: false,
+        compression_min_size: 1024,
+        avatars: AvatarConfig::default(),
+        credentials: CredentialConfig::default(),
+        username_change_cooldown_days: 0,
+        username_reservation_days: 0,
+        password_reset_cooldown_secs: 0,
+        invalidate_reset_on_login: false,
+        require_verified_email_for_login: false,
+        login_verification_resend_secs: 0,
+        locale: LocaleConfig {
+            default: "en".to_string(),
+            supported: vec!["en".to_string(), "id".to_string()],
+        },
+    }
+}
+
+// A client at `ip` sending `user_agent`, as handlers see it
+pub fn test_client(ip: &str, user_agent: &str) -> ClientContext {
+    ClientContext {
+        ip: ip.parse().expect("Invalid test IP"),
+        user_agent: Some(user_agent.to_string()),
+        device_info: None,
+        country: None,
+        accept_language: None,
+    }
+}
+
+// The services wired together the same way main.rs does, on a test database
+pub struct TestApp {
+    pub repos: Arc<Repositories>,
+    pub events: Arc<EventBus>,
+    pub token_service: Arc<TokenService>,
+    pub user_management: Arc<UserManagementService>,
+    pub email_service: Arc<EmailService>,
+    pub auth_service: Arc<AuthService>,
+    pub badge_service: Arc<BadgeService>,
+    pub audit_service: Arc<AuditService>,
+    pub user_email_service: Arc<UserEmailService>,
+    pub invite_service: Arc<InviteService>,
+}
+
+impl TestApp {
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_config(pool, test_config())
+    }
+
+    pub fn with_config(pool: PgPool, config: AppConfig) -> Self {
+        let repos = Arc::new(Repositories::new(pool.clone()));
+        let events = Arc::new(EventBus::new());
+        let user_repo = UserRepository::new(pool.clone());
+        let token_repo = TokenRepository::new(pool.clone());
+
+        let token_service = Arc::new(TokenService::new(config.clone()));
+        let user_management = Arc::new(
+            UserManagementService::new(user_repo.clone(), events.clone())
+                .with_password_history(config.password_history_size)
+                .with_avatars(config.avatars.clone())
+                .with_username_policy(
+                    config.username_change_cooldown_days,
+                    config.username_reservation_days,
+                ),
+        );
+        let email_service = Arc::new(EmailService::new(config.email.clone(), token_repo.clone()));
+        let oauth_service = Arc::new(OAuthService::new(
+            user_repo.clone(),
+            OAuthRepository::new(pool.clone()),
+            user_management.clone(),
+            config.clone(),
+        ));
+        let auth_service = Arc::new(
+            AuthService::new(
+                user_repo,
+                token_repo,
+                SessionRepository::new(pool),
+                token_service.clone(),
+                user_management.clone(),
+            )
+            .with_oauth_service(oauth_service)
+            .with_email_service(email_service.clone())
+            .with_password_reset_cooldown(config.password_reset_cooldown_secs)
+            .with_reset_invalidation_on_login(config.invalidate_reset_on_login)
+            .with_verified_email_login(
+                config.require_verified_email_for_login,
+                config.login_verification_resend_secs,
+            ),
+        );
+
+        Self {
+            badge_service: Arc::new(
+                BadgeService::new(repos.clone(), events.clone())
+                    .with_avatars(config.avatars.clone())
+                    .with_credentials(CredentialSigner::from_config(&config.credentials).unwrap()),
+            ),
+            audit_service: Arc::new(AuditService::new(repos.clone(), events.clone())),
+            user_email_service: Arc::new(UserEmailService::new(
+                repos.clone(),
+                email_service.clone(),
+            )),
+            invite_service: Arc::new(
+                InviteService::new(
+                    repos.clone(),
+                    user_management.clone(),
+                    email_service.clone(),
+                    config.registration_mode,
+                )
+                .with_allowed_email_domains(config.allowed_email_domains.clone()),
+            ),
+            repos,
+            events,
+            token_service,
+            user_management,
+            email_service,
+            auth_service,
+        }
+    }
+
+    // Register a user with TEST_PASSWORD and the email `<username>@example.com`
+    pub async fn create_user(&self, username: &str) -> User {
+        self.user_management
+            .register_user(CreateUserDto {
+                email: format!("{}@example.com", username),
+                username: username.to_string(),
+                password: TEST_PASSWORD.to_string(),
+                full_name: None,
+                avatar_url: None,
+                invite_token: None,
+            })
+            .await
+            .expect("Failed to create test user")
+    }
+}
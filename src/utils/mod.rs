@@ -1 +1,4 @@
 // Utility functions will be implemented later
+
+pub mod logging;
+pub mod redact;